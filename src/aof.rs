@@ -0,0 +1,633 @@
+use crate::protocol::decoder::{FrameError, RedisDecoder};
+use crate::protocol::encoder::RedisEncoder;
+use crate::rdb::RDB;
+use crate::store::{now_millis, Snapshot, Store};
+use bytes::BytesMut;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex, MutexGuard};
+
+/// appendfsync 정책
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppendFsync {
+    /// 매 쓰기마다 fsync
+    Always,
+    /// 1초에 한 번 백그라운드에서 fsync
+    EverySec,
+    /// fsync 는 OS 에 맡긴다
+    No,
+}
+
+impl AppendFsync {
+    pub fn parse(s: &str) -> Option<AppendFsync> {
+        match s.to_lowercase().as_str() {
+            "always" => Some(AppendFsync::Always),
+            "everysec" => Some(AppendFsync::EverySec),
+            "no" => Some(AppendFsync::No),
+            _ => None,
+        }
+    }
+}
+
+/// manifest 에 기록되는 AOF 파일 종류
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AofFileType {
+    /// rewrite 로 만들어진 base 파일 (RDB preamble 또는 AOF 형식)
+    Base,
+    /// base 이후의 쓰기 명령이 쌓이는 파일
+    Incr,
+    /// rewrite 이후 더 이상 쓰지 않는 파일 (삭제 대상)
+    History,
+}
+
+impl AofFileType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AofFileType::Base => "b",
+            AofFileType::Incr => "i",
+            AofFileType::History => "h",
+        }
+    }
+
+    fn parse(s: &str) -> Option<AofFileType> {
+        match s {
+            "b" => Some(AofFileType::Base),
+            "i" => Some(AofFileType::Incr),
+            "h" => Some(AofFileType::History),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AofFileInfo {
+    pub name: String,
+    pub seq: u64,
+    pub file_type: AofFileType,
+}
+
+/// Redis 7 multi-part AOF 의 manifest (`appendonly.aof.manifest`)
+///
+/// ```text
+/// file appendonly.aof.2.base.rdb seq 2 type b
+/// file appendonly.aof.3.incr.aof seq 3 type i
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AofManifest {
+    pub base: Option<AofFileInfo>,
+    pub incrs: Vec<AofFileInfo>,
+}
+
+impl AofManifest {
+    pub fn parse(s: &str) -> io::Result<AofManifest> {
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid AOF manifest line: {:?}", line),
+            )
+        };
+
+        let mut manifest = AofManifest::default();
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let mut name = None;
+            let mut seq = None;
+            let mut file_type = None;
+            for pair in parts.chunks(2) {
+                match pair {
+                    ["file", v] => name = Some(v.to_string()),
+                    ["seq", v] => seq = v.parse::<u64>().ok(),
+                    ["type", v] => file_type = AofFileType::parse(v),
+                    _ => return Err(invalid(line)),
+                }
+            }
+
+            let info = match (name, seq, file_type) {
+                (Some(name), Some(seq), Some(file_type)) => AofFileInfo { name, seq, file_type },
+                _ => return Err(invalid(line)),
+            };
+            match info.file_type {
+                AofFileType::Base => {
+                    if manifest.base.is_some() {
+                        return Err(invalid(line));
+                    }
+                    manifest.base = Some(info);
+                }
+                AofFileType::Incr => manifest.incrs.push(info),
+                // history 파일은 이미 필요 없는 파일이므로 무시
+                AofFileType::History => {}
+            }
+        }
+
+        manifest.incrs.sort_by_key(|info| info.seq);
+        Ok(manifest)
+    }
+
+    fn next_base_seq(&self) -> u64 {
+        self.base.as_ref().map_or(1, |b| b.seq + 1)
+    }
+
+    fn next_incr_seq(&self) -> u64 {
+        self.incrs.last().map_or(1, |i| i.seq + 1)
+    }
+}
+
+impl fmt::Display for AofManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for info in self.base.iter().chain(self.incrs.iter()) {
+            writeln!(f, "file {} seq {} type {}", info.name, info.seq, info.file_type.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+/// 명령 하나를 RESP 배열 형태로 인코딩한다.
+fn encode_command(buf: &mut BytesMut, args: &[String]) {
+    let items: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+    RedisEncoder::new().encode_array(buf, &items);
+}
+
+/// 스냅샷의 함수 라이브러리를 FUNCTION LOAD, 모든 키를 SET 명령으로 인코딩한다 (aof-use-rdb-preamble no 일 때의 base 형식).
+fn encode_snapshot_commands(snapshot: &Snapshot) -> BytesMut {
+    let now = now_millis();
+    let mut buf = BytesMut::new();
+    for code in &snapshot.functions {
+        encode_command(&mut buf, &["FUNCTION".to_string(), "LOAD".to_string(), code.clone()]);
+    }
+    for (key, value, expiry) in &snapshot.entries {
+        let mut args = vec!["SET".to_string(), key.clone(), value.clone()];
+        if let Some(ts) = expiry {
+            if *ts <= now {
+                continue;
+            }
+            args.push("PXAT".to_string());
+            args.push(ts.to_string());
+        }
+        encode_command(&mut buf, &args);
+    }
+    buf
+}
+
+/// 내용을 임시 파일에 쓰고 fsync 한 뒤 rename 으로 교체한다.
+fn write_file_atomic(dir: &Path, name: &str, contents: &[u8]) -> io::Result<()> {
+    let temp_path = dir.join(format!("temp-{}", name));
+    let mut file = File::create(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, dir.join(name))?;
+    File::open(dir)?.sync_all()
+}
+
+/// 현재 INCR 파일에 명령을 덧붙이는 쪽. Aof::writer() 로 락을 잡은 동안에만 접근할 수 있다.
+#[derive(Debug)]
+pub struct AofWriter {
+    file: File,
+    fsync: AppendFsync,
+    /// everysec 정책에서 아직 fsync 되지 않은 쓰기가 있는지
+    pending_fsync: bool,
+    manifest: AofManifest,
+    /// 마지막으로 기록한 명령의 replication offset
+    reploff: u64,
+    /// 디스크에 fsync 된 명령의 replication offset (WAITAOF)
+    fsynced_reploff: watch::Sender<u64>,
+}
+
+impl AofWriter {
+    /// 다음에 기록할 명령의 replication offset. fsync 되면 WAITAOF 에 알린다.
+    pub fn set_reploff(&mut self, reploff: u64) {
+        self.reploff = reploff;
+    }
+
+    fn mark_fsynced(&self, reploff: u64) {
+        self.fsynced_reploff.send_if_modified(|fsynced| {
+            let advanced = reploff > *fsynced;
+            if advanced {
+                *fsynced = reploff;
+            }
+            advanced
+        });
+    }
+
+    /// 명령 하나를 RESP 배열 형태로 기록한다.
+    pub fn append(&mut self, args: &[String]) -> io::Result<()> {
+        let mut buf = BytesMut::new();
+        encode_command(&mut buf, args);
+
+        self.file.write_all(&buf)?;
+        match self.fsync {
+            AppendFsync::Always => {
+                self.file.sync_data()?;
+                self.mark_fsynced(self.reploff);
+            }
+            AppendFsync::EverySec => self.pending_fsync = true,
+            AppendFsync::No => {}
+        }
+        Ok(())
+    }
+}
+
+/// AOF 로부터 복원한 데이터
+#[derive(Debug)]
+pub struct AofData {
+    /// base 가 RDB preamble 이면 그 내용
+    pub store: Option<Store>,
+    /// base (AOF 형식) 와 INCR 파일들의 명령을 순서대로
+    pub commands: Vec<Vec<String>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RewriteError {
+    /// 이미 BGREWRITEAOF 가 진행 중
+    InProgress,
+    Io(String),
+}
+
+/// Redis 7 방식의 multi-part append-only file
+///
+/// `appendonlydir` 안에 base 파일 하나와 INCR 파일들을 두고 manifest 로 관리한다.
+/// 쓰기 명령은 항상 마지막 INCR 파일에 덧붙인다.
+#[derive(Debug)]
+pub struct Aof {
+    dir: PathBuf,
+    basename: String,
+    use_rdb_preamble: bool,
+    writer: Mutex<AofWriter>,
+    fsynced_reploff: watch::Receiver<u64>,
+    rewrite_in_progress: AtomicBool,
+    last_rewrite_ok: AtomicBool,
+}
+
+impl Aof {
+    fn manifest_name(basename: &str) -> String {
+        format!("{}.manifest", basename)
+    }
+
+    fn read_manifest(dir: &Path, basename: &str) -> io::Result<Option<AofManifest>> {
+        match fs::read_to_string(dir.join(Self::manifest_name(basename))) {
+            Ok(contents) => AofManifest::parse(&contents).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write_manifest(dir: &Path, basename: &str, manifest: &AofManifest) -> io::Result<()> {
+        write_file_atomic(dir, &Self::manifest_name(basename), manifest.to_string().as_bytes())
+    }
+
+    /// Redis 7 이전의 단일 AOF 파일을 `appendonlydir` 안의 base 파일로 옮긴다.
+    fn upgrade_legacy(legacy_path: &Path, dir: &Path, basename: &str) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let base = AofFileInfo {
+            name: format!("{}.1.base.aof", basename),
+            seq: 1,
+            file_type: AofFileType::Base,
+        };
+        fs::rename(legacy_path, dir.join(&base.name))?;
+
+        let manifest = AofManifest { base: Some(base), incrs: vec![] };
+        Self::write_manifest(dir, basename, &manifest)
+    }
+
+    /// manifest 에 적힌 파일들을 읽는다. AOF 가 아직 없으면 None.
+    /// 마지막 INCR 파일의 끝이 잘려 있을 때만 `load_truncated` 가 적용된다.
+    pub async fn load<P: AsRef<Path>>(
+        dir: P,
+        basename: &str,
+        legacy_path: P,
+        load_truncated: bool,
+    ) -> io::Result<Option<AofData>> {
+        let dir = dir.as_ref();
+        let mut manifest = Self::read_manifest(dir, basename)?;
+        if manifest.is_none() && legacy_path.as_ref().is_file() {
+            Self::upgrade_legacy(legacy_path.as_ref(), dir, basename)?;
+            manifest = Self::read_manifest(dir, basename)?;
+        }
+        let manifest = match manifest {
+            Some(manifest) => manifest,
+            None => return Ok(None),
+        };
+
+        let mut data = AofData { store: None, commands: Vec::new() };
+        if let Some(base) = &manifest.base {
+            let path = dir.join(&base.name);
+            if base.name.ends_with(".rdb") {
+                data.store = Some(RDB::read_rdb(&path).await?);
+            } else {
+                data.commands = Self::load_commands(&path, false)?;
+            }
+        }
+
+        for (i, incr) in manifest.incrs.iter().enumerate() {
+            let is_last = i + 1 == manifest.incrs.len();
+            let commands = Self::load_commands(dir.join(&incr.name), load_truncated && is_last)?;
+            data.commands.extend(commands);
+        }
+
+        Ok(Some(data))
+    }
+
+    /// AOF 파일을 읽어 명령 목록을 돌려준다.
+    /// 마지막 명령이 잘려 있거나 EXEC 없이 끝난 MULTI 가 있으면 `load_truncated` 일 때
+    /// 잘린 부분 (끝나지 않은 트랜잭션은 MULTI 부터) 을 잘라내고 계속 진행한다.
+    pub fn load_commands<P: AsRef<Path>>(path: P, load_truncated: bool) -> io::Result<Vec<Vec<String>>> {
+        let path = path.as_ref();
+        let buffer = fs::read(path)?;
+        // 서버가 직접 쓴 파일이라 proto-max-bulk-len / proto-max-multibulk-len 을 적용하지 않는다
+        let decoder = RedisDecoder::with_limits(usize::MAX, usize::MAX);
+
+        let mut commands = Vec::new();
+        let mut pos = 0;
+        // EXEC 를 아직 만나지 않은 MULTI 의 위치와 그때까지 읽은 명령 수
+        let mut multi: Option<(usize, usize)> = None;
+        let mut truncated = false;
+        while pos < buffer.len() {
+            match decoder.parse_args(&buffer[pos..]) {
+                Ok((args, consumed)) => {
+                    if args.len() == 1 && args[0].eq_ignore_ascii_case("MULTI") {
+                        multi = Some((pos, commands.len()));
+                    } else if args.len() == 1 && args[0].eq_ignore_ascii_case("EXEC") {
+                        multi = None;
+                    }
+                    commands.push(args);
+                    pos += consumed;
+                }
+                Err(FrameError::Incomplete) => {
+                    truncated = true;
+                    break;
+                }
+                Err(FrameError::Invalid | FrameError::TooLarge(_)) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Bad file format reading the append only file",
+                    ));
+                }
+            }
+        }
+
+        // 잘린 명령이나 EXEC 없이 끝난 트랜잭션은 잘린 파일로 본다
+        if truncated || multi.is_some() {
+            if !load_truncated {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Unexpected end of file reading the append only file",
+                ));
+            }
+            if let Some((multi_pos, count)) = multi {
+                eprintln!("AOF {:?} ends inside MULTI/EXEC, reverting the incomplete transaction", path);
+                pos = multi_pos;
+                commands.truncate(count);
+            }
+            eprintln!("AOF {:?} is truncated, removing the last {} bytes", path, buffer.len() - pos);
+            OpenOptions::new().write(true).open(path)?.set_len(pos as u64)?;
+        }
+
+        Ok(commands)
+    }
+
+    /// `appendonlydir` 을 열고 마지막 INCR 파일을 append 모드로 연다.
+    /// manifest 가 없거나 INCR 파일이 없으면 새로 만든다.
+    pub fn open<P: AsRef<Path>>(
+        dir: P,
+        basename: &str,
+        fsync: AppendFsync,
+        use_rdb_preamble: bool,
+    ) -> io::Result<Aof> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut manifest = Self::read_manifest(dir, basename)?.unwrap_or_default();
+        if manifest.incrs.is_empty() {
+            let seq = manifest.next_incr_seq();
+            manifest.incrs.push(AofFileInfo {
+                name: format!("{}.{}.incr.aof", basename, seq),
+                seq,
+                file_type: AofFileType::Incr,
+            });
+            Self::write_manifest(dir, basename, &manifest)?;
+        }
+
+        let current = manifest.incrs.last().unwrap();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(&current.name))?;
+
+        let (fsynced_reploff, fsynced_rx) = watch::channel(0);
+        Ok(Aof {
+            dir: dir.to_path_buf(),
+            basename: basename.to_string(),
+            use_rdb_preamble,
+            writer: Mutex::new(AofWriter {
+                file,
+                fsync,
+                pending_fsync: false,
+                manifest,
+                reploff: 0,
+                fsynced_reploff,
+            }),
+            fsynced_reploff: fsynced_rx,
+            rewrite_in_progress: AtomicBool::new(false),
+            last_rewrite_ok: AtomicBool::new(true),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 쓰기 락. Store 에 명령을 적용하고 기록하는 동안 잡고 있으면
+    /// 여러 클라이언트의 쓰기가 Store 에 적용된 순서대로 AOF 에 남는다.
+    pub async fn writer(&self) -> MutexGuard<'_, AofWriter> {
+        self.writer.lock().await
+    }
+
+    /// fsync 된 replication offset 을 지켜보는 채널
+    pub fn fsynced_reploff(&self) -> watch::Receiver<u64> {
+        self.fsynced_reploff.clone()
+    }
+
+    pub async fn manifest(&self) -> AofManifest {
+        self.writer.lock().await.manifest.clone()
+    }
+
+    pub fn rewrite_in_progress(&self) -> bool {
+        self.rewrite_in_progress.load(Ordering::SeqCst)
+    }
+
+    /// rewrite 시작: 새 INCR 파일로 갈아타고 그 시점의 스냅샷을 뜬다.
+    /// 쓰기 락을 잡은 채로 스냅샷을 뜨기 때문에 스냅샷 이후의 쓰기는 모두 새 INCR 파일에 들어간다.
+    async fn start_rewrite(&self, store: &Store) -> io::Result<(Snapshot, AofFileInfo, u64)> {
+        let mut writer = self.writer.lock().await;
+
+        let incr_seq = writer.manifest.next_incr_seq();
+        let incr = AofFileInfo {
+            name: format!("{}.{}.incr.aof", self.basename, incr_seq),
+            seq: incr_seq,
+            file_type: AofFileType::Incr,
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(&incr.name))?;
+
+        let mut manifest = writer.manifest.clone();
+        manifest.incrs.push(incr);
+        Self::write_manifest(&self.dir, &self.basename, &manifest)?;
+
+        writer.file.sync_data()?;
+        let reploff = writer.reploff;
+        writer.mark_fsynced(reploff);
+        writer.file = file;
+        writer.pending_fsync = false;
+        writer.manifest = manifest;
+
+        let base_seq = writer.manifest.next_base_seq();
+        let extension = if self.use_rdb_preamble { "rdb" } else { "aof" };
+        let base = AofFileInfo {
+            name: format!("{}.{}.base.{}", self.basename, base_seq, extension),
+            seq: base_seq,
+            file_type: AofFileType::Base,
+        };
+
+        Ok((store.snapshot().await, base, incr_seq))
+    }
+
+    /// 스냅샷으로 base 파일을 만든다.
+    fn write_base(&self, snapshot: Snapshot, base: &AofFileInfo) -> io::Result<()> {
+        let contents = if self.use_rdb_preamble {
+            RDB::encode_snapshots(&[snapshot])
+        } else {
+            encode_snapshot_commands(&snapshot).to_vec()
+        };
+        write_file_atomic(&self.dir, &base.name, &contents)
+    }
+
+    /// rewrite 완료: manifest 의 base 를 교체하고 rewrite 전의 파일들을 지운다.
+    async fn finish_rewrite(&self, base: AofFileInfo, first_incr_seq: u64) -> io::Result<()> {
+        let mut writer = self.writer.lock().await;
+
+        let mut manifest = writer.manifest.clone();
+        let mut history: Vec<AofFileInfo> = manifest.base.take().into_iter().collect();
+        let (old, kept): (Vec<_>, Vec<_>) = manifest
+            .incrs
+            .into_iter()
+            .partition(|incr| incr.seq < first_incr_seq);
+        history.extend(old);
+        manifest.base = Some(base);
+        manifest.incrs = kept;
+
+        Self::write_manifest(&self.dir, &self.basename, &manifest)?;
+        writer.manifest = manifest;
+
+        for info in history {
+            if let Err(e) = fs::remove_file(self.dir.join(&info.name)) {
+                eprintln!("Failed to remove history AOF file {}: {:?}", info.name, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// 포그라운드 rewrite. 서버 시작 시 base 파일이 없을 때 사용한다.
+    pub async fn rewrite_now(&self, store: &Store) -> io::Result<()> {
+        let (snapshot, base, first_incr_seq) = self.start_rewrite(store).await?;
+        self.write_base(snapshot, &base)?;
+        self.finish_rewrite(base, first_incr_seq).await
+    }
+
+    /// base 파일이 아직 없는지 (새로 만든 AOF)
+    pub async fn needs_base(&self) -> bool {
+        self.writer.lock().await.manifest.base.is_none()
+    }
+
+    /// BGREWRITEAOF: 현재 Store 를 새 base 파일로 압축한다.
+    /// rewrite 하는 동안 들어오는 쓰기는 새 INCR 파일에 기록된다.
+    pub async fn rewrite(self: &Arc<Self>, store: &Store) -> Result<(), RewriteError> {
+        if self
+            .rewrite_in_progress
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(RewriteError::InProgress);
+        }
+
+        let (snapshot, base, first_incr_seq) = match self.start_rewrite(store).await {
+            Ok(started) => started,
+            Err(e) => {
+                self.rewrite_in_progress.store(false, Ordering::SeqCst);
+                self.last_rewrite_ok.store(false, Ordering::SeqCst);
+                return Err(RewriteError::Io(e.to_string()));
+            }
+        };
+
+        let aof = Arc::clone(self);
+        tokio::spawn(async move {
+            let writer_aof = Arc::clone(&aof);
+            let base_info = base.clone();
+            let written = tokio::task::spawn_blocking(move || writer_aof.write_base(snapshot, &base_info))
+                .await
+                .unwrap_or_else(|e| Err(io::Error::other(e)));
+
+            let result = match written {
+                Ok(()) => aof.finish_rewrite(base, first_incr_seq).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = &result {
+                eprintln!("Background AOF rewrite error: {:?}", e);
+            }
+            aof.last_rewrite_ok.store(result.is_ok(), Ordering::SeqCst);
+            aof.rewrite_in_progress.store(false, Ordering::SeqCst);
+        });
+
+        Ok(())
+    }
+
+    /// everysec 정책일 때 1초마다 밀린 쓰기를 fsync 한다.
+    pub async fn run_fsync_policy(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+
+            // fsync 는 락 밖에서 복제한 핸들로 수행해서 쓰기를 막지 않는다
+            let (file, reploff) = {
+                let mut writer = self.writer.lock().await;
+                if writer.fsync != AppendFsync::EverySec || !writer.pending_fsync {
+                    continue;
+                }
+                writer.pending_fsync = false;
+                (writer.file.try_clone(), writer.reploff)
+            };
+
+            let result = match file {
+                Ok(file) => tokio::task::spawn_blocking(move || file.sync_data())
+                    .await
+                    .unwrap_or_else(|e| Err(io::Error::other(e))),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(_) => self.writer.lock().await.mark_fsynced(reploff),
+                Err(e) => eprintln!("Error syncing the append only file: {:?}", e),
+            }
+        }
+    }
+
+    /// INFO persistence 섹션의 AOF 항목
+    pub fn info(&self) -> String {
+        let status = if self.last_rewrite_ok.load(Ordering::SeqCst) { "ok" } else { "err" };
+        format!(
+            "aof_rewrite_in_progress:{}\r\n\
+             aof_last_bgrewrite_status:{}\r\n",
+            self.rewrite_in_progress() as u8,
+            status,
+        )
+    }
+}
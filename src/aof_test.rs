@@ -0,0 +1,214 @@
+use crate::aof::{Aof, AofFileType, AofManifest, AppendFsync};
+use crate::store::Store;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::test;
+
+fn args(cmd: &[&str]) -> Vec<String> {
+    cmd.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+async fn test_append_and_load() {
+    let dir = "test_append_aofdir";
+    let _ = fs::remove_dir_all(dir);
+
+    let aof = Aof::open(dir, "appendonly.aof", AppendFsync::Always, true).unwrap();
+    {
+        let mut writer = aof.writer().await;
+        writer.append(&args(&["SET", "key1", "value1"])).unwrap();
+        writer.append(&args(&["SET", "key2", "value2", "PXAT", "1700000000000"])).unwrap();
+    }
+
+    // 새 AOF 는 INCR 파일 하나로 시작
+    let manifest = fs::read_to_string(format!("{}/appendonly.aof.manifest", dir)).unwrap();
+    assert_eq!(manifest, "file appendonly.aof.1.incr.aof seq 1 type i\n");
+
+    let contents = fs::read(format!("{}/appendonly.aof.1.incr.aof", dir)).unwrap();
+    assert!(contents.starts_with(b"*3\r\n$3\r\nSET\r\n$4\r\nkey1\r\n$6\r\nvalue1\r\n"));
+
+    let data = Aof::load(dir, "appendonly.aof", "missing.aof", true).await.unwrap().unwrap();
+    assert!(data.store.is_none());
+    assert_eq!(data.commands, vec![
+        args(&["SET", "key1", "value1"]),
+        args(&["SET", "key2", "value2", "PXAT", "1700000000000"]),
+    ]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+async fn test_load_missing_aof() {
+    let data = Aof::load("test_missing_aofdir", "appendonly.aof", "missing.aof", true).await.unwrap();
+    assert!(data.is_none());
+}
+
+#[test]
+async fn test_load_truncated_tail() {
+    let path = "test_truncated.aof";
+    let complete = b"*3\r\n$3\r\nSET\r\n$4\r\nkey1\r\n$6\r\nvalue1\r\n";
+    let mut contents = complete.to_vec();
+    contents.extend_from_slice(b"*3\r\n$3\r\nSET\r\n$4\r\nke");
+    fs::write(path, &contents).unwrap();
+
+    // aof-load-truncated no 이면 로드를 거부
+    assert!(Aof::load_commands(path, false).is_err());
+
+    // yes 이면 잘린 명령을 버리고 파일도 잘라낸다
+    let commands = Aof::load_commands(path, true).unwrap();
+    assert_eq!(commands, vec![args(&["SET", "key1", "value1"])]);
+    assert_eq!(fs::read(path).unwrap(), complete);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_load_unterminated_transaction() {
+    let path = "test_unterminated_multi.aof";
+    let complete = b"*1\r\n$5\r\nMULTI\r\n*2\r\n$3\r\nDEL\r\n$1\r\na\r\n*1\r\n$4\r\nEXEC\r\n";
+    let mut contents = complete.to_vec();
+    // EXEC 전에 끝난 트랜잭션. 명령 자체는 온전하다.
+    contents.extend_from_slice(b"*1\r\n$5\r\nMULTI\r\n*2\r\n$3\r\nDEL\r\n$1\r\nb\r\n");
+    fs::write(path, &contents).unwrap();
+
+    assert!(Aof::load_commands(path, false).is_err());
+
+    // 끝나지 않은 트랜잭션은 MULTI 부터 통째로 버린다
+    let commands = Aof::load_commands(path, true).unwrap();
+    assert_eq!(commands, vec![args(&["MULTI"]), args(&["DEL", "a"]), args(&["EXEC"])]);
+    assert_eq!(fs::read(path).unwrap(), complete);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_load_invalid_format() {
+    let path = "test_invalid.aof";
+    fs::write(path, b"*1\r\n$4\r\nPING\r\ngarbage\r\n").unwrap();
+
+    let err = Aof::load_commands(path, true).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_parse_manifest() {
+    let manifest = AofManifest::parse(
+        "file appendonly.aof.2.base.rdb seq 2 type b\n\
+         file appendonly.aof.1.incr.aof seq 1 type h\n\
+         file appendonly.aof.4.incr.aof seq 4 type i\n\
+         file appendonly.aof.3.incr.aof seq 3 type i\n",
+    )
+    .unwrap();
+
+    let base = manifest.base.as_ref().unwrap();
+    assert_eq!(base.name, "appendonly.aof.2.base.rdb");
+    assert_eq!(base.file_type, AofFileType::Base);
+
+    // history 는 버리고 INCR 은 seq 순서로 정렬
+    let incrs: Vec<u64> = manifest.incrs.iter().map(|i| i.seq).collect();
+    assert_eq!(incrs, vec![3, 4]);
+
+    assert_eq!(
+        manifest.to_string(),
+        "file appendonly.aof.2.base.rdb seq 2 type b\n\
+         file appendonly.aof.3.incr.aof seq 3 type i\n\
+         file appendonly.aof.4.incr.aof seq 4 type i\n"
+    );
+
+    assert!(AofManifest::parse("file appendonly.aof.1.base.rdb seq x type b\n").is_err());
+}
+
+#[test]
+async fn test_rewrite_with_rdb_preamble() {
+    let dir = "test_rewrite_aofdir";
+    let _ = fs::remove_dir_all(dir);
+
+    let store = Store::new();
+    store.insert("key1".to_string(), "value1".to_string(), None).await;
+
+    let aof = Arc::new(Aof::open(dir, "appendonly.aof", AppendFsync::Always, true).unwrap());
+    aof.writer().await.append(&args(&["SET", "key1", "value1"])).unwrap();
+
+    aof.rewrite(&store).await.unwrap();
+    // rewrite 중의 쓰기는 새 INCR 파일로 간다
+    store.insert("key2".to_string(), "value2".to_string(), None).await;
+    aof.writer().await.append(&args(&["SET", "key2", "value2"])).unwrap();
+
+    while aof.rewrite_in_progress() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let manifest = aof.manifest().await;
+    assert_eq!(manifest.base.unwrap().name, "appendonly.aof.1.base.rdb");
+    let incrs: Vec<String> = manifest.incrs.into_iter().map(|i| i.name).collect();
+    assert_eq!(incrs, vec!["appendonly.aof.2.incr.aof"]);
+
+    // 이전 INCR 파일은 삭제
+    assert!(!std::path::Path::new(&format!("{}/appendonly.aof.1.incr.aof", dir)).exists());
+
+    let base = fs::read(format!("{}/appendonly.aof.1.base.rdb", dir)).unwrap();
+    assert_eq!(&base[0..9], b"REDIS0011");
+
+    let data = Aof::load(dir, "appendonly.aof", "missing.aof", true).await.unwrap().unwrap();
+    let loaded = data.store.unwrap();
+    assert_eq!(loaded.get("key1").await.unwrap(), "value1");
+    assert!(loaded.get("key2").await.is_none());
+    assert_eq!(data.commands, vec![args(&["SET", "key2", "value2"])]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+async fn test_upgrade_legacy_aof() {
+    let dir = "test_legacy_aofdir";
+    let legacy = "test_legacy.aof";
+    let _ = fs::remove_dir_all(dir);
+    fs::write(legacy, b"*3\r\n$3\r\nSET\r\n$4\r\nkey1\r\n$6\r\nvalue1\r\n").unwrap();
+
+    let data = Aof::load(dir, "appendonly.aof", legacy, true).await.unwrap().unwrap();
+    assert_eq!(data.commands, vec![args(&["SET", "key1", "value1"])]);
+
+    // 기존 파일은 appendonlydir 안의 AOF 형식 base 가 된다
+    assert!(!std::path::Path::new(legacy).exists());
+    let manifest = fs::read_to_string(format!("{}/appendonly.aof.manifest", dir)).unwrap();
+    assert_eq!(manifest, "file appendonly.aof.1.base.aof seq 1 type b\n");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+async fn test_fsynced_reploff() {
+    let dir = "test_fsynced_reploff_aofdir";
+    let _ = fs::remove_dir_all(dir);
+
+    // always 는 기록과 동시에 fsync
+    let aof = Aof::open(dir, "appendonly.aof", AppendFsync::Always, true).unwrap();
+    let fsynced = aof.fsynced_reploff();
+    {
+        let mut writer = aof.writer().await;
+        writer.set_reploff(31);
+        writer.append(&args(&["SET", "key1", "value1"])).unwrap();
+    }
+    assert_eq!(*fsynced.borrow(), 31);
+    drop(aof);
+    fs::remove_dir_all(dir).unwrap();
+
+    // everysec 는 백그라운드 fsync 이후에 반영
+    let aof = Arc::new(Aof::open(dir, "appendonly.aof", AppendFsync::EverySec, true).unwrap());
+    let mut fsynced = aof.fsynced_reploff();
+    {
+        let mut writer = aof.writer().await;
+        writer.set_reploff(31);
+        writer.append(&args(&["SET", "key1", "value1"])).unwrap();
+    }
+    assert_eq!(*fsynced.borrow(), 0);
+    let task = tokio::spawn(Arc::clone(&aof).run_fsync_policy());
+    tokio::time::timeout(Duration::from_secs(3), fsynced.changed()).await.unwrap().unwrap();
+    assert_eq!(*fsynced.borrow(), 31);
+    task.abort();
+
+    fs::remove_dir_all(dir).unwrap();
+}
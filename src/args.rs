@@ -3,6 +3,14 @@ use clap::Parser;
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
+    /// Port to listen on (optional, default 6379)
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Master to replicate from, e.g. "localhost 6379" (optional)
+    #[arg(long)]
+    pub replicaof: Option<String>,
+
     /// Directory path (optional)
     #[arg(long)]
     pub dir: Option<String>,
@@ -10,4 +18,139 @@ pub struct Args {
     /// DB filename (optional)
     #[arg(long)]
     pub dbfilename: Option<String>,
+
+    /// Snapshot save points, e.g. "3600 1 300 100" (optional, "" disables)
+    #[arg(long)]
+    pub save: Option<String>,
+
+    /// Verify the RDB checksum on load: yes | no (optional, default yes)
+    #[arg(long)]
+    pub rdbchecksum: Option<String>,
+
+    /// Enable append-only file persistence: yes | no (optional, default no)
+    #[arg(long)]
+    pub appendonly: Option<String>,
+
+    /// AOF filename (optional, default appendonly.aof)
+    #[arg(long)]
+    pub appendfilename: Option<String>,
+
+    /// AOF fsync policy: always | everysec | no (optional, default everysec)
+    #[arg(long)]
+    pub appendfsync: Option<String>,
+
+    /// Load an AOF whose last command is truncated: yes | no (optional, default yes)
+    #[arg(long)]
+    pub aof_load_truncated: Option<String>,
+
+    /// Directory for the multi-part AOF files (optional, default appendonlydir)
+    #[arg(long)]
+    pub appenddirname: Option<String>,
+
+    /// Write the AOF base file in RDB format: yes | no (optional, default yes)
+    #[arg(long)]
+    pub aof_use_rdb_preamble: Option<String>,
+
+    /// Replication backlog size, e.g. 1mb (optional, default 1mb)
+    #[arg(long)]
+    pub repl_backlog_size: Option<String>,
+
+    /// Send the RDB for full resyncs straight to the replica socket: yes | no (optional, default yes)
+    #[arg(long)]
+    pub repl_diskless_sync: Option<String>,
+
+    /// Serve possibly stale data while the link with the master is down: yes | no (optional, default yes)
+    #[arg(long)]
+    pub replica_serve_stale_data: Option<String>,
+
+    /// Run as a sentinel instead of a data server
+    #[arg(long)]
+    pub sentinel: bool,
+
+    /// Master for the sentinel to monitor: "<name> <host> <port> <quorum>" (sentinel mode)
+    #[arg(long)]
+    pub sentinel_monitor: Option<String>,
+
+    /// Milliseconds without a valid reply before an instance is considered down (optional, default 30000)
+    #[arg(long)]
+    pub sentinel_down_after_milliseconds: Option<String>,
+
+    /// Failover timeout in milliseconds (optional, default 180000)
+    #[arg(long)]
+    pub sentinel_failover_timeout: Option<String>,
+
+    /// Another sentinel monitoring the same master, "<host> <port>" (may be repeated)
+    #[arg(long)]
+    pub sentinel_known_sentinel: Vec<String>,
+
+    /// Run as a cluster node: yes | no (optional, default no)
+    #[arg(long)]
+    pub cluster_enabled: Option<String>,
+
+    /// Cluster node table file, written by the server (optional, default nodes.conf)
+    #[arg(long)]
+    pub cluster_config_file: Option<String>,
+
+    /// Milliseconds a cluster node can be unreachable before it is considered failing (optional, default 15000)
+    #[arg(long)]
+    pub cluster_node_timeout: Option<String>,
+
+    /// Keyspace notification classes, e.g. "KEA" or "Ex" (optional, default none)
+    #[arg(long)]
+    pub notify_keyspace_events: Option<String>,
+
+    /// 스크립트가 이 시간(밀리초) 넘게 실행되면 다른 명령에 BUSY 로 응답한다
+    #[arg(long)]
+    pub busy_script_time: Option<String>,
+
+    /// Minimum level of script log messages: debug, verbose, notice or warning (optional, default notice)
+    #[arg(long)]
+    pub loglevel: Option<String>,
+
+    /// Maximum size of a single request argument, e.g. 512mb (optional, default 512mb)
+    #[arg(long)]
+    pub proto_max_bulk_len: Option<String>,
+
+    /// Maximum number of arguments in a single request (optional, default 1048576)
+    #[arg(long)]
+    pub proto_max_multibulk_len: Option<String>,
+
+    /// Maximum size of a client's unparsed query buffer, e.g. 1gb (optional, default 1gb)
+    #[arg(long)]
+    pub client_query_buffer_limit: Option<String>,
+}
+
+impl Args {
+    /// 커맨드라인으로 아무 옵션도 넘기지 않았는지
+    pub fn is_empty(&self) -> bool {
+        self.port.is_none()
+            && self.replicaof.is_none()
+            && self.dir.is_none()
+            && self.dbfilename.is_none()
+            && self.save.is_none()
+            && self.rdbchecksum.is_none()
+            && self.appendonly.is_none()
+            && self.appendfilename.is_none()
+            && self.appendfsync.is_none()
+            && self.aof_load_truncated.is_none()
+            && self.appenddirname.is_none()
+            && self.aof_use_rdb_preamble.is_none()
+            && self.repl_backlog_size.is_none()
+            && self.repl_diskless_sync.is_none()
+            && self.replica_serve_stale_data.is_none()
+            && !self.sentinel
+            && self.sentinel_monitor.is_none()
+            && self.sentinel_down_after_milliseconds.is_none()
+            && self.sentinel_failover_timeout.is_none()
+            && self.sentinel_known_sentinel.is_empty()
+            && self.cluster_enabled.is_none()
+            && self.cluster_config_file.is_none()
+            && self.cluster_node_timeout.is_none()
+            && self.notify_keyspace_events.is_none()
+            && self.busy_script_time.is_none()
+            && self.loglevel.is_none()
+            && self.proto_max_bulk_len.is_none()
+            && self.proto_max_multibulk_len.is_none()
+            && self.client_query_buffer_limit.is_none()
+    }
 }
@@ -0,0 +1,73 @@
+use crate::protocol::decoder::{FrameError, RedisDecoder, Reply};
+use crate::protocol::encoder::RedisEncoder;
+use crate::replication::MasterAddr;
+use anyhow::{anyhow, bail, Result};
+use bytes::{Buf, BytesMut};
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// 다른 Redis 인스턴스에 명령을 보내고 응답을 받는 클라이언트.
+/// 모든 요청은 `timeout` 안에 끝나지 않으면 실패한다.
+#[derive(Debug)]
+pub struct Client {
+    stream: TcpStream,
+    buf: BytesMut,
+    timeout: Duration,
+}
+
+impl Client {
+    pub async fn connect(addr: &MasterAddr, request_timeout: Duration) -> Result<Client> {
+        let stream = timeout(request_timeout, TcpStream::connect((addr.host.as_str(), addr.port)))
+            .await
+            .map_err(|_| anyhow!("timed out connecting to {}:{}", addr.host, addr.port))??;
+        Ok(Client { stream, buf: BytesMut::new(), timeout: request_timeout })
+    }
+
+    /// 상대에게 보이는 이 연결의 IP
+    pub fn local_ip(&self) -> Result<IpAddr> {
+        Ok(self.stream.local_addr()?.ip())
+    }
+
+    /// 명령을 보내고 응답 하나를 읽는다. 에러 응답도 Ok(Reply::Error) 로 돌려준다.
+    pub async fn command(&mut self, args: &[&str]) -> Result<Reply> {
+        let args: Vec<&[u8]> = args.iter().map(|arg| arg.as_bytes()).collect();
+        self.command_bytes(&args).await
+    }
+
+    /// 바이너리 인자가 있는 명령 (예: RESTORE 의 DUMP 페이로드)
+    pub async fn command_bytes(&mut self, args: &[&[u8]]) -> Result<Reply> {
+        let encoder = RedisEncoder::new();
+        let mut request = BytesMut::new();
+        encoder.encode_array_len(&mut request, args.len());
+        for arg in args {
+            encoder.encode_bulk_bytes(&mut request, arg);
+        }
+
+        timeout(self.timeout, async {
+            self.stream.write_all(&request).await?;
+            self.read_reply().await
+        })
+        .await
+        .map_err(|_| anyhow!("timed out waiting for reply to {:?}", args.first().map(|name| String::from_utf8_lossy(name))))?
+    }
+
+    async fn read_reply(&mut self) -> Result<Reply> {
+        let decoder = RedisDecoder::new();
+        loop {
+            match decoder.parse_reply(&self.buf) {
+                Ok((reply, consumed)) => {
+                    self.buf.advance(consumed);
+                    return Ok(reply);
+                }
+                Err(FrameError::Incomplete) => {}
+                Err(FrameError::Invalid | FrameError::TooLarge(_)) => bail!("protocol error in reply"),
+            }
+            if self.stream.read_buf(&mut self.buf).await? == 0 {
+                bail!("connection closed");
+            }
+        }
+    }
+}
@@ -0,0 +1,1202 @@
+use crate::protocol::error::RedisError;
+use crate::replication::{new_replid, random_delay, MasterAddr};
+use crate::store::Store;
+use anyhow::{anyhow, bail, Result};
+use crc::{Crc, CRC_16_XMODEM};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 해시 슬롯 개수
+pub const CLUSTER_SLOTS: usize = 16384;
+
+/// 클러스터 버스 포트는 클라이언트 포트 + 10000
+pub const BUS_PORT_OFFSET: u16 = 10000;
+
+/// 장애 보고가 유효한 시간 (node timeout 의 배수)
+const FAIL_REPORT_VALIDITY_MULT: u32 = 2;
+
+/// 슬롯을 가진 채 FAIL 이 된 master 가 돌아왔을 때 FAIL 을 풀기까지 기다리는 시간 (node timeout 의 배수)
+const FAIL_UNDO_TIME_MULT: u32 = 2;
+
+/// failover 투표를 기다리는 시간이자, 같은 master 의 replica 에게 다시 투표하기까지의 시간 (node timeout 의 배수)
+const FAILOVER_TIMEOUT_MULT: u32 = 2;
+
+const CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_XMODEM);
+
+/// 키가 속한 해시 슬롯. 키에 `{...}` 가 있으면 그 안의 문자열 (hashtag) 만 해시한다.
+pub fn key_hash_slot(key: &str) -> u16 {
+    let bytes = key.as_bytes();
+    let hashed = match bytes.iter().position(|&b| b == b'{') {
+        Some(start) => match bytes[start + 1..].iter().position(|&b| b == b'}') {
+            // `{}` 처럼 비어 있으면 키 전체를 해시한다
+            Some(len) if len > 0 => &bytes[start + 1..start + 1 + len],
+            _ => bytes,
+        },
+        None => bytes,
+    };
+    CRC16.checksum(hashed) & (CLUSTER_SLOTS as u16 - 1)
+}
+
+/// 슬롯 번호를 읽는다 (0 ~ 16383)
+pub fn parse_slot(s: &str) -> Result<u16> {
+    match s.parse::<u16>() {
+        Ok(slot) if (slot as usize) < CLUSTER_SLOTS => Ok(slot),
+        _ => bail!("Invalid or out of range slot"),
+    }
+}
+
+/// 클러스터를 이루는 노드 하나
+#[derive(Debug, Clone)]
+pub struct ClusterNode {
+    pub id: String,
+    pub ip: String,
+    pub port: u16,
+    /// 클러스터 버스 포트
+    pub cport: u16,
+    /// replica 면 따르는 master 의 node ID
+    pub master_id: Option<String>,
+    pub config_epoch: u64,
+    /// node timeout 동안 응답이 없었다 (이 노드만의 판단)
+    pub pfail: bool,
+    /// 과반수의 master 가 장애에 동의했다
+    pub fail: bool,
+    fail_time: Option<Instant>,
+    /// 마지막으로 PING 을 보낸 시각
+    ping_sent: Option<Instant>,
+    pong_received: Instant,
+    /// 다른 master 들의 장애 보고 (보고한 node ID → 시각)
+    fail_reports: HashMap<String, Instant>,
+    /// 이 master 의 replica 에게 마지막으로 failover 투표를 한 시각
+    voted_time: Option<Instant>,
+    /// gossip 으로 받은 replication offset
+    repl_offset: u64,
+}
+
+impl ClusterNode {
+    fn new(id: String, ip: String, port: u16, cport: u16) -> Self {
+        ClusterNode {
+            id,
+            ip,
+            port,
+            cport,
+            master_id: None,
+            config_epoch: 0,
+            pfail: false,
+            fail: false,
+            fail_time: None,
+            ping_sent: None,
+            pong_received: Instant::now(),
+            fail_reports: HashMap::new(),
+            voted_time: None,
+            repl_offset: 0,
+        }
+    }
+
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.ip, self.port)
+    }
+
+    /// 클러스터 버스 주소
+    pub fn bus_addr(&self) -> MasterAddr {
+        MasterAddr { host: self.ip.clone(), port: self.cport }
+    }
+
+    pub fn is_master(&self) -> bool {
+        self.master_id.is_none()
+    }
+
+    fn flags(&self, myself: bool) -> String {
+        let mut flags = Vec::new();
+        if myself {
+            flags.push("myself");
+        }
+        flags.push(if self.is_master() { "master" } else { "slave" });
+        if self.fail {
+            flags.push("fail");
+        } else if self.pfail {
+            flags.push("fail?");
+        }
+        flags.join(",")
+    }
+}
+
+/// 이 노드에서 처리할 수 없는 요청에 돌려줄 에러
+#[derive(Debug, PartialEq, Eq)]
+pub enum Redirect {
+    /// 슬롯의 주인이 다른 노드다
+    Moved(u16, String),
+    /// 슬롯을 옮기는 중이고 키는 이미 옮겨 간 노드에 있다
+    Ask(u16, String),
+    /// 여러 키가 서로 다른 슬롯에 있다
+    CrossSlot,
+    /// 슬롯을 옮기는 중이라 여러 키를 한 번에 처리할 수 없다
+    TryAgain,
+    /// 슬롯을 가진 노드가 없다
+    Unbound,
+    /// 배정되지 않은 슬롯이 있거나 슬롯을 가진 노드가 FAIL 이다
+    Down,
+}
+
+impl From<Redirect> for RedisError {
+    fn from(redirect: Redirect) -> Self {
+        match redirect {
+            Redirect::Moved(slot, addr) => RedisError::Moved(slot, addr),
+            Redirect::Ask(slot, addr) => RedisError::Ask(slot, addr),
+            Redirect::CrossSlot => RedisError::CrossSlot,
+            Redirect::TryAgain => RedisError::TryAgain,
+            Redirect::Unbound => RedisError::ClusterDown("Hash slot not served".to_string()),
+            Redirect::Down => RedisError::ClusterDown("The cluster is down".to_string()),
+        }
+    }
+}
+
+/// 한 master 가 가진 슬롯들과 그 replica 들
+#[derive(Debug, Clone)]
+pub struct Shard {
+    pub master: ClusterNode,
+    pub replicas: Vec<ClusterNode>,
+    /// 연속된 슬롯 구간 (start, end)
+    pub ranges: Vec<(u16, u16)>,
+}
+
+/// CLUSTER SETSLOT <slot> ...
+#[derive(Debug, PartialEq, Eq)]
+pub enum SetSlot {
+    /// 다른 노드에서 이 노드로 가져오는 중
+    Importing(String),
+    /// 이 노드에서 다른 노드로 옮기는 중
+    Migrating(String),
+    /// 옮기는 중이던 상태를 지운다
+    Stable,
+    /// 슬롯의 주인을 정한다 (옮기기 끝)
+    Node(String),
+}
+
+/// gossip 으로 전하는 다른 노드 하나의 상태
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gossip {
+    pub id: String,
+    pub ip: String,
+    pub port: u16,
+    pub cport: u16,
+    pub pfail: bool,
+    pub fail: bool,
+}
+
+/// 클러스터 버스 메시지를 보내는 노드의 상태
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub sender: String,
+    pub ip: String,
+    pub port: u16,
+    pub cport: u16,
+    pub master_id: Option<String>,
+    pub config_epoch: u64,
+    pub current_epoch: u64,
+    pub repl_offset: u64,
+    /// 보내는 노드가 가진 슬롯 구간
+    pub slots: Vec<(u16, u16)>,
+    pub gossip: Vec<Gossip>,
+}
+
+/// 클러스터 버스 메시지. RESP 배열로 주고받는다.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Ping(Header),
+    /// PING 과 MEET 의 응답
+    Pong(Header),
+    /// CLUSTER MEET: 받은 노드는 보낸 노드를 클러스터에 넣는다
+    Meet(Header),
+    /// 노드가 FAIL 이 됐다 (보낸 노드, FAIL 노드)
+    Fail(String, String),
+    /// replica 가 failover 투표를 요청한다
+    AuthRequest(Header),
+}
+
+impl Header {
+    fn to_args(&self, args: &mut Vec<String>) {
+        let slots = if self.slots.is_empty() {
+            "-".to_string()
+        } else {
+            self.slots.iter().map(|(start, end)| format!("{}-{}", start, end)).collect::<Vec<_>>().join(",")
+        };
+        args.extend([
+            self.sender.clone(),
+            self.ip.clone(),
+            self.port.to_string(),
+            self.cport.to_string(),
+            self.master_id.clone().unwrap_or_else(|| "-".to_string()),
+            self.config_epoch.to_string(),
+            self.current_epoch.to_string(),
+            self.repl_offset.to_string(),
+            slots,
+        ]);
+        for g in &self.gossip {
+            let flags = if g.fail { "fail" } else if g.pfail { "fail?" } else { "-" };
+            args.extend([g.id.clone(), g.ip.clone(), g.port.to_string(), g.cport.to_string(), flags.to_string()]);
+        }
+    }
+
+    fn parse(args: &[String]) -> Option<Header> {
+        if args.len() < 9 || !(args.len() - 9).is_multiple_of(5) {
+            return None;
+        }
+        let mut slots = Vec::new();
+        if args[8] != "-" {
+            for range in args[8].split(',') {
+                let (start, end) = range.split_once('-')?;
+                slots.push((parse_slot(start).ok()?, parse_slot(end).ok()?));
+            }
+        }
+        let mut gossip = Vec::new();
+        for g in args[9..].chunks(5) {
+            gossip.push(Gossip {
+                id: g[0].clone(),
+                ip: g[1].clone(),
+                port: g[2].parse().ok()?,
+                cport: g[3].parse().ok()?,
+                pfail: g[4] == "fail?",
+                fail: g[4] == "fail",
+            });
+        }
+        Some(Header {
+            sender: args[0].clone(),
+            ip: args[1].clone(),
+            port: args[2].parse().ok()?,
+            cport: args[3].parse().ok()?,
+            master_id: Some(args[4].clone()).filter(|id| id != "-"),
+            config_epoch: args[5].parse().ok()?,
+            current_epoch: args[6].parse().ok()?,
+            repl_offset: args[7].parse().ok()?,
+            slots,
+            gossip,
+        })
+    }
+}
+
+impl Message {
+    pub fn to_args(&self) -> Vec<String> {
+        let (kind, header) = match self {
+            Message::Ping(h) => ("PING", h),
+            Message::Pong(h) => ("PONG", h),
+            Message::Meet(h) => ("MEET", h),
+            Message::AuthRequest(h) => ("AUTH-REQUEST", h),
+            Message::Fail(sender, node) => return vec!["FAIL".to_string(), sender.clone(), node.clone()],
+        };
+        let mut args = vec![kind.to_string()];
+        header.to_args(&mut args);
+        args
+    }
+
+    pub fn parse(args: &[String]) -> Option<Message> {
+        let (kind, rest) = args.split_first()?;
+        match kind.as_str() {
+            "PING" => Header::parse(rest).map(Message::Ping),
+            "PONG" => Header::parse(rest).map(Message::Pong),
+            "MEET" => Header::parse(rest).map(Message::Meet),
+            "AUTH-REQUEST" => Header::parse(rest).map(Message::AuthRequest),
+            "FAIL" => match rest {
+                [sender, node] => Some(Message::Fail(sender.clone(), node.clone())),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// 이 replica 의 failover 진행 상태
+#[derive(Debug)]
+struct Failover {
+    /// 이 시각이 지나면 투표를 요청한다
+    auth_time: Instant,
+    /// 투표를 요청한 epoch
+    epoch: Option<u64>,
+}
+
+#[derive(Debug)]
+struct ClusterState {
+    myself: String,
+    current_epoch: u64,
+    /// 마지막으로 failover 투표를 한 epoch
+    last_vote_epoch: u64,
+    nodes: Vec<ClusterNode>,
+    /// 슬롯별 주인 노드 ID
+    slots: Vec<Option<String>>,
+    /// 이 노드에서 다른 노드로 옮기는 중인 슬롯
+    migrating: HashMap<u16, String>,
+    /// 다른 노드에서 이 노드로 가져오는 중인 슬롯
+    importing: HashMap<u16, String>,
+    /// CLUSTER MEET 으로 받은, 아직 MEET 을 보내지 않은 버스 주소
+    meets: Vec<MasterAddr>,
+    failover: Option<Failover>,
+}
+
+impl ClusterState {
+    fn new(myself: String) -> Self {
+        ClusterState {
+            myself,
+            current_epoch: 0,
+            last_vote_epoch: 0,
+            nodes: Vec::new(),
+            slots: vec![None; CLUSTER_SLOTS],
+            migrating: HashMap::new(),
+            importing: HashMap::new(),
+            meets: Vec::new(),
+            failover: None,
+        }
+    }
+
+    fn node(&self, id: &str) -> Option<&ClusterNode> {
+        self.nodes.iter().find(|n| n.id == id)
+    }
+
+    fn node_mut(&mut self, id: &str) -> Option<&mut ClusterNode> {
+        self.nodes.iter_mut().find(|n| n.id == id)
+    }
+
+    fn myself(&self) -> &ClusterNode {
+        self.node(&self.myself).expect("myself is always in the node table")
+    }
+
+    fn myself_mut(&mut self) -> &mut ClusterNode {
+        let myself = self.myself.clone();
+        self.node_mut(&myself).expect("myself is always in the node table")
+    }
+
+    /// 모든 슬롯에 주인이 있고 FAIL 인 주인이 없다
+    fn is_ok(&self) -> bool {
+        let failed: Vec<&str> = self.nodes.iter().filter(|n| n.fail).map(|n| n.id.as_str()).collect();
+        self.slots.iter().all(|owner| match owner {
+            Some(id) => !failed.contains(&id.as_str()),
+            None => false,
+        })
+    }
+
+    fn has_slots(&self, id: &str) -> bool {
+        self.slots.iter().any(|owner| owner.as_deref() == Some(id))
+    }
+
+    /// 슬롯을 가진 master 수. 장애 판단과 failover 투표의 과반수는 이 수를 기준으로 한다.
+    fn size(&self) -> usize {
+        self.nodes.iter().filter(|n| n.is_master() && self.has_slots(&n.id)).count()
+    }
+
+    /// 노드가 가진 슬롯들을 연속 구간으로 묶는다
+    fn ranges(&self, id: &str) -> Vec<(u16, u16)> {
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        for (slot, owner) in self.slots.iter().enumerate() {
+            if owner.as_deref() != Some(id) {
+                continue;
+            }
+            let slot = slot as u16;
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == slot => *end = slot,
+                _ => ranges.push((slot, slot)),
+            }
+        }
+        ranges
+    }
+
+    /// 다른 노드와 합의 없이 이 노드의 config epoch 를 가장 크게 만든다 (SETSLOT NODE 로 슬롯을 가져왔을 때)
+    fn bump_config_epoch(&mut self) {
+        let max_epoch = self.nodes.iter().map(|n| n.config_epoch).max().unwrap_or(0);
+        if self.myself().config_epoch == 0 || self.myself().config_epoch != max_epoch {
+            self.current_epoch += 1;
+            let epoch = self.current_epoch;
+            self.myself_mut().config_epoch = epoch;
+        }
+    }
+
+    /// nodes.conf 한 줄 (CLUSTER NODES 와 같은 형식)
+    fn node_line(&self, node: &ClusterNode) -> String {
+        let myself = node.id == self.myself;
+        let link = if myself || !(node.pfail || node.fail) { "connected" } else { "disconnected" };
+        let mut line = format!(
+            "{} {}:{}@{} {} {} 0 0 {} {}",
+            node.id,
+            node.ip,
+            node.port,
+            node.cport,
+            node.flags(myself),
+            node.master_id.as_deref().unwrap_or("-"),
+            node.config_epoch,
+            link,
+        );
+        for (start, end) in self.ranges(&node.id) {
+            if start == end {
+                line.push_str(&format!(" {}", start));
+            } else {
+                line.push_str(&format!(" {}-{}", start, end));
+            }
+        }
+        if myself {
+            let mut migrating: Vec<_> = self.migrating.iter().collect();
+            migrating.sort();
+            for (slot, to) in migrating {
+                line.push_str(&format!(" [{}->-{}]", slot, to));
+            }
+            let mut importing: Vec<_> = self.importing.iter().collect();
+            importing.sort();
+            for (slot, from) in importing {
+                line.push_str(&format!(" [{}-<-{}]", slot, from));
+            }
+        }
+        line
+    }
+
+    fn parse(content: &str, port: u16) -> Result<ClusterState> {
+        let mut state = ClusterState::new(String::new());
+
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [] => continue,
+                ["vars", vars @ ..] => {
+                    for pair in vars.chunks(2) {
+                        match pair {
+                            ["currentEpoch", epoch] => state.current_epoch = epoch.parse()?,
+                            ["lastVoteEpoch", epoch] => state.last_vote_epoch = epoch.parse()?,
+                            _ => {}
+                        }
+                    }
+                }
+                [id, addr, flags, master, _ping, _pong, epoch, _link, slots @ ..] => {
+                    // ip:port@cport (뒤에 ,hostname 이 붙을 수 있다)
+                    let addr = addr.split(',').next().unwrap_or(addr);
+                    let (ip_port, cport) = addr.split_once('@').ok_or_else(|| anyhow!("Invalid node address: {}", addr))?;
+                    let (ip, node_port) = ip_port.rsplit_once(':').ok_or_else(|| anyhow!("Invalid node address: {}", addr))?;
+                    let flags: Vec<&str> = flags.split(',').collect();
+                    let myself = flags.contains(&"myself");
+                    // 설정 파일과 다른 포트로 띄웠으면 지금 포트를 쓴다
+                    let (node_port, cport) = match myself {
+                        true => (port, port + BUS_PORT_OFFSET),
+                        false => (node_port.parse()?, cport.parse()?),
+                    };
+                    let mut node = ClusterNode::new(id.to_string(), ip.to_string(), node_port, cport);
+                    if flags.contains(&"slave") {
+                        node.master_id = Some(master.to_string());
+                    }
+                    node.config_epoch = epoch.parse()?;
+                    if flags.contains(&"fail") {
+                        node.fail = true;
+                        node.fail_time = Some(Instant::now());
+                    }
+                    if myself {
+                        state.myself = node.id.clone();
+                    }
+
+                    for slot in slots.iter() {
+                        if let Some(migration) = slot.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                            if let Some((slot, to)) = migration.split_once("->-") {
+                                state.migrating.insert(parse_slot(slot)?, to.to_string());
+                            } else if let Some((slot, from)) = migration.split_once("-<-") {
+                                state.importing.insert(parse_slot(slot)?, from.to_string());
+                            }
+                            continue;
+                        }
+                        let (start, end) = match slot.split_once('-') {
+                            Some((start, end)) => (parse_slot(start)?, parse_slot(end)?),
+                            None => (parse_slot(slot)?, parse_slot(slot)?),
+                        };
+                        for s in start..=end {
+                            state.slots[s as usize] = Some(node.id.clone());
+                        }
+                    }
+                    state.nodes.push(node);
+                }
+                _ => bail!("Invalid line in cluster config: {:?}", line),
+            }
+        }
+
+        if state.myself.is_empty() {
+            bail!("Cluster config has no myself node");
+        }
+        Ok(state)
+    }
+}
+
+/// 클러스터 노드 테이블과 슬롯 배정.
+/// 노드 테이블은 cluster-config-file (nodes.conf) 에 저장되고, 바뀔 때마다 다시 쓴다.
+#[derive(Debug)]
+pub struct Cluster {
+    config_path: String,
+    node_timeout: Duration,
+    state: Mutex<ClusterState>,
+}
+
+impl Cluster {
+    /// 설정 파일이 있으면 읽고, 없으면 새 node ID 로 혼자인 클러스터를 만든다.
+    pub fn load(config_path: &str, port: u16, node_timeout: Duration) -> Result<Cluster> {
+        let state = if Path::new(config_path).exists() {
+            ClusterState::parse(&fs::read_to_string(config_path)?, port)?
+        } else {
+            let myself = ClusterNode::new(new_replid(), "127.0.0.1".to_string(), port, port + BUS_PORT_OFFSET);
+            let mut state = ClusterState::new(myself.id.clone());
+            state.nodes.push(myself);
+            state
+        };
+
+        let cluster = Cluster { config_path: config_path.to_string(), node_timeout, state: Mutex::new(state) };
+        cluster.save()?;
+        Ok(cluster)
+    }
+
+    /// 노드 테이블을 cluster-config-file 에 쓴다
+    fn save(&self) -> Result<()> {
+        let content = {
+            let state = self.state.lock().unwrap();
+            let mut content = String::new();
+            for node in &state.nodes {
+                content.push_str(&state.node_line(node));
+                content.push('\n');
+            }
+            content.push_str(&format!("vars currentEpoch {} lastVoteEpoch {}\n", state.current_epoch, state.last_vote_epoch));
+            content
+        };
+
+        // 쓰다가 죽어도 이전 파일이 남도록 임시 파일에 쓰고 이름을 바꾼다
+        let temp_path = format!("{}.tmp", self.config_path);
+        fs::write(&temp_path, content)?;
+        fs::rename(&temp_path, &self.config_path)?;
+        Ok(())
+    }
+
+    /// 클러스터 버스에서 바뀐 상태를 저장한다. 실패해도 메모리의 상태로 계속 동작한다.
+    fn save_or_log(&self) {
+        if let Err(e) = self.save() {
+            eprintln!("Failed to save cluster config: {:?}", e);
+        }
+    }
+
+    pub fn myid(&self) -> String {
+        self.state.lock().unwrap().myself.clone()
+    }
+
+    pub fn node_timeout(&self) -> Duration {
+        self.node_timeout
+    }
+
+    /// 이 노드의 클러스터 버스 포트
+    pub fn cport(&self) -> u16 {
+        self.state.lock().unwrap().myself().cport
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.state.lock().unwrap().is_ok()
+    }
+
+    /// 이 노드가 replica 면 따라야 할 master 의 주소
+    pub fn master_addr(&self) -> Option<MasterAddr> {
+        let state = self.state.lock().unwrap();
+        let master = state.node(state.myself().master_id.as_deref()?)?;
+        Some(MasterAddr { host: master.ip.clone(), port: master.port })
+    }
+
+    /// 슬롯의 주인 노드
+    pub fn slot_owner(&self, slot: u16) -> Option<ClusterNode> {
+        let state = self.state.lock().unwrap();
+        state.slots[slot as usize].as_deref().and_then(|id| state.node(id)).cloned()
+    }
+
+    /// 이 노드 (또는 이 노드의 master) 가 주인인 슬롯인지. 아니면 그 슬롯의 shard 채널 구독을 풀어야 한다.
+    pub fn serves_slot(&self, slot: u16) -> bool {
+        let state = self.state.lock().unwrap();
+        let Some(owner) = state.slots[slot as usize].as_deref() else {
+            return false;
+        };
+        owner == state.myself || state.myself().master_id.as_deref() == Some(owner)
+    }
+
+    /// 요청의 키들을 이 노드에서 처리해도 되는지 판단한다.
+    /// 슬롯을 옮기는 중이면 키가 아직 이 노드에 있는지 `store` 에서 확인한다.
+    pub async fn route(&self, store: &Store, keys: &[&str], asking: bool) -> Result<(), Redirect> {
+        let Some((first, rest)) = keys.split_first() else {
+            return Ok(());
+        };
+        let slot = key_hash_slot(first);
+        if rest.iter().any(|key| key_hash_slot(key) != slot) {
+            return Err(Redirect::CrossSlot);
+        }
+
+        let (owner, mine, migrating_to, importing) = {
+            let state = self.state.lock().unwrap();
+            if !state.is_ok() {
+                return Err(Redirect::Down);
+            }
+            let owner = state.slots[slot as usize].as_deref().and_then(|id| state.node(id)).ok_or(Redirect::Unbound)?;
+            let mine = owner.id == state.myself;
+            let migrating_to = state.migrating.get(&slot).and_then(|id| state.node(id)).filter(|_| mine).map(|n| n.addr());
+            let importing = !mine && state.importing.contains_key(&slot);
+            (owner.addr(), mine, migrating_to, importing)
+        };
+
+        if migrating_to.is_some() || importing {
+            let mut missing = 0;
+            for key in keys {
+                if !store.contains(key).await {
+                    missing += 1;
+                }
+            }
+
+            if let Some(target) = migrating_to {
+                // 옮겨 간 키는 새 노드에 물어보게 한다. 일부만 옮겨 갔으면 다 옮겨질 때까지 다시 시도하게 한다.
+                match missing {
+                    0 => {}
+                    n if n == keys.len() => return Err(Redirect::Ask(slot, target)),
+                    _ => return Err(Redirect::TryAgain),
+                }
+            }
+            if importing && asking {
+                if keys.len() > 1 && missing > 0 {
+                    return Err(Redirect::TryAgain);
+                }
+                return Ok(());
+            }
+        }
+
+        if !mine {
+            return Err(Redirect::Moved(slot, owner));
+        }
+        Ok(())
+    }
+
+    /// 이 노드에 슬롯들을 배정한다. 이미 주인이 있는 슬롯이 있으면 아무것도 바꾸지 않는다.
+    pub fn add_slots(&self, slots: &[u16]) -> Result<()> {
+        {
+            let mut state = self.state.lock().unwrap();
+            for (i, slot) in slots.iter().enumerate() {
+                if state.slots[*slot as usize].is_some() || slots[..i].contains(slot) {
+                    bail!("Slot {} is already busy", slot);
+                }
+            }
+            let myself = state.myself.clone();
+            for slot in slots {
+                state.slots[*slot as usize] = Some(myself.clone());
+                state.importing.remove(slot);
+            }
+        }
+        self.save()
+    }
+
+    /// 슬롯 배정을 지운다
+    pub fn del_slots(&self, slots: &[u16]) -> Result<()> {
+        {
+            let mut state = self.state.lock().unwrap();
+            for (i, slot) in slots.iter().enumerate() {
+                if state.slots[*slot as usize].is_none() || slots[..i].contains(slot) {
+                    bail!("Slot {} is already unassigned", slot);
+                }
+            }
+            for slot in slots {
+                state.slots[*slot as usize] = None;
+                state.migrating.remove(slot);
+                state.importing.remove(slot);
+            }
+        }
+        self.save()
+    }
+
+    /// CLUSTER SETSLOT. `keys_in_slot` 은 이 노드에 남아 있는 그 슬롯의 키 수.
+    pub fn set_slot(&self, slot: u16, action: SetSlot, keys_in_slot: usize) -> Result<()> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let owner = state.slots[slot as usize].clone();
+            let mine = owner.as_deref() == Some(state.myself.as_str());
+            let known = |id: &str| match state.node(id) {
+                Some(node) => Ok(node.is_master()),
+                None => Err(anyhow!("I don't know about node {}", id)),
+            };
+
+            match action {
+                SetSlot::Migrating(to) => {
+                    if !mine {
+                        bail!("I'm not the owner of hash slot {}", slot);
+                    }
+                    if !known(&to)? {
+                        bail!("Target node is not a master");
+                    }
+                    state.migrating.insert(slot, to);
+                }
+                SetSlot::Importing(from) => {
+                    if mine {
+                        bail!("I'm already the owner of hash slot {}", slot);
+                    }
+                    if !known(&from)? {
+                        bail!("Target node is not a master");
+                    }
+                    state.importing.insert(slot, from);
+                }
+                SetSlot::Stable => {
+                    state.migrating.remove(&slot);
+                    state.importing.remove(&slot);
+                }
+                SetSlot::Node(id) => {
+                    if !known(&id)? {
+                        bail!("Target node is not a master");
+                    }
+                    let to_myself = id == state.myself;
+                    if mine && !to_myself && keys_in_slot > 0 {
+                        bail!("Can't assign hashslot {} to a different node while I still hold keys for this hash slot.", slot);
+                    }
+                    if mine && !to_myself {
+                        state.migrating.remove(&slot);
+                    }
+                    // 가져오기가 끝났으면 다른 노드들이 새 주인을 따르도록 config epoch 를 올린다
+                    if to_myself && state.importing.remove(&slot).is_some() {
+                        state.bump_config_epoch();
+                    }
+                    state.slots[slot as usize] = Some(id);
+                }
+            }
+        }
+        self.save()
+    }
+
+    /// CLUSTER REPLICATE: 이 노드를 다른 master 의 replica 로 만든다
+    pub fn replicate(&self, id: &str) -> Result<MasterAddr> {
+        let addr = {
+            let mut state = self.state.lock().unwrap();
+            let master = state.node(id).ok_or_else(|| anyhow!("Unknown node {}", id))?;
+            if master.id == state.myself {
+                bail!("Can't replicate myself");
+            }
+            if !master.is_master() {
+                bail!("I can only replicate a master, not a replica.");
+            }
+            let addr = MasterAddr { host: master.ip.clone(), port: master.port };
+            if state.myself().is_master() && state.has_slots(&state.myself) {
+                bail!("To set a master the node must be empty and without assigned slots.");
+            }
+            state.myself_mut().master_id = Some(id.to_string());
+            state.migrating.clear();
+            state.importing.clear();
+            addr
+        };
+        self.save()?;
+        Ok(addr)
+    }
+
+    /// CLUSTER MEET: 다음 클러스터 버스 주기에 MEET 을 보낸다
+    pub fn meet(&self, ip: &str, cport: u16) {
+        self.state.lock().unwrap().meets.push(MasterAddr { host: ip.to_string(), port: cport });
+    }
+
+    pub fn take_meets(&self) -> Vec<MasterAddr> {
+        std::mem::take(&mut self.state.lock().unwrap().meets)
+    }
+
+    /// 이 노드의 상태와 알고 있는 다른 노드들의 gossip 을 담은 헤더
+    pub fn header(&self, repl_offset: u64) -> Header {
+        let state = self.state.lock().unwrap();
+        let myself = state.myself();
+        Header {
+            sender: myself.id.clone(),
+            ip: myself.ip.clone(),
+            port: myself.port,
+            cport: myself.cport,
+            master_id: myself.master_id.clone(),
+            config_epoch: myself.config_epoch,
+            current_epoch: state.current_epoch,
+            repl_offset,
+            slots: state.ranges(&myself.id),
+            gossip: state
+                .nodes
+                .iter()
+                .filter(|n| n.id != state.myself)
+                .map(|n| Gossip { id: n.id.clone(), ip: n.ip.clone(), port: n.port, cport: n.cport, pfail: n.pfail, fail: n.fail })
+                .collect(),
+        }
+    }
+
+    /// 다른 노드가 보낸 헤더를 반영한다. 모르는 노드가 보냈으면 `trusted` (MEET 과 그 응답) 일 때만 받아들인다.
+    /// 이 노드가 다른 master 의 replica 가 되어야 하면 그 주소를 돌려준다.
+    pub fn process(&self, h: &Header, trusted: bool) -> Option<MasterAddr> {
+        let mut state = self.state.lock().unwrap();
+        if h.sender == state.myself {
+            return None;
+        }
+        let mut changed = false;
+        if state.node(&h.sender).is_none() {
+            if !trusted {
+                return None;
+            }
+            println!("Added cluster node {} at {}:{}", h.sender, h.ip, h.port);
+            state.nodes.push(ClusterNode::new(h.sender.clone(), h.ip.clone(), h.port, h.cport));
+            changed = true;
+        }
+
+        if h.current_epoch > state.current_epoch {
+            state.current_epoch = h.current_epoch;
+            changed = true;
+        }
+        {
+            let sender = state.node_mut(&h.sender).unwrap();
+            changed |= sender.ip != h.ip || sender.port != h.port || sender.master_id != h.master_id || sender.config_epoch != h.config_epoch;
+            sender.ip = h.ip.clone();
+            sender.port = h.port;
+            sender.cport = h.cport;
+            sender.master_id = h.master_id.clone();
+            sender.config_epoch = h.config_epoch;
+            sender.repl_offset = h.repl_offset;
+        }
+
+        // gossip: 모르는 노드를 추가하고, master 가 보낸 장애 보고를 기록한다
+        let now = Instant::now();
+        for g in &h.gossip {
+            if g.id == state.myself {
+                continue;
+            }
+            match state.node_mut(&g.id) {
+                Some(node) => {
+                    if h.master_id.is_some() {
+                        continue;
+                    }
+                    if g.pfail || g.fail {
+                        node.fail_reports.insert(h.sender.clone(), now);
+                    } else {
+                        node.fail_reports.remove(&h.sender);
+                    }
+                }
+                None => {
+                    println!("Discovered cluster node {} at {}:{} through gossip", g.id, g.ip, g.port);
+                    state.nodes.push(ClusterNode::new(g.id.clone(), g.ip.clone(), g.port, g.cport));
+                    changed = true;
+                }
+            }
+        }
+
+        let mut role_change = None;
+        if h.master_id.is_some() {
+            // replica 가 된 노드는 슬롯을 가질 수 없다
+            for owner in state.slots.iter_mut() {
+                if owner.as_deref() == Some(h.sender.as_str()) {
+                    *owner = None;
+                    changed = true;
+                }
+            }
+        } else {
+            // 더 큰 config epoch 로 주장하는 슬롯은 보낸 노드에게 넘긴다
+            let mut lost_from = HashSet::new();
+            for (start, end) in &h.slots {
+                for slot in *start..=*end {
+                    if state.importing.contains_key(&slot) {
+                        continue;
+                    }
+                    if let Some(owner) = state.slots[slot as usize].as_deref() {
+                        if owner == h.sender {
+                            continue;
+                        }
+                        if state.node(owner).is_some_and(|n| n.config_epoch >= h.config_epoch) {
+                            continue;
+                        }
+                        lost_from.insert(owner.to_string());
+                    }
+                    state.slots[slot as usize] = Some(h.sender.clone());
+                    changed = true;
+                }
+            }
+
+            // 두 master 의 config epoch 가 같으면 node ID 가 작은 쪽이 epoch 를 올린다
+            let myself = state.myself();
+            if myself.is_master() && myself.config_epoch == h.config_epoch && state.myself < h.sender {
+                state.current_epoch += 1;
+                let epoch = state.current_epoch;
+                state.myself_mut().config_epoch = epoch;
+                println!("Config epoch collision with {}, moving to config epoch {}", h.sender, epoch);
+                changed = true;
+            }
+
+            // 이 노드 (또는 이 노드의 master) 가 슬롯을 모두 잃었으면 새 주인의 replica 가 된다
+            let myself = state.myself();
+            let shard_master = myself.master_id.clone().unwrap_or_else(|| myself.id.clone());
+            if lost_from.contains(&shard_master) && !state.has_slots(&shard_master) {
+                println!("Lost all slots to {}, replicating it", h.sender);
+                state.myself_mut().master_id = Some(h.sender.clone());
+                state.migrating.clear();
+                state.importing.clear();
+                role_change = Some(MasterAddr { host: h.ip.clone(), port: h.port });
+                changed = true;
+            }
+        }
+
+        drop(state);
+        if changed {
+            self.save_or_log();
+        }
+        role_change
+    }
+
+    /// PING 을 보낼 노드들 (마지막 PING 에서 `interval` 이 지난 노드)
+    pub fn ping_targets(&self, interval: Duration) -> Vec<(String, MasterAddr)> {
+        let mut state = self.state.lock().unwrap();
+        let myself = state.myself.clone();
+        let now = Instant::now();
+        state
+            .nodes
+            .iter_mut()
+            .filter(|n| n.id != myself && n.ping_sent.is_none_or(|t| t.elapsed() >= interval))
+            .map(|n| {
+                n.ping_sent = Some(now);
+                (n.id.clone(), n.bus_addr())
+            })
+            .collect()
+    }
+
+    /// PONG 을 받았다. 돌아온 노드의 FAIL 은 더 이상 문제가 되지 않으면 푼다.
+    pub fn pong_received(&self, id: &str) {
+        let node_timeout = self.node_timeout;
+        let mut state = self.state.lock().unwrap();
+        let has_slots = state.has_slots(id);
+        let Some(node) = state.node_mut(id) else {
+            return;
+        };
+        node.pong_received = Instant::now();
+        node.pfail = false;
+        let undo = !node.is_master() || !has_slots || node.fail_time.is_some_and(|t| t.elapsed() > node_timeout * FAIL_UNDO_TIME_MULT);
+        if node.fail && undo {
+            println!("Clear FAIL state for node {}", id);
+            node.fail = false;
+            node.fail_time = None;
+            drop(state);
+            self.save_or_log();
+        }
+    }
+
+    /// node timeout 동안 응답이 없는 노드를 PFAIL 로, 과반수의 master 가 동의한 PFAIL 노드를 FAIL 로 바꾼다.
+    /// 새로 FAIL 이 된 노드를 돌려준다.
+    pub fn update_failures(&self) -> Vec<String> {
+        let node_timeout = self.node_timeout;
+        let mut state = self.state.lock().unwrap();
+        let needed = state.size() / 2 + 1;
+        let masters: HashSet<String> = state.nodes.iter().filter(|n| n.is_master()).map(|n| n.id.clone()).collect();
+        let myself_is_master = state.myself().is_master();
+        let myself = state.myself.clone();
+
+        let mut failed = Vec::new();
+        for node in state.nodes.iter_mut().filter(|n| n.id != myself) {
+            if !node.pfail && node.pong_received.elapsed() > node_timeout {
+                println!("Marking node {} as failing (quorum not reached yet)", node.id);
+                node.pfail = true;
+            }
+            node.fail_reports.retain(|reporter, at| masters.contains(reporter) && at.elapsed() <= node_timeout * FAIL_REPORT_VALIDITY_MULT);
+            if node.pfail && !node.fail {
+                let reports = node.fail_reports.len() + myself_is_master as usize;
+                if reports >= needed {
+                    println!("Marking node {} as failing (quorum reached: {}/{})", node.id, reports, needed);
+                    node.fail = true;
+                    node.fail_time = Some(Instant::now());
+                    failed.push(node.id.clone());
+                }
+            }
+        }
+
+        drop(state);
+        if !failed.is_empty() {
+            self.save_or_log();
+        }
+        failed
+    }
+
+    /// 다른 노드가 보낸 FAIL
+    pub fn mark_failed(&self, id: &str) {
+        let mut state = self.state.lock().unwrap();
+        if id == state.myself {
+            return;
+        }
+        if let Some(node) = state.node_mut(id).filter(|n| !n.fail) {
+            println!("FAIL message received about {}", id);
+            node.fail = true;
+            node.fail_time = Some(Instant::now());
+            drop(state);
+            self.save_or_log();
+        }
+    }
+
+    /// 이 노드를 뺀 모든 노드의 버스 주소
+    pub fn bus_addrs(&self) -> Vec<MasterAddr> {
+        let state = self.state.lock().unwrap();
+        state.nodes.iter().filter(|n| n.id != state.myself).map(|n| n.bus_addr()).collect()
+    }
+
+    /// failover 투표를 할 수 있는 master 들의 버스 주소와 이기는 데 필요한 표 수
+    pub fn voters(&self) -> (Vec<MasterAddr>, usize) {
+        let state = self.state.lock().unwrap();
+        let voters = state
+            .nodes
+            .iter()
+            .filter(|n| n.is_master() && !n.fail && n.id != state.myself && state.has_slots(&n.id))
+            .map(|n| n.bus_addr())
+            .collect();
+        (voters, state.size() / 2 + 1)
+    }
+
+    /// replica 의 failover 투표 요청. 한 epoch 에 한 번, 같은 master 의 replica 에게는 failover timeout 에 한 번만 투표한다.
+    pub fn vote(&self, request: &Header) -> bool {
+        let node_timeout = self.node_timeout;
+        let mut state = self.state.lock().unwrap();
+        if !state.myself().is_master() || !state.has_slots(&state.myself) {
+            return false;
+        }
+        if request.current_epoch < state.current_epoch || state.last_vote_epoch == state.current_epoch {
+            return false;
+        }
+        let Some(master_id) = request.master_id.as_deref() else {
+            return false;
+        };
+        let Some(master) = state.node(master_id) else {
+            return false;
+        };
+        if !master.fail || master.voted_time.is_some_and(|t| t.elapsed() < node_timeout * FAILOVER_TIMEOUT_MULT) {
+            return false;
+        }
+        // 요청한 replica 가 가져갈 슬롯을 더 새로운 설정의 주인이 갖고 있으면 안 된다
+        let master_epoch = master.config_epoch;
+        for (start, end) in &request.slots {
+            for slot in *start..=*end {
+                let owner_epoch = state.slots[slot as usize].as_deref().and_then(|id| state.node(id)).map(|n| n.config_epoch);
+                if owner_epoch.is_some_and(|e| e > master_epoch) {
+                    return false;
+                }
+            }
+        }
+
+        state.last_vote_epoch = state.current_epoch;
+        state.node_mut(master_id).unwrap().voted_time = Some(Instant::now());
+        println!("Failover auth granted to {} for epoch {}", request.sender, state.current_epoch);
+        drop(state);
+        self.save_or_log();
+        true
+    }
+
+    /// master 가 FAIL 인 replica 가 투표를 요청할 때가 되면 새 epoch 를 열고 돌려준다.
+    /// offset 이 큰 replica 일수록 먼저 요청하도록 순위만큼 늦게 시작한다.
+    pub fn failover_request(&self, repl_offset: u64) -> Option<u64> {
+        let node_timeout = self.node_timeout;
+        let mut state = self.state.lock().unwrap();
+        let master_id = state.myself().master_id.clone();
+        let master_failed = master_id.as_deref().and_then(|id| state.node(id)).is_some_and(|m| m.fail && state.has_slots(&m.id));
+        if !master_failed {
+            state.failover = None;
+            return None;
+        }
+
+        let now = Instant::now();
+        match &mut state.failover {
+            None => {
+                let rank = state
+                    .nodes
+                    .iter()
+                    .filter(|n| n.id != state.myself && n.master_id == master_id && n.repl_offset > repl_offset)
+                    .count();
+                let delay = Duration::from_millis(500) + random_delay(Duration::from_millis(500)) + Duration::from_secs(rank as u64);
+                println!("Start of election delayed for {}ms (rank #{}, offset {})", delay.as_millis(), rank, repl_offset);
+                state.failover = Some(Failover { auth_time: now + delay, epoch: None });
+                None
+            }
+            Some(failover) if now < failover.auth_time => None,
+            Some(failover) if failover.epoch.is_some() => {
+                // 투표를 모으지 못했으면 처음부터 다시 한다
+                if now > failover.auth_time + node_timeout * FAILOVER_TIMEOUT_MULT {
+                    state.failover = None;
+                }
+                None
+            }
+            Some(_) => {
+                state.current_epoch += 1;
+                let epoch = state.current_epoch;
+                state.failover.as_mut().unwrap().epoch = Some(epoch);
+                println!("Starting a failover election for epoch {}", epoch);
+                drop(state);
+                self.save_or_log();
+                Some(epoch)
+            }
+        }
+    }
+
+    /// 투표에서 이겼다. master 의 슬롯을 가져와 master 가 된다.
+    pub fn promote(&self, epoch: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.failover.as_ref().and_then(|f| f.epoch) != Some(epoch) {
+            return false;
+        }
+        let Some(old_master) = state.myself().master_id.clone() else {
+            return false;
+        };
+
+        let myself = state.myself.clone();
+        for owner in state.slots.iter_mut() {
+            if owner.as_deref() == Some(old_master.as_str()) {
+                *owner = Some(myself.clone());
+            }
+        }
+        let me = state.myself_mut();
+        me.master_id = None;
+        me.config_epoch = epoch;
+        state.failover = None;
+        println!("Failover election won, promoted to master with config epoch {}", epoch);
+        drop(state);
+        self.save_or_log();
+        true
+    }
+
+    /// master 별 슬롯 구간과 replica 들. 슬롯이 없는 master 도 포함한다.
+    pub fn shards(&self) -> Vec<Shard> {
+        let state = self.state.lock().unwrap();
+        state
+            .nodes
+            .iter()
+            .filter(|n| n.is_master())
+            .map(|master| Shard {
+                master: master.clone(),
+                replicas: state.nodes.iter().filter(|n| n.master_id.as_deref() == Some(&master.id)).cloned().collect(),
+                ranges: state.ranges(&master.id),
+            })
+            .collect()
+    }
+
+    /// CLUSTER NODES
+    pub fn nodes(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut nodes = String::new();
+        for node in &state.nodes {
+            nodes.push_str(&state.node_line(node));
+            nodes.push('\n');
+        }
+        nodes
+    }
+
+    /// CLUSTER INFO
+    pub fn info(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let assigned = state.slots.iter().filter(|s| s.is_some()).count();
+        let slots_of = |pred: fn(&ClusterNode) -> bool| {
+            state.nodes.iter().filter(|n| pred(n)).map(|n| state.slots.iter().filter(|s| s.as_deref() == Some(&n.id)).count()).sum::<usize>()
+        };
+        let pfail = slots_of(|n| n.pfail && !n.fail);
+        let fail = slots_of(|n| n.fail);
+        format!(
+            "cluster_enabled:1\r\n\
+             cluster_state:{}\r\n\
+             cluster_slots_assigned:{}\r\n\
+             cluster_slots_ok:{}\r\n\
+             cluster_slots_pfail:{}\r\n\
+             cluster_slots_fail:{}\r\n\
+             cluster_known_nodes:{}\r\n\
+             cluster_size:{}\r\n\
+             cluster_current_epoch:{}\r\n\
+             cluster_my_epoch:{}\r\n",
+            if state.is_ok() { "ok" } else { "fail" },
+            assigned,
+            assigned - pfail - fail,
+            pfail,
+            fail,
+            state.nodes.len(),
+            state.size(),
+            state.current_epoch,
+            state.myself().config_epoch,
+        )
+    }
+}
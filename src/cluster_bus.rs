@@ -0,0 +1,223 @@
+use crate::client::Client;
+use crate::cluster::{Cluster, Message};
+use crate::protocol::decoder::{FrameError, RedisDecoder, Reply};
+use crate::protocol::encoder::RedisEncoder;
+use crate::protocol::error::RedisError;
+use crate::replication::MasterAddr;
+use crate::server::ServerState;
+use anyhow::Result;
+use bytes::{Buf, BytesMut};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// 클러스터 버스 cron 주기
+const CRON_PERIOD: Duration = Duration::from_millis(100);
+/// 다른 노드에 PING 을 보내는 주기 (node timeout 의 절반보다 길지 않게)
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+/// 다른 노드에 보내는 메시지 하나의 타임아웃
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// 클러스터 버스 포트 (클라이언트 포트 + 10000) 에서 다른 노드의 메시지를 받고, 주기적으로 gossip 을 보낸다.
+pub async fn run(state: Arc<ServerState>) {
+    let Some(cluster) = &state.cluster else {
+        return;
+    };
+    let listener = match TcpListener::bind(format!("127.0.0.1:{}", cluster.cport())).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to bind cluster bus port {}: {:?}", cluster.cport(), e);
+            return;
+        }
+    };
+
+    tokio::spawn(cron(Arc::clone(&state)));
+
+    loop {
+        let socket = match listener.accept().await {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                eprintln!("Cluster bus accept failed: {:?}", e);
+                continue;
+            }
+        };
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            if let Err(err) = handle_link(socket, state).await {
+                eprintln!("Cluster bus error: {:?}", err);
+            }
+        });
+    }
+}
+
+/// 다른 노드가 맺은 링크. 메시지마다 응답 하나를 돌려준다.
+async fn handle_link(mut socket: TcpStream, state: Arc<ServerState>) -> Result<()> {
+    let cluster = state.cluster.as_ref().unwrap();
+    let decoder = RedisDecoder::new();
+    let encoder = RedisEncoder::new();
+    let mut buf = BytesMut::with_capacity(1024);
+    let mut response = BytesMut::new();
+
+    loop {
+        if socket.read_buf(&mut buf).await? == 0 {
+            return Ok(());
+        }
+        loop {
+            let args = match decoder.parse_args(&buf) {
+                Ok((args, consumed)) => {
+                    buf.advance(consumed);
+                    args
+                }
+                Err(FrameError::Incomplete) => break,
+                Err(FrameError::Invalid | FrameError::TooLarge(_)) => return Ok(()),
+            };
+
+            match Message::parse(&args) {
+                Some(Message::Meet(header)) | Some(Message::Ping(header)) => {
+                    let trusted = args[0] == "MEET";
+                    if let Some(master) = cluster.process(&header, trusted) {
+                        follow(&state, master).await;
+                    }
+                    let pong = Message::Pong(cluster.header(state.replication.master_repl_offset()));
+                    encode_message(&encoder, &mut response, &pong);
+                }
+                Some(Message::Fail(_, node)) => {
+                    cluster.mark_failed(&node);
+                    encoder.encode_ok(&mut response);
+                }
+                Some(Message::AuthRequest(header)) => {
+                    if let Some(master) = cluster.process(&header, false) {
+                        follow(&state, master).await;
+                    }
+                    encoder.encode_integer(&mut response, cluster.vote(&header) as i64);
+                }
+                Some(Message::Pong(_)) | None => encoder.encode_error(&mut response, &RedisError::Err("unknown cluster bus message".to_string())),
+            }
+        }
+        socket.write_all(&response).await?;
+        response.clear();
+    }
+}
+
+fn encode_message(encoder: &RedisEncoder, dst: &mut BytesMut, message: &Message) {
+    let args = message.to_args();
+    let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+    encoder.encode_array(dst, &args);
+}
+
+/// 슬롯을 모두 잃은 master (또는 그 replica) 가 새 주인을 따르게 한다
+async fn follow(state: &ServerState, master: MasterAddr) {
+    let _lock = state.command_lock.lock().await;
+    state.replication.set_master(Some(master));
+}
+
+/// MEET, PING, 장애 감지, failover 선거
+async fn cron(state: Arc<ServerState>) {
+    let cluster = state.cluster.as_ref().unwrap();
+    let mut links = Links { clients: HashMap::new() };
+    let mut interval = tokio::time::interval(CRON_PERIOD);
+
+    loop {
+        interval.tick().await;
+        let offset = state.replication.master_repl_offset();
+
+        for addr in cluster.take_meets() {
+            match links.send(&addr, &Message::Meet(cluster.header(offset))).await {
+                Some(Message::Pong(header)) => {
+                    if let Some(master) = cluster.process(&header, true) {
+                        follow(&state, master).await;
+                    }
+                    cluster.pong_received(&header.sender);
+                }
+                _ => eprintln!("No reply to MEET from {}:{}", addr.host, addr.port),
+            }
+        }
+
+        ping_nodes(&state, cluster, &mut links, PING_INTERVAL.min(cluster.node_timeout() / 2)).await;
+
+        for node in cluster.update_failures() {
+            let fail = Message::Fail(cluster.myid(), node);
+            for addr in cluster.bus_addrs() {
+                links.send(&addr, &fail).await;
+            }
+        }
+
+        if let Some(epoch) = cluster.failover_request(offset) {
+            let (voters, needed) = cluster.voters();
+            let request = Message::AuthRequest(cluster.header(offset));
+            let mut acks = 0;
+            for addr in voters {
+                if let Some(Reply::Integer(1)) = links.command(&addr, &request).await {
+                    acks += 1;
+                }
+            }
+            println!("Failover election for epoch {}: {}/{} votes", epoch, acks, needed);
+            if acks >= needed && cluster.promote(epoch) {
+                {
+                    let _lock = state.command_lock.lock().await;
+                    state.replication.set_master(None);
+                }
+                // 새 설정을 바로 알린다
+                ping_nodes(&state, cluster, &mut links, Duration::ZERO).await;
+            }
+        }
+
+        // gossip 으로 슬롯을 다른 노드에 넘겼으면 그 슬롯의 shard 채널 구독자를 내보낸다
+        state.drop_unserved_shard_channels();
+    }
+}
+
+/// 마지막 PING 에서 `interval` 이 지난 노드들에 PING 을 보내고 PONG 을 반영한다
+async fn ping_nodes(state: &ServerState, cluster: &Cluster, links: &mut Links, interval: Duration) {
+    for (id, addr) in cluster.ping_targets(interval) {
+        let ping = Message::Ping(cluster.header(state.replication.master_repl_offset()));
+        if let Some(Message::Pong(header)) = links.send(&addr, &ping).await {
+            if let Some(master) = cluster.process(&header, false) {
+                follow(state, master).await;
+            }
+            cluster.pong_received(&id);
+        }
+    }
+}
+
+/// 다른 노드의 클러스터 버스로 맺은 링크들
+struct Links {
+    clients: HashMap<MasterAddr, Client>,
+}
+
+impl Links {
+    async fn command(&mut self, addr: &MasterAddr, message: &Message) -> Option<Reply> {
+        if !self.clients.contains_key(addr) {
+            let client = Client::connect(addr, REQUEST_TIMEOUT).await.ok()?;
+            self.clients.insert(addr.clone(), client);
+        }
+        let args = message.to_args();
+        let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        match self.clients.get_mut(addr)?.command(&args).await {
+            Ok(reply) => Some(reply),
+            Err(_) => {
+                self.clients.remove(addr);
+                None
+            }
+        }
+    }
+
+    /// 메시지를 보내고 응답을 메시지로 읽는다
+    async fn send(&mut self, addr: &MasterAddr, message: &Message) -> Option<Message> {
+        match self.command(addr, message).await? {
+            Reply::Array(Some(items)) => {
+                let args: Option<Vec<String>> = items
+                    .into_iter()
+                    .map(|item| match item {
+                        Reply::Bulk(Some(s)) => Some(s),
+                        _ => None,
+                    })
+                    .collect();
+                Message::parse(&args?)
+            }
+            _ => None,
+        }
+    }
+}
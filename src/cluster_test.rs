@@ -0,0 +1,270 @@
+use crate::cluster::{key_hash_slot, Cluster, Gossip, Header, Message, Redirect, SetSlot, CLUSTER_SLOTS};
+use crate::store::Store;
+use std::fs;
+use std::time::Duration;
+use tokio::test;
+
+const TIMEOUT: Duration = Duration::from_millis(15000);
+
+const NODE_A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+const NODE_B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+const NODE_C: &str = "cccccccccccccccccccccccccccccccccccccccc";
+
+/// A (이 노드) 가 0-8191, B 가 8192-16383, C 는 B 의 replica
+fn two_node_config() -> String {
+    format!(
+        "{a} 127.0.0.1:7000@17000 myself,master - 0 0 1 connected 0-8191\n\
+         {b} 127.0.0.1:7001@17001 master - 0 0 2 connected 8192-16383\n\
+         {c} 127.0.0.1:7002@17002 slave {b} 0 0 2 connected\n\
+         vars currentEpoch 2 lastVoteEpoch 0\n",
+        a = NODE_A,
+        b = NODE_B,
+        c = NODE_C,
+    )
+}
+
+#[test]
+async fn test_key_hash_slot() {
+    assert_eq!(key_hash_slot("foo"), 12182);
+    assert_eq!(key_hash_slot("bar"), 5061);
+    assert_eq!(key_hash_slot(""), 0);
+
+    // hashtag 안의 문자열만 해시한다
+    assert_eq!(key_hash_slot("{user1000}.following"), key_hash_slot("user1000"));
+    assert_eq!(key_hash_slot("{user1000}.followers"), key_hash_slot("{user1000}.following"));
+    // 비어 있는 hashtag 는 무시하고, 첫 번째 `{` 와 그 뒤 첫 번째 `}` 만 본다
+    assert_ne!(key_hash_slot("foo{}{bar}"), key_hash_slot("bar"));
+    assert_eq!(key_hash_slot("foo{{bar}}zap"), key_hash_slot("{bar"));
+    assert_eq!(key_hash_slot("foo{bar}{zap}"), key_hash_slot("bar"));
+}
+
+#[test]
+async fn test_new_cluster_config() {
+    let path = "test_cluster_new.conf";
+    let _ = fs::remove_file(path);
+
+    let cluster = Cluster::load(path, 7000, TIMEOUT).unwrap();
+    let myid = cluster.myid();
+    assert_eq!(myid.len(), 40);
+    assert!(!cluster.is_ok());
+
+    // 다시 띄우면 같은 node ID 를 쓴다
+    cluster.add_slots(&[0, 1, 2]).unwrap();
+    let reloaded = Cluster::load(path, 7000, TIMEOUT).unwrap();
+    assert_eq!(reloaded.myid(), myid);
+    assert_eq!(reloaded.slot_owner(1).map(|n| n.id), Some(myid));
+    assert!(reloaded.slot_owner(3).is_none());
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_load_nodes_conf() {
+    let path = "test_cluster_load.conf";
+    fs::write(path, two_node_config()).unwrap();
+
+    let cluster = Cluster::load(path, 7000, TIMEOUT).unwrap();
+    assert_eq!(cluster.myid(), NODE_A);
+    assert!(cluster.is_ok());
+
+    let shards = cluster.shards();
+    assert_eq!(shards.len(), 2);
+    assert_eq!(shards[0].ranges, vec![(0, 8191)]);
+    assert_eq!(shards[1].master.port, 7001);
+    assert_eq!(shards[1].ranges, vec![(8192, (CLUSTER_SLOTS - 1) as u16)]);
+    assert_eq!(shards[1].replicas.len(), 1);
+    assert_eq!(shards[1].replicas[0].id, NODE_C);
+
+    // 저장한 파일은 다시 읽어도 같다
+    let nodes = cluster.nodes();
+    assert!(nodes.contains(&format!("{} 127.0.0.1:7000@17000 myself,master - 0 0 1 connected 0-8191", NODE_A)));
+    assert!(nodes.contains(&format!("{} 127.0.0.1:7002@17002 slave {} 0 0 2 connected", NODE_C, NODE_B)));
+    assert_eq!(Cluster::load(path, 7000, TIMEOUT).unwrap().nodes(), nodes);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_route() {
+    let path = "test_cluster_route.conf";
+    fs::write(path, two_node_config()).unwrap();
+    let cluster = Cluster::load(path, 7000, TIMEOUT).unwrap();
+    let store = Store::new();
+
+    // bar (5061) 는 이 노드, foo (12182) 는 B
+    assert_eq!(cluster.route(&store, &["bar"], false).await, Ok(()));
+    assert_eq!(cluster.route(&store, &["foo"], false).await, Err(Redirect::Moved(12182, "127.0.0.1:7001".to_string())));
+    assert_eq!(cluster.route(&store, &["foo", "bar"], false).await, Err(Redirect::CrossSlot));
+    assert_eq!(cluster.route(&store, &["{foo}a", "{foo}b"], false).await, Err(Redirect::Moved(12182, "127.0.0.1:7001".to_string())));
+    // 키가 없는 명령은 어디서든 실행한다
+    assert_eq!(cluster.route(&store, &[], false).await, Ok(()));
+
+    // 슬롯이 하나라도 비어 있으면 클러스터가 내려간 것으로 본다
+    cluster.del_slots(&[0]).unwrap();
+    assert_eq!(cluster.route(&store, &["bar"], false).await, Err(Redirect::Down));
+    assert!(cluster.del_slots(&[0]).is_err());
+    cluster.add_slots(&[0]).unwrap();
+    assert!(cluster.add_slots(&[0]).is_err());
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_route_during_migration() {
+    let path = "test_cluster_migration.conf";
+    // A 는 5061 을 B 로 옮기는 중이고, B 의 12182 를 가져오는 중이다
+    let config = two_node_config().replace(
+        "connected 0-8191\n",
+        &format!("connected 0-8191 [5061->-{}] [12182-<-{}]\n", NODE_B, NODE_B),
+    );
+    fs::write(path, config).unwrap();
+    let cluster = Cluster::load(path, 7000, TIMEOUT).unwrap();
+    let store = Store::new();
+    store.insert("bar".to_string(), "1".to_string(), None).await;
+    store.insert("{bar}x".to_string(), "1".to_string(), None).await;
+
+    // 아직 옮기지 않은 키는 여기서, 옮겨 간 키는 ASK
+    assert_eq!(cluster.route(&store, &["bar"], false).await, Ok(()));
+    assert_eq!(cluster.route(&store, &["{bar}gone"], false).await, Err(Redirect::Ask(5061, "127.0.0.1:7001".to_string())));
+    assert_eq!(cluster.route(&store, &["{bar}x", "{bar}gone"], false).await, Err(Redirect::TryAgain));
+
+    // 가져오는 중인 슬롯은 ASKING 을 보낸 클라이언트만 처리한다
+    assert_eq!(cluster.route(&store, &["foo"], false).await, Err(Redirect::Moved(12182, "127.0.0.1:7001".to_string())));
+    assert_eq!(cluster.route(&store, &["foo"], true).await, Ok(()));
+    assert!(cluster.nodes().contains(&format!("[5061->-{}] [12182-<-{}]", NODE_B, NODE_B)));
+
+    fs::remove_file(path).unwrap();
+}
+
+/// `sender` 가 보내는 PING 헤더
+fn header(sender: &str, port: u16, config_epoch: u64, slots: Vec<(u16, u16)>) -> Header {
+    Header {
+        sender: sender.to_string(),
+        ip: "127.0.0.1".to_string(),
+        port,
+        cport: port + 10000,
+        master_id: None,
+        config_epoch,
+        current_epoch: config_epoch,
+        repl_offset: 0,
+        slots,
+        gossip: vec![],
+    }
+}
+
+#[test]
+async fn test_message_roundtrip() {
+    let mut ping = header(NODE_B, 7001, 2, vec![(0, 100), (200, 200)]);
+    ping.master_id = Some(NODE_A.to_string());
+    ping.gossip.push(Gossip { id: NODE_C.to_string(), ip: "127.0.0.1".to_string(), port: 7002, cport: 17002, pfail: true, fail: false });
+
+    for message in [Message::Ping(ping.clone()), Message::AuthRequest(ping), Message::Fail(NODE_A.to_string(), NODE_B.to_string())] {
+        assert_eq!(Message::parse(&message.to_args()), Some(message));
+    }
+    let empty = Message::Pong(header(NODE_B, 7001, 0, vec![]));
+    assert_eq!(empty.to_args()[9], "-");
+    assert_eq!(Message::parse(&empty.to_args()), Some(empty));
+    assert_eq!(Message::parse(&["PING".to_string(), NODE_A.to_string()]), None);
+}
+
+#[test]
+async fn test_gossip_slot_takeover() {
+    let path = "test_cluster_takeover.conf";
+    fs::write(path, two_node_config()).unwrap();
+    let cluster = Cluster::load(path, 7000, TIMEOUT).unwrap();
+
+    // 모르는 노드는 MEET 으로만 추가한다
+    let d = "dddddddddddddddddddddddddddddddddddddddd";
+    assert!(cluster.process(&header(d, 7003, 0, vec![]), false).is_none());
+    assert!(!cluster.nodes().contains(d));
+    cluster.process(&header(d, 7003, 0, vec![]), true);
+    assert!(cluster.nodes().contains(&format!("{} 127.0.0.1:7003@17003 master", d)));
+
+    // config epoch 가 더 낮은 주장은 무시하고, 더 높으면 슬롯을 넘긴다
+    cluster.process(&header(NODE_B, 7001, 0, vec![(0, 10)]), false);
+    assert_eq!(cluster.slot_owner(0).unwrap().id, NODE_A);
+    cluster.process(&header(NODE_B, 7001, 3, vec![(0, 10), (8192, 16383)]), false);
+    assert_eq!(cluster.slot_owner(10).unwrap().id, NODE_B);
+    assert_eq!(cluster.slot_owner(11).unwrap().id, NODE_A);
+
+    // 슬롯을 모두 잃으면 새 주인의 replica 가 된다
+    let master = cluster.process(&header(NODE_B, 7001, 4, vec![(0, 16383)]), false);
+    assert_eq!(master.map(|m| m.port), Some(7001));
+    assert_eq!(cluster.master_addr().map(|m| m.port), Some(7001));
+    assert!(cluster.nodes().contains(&format!("{} 127.0.0.1:7000@17000 myself,slave {}", NODE_A, NODE_B)));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_failure_detection_and_vote() {
+    let path = "test_cluster_failure.conf";
+    // A, B, D 가 슬롯을 나눠 갖고 C 는 B 의 replica
+    let d = "dddddddddddddddddddddddddddddddddddddddd";
+    let config = two_node_config()
+        .replace("connected 8192-16383\n", "connected 8192-12287\n")
+        .replace("vars", &format!("{} 127.0.0.1:7003@17003 master - 0 0 3 connected 12288-16383\nvars", d))
+        .replace("currentEpoch 2", "currentEpoch 3");
+    fs::write(path, config).unwrap();
+    let cluster = Cluster::load(path, 7000, Duration::from_millis(50)).unwrap();
+
+    // node timeout 이 지나면 PFAIL, 과반수 (A 와 D) 가 동의하면 FAIL
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    cluster.pong_received(d);
+    cluster.pong_received(NODE_C);
+    assert!(cluster.update_failures().is_empty());
+    assert!(cluster.nodes().contains(&format!("{} 127.0.0.1:7001@17001 master,fail? - 0 0 2 disconnected", NODE_B)));
+
+    // C 의 투표 요청은 master 가 FAIL 이 아니면 거절한다
+    let mut request = header(NODE_C, 7002, 2, vec![]);
+    request.master_id = Some(NODE_B.to_string());
+    request.current_epoch = 4;
+    assert!(!cluster.vote(&request));
+
+    let mut report = header(d, 7003, 3, vec![(12288, 16383)]);
+    report.gossip.push(Gossip { id: NODE_B.to_string(), ip: "127.0.0.1".to_string(), port: 7001, cport: 17001, pfail: true, fail: false });
+    cluster.process(&report, false);
+    assert_eq!(cluster.update_failures(), vec![NODE_B.to_string()]);
+    assert!(!cluster.is_ok());
+
+    // 한 epoch 에 한 번만 투표한다
+    assert!(cluster.vote(&request));
+    assert!(!cluster.vote(&request));
+
+    // replica 가 이겨서 슬롯을 가져가면 FAIL 이던 옛 master 가 돌아와도 문제없다
+    cluster.process(&header(NODE_C, 7002, 4, vec![(8192, 12287)]), false);
+    assert_eq!(cluster.slot_owner(8192).unwrap().id, NODE_C);
+    assert!(cluster.is_ok());
+    cluster.pong_received(NODE_B);
+    assert!(!cluster.nodes().contains("fail"));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_setslot() {
+    let path = "test_cluster_setslot.conf";
+    fs::write(path, two_node_config()).unwrap();
+    let cluster = Cluster::load(path, 7000, TIMEOUT).unwrap();
+
+    // 가진 슬롯만 MIGRATING, 갖지 않은 슬롯만 IMPORTING 할 수 있다
+    assert!(cluster.set_slot(9000, SetSlot::Migrating(NODE_B.to_string()), 0).is_err());
+    assert!(cluster.set_slot(100, SetSlot::Importing(NODE_B.to_string()), 0).is_err());
+    assert!(cluster.set_slot(100, SetSlot::Migrating(NODE_C.to_string()), 0).is_err());
+    assert!(cluster.set_slot(100, SetSlot::Migrating("unknown".to_string()), 0).is_err());
+    cluster.set_slot(100, SetSlot::Migrating(NODE_B.to_string()), 0).unwrap();
+    cluster.set_slot(9000, SetSlot::Importing(NODE_B.to_string()), 0).unwrap();
+
+    // 키가 남아 있으면 슬롯을 넘길 수 없다
+    assert!(cluster.set_slot(100, SetSlot::Node(NODE_B.to_string()), 1).is_err());
+    cluster.set_slot(100, SetSlot::Node(NODE_B.to_string()), 0).unwrap();
+    assert_eq!(cluster.slot_owner(100).unwrap().id, NODE_B);
+
+    // 가져오기를 끝내면 config epoch 를 올려서 다른 노드들이 새 주인을 따르게 한다
+    cluster.set_slot(9000, SetSlot::Node(NODE_A.to_string()), 0).unwrap();
+    assert_eq!(cluster.slot_owner(9000).unwrap().id, NODE_A);
+    assert!(cluster.info().contains("cluster_my_epoch:3\r\n"));
+    assert!(!cluster.nodes().contains("->-") && !cluster.nodes().contains("-<-"));
+
+    fs::remove_file(path).unwrap();
+}
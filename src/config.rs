@@ -1,34 +1,340 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use clap::Parser;
 use std::fs::File;
 use std::io::Write;
+use crate::aof::AppendFsync;
 use crate::args::Args;
+use crate::notify::NotifyFlags;
+use crate::persistence::SavePoint;
+use crate::protocol::decoder::{DEFAULT_MAX_BULK_LEN, DEFAULT_MAX_MULTIBULK_LEN};
+use crate::replication::MasterAddr;
+use crate::scripting::{LOG_LEVELS, LOG_NOTICE};
+use crate::sentinel::SentinelConfig;
+use std::time::Duration;
+
+/// redis.conf 의 메모리 단위 (1k = 1000, 1kb = 1024 ...)
+pub fn parse_memory(s: &str) -> Option<u64> {
+    let s = s.to_lowercase();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let multiplier = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
 
 #[derive(Debug, Default)]
 pub struct Config {
+    pub port: Option<u16>,
+    pub replicaof: Option<String>,
     pub dir: Option<String>,
     pub dbfilename: Option<String>,
+    pub save: Option<String>,
+    pub rdbchecksum: Option<String>,
+    pub appendonly: Option<String>,
+    pub appendfilename: Option<String>,
+    pub appendfsync: Option<String>,
+    pub aof_load_truncated: Option<String>,
+    pub appenddirname: Option<String>,
+    pub aof_use_rdb_preamble: Option<String>,
+    pub repl_backlog_size: Option<String>,
+    pub repl_diskless_sync: Option<String>,
+    pub replica_serve_stale_data: Option<String>,
+    pub sentinel: bool,
+    pub sentinel_monitor: Option<String>,
+    pub sentinel_down_after_milliseconds: Option<String>,
+    pub sentinel_failover_timeout: Option<String>,
+    pub sentinel_known_sentinel: Vec<String>,
+    pub cluster_enabled: Option<String>,
+    pub cluster_config_file: Option<String>,
+    pub cluster_node_timeout: Option<String>,
+    pub notify_keyspace_events: Option<String>,
+    pub busy_script_time: Option<String>,
+    pub loglevel: Option<String>,
+    pub proto_max_bulk_len: Option<String>,
+    pub proto_max_multibulk_len: Option<String>,
+    pub client_query_buffer_limit: Option<String>,
 }
 
 impl Config {
     pub fn new() -> Result<Self> {
         let args = Args::try_parse().unwrap();
         
-        if args.dir.is_none() && args.dbfilename.is_none() {
+        if args.is_empty() {
             Self::from_file()
         } else {
             let config = Config {
+                port: args.port,
+                replicaof: args.replicaof,
                 dir: args.dir,
                 dbfilename: args.dbfilename,
+                save: args.save,
+                rdbchecksum: args.rdbchecksum,
+                appendonly: args.appendonly,
+                appendfilename: args.appendfilename,
+                appendfsync: args.appendfsync,
+                aof_load_truncated: args.aof_load_truncated,
+                appenddirname: args.appenddirname,
+                aof_use_rdb_preamble: args.aof_use_rdb_preamble,
+                repl_backlog_size: args.repl_backlog_size,
+                repl_diskless_sync: args.repl_diskless_sync,
+                replica_serve_stale_data: args.replica_serve_stale_data,
+                sentinel: args.sentinel,
+                sentinel_monitor: args.sentinel_monitor,
+                sentinel_down_after_milliseconds: args.sentinel_down_after_milliseconds,
+                sentinel_failover_timeout: args.sentinel_failover_timeout,
+                sentinel_known_sentinel: args.sentinel_known_sentinel,
+                cluster_enabled: args.cluster_enabled,
+                cluster_config_file: args.cluster_config_file,
+                cluster_node_timeout: args.cluster_node_timeout,
+                notify_keyspace_events: args.notify_keyspace_events,
+                busy_script_time: args.busy_script_time,
+                loglevel: args.loglevel,
+                proto_max_bulk_len: args.proto_max_bulk_len,
+                proto_max_multibulk_len: args.proto_max_multibulk_len,
+                client_query_buffer_limit: args.client_query_buffer_limit,
             };
             config.save_to_file()?;
             Ok(config)
         }
     }
 
+    /// sentinel 모드의 기본 포트는 26379
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(if self.sentinel { 26379 } else { 6379 })
+    }
+
+    /// `replicaof <host> <port>` 로 지정한 master
+    pub fn master(&self) -> Option<MasterAddr> {
+        let replicaof = self.replicaof.as_deref()?;
+        let master = MasterAddr::parse(replicaof);
+        if master.is_none() {
+            eprintln!("Invalid replicaof: {:?}", replicaof);
+        }
+        master
+    }
+
+    /// dir 과 dbfilename 을 합친 RDB 파일 경로
+    pub fn rdb_path(&self) -> String {
+        let dir = self.dir.as_deref().unwrap_or(".");
+        let filename = self.dbfilename.as_deref().unwrap_or("dump.rdb");
+        format!("{}/{}", dir, filename)
+    }
+
+    /// 설정이 없으면 Redis 기본 save point 를 사용한다.
+    pub fn save_points(&self) -> Vec<SavePoint> {
+        match self.save.as_deref() {
+            Some(s) => SavePoint::parse(s).unwrap_or_else(|| {
+                eprintln!("Invalid save parameters: {:?}", s);
+                SavePoint::defaults()
+            }),
+            None => SavePoint::defaults(),
+        }
+    }
+
+    /// `rdbchecksum no` 일 때만 로드 시 체크섬 검사를 끈다.
+    pub fn rdb_checksum(&self) -> bool {
+        !matches!(self.rdbchecksum.as_deref(), Some("no"))
+    }
+
+    pub fn appendonly(&self) -> bool {
+        matches!(self.appendonly.as_deref(), Some("yes"))
+    }
+
+    /// AOF 파일 이름의 접두어 (appendonly.aof -> appendonly.aof.1.base.rdb)
+    pub fn appendfilename(&self) -> &str {
+        self.appendfilename.as_deref().unwrap_or("appendonly.aof")
+    }
+
+    /// dir 과 appendfilename 을 합친 Redis 7 이전의 단일 AOF 파일 경로
+    pub fn aof_path(&self) -> String {
+        let dir = self.dir.as_deref().unwrap_or(".");
+        format!("{}/{}", dir, self.appendfilename())
+    }
+
+    /// multi-part AOF 파일들이 들어가는 디렉토리
+    pub fn aof_dir(&self) -> String {
+        let dir = self.dir.as_deref().unwrap_or(".");
+        let dirname = self.appenddirname.as_deref().unwrap_or("appendonlydir");
+        format!("{}/{}", dir, dirname)
+    }
+
+    pub fn aof_use_rdb_preamble(&self) -> bool {
+        !matches!(self.aof_use_rdb_preamble.as_deref(), Some("no"))
+    }
+
+    pub fn appendfsync(&self) -> AppendFsync {
+        match self.appendfsync.as_deref() {
+            Some(s) => AppendFsync::parse(s).unwrap_or_else(|| {
+                eprintln!("Invalid appendfsync: {:?}", s);
+                AppendFsync::EverySec
+            }),
+            None => AppendFsync::EverySec,
+        }
+    }
+
+    pub fn aof_load_truncated(&self) -> bool {
+        !matches!(self.aof_load_truncated.as_deref(), Some("no"))
+    }
+
+    /// 기본 1mb
+    pub fn repl_backlog_size(&self) -> usize {
+        match self.repl_backlog_size.as_deref() {
+            Some(s) => parse_memory(s).unwrap_or_else(|| {
+                eprintln!("Invalid repl-backlog-size: {:?}", s);
+                1024 * 1024
+            }) as usize,
+            None => 1024 * 1024,
+        }
+    }
+
+    /// 디스크를 거치지 않고 소켓으로 바로 RDB 를 보낼지 (Redis 7 기본값 yes)
+    pub fn repl_diskless_sync(&self) -> bool {
+        !matches!(self.repl_diskless_sync.as_deref(), Some("no"))
+    }
+
+    /// master 와의 링크가 끊긴 동안에도 가진 데이터로 응답할지 (기본 yes)
+    pub fn replica_serve_stale_data(&self) -> bool {
+        !matches!(self.replica_serve_stale_data.as_deref(), Some("no"))
+    }
+
+    pub fn cluster_enabled(&self) -> bool {
+        matches!(self.cluster_enabled.as_deref(), Some("yes"))
+    }
+
+    /// 클러스터 노드 테이블 파일 경로 (dir 기준)
+    pub fn cluster_config_path(&self) -> String {
+        let dir = self.dir.as_deref().unwrap_or(".");
+        format!("{}/{}", dir, self.cluster_config_file.as_deref().unwrap_or("nodes.conf"))
+    }
+
+    /// 키스페이스 알림 종류 (기본 끔). 설정 파일에서는 빈 값을 "" 로 쓸 수 있다.
+    pub fn notify_keyspace_events(&self) -> Result<NotifyFlags> {
+        match self.notify_keyspace_events.as_deref() {
+            Some(s) => NotifyFlags::parse(s.trim_matches('"')).ok_or_else(|| anyhow!("Invalid notify-keyspace-events: {:?}", s)),
+            None => Ok(NotifyFlags::NONE),
+        }
+    }
+
+    /// 클러스터 노드가 응답하지 않으면 장애로 보기까지의 시간 (기본 15초)
+    pub fn cluster_node_timeout(&self) -> Result<Duration> {
+        match self.cluster_node_timeout.as_deref() {
+            Some(ms) => Ok(Duration::from_millis(ms.parse().map_err(|_| anyhow!("Invalid cluster-node-timeout: {:?}", ms))?)),
+            None => Ok(Duration::from_millis(15000)),
+        }
+    }
+
+    /// 스크립트가 오래 실행될 때 BUSY 로 응답하기 시작하는 시간 (기본 5초)
+    pub fn busy_script_time(&self) -> Result<Duration> {
+        match self.busy_script_time.as_deref() {
+            Some(ms) => Ok(Duration::from_millis(ms.parse().map_err(|_| anyhow!("Invalid busy-script-time: {:?}", ms))?)),
+            None => Ok(Duration::from_millis(5000)),
+        }
+    }
+
+    /// redis.log 로 남길 최소 로그 레벨 (기본 notice). redis.LOG_* 와 같은 숫자다.
+    pub fn loglevel(&self) -> usize {
+        match self.loglevel.as_deref() {
+            Some(s) => LOG_LEVELS.iter().position(|level| s.eq_ignore_ascii_case(level)).unwrap_or_else(|| {
+                eprintln!("Invalid loglevel: {:?}", s);
+                LOG_NOTICE
+            }),
+            None => LOG_NOTICE,
+        }
+    }
+
+    /// 클라이언트가 보내는 인자 하나의 최대 길이 (기본 512mb)
+    pub fn proto_max_bulk_len(&self) -> usize {
+        match self.proto_max_bulk_len.as_deref() {
+            Some(s) => parse_memory(s).unwrap_or_else(|| {
+                eprintln!("Invalid proto-max-bulk-len: {:?}", s);
+                DEFAULT_MAX_BULK_LEN as u64
+            }) as usize,
+            None => DEFAULT_MAX_BULK_LEN,
+        }
+    }
+
+    /// 클라이언트가 보내는 명령 하나의 최대 인자 수 (기본 1048576)
+    pub fn proto_max_multibulk_len(&self) -> usize {
+        match self.proto_max_multibulk_len.as_deref() {
+            Some(s) => s.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid proto-max-multibulk-len: {:?}", s);
+                DEFAULT_MAX_MULTIBULK_LEN
+            }),
+            None => DEFAULT_MAX_MULTIBULK_LEN,
+        }
+    }
+
+    /// 아직 명령으로 읽지 못한 클라이언트 입력 버퍼의 최대 크기 (기본 1gb)
+    pub fn client_query_buffer_limit(&self) -> usize {
+        match self.client_query_buffer_limit.as_deref() {
+            Some(s) => parse_memory(s).unwrap_or_else(|| {
+                eprintln!("Invalid client-query-buffer-limit: {:?}", s);
+                1024 * 1024 * 1024
+            }) as usize,
+            None => 1024 * 1024 * 1024,
+        }
+    }
+
+    /// `sentinel monitor <name> <host> <port> <quorum>` 과 관련 설정
+    pub fn sentinel_config(&self) -> Result<SentinelConfig> {
+        let monitor = self
+            .sentinel_monitor
+            .as_deref()
+            .ok_or_else(|| anyhow!("sentinel mode requires sentinel monitor <name> <host> <port> <quorum>"))?;
+        let parts: Vec<&str> = monitor.split_whitespace().collect();
+        let (master_name, master, quorum) = match parts.as_slice() {
+            [name, host, port, quorum] => match (port.parse(), quorum.parse::<usize>()) {
+                (Ok(port), Ok(quorum)) if quorum > 0 => {
+                    (name.to_string(), MasterAddr { host: host.to_string(), port }, quorum)
+                }
+                _ => bail!("Invalid sentinel monitor: {:?}", monitor),
+            },
+            _ => bail!("Invalid sentinel monitor: {:?}", monitor),
+        };
+
+        let millis = |value: Option<&str>, default: u64, name: &str| -> Result<Duration> {
+            match value {
+                Some(v) => v.parse().map(Duration::from_millis).map_err(|_| anyhow!("Invalid {}: {:?}", name, v)),
+                None => Ok(Duration::from_millis(default)),
+            }
+        };
+
+        let known_sentinels = self
+            .sentinel_known_sentinel
+            .iter()
+            .map(|s| MasterAddr::parse(s).ok_or_else(|| anyhow!("Invalid sentinel known-sentinel: {:?}", s)))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(SentinelConfig {
+            port: self.port(),
+            master_name,
+            master,
+            quorum,
+            down_after: millis(self.sentinel_down_after_milliseconds.as_deref(), 30000, "down-after-milliseconds")?,
+            failover_timeout: millis(self.sentinel_failover_timeout.as_deref(), 180000, "failover-timeout")?,
+            known_sentinels,
+        })
+    }
+
     fn save_to_file(&self) -> Result<()> {
         let mut config_content = String::new();
 
+        if let Some(port) = self.port.as_ref() {
+            config_content.push_str(&format!("port {}\n", port));
+        }
+
+        if let Some(replicaof) = self.replicaof.as_ref() {
+            config_content.push_str(&format!("replicaof {}\n", replicaof));
+        }
+
         if let Some(dir) = self.dir.as_ref() {
             config_content.push_str(&format!("dir {}\n", dir));
         }
@@ -37,6 +343,101 @@ impl Config {
             config_content.push_str(&format!("dbfilename {}\n", dbfilename));
         }
 
+        if let Some(save) = self.save.as_ref() {
+            config_content.push_str(&format!("save {}\n", save));
+        }
+
+        if let Some(rdbchecksum) = self.rdbchecksum.as_ref() {
+            config_content.push_str(&format!("rdbchecksum {}\n", rdbchecksum));
+        }
+
+        if let Some(appendonly) = self.appendonly.as_ref() {
+            config_content.push_str(&format!("appendonly {}\n", appendonly));
+        }
+
+        if let Some(appendfilename) = self.appendfilename.as_ref() {
+            config_content.push_str(&format!("appendfilename {}\n", appendfilename));
+        }
+
+        if let Some(appendfsync) = self.appendfsync.as_ref() {
+            config_content.push_str(&format!("appendfsync {}\n", appendfsync));
+        }
+
+        if let Some(aof_load_truncated) = self.aof_load_truncated.as_ref() {
+            config_content.push_str(&format!("aof-load-truncated {}\n", aof_load_truncated));
+        }
+
+        if let Some(appenddirname) = self.appenddirname.as_ref() {
+            config_content.push_str(&format!("appenddirname {}\n", appenddirname));
+        }
+
+        if let Some(aof_use_rdb_preamble) = self.aof_use_rdb_preamble.as_ref() {
+            config_content.push_str(&format!("aof-use-rdb-preamble {}\n", aof_use_rdb_preamble));
+        }
+
+        if let Some(repl_backlog_size) = self.repl_backlog_size.as_ref() {
+            config_content.push_str(&format!("repl-backlog-size {}\n", repl_backlog_size));
+        }
+
+        if let Some(repl_diskless_sync) = self.repl_diskless_sync.as_ref() {
+            config_content.push_str(&format!("repl-diskless-sync {}\n", repl_diskless_sync));
+        }
+
+        if let Some(replica_serve_stale_data) = self.replica_serve_stale_data.as_ref() {
+            config_content.push_str(&format!("replica-serve-stale-data {}\n", replica_serve_stale_data));
+        }
+
+        // redis.conf 와 같은 `sentinel <option> <name> ...` 형식
+        if let Some(monitor) = self.sentinel_monitor.as_ref() {
+            config_content.push_str(&format!("sentinel monitor {}\n", monitor));
+            let name = monitor.split_whitespace().next().unwrap_or_default();
+            if let Some(ms) = self.sentinel_down_after_milliseconds.as_ref() {
+                config_content.push_str(&format!("sentinel down-after-milliseconds {} {}\n", name, ms));
+            }
+            if let Some(ms) = self.sentinel_failover_timeout.as_ref() {
+                config_content.push_str(&format!("sentinel failover-timeout {} {}\n", name, ms));
+            }
+            for known in &self.sentinel_known_sentinel {
+                config_content.push_str(&format!("sentinel known-sentinel {} {}\n", name, known));
+            }
+        }
+
+        if let Some(cluster_enabled) = self.cluster_enabled.as_ref() {
+            config_content.push_str(&format!("cluster-enabled {}\n", cluster_enabled));
+        }
+
+        if let Some(cluster_config_file) = self.cluster_config_file.as_ref() {
+            config_content.push_str(&format!("cluster-config-file {}\n", cluster_config_file));
+        }
+
+        if let Some(cluster_node_timeout) = self.cluster_node_timeout.as_ref() {
+            config_content.push_str(&format!("cluster-node-timeout {}\n", cluster_node_timeout));
+        }
+
+        if let Some(notify_keyspace_events) = self.notify_keyspace_events.as_ref() {
+            config_content.push_str(&format!("notify-keyspace-events {}\n", notify_keyspace_events));
+        }
+
+        if let Some(busy_script_time) = self.busy_script_time.as_ref() {
+            config_content.push_str(&format!("busy-script-time {}\n", busy_script_time));
+        }
+
+        if let Some(loglevel) = self.loglevel.as_ref() {
+            config_content.push_str(&format!("loglevel {}\n", loglevel));
+        }
+
+        if let Some(proto_max_bulk_len) = self.proto_max_bulk_len.as_ref() {
+            config_content.push_str(&format!("proto-max-bulk-len {}\n", proto_max_bulk_len));
+        }
+
+        if let Some(proto_max_multibulk_len) = self.proto_max_multibulk_len.as_ref() {
+            config_content.push_str(&format!("proto-max-multibulk-len {}\n", proto_max_multibulk_len));
+        }
+
+        if let Some(client_query_buffer_limit) = self.client_query_buffer_limit.as_ref() {
+            config_content.push_str(&format!("client-query-buffer-limit {}\n", client_query_buffer_limit));
+        }
+
         if !config_content.is_empty() {
             let mut file = File::create("redis.conf")?;
             file.write_all(config_content.as_bytes())?;
@@ -47,18 +448,108 @@ impl Config {
 
     fn from_file() -> Result<Self> {
         let config = std::fs::read_to_string("redis.conf").unwrap_or_default();
+        let mut port = None;
+        let mut replicaof = None;
         let mut dir = None;
         let mut dbfilename = None;
+        let mut save = None;
+        let mut rdbchecksum = None;
+        let mut appendonly = None;
+        let mut appendfilename = None;
+        let mut appendfsync = None;
+        let mut aof_load_truncated = None;
+        let mut appenddirname = None;
+        let mut aof_use_rdb_preamble = None;
+        let mut repl_backlog_size = None;
+        let mut repl_diskless_sync = None;
+        let mut replica_serve_stale_data = None;
+        let mut sentinel_monitor = None;
+        let mut sentinel_down_after_milliseconds = None;
+        let mut sentinel_failover_timeout = None;
+        let mut sentinel_known_sentinel = Vec::new();
+        let mut cluster_enabled = None;
+        let mut cluster_config_file = None;
+        let mut cluster_node_timeout = None;
+        let mut notify_keyspace_events = None;
+        let mut busy_script_time = None;
+        let mut loglevel = None;
+        let mut proto_max_bulk_len = None;
+        let mut proto_max_multibulk_len = None;
+        let mut client_query_buffer_limit = None;
 
         for line in config.lines() {
             let mut parts = line.split_whitespace();
             match parts.next() {
+                Some("port") => port = parts.next().and_then(|p| p.parse().ok()),
+                Some("replicaof") => replicaof = Some(parts.collect::<Vec<_>>().join(" ")),
                 Some("dir") => dir = parts.next().map(String::from),
                 Some("dbfilename") => dbfilename = parts.next().map(String::from),
+                Some("save") => save = Some(parts.collect::<Vec<_>>().join(" ")),
+                Some("rdbchecksum") => rdbchecksum = parts.next().map(String::from),
+                Some("appendonly") => appendonly = parts.next().map(String::from),
+                Some("appendfilename") => appendfilename = parts.next().map(String::from),
+                Some("appendfsync") => appendfsync = parts.next().map(String::from),
+                Some("aof-load-truncated") => aof_load_truncated = parts.next().map(String::from),
+                Some("appenddirname") => appenddirname = parts.next().map(String::from),
+                Some("aof-use-rdb-preamble") => aof_use_rdb_preamble = parts.next().map(String::from),
+                Some("repl-backlog-size") => repl_backlog_size = parts.next().map(String::from),
+                Some("repl-diskless-sync") => repl_diskless_sync = parts.next().map(String::from),
+                Some("replica-serve-stale-data") => replica_serve_stale_data = parts.next().map(String::from),
+                Some("sentinel") => {
+                    let option = parts.next();
+                    let rest: Vec<&str> = parts.collect();
+                    match (option, rest.as_slice()) {
+                        (Some("monitor"), _) => sentinel_monitor = Some(rest.join(" ")),
+                        (Some("down-after-milliseconds"), [_, ms]) => sentinel_down_after_milliseconds = Some(ms.to_string()),
+                        (Some("failover-timeout"), [_, ms]) => sentinel_failover_timeout = Some(ms.to_string()),
+                        (Some("known-sentinel"), [_, host, port, ..]) => sentinel_known_sentinel.push(format!("{} {}", host, port)),
+                        _ => continue,
+                    }
+                }
+                Some("cluster-enabled") => cluster_enabled = parts.next().map(String::from),
+                Some("cluster-config-file") => cluster_config_file = parts.next().map(String::from),
+                Some("cluster-node-timeout") => cluster_node_timeout = parts.next().map(String::from),
+                Some("notify-keyspace-events") => notify_keyspace_events = parts.next().map(String::from),
+                Some("busy-script-time") => busy_script_time = parts.next().map(String::from),
+                Some("loglevel") => loglevel = parts.next().map(String::from),
+                Some("proto-max-bulk-len") => proto_max_bulk_len = parts.next().map(String::from),
+                Some("proto-max-multibulk-len") => proto_max_multibulk_len = parts.next().map(String::from),
+                Some("client-query-buffer-limit") => client_query_buffer_limit = parts.next().map(String::from),
                 _ => continue,
             }
         }
 
-        Ok(Config { dir, dbfilename })
+        Ok(Config {
+            port,
+            replicaof,
+            dir,
+            dbfilename,
+            save,
+            rdbchecksum,
+            appendonly,
+            appendfilename,
+            appendfsync,
+            aof_load_truncated,
+            appenddirname,
+            aof_use_rdb_preamble,
+            repl_backlog_size,
+            repl_diskless_sync,
+            replica_serve_stale_data,
+            // sentinel monitor 가 있는 설정 파일은 sentinel 용이다
+            sentinel: sentinel_monitor.is_some(),
+            sentinel_monitor,
+            sentinel_down_after_milliseconds,
+            sentinel_failover_timeout,
+            sentinel_known_sentinel,
+            cluster_enabled,
+            cluster_config_file,
+            cluster_node_timeout,
+            notify_keyspace_events,
+            busy_script_time,
+            loglevel,
+            proto_max_bulk_len,
+            proto_max_multibulk_len,
+            client_query_buffer_limit,
+        })
     }
 }
@@ -1,19 +1,89 @@
+pub mod aof;
+
+#[cfg(test)]
+pub(crate) mod aof_test;
 pub mod args;
+pub mod client;
+pub mod cluster;
+
+#[cfg(test)]
+pub(crate) mod cluster_test;
+pub mod cluster_bus;
 pub mod config;
 pub mod protocol {
     pub mod decoder;
     pub mod encoder;
+    pub mod error;
 
     #[cfg(test)]
     pub(crate) mod encoder_test;
 
     #[cfg(test)]
     pub(crate) mod decoder_test;
+
+    #[cfg(test)]
+    pub(crate) mod error_test;
 }
+pub mod replication;
+
+#[cfg(test)]
+pub(crate) mod replication_test;
 pub mod server;
+
+#[cfg(test)]
+pub(crate) mod server_test;
 pub mod store;
+
+#[cfg(test)]
+pub(crate) mod store_test;
 pub mod rdb;
 
 #[cfg(test)]
 pub(crate) mod rdb_test;
 pub mod pattern_parser;
+
+#[cfg(test)]
+pub(crate) mod pattern_parser_test;
+pub mod notify;
+
+#[cfg(test)]
+pub(crate) mod notify_test;
+pub mod persistence;
+
+#[cfg(test)]
+pub(crate) mod persistence_test;
+pub mod pubsub;
+
+#[cfg(test)]
+pub(crate) mod pubsub_test;
+pub mod tracking;
+
+#[cfg(test)]
+pub(crate) mod tracking_test;
+pub mod scripting;
+
+#[cfg(test)]
+pub(crate) mod scripting_test;
+pub mod lua {
+    pub mod interpreter;
+    pub mod lexer;
+    pub mod parser;
+    pub mod pattern;
+    pub mod redislib;
+    pub mod stdlib;
+    pub mod value;
+
+    #[cfg(test)]
+    pub(crate) mod interpreter_test;
+
+    #[cfg(test)]
+    pub(crate) mod redislib_test;
+}
+pub mod sha1;
+
+#[cfg(test)]
+pub(crate) mod sha1_test;
+pub mod sentinel;
+
+#[cfg(test)]
+pub(crate) mod sentinel_test;
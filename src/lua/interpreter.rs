@@ -0,0 +1,521 @@
+use crate::lua::parser::{parse, BinOp, Block, Expr, Field, FuncBody, Stat, UnOp};
+use crate::lua::stdlib;
+use crate::lua::value::{format_number, Closure, Function, Table, TableRef, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// 함수 호출이 중첩될 수 있는 깊이 (Lua 5.1 의 LUAI_MAXCCALLS)
+const MAX_CALL_DEPTH: usize = 200;
+
+/// 스크립트 실행을 멈추는 에러
+#[derive(Debug)]
+pub enum LuaError {
+    /// error() 나 런타임 에러. pcall 로 잡을 수 있다.
+    Error(Value),
+    /// 밖에서 실행을 멈췄다 (SCRIPT KILL). pcall 로 잡을 수 없다.
+    Interrupted,
+}
+
+/// 블록 하나의 지역 변수. 클로저가 만들어진 시점의 Scope 를 잡고 있으므로 변수는 공유된다.
+pub struct Scope {
+    vars: RefCell<Vec<(String, Rc<RefCell<Value>>)>>,
+    /// 가변 인자 함수의 `...` (함수 본문의 Scope 에만 있다)
+    varargs: Option<Vec<Value>>,
+    parent: Option<Rc<Scope>>,
+}
+
+impl Scope {
+    fn child(parent: &Rc<Scope>) -> Rc<Scope> {
+        Rc::new(Scope { vars: RefCell::new(Vec::new()), varargs: None, parent: Some(Rc::clone(parent)) })
+    }
+
+    fn declare(&self, name: &str, value: Value) {
+        self.vars.borrow_mut().push((name.to_string(), Rc::new(RefCell::new(value))));
+    }
+
+    fn lookup(&self, name: &str) -> Option<Rc<RefCell<Value>>> {
+        if let Some((_, cell)) = self.vars.borrow().iter().rev().find(|(n, _)| n == name) {
+            return Some(Rc::clone(cell));
+        }
+        self.parent.as_ref()?.lookup(name)
+    }
+
+    fn varargs(&self) -> Vec<Value> {
+        match (&self.varargs, &self.parent) {
+            (Some(varargs), _) => varargs.clone(),
+            (None, Some(parent)) => parent.varargs(),
+            (None, None) => Vec::new(),
+        }
+    }
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Return(Vec<Value>),
+}
+
+pub struct Interpreter {
+    pub globals: TableRef,
+    /// 문자열의 메서드 호출 (s:upper()) 에 쓰는 string 라이브러리
+    pub string_lib: TableRef,
+    /// 켜면 전역 변수를 새로 만들거나 없는 전역 변수를 읽을 때 에러를 낸다
+    pub strict_globals: bool,
+    /// 에러 메시지에 붙이는 청크 이름
+    chunk: String,
+    /// 실행 중인 문장의 줄
+    line: u32,
+    depth: usize,
+    /// 반복문과 함수 호출마다 확인해서 true 면 실행을 멈춘다
+    interrupt: Option<Box<dyn FnMut() -> bool>>,
+}
+
+impl Interpreter {
+    /// 표준 라이브러리 (base, string, table, math) 를 연 인터프리터
+    pub fn new(chunk: &str) -> Interpreter {
+        let mut interp = Interpreter {
+            globals: Rc::new(RefCell::new(Table::new())),
+            string_lib: Rc::new(RefCell::new(Table::new())),
+            strict_globals: false,
+            chunk: chunk.to_string(),
+            line: 0,
+            depth: 0,
+            interrupt: None,
+        };
+        stdlib::open(&mut interp);
+        interp
+    }
+
+    pub fn set_interrupt(&mut self, interrupt: impl FnMut() -> bool + 'static) {
+        self.interrupt = Some(Box::new(interrupt));
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.borrow_mut().set_str(name, value);
+    }
+
+    /// 소스를 컴파일해서 인자 없이 부를 수 있는 함수로 만든다
+    pub fn load(&self, src: &[u8]) -> Result<Value, String> {
+        let body = parse(src, &self.chunk)?;
+        let scope = Rc::new(Scope { vars: RefCell::new(Vec::new()), varargs: None, parent: None });
+        Ok(Value::Function(Function::Lua(Rc::new(Closure { body, scope }))))
+    }
+
+    /// 현재 줄을 붙인 런타임 에러
+    pub fn error(&self, msg: impl AsRef<str>) -> LuaError {
+        LuaError::Error(Value::str(format!("{}:{}: {}", self.chunk, self.line, msg.as_ref())))
+    }
+
+    /// error() 의 위치 정보 ("chunk:줄:")
+    pub fn location(&self) -> String {
+        format!("{}:{}:", self.chunk, self.line)
+    }
+
+    fn check_interrupt(&mut self) -> Result<(), LuaError> {
+        if let Some(interrupt) = &mut self.interrupt {
+            if interrupt() {
+                return Err(LuaError::Interrupted);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn call(&mut self, function: &Value, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+        self.check_interrupt()?;
+        let Value::Function(function) = function else {
+            return Err(self.error(format!("attempt to call a {} value", function.type_name())));
+        };
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(self.error("stack overflow"));
+        }
+        self.depth += 1;
+        let line = self.line;
+        let result = match function {
+            Function::Builtin(builtin) => (builtin.f)(self, args),
+            Function::Lua(closure) => self.call_closure(closure, args),
+        };
+        self.line = line;
+        self.depth -= 1;
+        result
+    }
+
+    fn call_closure(&mut self, closure: &Closure, mut args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+        let body = &closure.body;
+        let varargs = if body.vararg && args.len() > body.params.len() { Some(args.split_off(body.params.len())) } else { None };
+        let scope = Rc::new(Scope {
+            vars: RefCell::new(Vec::new()),
+            varargs: if body.vararg { Some(varargs.unwrap_or_default()) } else { None },
+            parent: Some(Rc::clone(&closure.scope)),
+        });
+        let mut args = args.into_iter();
+        for param in &body.params {
+            scope.declare(param, args.next().unwrap_or_default());
+        }
+        match self.exec_stats(&body.body, &scope)? {
+            Flow::Return(values) => Ok(values),
+            Flow::Normal | Flow::Break => Ok(Vec::new()),
+        }
+    }
+
+    fn exec_block(&mut self, block: &Block, parent: &Rc<Scope>) -> Result<Flow, LuaError> {
+        let scope = Scope::child(parent);
+        self.exec_stats(block, &scope)
+    }
+
+    fn exec_stats(&mut self, block: &Block, scope: &Rc<Scope>) -> Result<Flow, LuaError> {
+        for (stat, line) in &block.stats {
+            self.line = *line;
+            match self.exec(stat, scope)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// 반복문 본문의 결과. Break 는 반복문에서 끝난다.
+    fn loop_body(&mut self, block: &Block, scope: &Rc<Scope>) -> Result<Option<Flow>, LuaError> {
+        self.check_interrupt()?;
+        match self.exec_stats(block, scope)? {
+            Flow::Normal => Ok(None),
+            Flow::Break => Ok(Some(Flow::Normal)),
+            flow => Ok(Some(flow)),
+        }
+    }
+
+    fn exec(&mut self, stat: &Stat, scope: &Rc<Scope>) -> Result<Flow, LuaError> {
+        match stat {
+            Stat::Local(names, exprs) => {
+                let values = self.eval_list(exprs, scope)?;
+                let mut values = values.into_iter();
+                for name in names {
+                    scope.declare(name, values.next().unwrap_or_default());
+                }
+            }
+            Stat::LocalFunction(name, body) => {
+                // 함수 안에서 자기 자신을 부를 수 있도록 먼저 선언한다
+                scope.declare(name, Value::Nil);
+                let function = self.closure(body, scope);
+                *scope.lookup(name).unwrap().borrow_mut() = function;
+            }
+            Stat::Assign(targets, exprs) => self.assign(targets, exprs, scope)?,
+            Stat::Call(expr) => {
+                self.eval_multi(expr, scope)?;
+            }
+            Stat::Do(block) => return self.exec_block(block, scope),
+            Stat::While(cond, block) => {
+                while self.eval(cond, scope)?.is_truthy() {
+                    if let Some(flow) = self.loop_body(block, &Scope::child(scope))? {
+                        return Ok(flow);
+                    }
+                }
+            }
+            Stat::Repeat(block, cond) => loop {
+                // until 조건에서 본문의 지역 변수를 볼 수 있다
+                let body_scope = Scope::child(scope);
+                if let Some(flow) = self.loop_body(block, &body_scope)? {
+                    return Ok(flow);
+                }
+                if self.eval(cond, &body_scope)?.is_truthy() {
+                    break;
+                }
+            },
+            Stat::If(clauses, otherwise) => {
+                for (cond, block) in clauses {
+                    if self.eval(cond, scope)?.is_truthy() {
+                        return self.exec_block(block, scope);
+                    }
+                }
+                if let Some(block) = otherwise {
+                    return self.exec_block(block, scope);
+                }
+            }
+            Stat::NumericFor(name, start, limit, step, block) => {
+                let start = self.for_number(start, scope, "initial")?;
+                let limit = self.for_number(limit, scope, "limit")?;
+                let step = match step {
+                    Some(step) => self.for_number(step, scope, "step")?,
+                    None => 1.0,
+                };
+                let mut i = start;
+                while (step > 0.0 && i <= limit) || (step <= 0.0 && i >= limit) {
+                    let body_scope = Scope::child(scope);
+                    body_scope.declare(name, Value::Number(i));
+                    if let Some(flow) = self.loop_body(block, &body_scope)? {
+                        return Ok(flow);
+                    }
+                    i += step;
+                }
+            }
+            Stat::GenericFor(names, exprs, block) => {
+                let mut values = self.eval_list(exprs, scope)?.into_iter();
+                let iterator = values.next().unwrap_or_default();
+                let state = values.next().unwrap_or_default();
+                let mut control = values.next().unwrap_or_default();
+                loop {
+                    let results = self.call(&iterator, vec![state.clone(), control.clone()])?;
+                    let mut results = results.into_iter();
+                    let first = results.next().unwrap_or_default();
+                    if matches!(first, Value::Nil) {
+                        break;
+                    }
+                    control = first.clone();
+                    let body_scope = Scope::child(scope);
+                    body_scope.declare(&names[0], first);
+                    for name in &names[1..] {
+                        body_scope.declare(name, results.next().unwrap_or_default());
+                    }
+                    if let Some(flow) = self.loop_body(block, &body_scope)? {
+                        return Ok(flow);
+                    }
+                }
+            }
+            Stat::Return(exprs) => return Ok(Flow::Return(self.eval_list(exprs, scope)?)),
+            Stat::Break => return Ok(Flow::Break),
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn for_number(&mut self, expr: &Expr, scope: &Rc<Scope>, what: &str) -> Result<f64, LuaError> {
+        self.eval(expr, scope)?.to_number().ok_or_else(|| self.error(format!("'for' {} value must be a number", what)))
+    }
+
+    fn assign(&mut self, targets: &[Expr], exprs: &[Expr], scope: &Rc<Scope>) -> Result<(), LuaError> {
+        // 대상의 테이블과 키를 먼저 계산하고 값을 계산한 뒤 대입한다
+        enum Target<'a> {
+            Name(&'a str),
+            Index(Value, Value, &'a Expr),
+        }
+        let mut resolved = Vec::with_capacity(targets.len());
+        for target in targets {
+            resolved.push(match target {
+                Expr::Name(name) => Target::Name(name),
+                Expr::Index(object, key) => Target::Index(self.eval(object, scope)?, self.eval(key, scope)?, object),
+                _ => unreachable!("parser only allows names and fields"),
+            });
+        }
+        let mut values = self.eval_list(exprs, scope)?.into_iter();
+        for target in resolved {
+            let value = values.next().unwrap_or_default();
+            match target {
+                Target::Name(name) => match scope.lookup(name) {
+                    Some(cell) => *cell.borrow_mut() = value,
+                    None if self.strict_globals => return Err(self.error("Attempt to modify a readonly table")),
+                    None => self.globals.borrow_mut().set_str(name, value),
+                },
+                Target::Index(object, key, expr) => match object {
+                    Value::Table(table) => table.borrow_mut().set(key, value).map_err(|e| self.error(e))?,
+                    other => return Err(self.error(format!("attempt to index {}", self.describe(expr, &other, scope)))),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn closure(&self, body: &Rc<FuncBody>, scope: &Rc<Scope>) -> Value {
+        Value::Function(Function::Lua(Rc::new(Closure { body: Rc::clone(body), scope: Rc::clone(scope) })))
+    }
+
+    /// 에러 메시지용 설명 ("global 'x' (a nil value)")
+    fn describe(&self, expr: &Expr, value: &Value, scope: &Rc<Scope>) -> String {
+        let what = match expr {
+            Expr::Name(name) if scope.lookup(name).is_some() => format!("local '{}' ", name),
+            Expr::Name(name) => format!("global '{}' ", name),
+            Expr::Index(_, key) => match key.as_ref() {
+                Expr::Str(key) => format!("field '{}' ", String::from_utf8_lossy(key)),
+                _ => String::new(),
+            },
+            Expr::Method(_, name, ..) => format!("method '{}' ", String::from_utf8_lossy(name)),
+            _ => String::new(),
+        };
+        if what.is_empty() {
+            format!("a {} value", value.type_name())
+        } else {
+            format!("{}(a {} value)", what, value.type_name())
+        }
+    }
+
+    /// 여러 값을 돌려줄 수 있는 표현식 (함수 호출, `...`) 은 모두, 나머지는 하나
+    fn eval_multi(&mut self, expr: &Expr, scope: &Rc<Scope>) -> Result<Vec<Value>, LuaError> {
+        match expr {
+            Expr::Call(function, args, line) => {
+                let f = self.eval(function, scope)?;
+                let args = self.eval_list(args, scope)?;
+                self.line = *line;
+                if !matches!(f, Value::Function(_)) {
+                    return Err(self.error(format!("attempt to call {}", self.describe(function, &f, scope))));
+                }
+                self.call(&f, args)
+            }
+            Expr::Method(object, name, args, line) => {
+                let object_value = self.eval(object, scope)?;
+                let f = self.index(&object_value, &Value::Str(Rc::clone(name)), object, scope)?;
+                let mut call_args = vec![object_value];
+                call_args.extend(self.eval_list(args, scope)?);
+                self.line = *line;
+                if !matches!(f, Value::Function(_)) {
+                    return Err(self.error(format!("attempt to call {}", self.describe(expr, &f, scope))));
+                }
+                self.call(&f, call_args)
+            }
+            Expr::Vararg => Ok(scope.varargs()),
+            _ => Ok(vec![self.eval(expr, scope)?]),
+        }
+    }
+
+    /// 마지막 표현식만 여러 값으로 펼친다
+    fn eval_list(&mut self, exprs: &[Expr], scope: &Rc<Scope>) -> Result<Vec<Value>, LuaError> {
+        let mut values = Vec::with_capacity(exprs.len());
+        for (i, expr) in exprs.iter().enumerate() {
+            if i + 1 == exprs.len() {
+                values.extend(self.eval_multi(expr, scope)?);
+            } else {
+                values.push(self.eval(expr, scope)?);
+            }
+        }
+        Ok(values)
+    }
+
+    fn index(&mut self, object: &Value, key: &Value, expr: &Expr, scope: &Rc<Scope>) -> Result<Value, LuaError> {
+        match object {
+            Value::Table(table) => Ok(table.borrow().get(key)),
+            Value::Str(_) => Ok(self.string_lib.borrow().get(key)),
+            other => Err(self.error(format!("attempt to index {}", self.describe(expr, other, scope)))),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr, scope: &Rc<Scope>) -> Result<Value, LuaError> {
+        Ok(match expr {
+            Expr::Nil => Value::Nil,
+            Expr::True => Value::Boolean(true),
+            Expr::False => Value::Boolean(false),
+            Expr::Number(n) => Value::Number(*n),
+            Expr::Str(s) => Value::Str(Rc::clone(s)),
+            Expr::Vararg => scope.varargs().into_iter().next().unwrap_or_default(),
+            Expr::Function(body) => self.closure(body, scope),
+            Expr::Name(name) => match scope.lookup(name) {
+                Some(cell) => cell.borrow().clone(),
+                None => {
+                    let value = self.globals.borrow().get_str(name);
+                    if self.strict_globals && matches!(value, Value::Nil) {
+                        return Err(self.error(format!("Script attempted to access nonexistent global variable '{}'", name)));
+                    }
+                    value
+                }
+            },
+            Expr::Index(object, key) => {
+                let object_value = self.eval(object, scope)?;
+                let key = self.eval(key, scope)?;
+                self.index(&object_value, &key, object, scope)?
+            }
+            Expr::Call(..) | Expr::Method(..) => self.eval_multi(expr, scope)?.into_iter().next().unwrap_or_default(),
+            Expr::Paren(inner) => self.eval(inner, scope)?,
+            Expr::Table(fields) => self.table(fields, scope)?,
+            Expr::Unary(op, operand, line) => {
+                let value = self.eval(operand, scope)?;
+                self.line = *line;
+                match op {
+                    UnOp::Not => Value::Boolean(!value.is_truthy()),
+                    UnOp::Neg => match value.to_number() {
+                        Some(n) => Value::Number(-n),
+                        None => return Err(self.error(format!("attempt to perform arithmetic on {}", self.describe(operand, &value, scope)))),
+                    },
+                    UnOp::Len => match &value {
+                        Value::Str(s) => Value::Number(s.len() as f64),
+                        Value::Table(t) => Value::Number(t.borrow().len() as f64),
+                        other => return Err(self.error(format!("attempt to get length of {}", self.describe(operand, other, scope)))),
+                    },
+                }
+            }
+            Expr::Binary(BinOp::And, left, right, _) => {
+                let left = self.eval(left, scope)?;
+                if left.is_truthy() { self.eval(right, scope)? } else { left }
+            }
+            Expr::Binary(BinOp::Or, left, right, _) => {
+                let left = self.eval(left, scope)?;
+                if left.is_truthy() { left } else { self.eval(right, scope)? }
+            }
+            Expr::Binary(op, left_expr, right_expr, line) => {
+                let left = self.eval(left_expr, scope)?;
+                let right = self.eval(right_expr, scope)?;
+                self.line = *line;
+                self.binary(*op, left, right, left_expr, right_expr, scope)?
+            }
+        })
+    }
+
+    fn binary(&mut self, op: BinOp, left: Value, right: Value, left_expr: &Expr, right_expr: &Expr, scope: &Rc<Scope>) -> Result<Value, LuaError> {
+        let arithmetic = |f: fn(f64, f64) -> f64| -> Result<Value, LuaError> {
+            match (left.to_number(), right.to_number()) {
+                (Some(a), Some(b)) => Ok(Value::Number(f(a, b))),
+                (None, _) => Err(self.error(format!("attempt to perform arithmetic on {}", self.describe(left_expr, &left, scope)))),
+                (_, None) => Err(self.error(format!("attempt to perform arithmetic on {}", self.describe(right_expr, &right, scope)))),
+            }
+        };
+        match op {
+            BinOp::Add => arithmetic(|a, b| a + b),
+            BinOp::Sub => arithmetic(|a, b| a - b),
+            BinOp::Mul => arithmetic(|a, b| a * b),
+            BinOp::Div => arithmetic(|a, b| a / b),
+            BinOp::Mod => arithmetic(|a, b| a - (a / b).floor() * b),
+            BinOp::Pow => arithmetic(f64::powf),
+            BinOp::Concat => match (left.to_str(), right.to_str()) {
+                (Some(a), Some(b)) => Ok(Value::Str([&a[..], &b[..]].concat().into())),
+                (None, _) => Err(self.error(format!("attempt to concatenate {}", self.describe(left_expr, &left, scope)))),
+                (_, None) => Err(self.error(format!("attempt to concatenate {}", self.describe(right_expr, &right, scope)))),
+            },
+            BinOp::Eq => Ok(Value::Boolean(left == right)),
+            BinOp::NotEq => Ok(Value::Boolean(left != right)),
+            BinOp::Less => Ok(Value::Boolean(self.less_than(&left, &right)?)),
+            BinOp::Greater => Ok(Value::Boolean(self.less_than(&right, &left)?)),
+            BinOp::LessEq => Ok(Value::Boolean(!self.less_than(&right, &left)?)),
+            BinOp::GreaterEq => Ok(Value::Boolean(!self.less_than(&left, &right)?)),
+            BinOp::And | BinOp::Or => unreachable!("short-circuit operators are handled in eval"),
+        }
+    }
+
+    /// `<` (숫자끼리, 문자열끼리만 비교할 수 있다)
+    pub fn less_than(&self, left: &Value, right: &Value) -> Result<bool, LuaError> {
+        match (left, right) {
+            (Value::Number(a), Value::Number(b)) => Ok(a < b),
+            (Value::Str(a), Value::Str(b)) => Ok(a < b),
+            (a, b) if a.type_name() == b.type_name() => Err(self.error(format!("attempt to compare two {} values", a.type_name()))),
+            (a, b) => Err(self.error(format!("attempt to compare {} with {}", a.type_name(), b.type_name()))),
+        }
+    }
+
+    fn table(&mut self, fields: &[Field], scope: &Rc<Scope>) -> Result<Value, LuaError> {
+        let mut table = Table::new();
+        let mut position = 1;
+        for (i, field) in fields.iter().enumerate() {
+            match field {
+                Field::Named(key, value) => {
+                    let key = self.eval(key, scope)?;
+                    let value = self.eval(value, scope)?;
+                    table.set(key, value).map_err(|e| self.error(e))?;
+                }
+                // 마지막 위치 필드는 여러 값으로 펼친다
+                Field::Positional(expr) if i + 1 == fields.len() => {
+                    for value in self.eval_multi(expr, scope)? {
+                        table.set(Value::Number(position as f64), value).map_err(|e| self.error(e))?;
+                        position += 1;
+                    }
+                }
+                Field::Positional(expr) => {
+                    let value = self.eval(expr, scope)?;
+                    table.set(Value::Number(position as f64), value).map_err(|e| self.error(e))?;
+                    position += 1;
+                }
+            }
+        }
+        Ok(Value::table(table))
+    }
+
+    /// tostring() 과 같은 문자열 (에러 메시지 등)
+    pub fn display(value: &Value) -> String {
+        match value {
+            Value::Number(n) => format_number(*n),
+            other => String::from_utf8_lossy(&other.to_display()).to_string(),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+#[cfg(test)]
+mod tests {
+    use crate::lua::interpreter::{Interpreter, LuaError};
+    use crate::lua::value::Value;
+
+    /// 스크립트를 실행해서 돌려준 값들을 tostring 한 결과.
+    /// 서버처럼 스택이 큰 스레드에서 실행한다.
+    fn run(src: &str) -> Result<Vec<String>, String> {
+        let src = src.to_string();
+        std::thread::Builder::new()
+            .stack_size(16 * 1024 * 1024)
+            .spawn(move || {
+                let mut interp = Interpreter::new("user_script");
+                let function = interp.load(src.as_bytes())?;
+                match interp.call(&function, Vec::new()) {
+                    Ok(values) => Ok(values.iter().map(Interpreter::display).collect()),
+                    Err(LuaError::Error(value)) => Err(Interpreter::display(&value)),
+                    Err(LuaError::Interrupted) => Err("interrupted".to_string()),
+                }
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    fn ok(src: &str) -> Vec<String> {
+        run(src).unwrap_or_else(|e| panic!("{}: {}", src, e))
+    }
+
+    #[test]
+    fn test_expressions() {
+        assert_eq!(ok("return 1 + 2 * 3, 2 ^ 10, 7 % 3, -7 % 3, 10 / 4"), ["7", "1024", "1", "2", "2.5"]);
+        assert_eq!(ok("return 'a' .. 1 .. 2.5, #'hello', '10' + 5"), ["a12.5", "5", "15"]);
+        assert_eq!(ok("return 1 < 2, 'a' < 'b', 1 == 1.0, 'x' ~= 'x', not nil"), ["true", "true", "true", "false", "true"]);
+        assert_eq!(ok("return nil or 'd', false and 1, 1 and 2"), ["d", "false", "2"]);
+        assert_eq!(ok("return 0.1, 1e15, 2^53, 1/0, 100000000000000"), ["0.1", "1e+15", "9.007199254741e+15", "inf", "100000000000000"]);
+    }
+
+    #[test]
+    fn test_statements() {
+        let src = "
+            local sum = 0
+            for i = 1, 10 do sum = sum + i end
+            for i = 10, 1, -3 do sum = sum + i end
+            local n = 0
+            while true do n = n + 1; if n == 5 then break end end
+            repeat local m = n; n = n - 1 until m <= 2
+            local s = ''
+            if n > 10 then s = 'big' elseif n > 0 then s = 'small' else s = 'zero' end
+            return sum, n, s";
+        assert_eq!(ok(src), ["77", "1", "small"]);
+    }
+
+    #[test]
+    fn test_functions_and_closures() {
+        let src = "
+            local function counter()
+                local n = 0
+                return function() n = n + 1; return n end
+            end
+            local c = counter()
+            c(); c()
+            local function fib(n) if n < 2 then return n end return fib(n - 1) + fib(n - 2) end
+            local function pack(...) return select('#', ...), ... end
+            local t = {}
+            function t.add(a, b) return a + b end
+            function t:get() return self.add(1, 2) end
+            return c(), fib(15), t:get(), pack(nil, 2)";
+        assert_eq!(ok(src), ["3", "610", "3", "2", "nil", "2"]);
+    }
+
+    #[test]
+    fn test_tables() {
+        let src = "
+            local t = {1, 2, 3, x = 'y', [10] = 'z'}
+            t[#t + 1] = 4
+            local keys = 0
+            for k, v in pairs(t) do keys = keys + 1 end
+            local sum = 0
+            for i, v in ipairs(t) do sum = sum + v end
+            table.insert(t, 1, 0)
+            local removed = table.remove(t)
+            local s = {5, 2, 8, 1}
+            table.sort(s)
+            local r = {5, 2, 8, 1}
+            table.sort(r, function(a, b) return a > b end)
+            return #t, keys, sum, removed, table.concat(s, ','), table.concat(r, ','), unpack({1, 2})";
+        assert_eq!(ok(src), ["4", "6", "10", "4", "1,2,5,8", "8,5,2,1", "1", "2"]);
+    }
+
+    #[test]
+    fn test_string_library() {
+        assert_eq!(ok("return string.format('%d %5.2f %s %x %q', 42, 3.14159, 'hi', 255, 'a\"b')"), ["42  3.14 hi ff \"a\\\"b\""]);
+        assert_eq!(ok("return ('hello'):upper(), string.sub('hello', 2, -2), string.rep('ab', 3), string.byte('A')"), ["HELLO", "ell", "ababab", "65"]);
+        assert_eq!(ok("return string.find('a.b', '.', 1, true), string.find('hello world', 'o w')"), ["2", "5", "7"]);
+        assert_eq!(ok("return string.match('key:123', '(%a+):(%d+)')"), ["key", "123"]);
+        assert_eq!(ok("return string.gsub('hello world', 'o', '0')"), ["hell0 w0rld", "2"]);
+        assert_eq!(ok("return string.gsub('abc', '%w', '%0%0'), string.gsub('$name', '%$(%w+)', {name = 'lua'})"), ["aabbcc", "lua", "1"]);
+        let src = "local words = {} for w in string.gmatch('one two three', '%a+') do words[#words + 1] = w end return table.concat(words, '|')";
+        assert_eq!(ok(src), ["one|two|three"]);
+        assert_eq!(ok("return string.match(' [x] ', '%b[]'), string.find('THE (quick) fox', '%f[%a]%a+', 5)"), ["[x]", "6", "10"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(run("local x = nil\nreturn x.y").unwrap_err(), "user_script:2: attempt to index local 'x' (a nil value)");
+        assert_eq!(run("return undefined_fn()").unwrap_err(), "user_script:1: attempt to call global 'undefined_fn' (a nil value)");
+        assert_eq!(run("return 1 + {}").unwrap_err(), "user_script:1: attempt to perform arithmetic on a table value");
+        assert_eq!(run("return 1 < 'x'").unwrap_err(), "user_script:1: attempt to compare number with string");
+        assert_eq!(run("\nerror('boom')").unwrap_err(), "user_script:2: boom");
+        assert_eq!(run("error({code = 1})").unwrap_err().split(':').next(), Some("table"));
+        assert_eq!(ok("return pcall(error, 'x', 0)"), ["false", "x"]);
+        assert_eq!(ok("local ok, e = pcall(function() local t = nil; return t[1] end) return ok, e"), ["false", "user_script:1: attempt to index local 't' (a nil value)"]);
+        assert_eq!(run("return string.rep()").unwrap_err(), "user_script:1: bad argument #1 to 'rep' (string expected, got no value)");
+        assert_eq!(run("local function f() return f() + 1 end return f()").unwrap_err(), "user_script:1: stack overflow");
+        assert_eq!(run("return 1 +").unwrap_err(), "user_script:1: unexpected symbol near <eof>");
+    }
+
+    #[test]
+    fn test_strict_globals() {
+        let mut interp = Interpreter::new("user_script");
+        interp.set_global("KEYS", Value::str("k"));
+        interp.strict_globals = true;
+        let function = interp.load(b"return KEYS").unwrap();
+        assert_eq!(interp.call(&function, Vec::new()).unwrap(), vec![Value::str("k")]);
+        for (src, expected) in [
+            ("x = 1", "user_script:1: Attempt to modify a readonly table"),
+            ("return y", "user_script:1: Script attempted to access nonexistent global variable 'y'"),
+        ] {
+            let function = interp.load(src.as_bytes()).unwrap();
+            match interp.call(&function, Vec::new()) {
+                Err(LuaError::Error(value)) => assert_eq!(Interpreter::display(&value), expected),
+                other => panic!("{:?}", other.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut interp = Interpreter::new("user_script");
+        let mut checks = 0;
+        interp.set_interrupt(move || {
+            checks += 1;
+            checks > 100
+        });
+        let function = interp.load(b"local ok = pcall(function() while true do end end) return ok").unwrap();
+        assert!(matches!(interp.call(&function, Vec::new()), Err(LuaError::Interrupted)));
+    }
+}
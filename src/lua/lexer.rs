@@ -0,0 +1,359 @@
+use std::rc::Rc;
+
+/// Lua 5.1 토큰
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Name(String),
+    Number(f64),
+    Str(Rc<[u8]>),
+    And,
+    Break,
+    Do,
+    Else,
+    ElseIf,
+    End,
+    False,
+    For,
+    Function,
+    If,
+    In,
+    Local,
+    Nil,
+    Not,
+    Or,
+    Repeat,
+    Return,
+    Then,
+    True,
+    Until,
+    While,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    Hash,
+    Eq,
+    NotEq,
+    LessEq,
+    GreaterEq,
+    Less,
+    Greater,
+    Assign,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Semicolon,
+    Colon,
+    Comma,
+    Dot,
+    Concat,
+    Ellipsis,
+    Eof,
+}
+
+impl Token {
+    /// 에러 메시지에서 보여줄 모양 ("near 'x'")
+    pub fn describe(&self) -> String {
+        let text = match self {
+            Token::Name(name) => return format!("'{}'", name),
+            Token::Number(n) => return format!("'{}'", crate::lua::value::format_number(*n)),
+            Token::Str(s) => return format!("'{}'", String::from_utf8_lossy(s)),
+            Token::Eof => return "<eof>".to_string(),
+            Token::And => "and",
+            Token::Break => "break",
+            Token::Do => "do",
+            Token::Else => "else",
+            Token::ElseIf => "elseif",
+            Token::End => "end",
+            Token::False => "false",
+            Token::For => "for",
+            Token::Function => "function",
+            Token::If => "if",
+            Token::In => "in",
+            Token::Local => "local",
+            Token::Nil => "nil",
+            Token::Not => "not",
+            Token::Or => "or",
+            Token::Repeat => "repeat",
+            Token::Return => "return",
+            Token::Then => "then",
+            Token::True => "true",
+            Token::Until => "until",
+            Token::While => "while",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::Caret => "^",
+            Token::Hash => "#",
+            Token::Eq => "==",
+            Token::NotEq => "~=",
+            Token::LessEq => "<=",
+            Token::GreaterEq => ">=",
+            Token::Less => "<",
+            Token::Greater => ">",
+            Token::Assign => "=",
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::LBrace => "{",
+            Token::RBrace => "}",
+            Token::LBracket => "[",
+            Token::RBracket => "]",
+            Token::Semicolon => ";",
+            Token::Colon => ":",
+            Token::Comma => ",",
+            Token::Dot => ".",
+            Token::Concat => "..",
+            Token::Ellipsis => "...",
+        };
+        format!("'{}'", text)
+    }
+}
+
+fn keyword(name: &str) -> Option<Token> {
+    Some(match name {
+        "and" => Token::And,
+        "break" => Token::Break,
+        "do" => Token::Do,
+        "else" => Token::Else,
+        "elseif" => Token::ElseIf,
+        "end" => Token::End,
+        "false" => Token::False,
+        "for" => Token::For,
+        "function" => Token::Function,
+        "if" => Token::If,
+        "in" => Token::In,
+        "local" => Token::Local,
+        "nil" => Token::Nil,
+        "not" => Token::Not,
+        "or" => Token::Or,
+        "repeat" => Token::Repeat,
+        "return" => Token::Return,
+        "then" => Token::Then,
+        "true" => Token::True,
+        "until" => Token::Until,
+        "while" => Token::While,
+        _ => return None,
+    })
+}
+
+/// 소스를 (토큰, 줄 번호) 목록으로 나눈다. 에러는 "줄: 메시지".
+pub fn tokenize(src: &[u8]) -> Result<Vec<(Token, u32)>, (u32, String)> {
+    Lexer { src, pos: 0, line: 1 }.run()
+}
+
+struct Lexer<'a> {
+    src: &'a [u8],
+    pos: usize,
+    line: u32,
+}
+
+impl Lexer<'_> {
+    fn peek(&self, ahead: usize) -> u8 {
+        self.src.get(self.pos + ahead).copied().unwrap_or(0)
+    }
+
+    fn run(mut self) -> Result<Vec<(Token, u32)>, (u32, String)> {
+        let mut tokens = Vec::new();
+        loop {
+            self.skip_space_and_comments()?;
+            let line = self.line;
+            if self.pos >= self.src.len() {
+                tokens.push((Token::Eof, line));
+                return Ok(tokens);
+            }
+            let token = self.next_token()?;
+            tokens.push((token, line));
+        }
+    }
+
+    fn skip_space_and_comments(&mut self) -> Result<(), (u32, String)> {
+        while self.pos < self.src.len() {
+            match self.peek(0) {
+                b'\n' => {
+                    self.line += 1;
+                    self.pos += 1;
+                }
+                b' ' | b'\t' | b'\r' | 0x0b | 0x0c => self.pos += 1,
+                b'-' if self.peek(1) == b'-' => {
+                    self.pos += 2;
+                    if self.peek(0) == b'[' {
+                        if let Some(level) = self.long_bracket_level() {
+                            self.long_string(level)?;
+                            continue;
+                        }
+                    }
+                    while self.pos < self.src.len() && self.peek(0) != b'\n' {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// `[[` 나 `[==[` 로 시작하면 = 의 개수
+    fn long_bracket_level(&self) -> Option<usize> {
+        let mut level = 0;
+        while self.peek(1 + level) == b'=' {
+            level += 1;
+        }
+        (self.peek(1 + level) == b'[').then_some(level)
+    }
+
+    fn long_string(&mut self, level: usize) -> Result<Vec<u8>, (u32, String)> {
+        let start_line = self.line;
+        self.pos += level + 2;
+        // 여는 괄호 바로 뒤의 줄바꿈은 무시한다
+        if self.peek(0) == b'\r' {
+            self.pos += 1;
+        }
+        if self.peek(0) == b'\n' {
+            self.line += 1;
+            self.pos += 1;
+        }
+        let mut out = Vec::new();
+        loop {
+            if self.pos >= self.src.len() {
+                return Err((start_line, "unfinished long string/comment near '<eof>'".to_string()));
+            }
+            let c = self.peek(0);
+            if c == b']' && (1..=level).all(|i| self.peek(i) == b'=') && self.peek(level + 1) == b']' {
+                self.pos += level + 2;
+                return Ok(out);
+            }
+            if c == b'\n' {
+                self.line += 1;
+            }
+            out.push(c);
+            self.pos += 1;
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token, (u32, String)> {
+        let c = self.peek(0);
+        if c.is_ascii_alphabetic() || c == b'_' {
+            let start = self.pos;
+            while self.peek(0).is_ascii_alphanumeric() || self.peek(0) == b'_' {
+                self.pos += 1;
+            }
+            let name = std::str::from_utf8(&self.src[start..self.pos]).unwrap();
+            return Ok(keyword(name).unwrap_or_else(|| Token::Name(name.to_string())));
+        }
+        if c.is_ascii_digit() || (c == b'.' && self.peek(1).is_ascii_digit()) {
+            return self.number();
+        }
+        if c == b'"' || c == b'\'' {
+            return self.string(c);
+        }
+        if c == b'[' {
+            if let Some(level) = self.long_bracket_level() {
+                return Ok(Token::Str(self.long_string(level)?.into()));
+            }
+        }
+
+        let (token, len) = match (c, self.peek(1), self.peek(2)) {
+            (b'.', b'.', b'.') => (Token::Ellipsis, 3),
+            (b'.', b'.', _) => (Token::Concat, 2),
+            (b'=', b'=', _) => (Token::Eq, 2),
+            (b'~', b'=', _) => (Token::NotEq, 2),
+            (b'<', b'=', _) => (Token::LessEq, 2),
+            (b'>', b'=', _) => (Token::GreaterEq, 2),
+            (b'+', _, _) => (Token::Plus, 1),
+            (b'-', _, _) => (Token::Minus, 1),
+            (b'*', _, _) => (Token::Star, 1),
+            (b'/', _, _) => (Token::Slash, 1),
+            (b'%', _, _) => (Token::Percent, 1),
+            (b'^', _, _) => (Token::Caret, 1),
+            (b'#', _, _) => (Token::Hash, 1),
+            (b'<', _, _) => (Token::Less, 1),
+            (b'>', _, _) => (Token::Greater, 1),
+            (b'=', _, _) => (Token::Assign, 1),
+            (b'(', _, _) => (Token::LParen, 1),
+            (b')', _, _) => (Token::RParen, 1),
+            (b'{', _, _) => (Token::LBrace, 1),
+            (b'}', _, _) => (Token::RBrace, 1),
+            (b'[', _, _) => (Token::LBracket, 1),
+            (b']', _, _) => (Token::RBracket, 1),
+            (b';', _, _) => (Token::Semicolon, 1),
+            (b':', _, _) => (Token::Colon, 1),
+            (b',', _, _) => (Token::Comma, 1),
+            (b'.', _, _) => (Token::Dot, 1),
+            _ => return Err((self.line, format!("unexpected symbol near '{}'", c as char))),
+        };
+        self.pos += len;
+        Ok(token)
+    }
+
+    fn number(&mut self) -> Result<Token, (u32, String)> {
+        let start = self.pos;
+        // Lua 5.1 처럼 숫자 뒤에 붙은 글자까지 한 덩어리로 읽고 나서 검사한다
+        while self.peek(0).is_ascii_alphanumeric() || self.peek(0) == b'.' || self.peek(0) == b'_'
+            || ((self.peek(0) == b'+' || self.peek(0) == b'-') && matches!(self.src[self.pos - 1], b'e' | b'E'))
+        {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.src[start..self.pos]).unwrap();
+        match crate::lua::value::parse_number(text) {
+            Some(n) => Ok(Token::Number(n)),
+            None => Err((self.line, format!("malformed number near '{}'", text))),
+        }
+    }
+
+    fn string(&mut self, quote: u8) -> Result<Token, (u32, String)> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let c = self.peek(0);
+            if self.pos >= self.src.len() || c == b'\n' {
+                return Err((self.line, "unfinished string".to_string()));
+            }
+            self.pos += 1;
+            if c == quote {
+                return Ok(Token::Str(out.into()));
+            }
+            if c != b'\\' {
+                out.push(c);
+                continue;
+            }
+            let escaped = self.peek(0);
+            self.pos += 1;
+            match escaped {
+                b'n' => out.push(b'\n'),
+                b't' => out.push(b'\t'),
+                b'r' => out.push(b'\r'),
+                b'a' => out.push(0x07),
+                b'b' => out.push(0x08),
+                b'f' => out.push(0x0c),
+                b'v' => out.push(0x0b),
+                b'\n' => {
+                    self.line += 1;
+                    out.push(b'\n');
+                }
+                b'0'..=b'9' => {
+                    let mut value = (escaped - b'0') as u32;
+                    for _ in 0..2 {
+                        if !self.peek(0).is_ascii_digit() {
+                            break;
+                        }
+                        value = value * 10 + (self.peek(0) - b'0') as u32;
+                        self.pos += 1;
+                    }
+                    if value > 255 {
+                        return Err((self.line, "escape sequence too large".to_string()));
+                    }
+                    out.push(value as u8);
+                }
+                0 if self.pos > self.src.len() => return Err((self.line, "unfinished string".to_string())),
+                other => out.push(other),
+            }
+        }
+    }
+}
@@ -0,0 +1,549 @@
+use crate::lua::lexer::{tokenize, Token};
+use std::rc::Rc;
+
+/// 표현식과 문장이 중첩될 수 있는 깊이 (Lua 5.1 의 LUAI_MAXCCALLS)
+const MAX_DEPTH: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Concat,
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Not,
+    Len,
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    Nil,
+    True,
+    False,
+    Number(f64),
+    Str(Rc<[u8]>),
+    Vararg,
+    Function(Rc<FuncBody>),
+    Name(String),
+    Index(Box<Expr>, Box<Expr>),
+    /// 함수, 인자, 줄 번호
+    Call(Box<Expr>, Vec<Expr>, u32),
+    /// obj:name(args)
+    Method(Box<Expr>, Rc<[u8]>, Vec<Expr>, u32),
+    Binary(BinOp, Box<Expr>, Box<Expr>, u32),
+    Unary(UnOp, Box<Expr>, u32),
+    Table(Vec<Field>),
+    /// 괄호로 감싸면 여러 값이 하나로 잘린다
+    Paren(Box<Expr>),
+}
+
+#[derive(Debug)]
+pub enum Field {
+    Positional(Expr),
+    Named(Expr, Expr),
+}
+
+#[derive(Debug)]
+pub struct FuncBody {
+    pub params: Vec<String>,
+    pub vararg: bool,
+    pub body: Block,
+}
+
+#[derive(Debug)]
+pub enum Stat {
+    Local(Vec<String>, Vec<Expr>),
+    Assign(Vec<Expr>, Vec<Expr>),
+    Call(Expr),
+    Do(Block),
+    While(Expr, Block),
+    Repeat(Block, Expr),
+    If(Vec<(Expr, Block)>, Option<Block>),
+    NumericFor(String, Expr, Expr, Option<Expr>, Block),
+    GenericFor(Vec<String>, Vec<Expr>, Block),
+    LocalFunction(String, Rc<FuncBody>),
+    Return(Vec<Expr>),
+    Break,
+}
+
+#[derive(Debug, Default)]
+pub struct Block {
+    /// 문장과 그 문장이 시작하는 줄
+    pub stats: Vec<(Stat, u32)>,
+}
+
+/// 청크 (스크립트 전체) 를 파싱한다. 에러는 Lua 처럼 "chunk:줄: 메시지".
+pub fn parse(src: &[u8], chunk: &str) -> Result<Rc<FuncBody>, String> {
+    let tokens = tokenize(src).map_err(|(line, msg)| format!("{}:{}: {}", chunk, line, msg))?;
+    let mut parser = Parser { tokens, pos: 0, depth: 0 };
+    let body = parser.block().and_then(|block| {
+        parser.expect(Token::Eof)?;
+        Ok(block)
+    });
+    body.map(|body| Rc::new(FuncBody { params: Vec::new(), vararg: true, body }))
+        .map_err(|(line, msg)| format!("{}:{}: {}", chunk, line, msg))
+}
+
+type ParseResult<T> = Result<T, (u32, String)>;
+
+struct Parser {
+    tokens: Vec<(Token, u32)>,
+    pos: usize,
+    depth: usize,
+}
+
+/// 이항 연산자의 (왼쪽, 오른쪽) 우선순위. 오른쪽이 더 작으면 오른쪽 결합이다.
+fn binary_priority(token: &Token) -> Option<(BinOp, u8, u8)> {
+    Some(match token {
+        Token::Or => (BinOp::Or, 1, 1),
+        Token::And => (BinOp::And, 2, 2),
+        Token::Less => (BinOp::Less, 3, 3),
+        Token::Greater => (BinOp::Greater, 3, 3),
+        Token::LessEq => (BinOp::LessEq, 3, 3),
+        Token::GreaterEq => (BinOp::GreaterEq, 3, 3),
+        Token::NotEq => (BinOp::NotEq, 3, 3),
+        Token::Eq => (BinOp::Eq, 3, 3),
+        Token::Concat => (BinOp::Concat, 5, 4),
+        Token::Plus => (BinOp::Add, 6, 6),
+        Token::Minus => (BinOp::Sub, 6, 6),
+        Token::Star => (BinOp::Mul, 7, 7),
+        Token::Slash => (BinOp::Div, 7, 7),
+        Token::Percent => (BinOp::Mod, 7, 7),
+        Token::Caret => (BinOp::Pow, 10, 9),
+        _ => return None,
+    })
+}
+
+const UNARY_PRIORITY: u8 = 8;
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn line(&self) -> u32 {
+        self.tokens[self.pos].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn check(&mut self, token: &Token) -> bool {
+        if self.peek() == token {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error<T>(&self, msg: &str) -> ParseResult<T> {
+        Err((self.line(), format!("{} near {}", msg, self.peek().describe())))
+    }
+
+    fn expect(&mut self, token: Token) -> ParseResult<()> {
+        if self.check(&token) {
+            Ok(())
+        } else {
+            self.error(&format!("{} expected", token.describe()))
+        }
+    }
+
+    /// `what` 을 닫는 토큰. 여는 줄과 다르면 Lua 처럼 그 줄을 알려준다.
+    fn expect_match(&mut self, token: Token, opener: Token, line: u32) -> ParseResult<()> {
+        if self.check(&token) {
+            return Ok(());
+        }
+        if line == self.line() {
+            self.error(&format!("{} expected", token.describe()))
+        } else {
+            self.error(&format!("{} expected (to close {} at line {})", token.describe(), opener.describe(), line))
+        }
+    }
+
+    fn name(&mut self) -> ParseResult<String> {
+        match self.peek().clone() {
+            Token::Name(name) => {
+                self.advance();
+                Ok(name)
+            }
+            _ => self.error("<name> expected"),
+        }
+    }
+
+    fn enter(&mut self) -> ParseResult<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return self.error("chunk has too many syntax levels");
+        }
+        Ok(())
+    }
+
+    fn block_follows(&self) -> bool {
+        matches!(self.peek(), Token::Else | Token::ElseIf | Token::End | Token::Until | Token::Eof)
+    }
+
+    fn block(&mut self) -> ParseResult<Block> {
+        self.enter()?;
+        let mut block = Block::default();
+        while !self.block_follows() {
+            let line = self.line();
+            // return 과 break 는 블록의 마지막 문장이어야 한다
+            if self.check(&Token::Return) {
+                let values = if self.block_follows() || *self.peek() == Token::Semicolon { Vec::new() } else { self.expr_list()? };
+                self.check(&Token::Semicolon);
+                block.stats.push((Stat::Return(values), line));
+                break;
+            }
+            if self.check(&Token::Break) {
+                self.check(&Token::Semicolon);
+                block.stats.push((Stat::Break, line));
+                break;
+            }
+            let stat = self.statement()?;
+            self.check(&Token::Semicolon);
+            block.stats.push((stat, line));
+        }
+        self.depth -= 1;
+        Ok(block)
+    }
+
+    fn statement(&mut self) -> ParseResult<Stat> {
+        let line = self.line();
+        match self.peek() {
+            Token::If => {
+                self.advance();
+                let mut clauses = Vec::new();
+                let cond = self.expr()?;
+                self.expect(Token::Then)?;
+                clauses.push((cond, self.block()?));
+                let mut otherwise = None;
+                loop {
+                    if self.check(&Token::ElseIf) {
+                        let cond = self.expr()?;
+                        self.expect(Token::Then)?;
+                        clauses.push((cond, self.block()?));
+                    } else if self.check(&Token::Else) {
+                        otherwise = Some(self.block()?);
+                        self.expect_match(Token::End, Token::If, line)?;
+                        break;
+                    } else {
+                        self.expect_match(Token::End, Token::If, line)?;
+                        break;
+                    }
+                }
+                Ok(Stat::If(clauses, otherwise))
+            }
+            Token::While => {
+                self.advance();
+                let cond = self.expr()?;
+                self.expect(Token::Do)?;
+                let body = self.block()?;
+                self.expect_match(Token::End, Token::While, line)?;
+                Ok(Stat::While(cond, body))
+            }
+            Token::Do => {
+                self.advance();
+                let body = self.block()?;
+                self.expect_match(Token::End, Token::Do, line)?;
+                Ok(Stat::Do(body))
+            }
+            Token::For => {
+                self.advance();
+                let first = self.name()?;
+                if self.check(&Token::Assign) {
+                    let start = self.expr()?;
+                    self.expect(Token::Comma)?;
+                    let limit = self.expr()?;
+                    let step = if self.check(&Token::Comma) { Some(self.expr()?) } else { None };
+                    self.expect(Token::Do)?;
+                    let body = self.block()?;
+                    self.expect_match(Token::End, Token::For, line)?;
+                    return Ok(Stat::NumericFor(first, start, limit, step, body));
+                }
+                let mut names = vec![first];
+                while self.check(&Token::Comma) {
+                    names.push(self.name()?);
+                }
+                if !self.check(&Token::In) {
+                    return self.error("'=' or 'in' expected");
+                }
+                let exprs = self.expr_list()?;
+                self.expect(Token::Do)?;
+                let body = self.block()?;
+                self.expect_match(Token::End, Token::For, line)?;
+                Ok(Stat::GenericFor(names, exprs, body))
+            }
+            Token::Repeat => {
+                self.advance();
+                let body = self.block()?;
+                self.expect_match(Token::Until, Token::Repeat, line)?;
+                let cond = self.expr()?;
+                Ok(Stat::Repeat(body, cond))
+            }
+            Token::Function => {
+                self.advance();
+                // function a.b.c:m() 는 a.b.c.m = function(self, ...) 과 같다
+                let mut target = Expr::Name(self.name()?);
+                let mut method = false;
+                loop {
+                    if self.check(&Token::Dot) {
+                        let key = self.name()?;
+                        target = Expr::Index(Box::new(target), Box::new(Expr::Str(key.into_bytes().into())));
+                    } else if self.check(&Token::Colon) {
+                        let key = self.name()?;
+                        target = Expr::Index(Box::new(target), Box::new(Expr::Str(key.into_bytes().into())));
+                        method = true;
+                        break;
+                    } else {
+                        break;
+                    }
+                }
+                let body = self.function_body(method, line)?;
+                Ok(Stat::Assign(vec![target], vec![Expr::Function(body)]))
+            }
+            Token::Local => {
+                self.advance();
+                if self.check(&Token::Function) {
+                    let name = self.name()?;
+                    let body = self.function_body(false, line)?;
+                    return Ok(Stat::LocalFunction(name, body));
+                }
+                let mut names = vec![self.name()?];
+                while self.check(&Token::Comma) {
+                    names.push(self.name()?);
+                }
+                let values = if self.check(&Token::Assign) { self.expr_list()? } else { Vec::new() };
+                Ok(Stat::Local(names, values))
+            }
+            _ => {
+                let expr = self.suffixed_expr()?;
+                if matches!(self.peek(), Token::Assign | Token::Comma) {
+                    let mut targets = vec![expr];
+                    while self.check(&Token::Comma) {
+                        targets.push(self.suffixed_expr()?);
+                    }
+                    self.expect(Token::Assign)?;
+                    if targets.iter().any(|t| !matches!(t, Expr::Name(_) | Expr::Index(..))) {
+                        return Err((line, "syntax error near '='".to_string()));
+                    }
+                    let values = self.expr_list()?;
+                    Ok(Stat::Assign(targets, values))
+                } else if matches!(expr, Expr::Call(..) | Expr::Method(..)) {
+                    Ok(Stat::Call(expr))
+                } else {
+                    self.error("syntax error")
+                }
+            }
+        }
+    }
+
+    fn function_body(&mut self, method: bool, line: u32) -> ParseResult<Rc<FuncBody>> {
+        self.expect(Token::LParen)?;
+        let mut params = Vec::new();
+        if method {
+            params.push("self".to_string());
+        }
+        let mut vararg = false;
+        if !self.check(&Token::RParen) {
+            loop {
+                if self.check(&Token::Ellipsis) {
+                    vararg = true;
+                    break;
+                }
+                params.push(self.name()?);
+                if !self.check(&Token::Comma) {
+                    break;
+                }
+            }
+            self.expect(Token::RParen)?;
+        }
+        let body = self.block()?;
+        self.expect_match(Token::End, Token::Function, line)?;
+        Ok(Rc::new(FuncBody { params, vararg, body }))
+    }
+
+    fn expr_list(&mut self) -> ParseResult<Vec<Expr>> {
+        let mut exprs = vec![self.expr()?];
+        while self.check(&Token::Comma) {
+            exprs.push(self.expr()?);
+        }
+        Ok(exprs)
+    }
+
+    fn expr(&mut self) -> ParseResult<Expr> {
+        self.sub_expr(0)
+    }
+
+    /// 우선순위가 `limit` 보다 높은 이항 연산자까지 묶는다
+    fn sub_expr(&mut self, limit: u8) -> ParseResult<Expr> {
+        self.enter()?;
+        let line = self.line();
+        let unary = match self.peek() {
+            Token::Not => Some(UnOp::Not),
+            Token::Minus => Some(UnOp::Neg),
+            Token::Hash => Some(UnOp::Len),
+            _ => None,
+        };
+        let mut left = match unary {
+            Some(op) => {
+                self.advance();
+                let operand = self.sub_expr(UNARY_PRIORITY)?;
+                match (op, operand) {
+                    // 상수는 미리 부호를 바꿔둔다
+                    (UnOp::Neg, Expr::Number(n)) => Expr::Number(-n),
+                    (op, operand) => Expr::Unary(op, Box::new(operand), line),
+                }
+            }
+            None => self.simple_expr()?,
+        };
+        while let Some((op, left_priority, right_priority)) = binary_priority(self.peek()) {
+            if left_priority <= limit {
+                break;
+            }
+            let line = self.line();
+            self.advance();
+            let right = self.sub_expr(right_priority)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right), line);
+        }
+        self.depth -= 1;
+        Ok(left)
+    }
+
+    fn simple_expr(&mut self) -> ParseResult<Expr> {
+        let line = self.line();
+        let expr = match self.peek().clone() {
+            Token::Number(n) => Expr::Number(n),
+            Token::Str(s) => Expr::Str(s),
+            Token::Nil => Expr::Nil,
+            Token::True => Expr::True,
+            Token::False => Expr::False,
+            Token::Ellipsis => Expr::Vararg,
+            Token::LBrace => return self.table(),
+            Token::Function => {
+                self.advance();
+                return Ok(Expr::Function(self.function_body(false, line)?));
+            }
+            _ => return self.suffixed_expr(),
+        };
+        self.advance();
+        Ok(expr)
+    }
+
+    fn primary_expr(&mut self) -> ParseResult<Expr> {
+        let line = self.line();
+        match self.peek().clone() {
+            Token::Name(name) => {
+                self.advance();
+                Ok(Expr::Name(name))
+            }
+            Token::LParen => {
+                self.advance();
+                let expr = self.expr()?;
+                self.expect_match(Token::RParen, Token::LParen, line)?;
+                Ok(Expr::Paren(Box::new(expr)))
+            }
+            _ => self.error("unexpected symbol"),
+        }
+    }
+
+    fn suffixed_expr(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.primary_expr()?;
+        loop {
+            let line = self.line();
+            match self.peek() {
+                Token::Dot => {
+                    self.advance();
+                    let key = self.name()?;
+                    expr = Expr::Index(Box::new(expr), Box::new(Expr::Str(key.into_bytes().into())));
+                }
+                Token::LBracket => {
+                    self.advance();
+                    let key = self.expr()?;
+                    self.expect(Token::RBracket)?;
+                    expr = Expr::Index(Box::new(expr), Box::new(key));
+                }
+                Token::Colon => {
+                    self.advance();
+                    let name = self.name()?;
+                    let args = self.call_args()?;
+                    expr = Expr::Method(Box::new(expr), name.into_bytes().into(), args, line);
+                }
+                Token::LParen | Token::Str(_) | Token::LBrace => {
+                    let args = self.call_args()?;
+                    expr = Expr::Call(Box::new(expr), args, line);
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    fn call_args(&mut self) -> ParseResult<Vec<Expr>> {
+        let line = self.line();
+        match self.peek().clone() {
+            Token::Str(s) => {
+                self.advance();
+                Ok(vec![Expr::Str(s)])
+            }
+            Token::LBrace => Ok(vec![self.table()?]),
+            Token::LParen => {
+                self.advance();
+                if self.check(&Token::RParen) {
+                    return Ok(Vec::new());
+                }
+                let args = self.expr_list()?;
+                self.expect_match(Token::RParen, Token::LParen, line)?;
+                Ok(args)
+            }
+            _ => self.error("function arguments expected"),
+        }
+    }
+
+    fn table(&mut self) -> ParseResult<Expr> {
+        let line = self.line();
+        self.expect(Token::LBrace)?;
+        let mut fields = Vec::new();
+        while *self.peek() != Token::RBrace {
+            match self.peek().clone() {
+                Token::LBracket => {
+                    self.advance();
+                    let key = self.expr()?;
+                    self.expect(Token::RBracket)?;
+                    self.expect(Token::Assign)?;
+                    fields.push(Field::Named(key, self.expr()?));
+                }
+                Token::Name(name) if self.tokens.get(self.pos + 1).is_some_and(|(t, _)| *t == Token::Assign) => {
+                    self.advance();
+                    self.advance();
+                    fields.push(Field::Named(Expr::Str(name.into_bytes().into()), self.expr()?));
+                }
+                _ => fields.push(Field::Positional(self.expr()?)),
+            }
+            if !self.check(&Token::Comma) && !self.check(&Token::Semicolon) {
+                break;
+            }
+        }
+        self.expect_match(Token::RBrace, Token::LBrace, line)?;
+        Ok(Expr::Table(fields))
+    }
+}
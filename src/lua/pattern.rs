@@ -0,0 +1,319 @@
+/// Lua 5.1 패턴 매칭 (lstrlib.c 를 옮긴 것)
+const MAX_CAPTURES: usize = 32;
+const CAP_UNFINISHED: isize = -1;
+const CAP_POSITION: isize = -2;
+/// 재귀 매칭 깊이 제한
+const MAX_RECURSION: usize = 200;
+
+/// 캡처 하나. `()` 는 위치 (1부터) 를 캡처한다.
+pub enum Capture<'a> {
+    Str(&'a [u8]),
+    Position(usize),
+}
+
+pub struct Matcher<'a> {
+    src: &'a [u8],
+    pat: &'a [u8],
+    level: usize,
+    /// (시작 위치, 길이 또는 CAP_*)
+    capture: [(usize, isize); MAX_CAPTURES],
+    depth: usize,
+}
+
+impl<'a> Matcher<'a> {
+    pub fn new(src: &'a [u8], pat: &'a [u8]) -> Matcher<'a> {
+        Matcher { src, pat, level: 0, capture: [(0, 0); MAX_CAPTURES], depth: 0 }
+    }
+
+    /// src[s..] 의 앞부분이 pat[p..] 과 맞으면 맞은 부분의 끝
+    pub fn match_at(&mut self, s: usize, p: usize) -> Result<Option<usize>, String> {
+        self.level = 0;
+        self.depth = 0;
+        self.do_match(s, p)
+    }
+
+    /// i 번째 캡처. 캡처가 없는 패턴은 0 번이 매치 전체다.
+    pub fn capture(&self, i: usize, s: usize, e: usize) -> Result<Capture<'a>, String> {
+        if i >= self.level {
+            return if i == 0 { Ok(Capture::Str(&self.src[s..e])) } else { Err("invalid capture index".to_string()) };
+        }
+        match self.capture[i] {
+            (_, CAP_UNFINISHED) => Err("unfinished capture".to_string()),
+            (start, CAP_POSITION) => Ok(Capture::Position(start + 1)),
+            (start, len) => Ok(Capture::Str(&self.src[start..start + len as usize])),
+        }
+    }
+
+    /// 모든 캡처. `whole_match` 면 캡처가 없을 때 매치 전체를 돌려준다.
+    pub fn captures(&self, s: usize, e: usize, whole_match: bool) -> Result<Vec<Capture<'a>>, String> {
+        let count = if self.level == 0 && whole_match { 1 } else { self.level };
+        (0..count).map(|i| self.capture(i, s, e)).collect()
+    }
+
+    fn do_match(&mut self, s: usize, p: usize) -> Result<Option<usize>, String> {
+        self.depth += 1;
+        if self.depth > MAX_RECURSION {
+            return Err("pattern too complex".to_string());
+        }
+        let result = self.match_here(s, p);
+        self.depth -= 1;
+        result
+    }
+
+    fn match_here(&mut self, mut s: usize, mut p: usize) -> Result<Option<usize>, String> {
+        let (src, pat) = (self.src, self.pat);
+        loop {
+            if p == pat.len() {
+                return Ok(Some(s));
+            }
+            match (pat[p], pat.get(p + 1).copied()) {
+                (b'(', Some(b')')) => return self.start_capture(s, p + 2, CAP_POSITION),
+                (b'(', _) => return self.start_capture(s, p + 1, CAP_UNFINISHED),
+                (b')', _) => return self.end_capture(s, p + 1),
+                (b'$', None) => return Ok((s == src.len()).then_some(s)),
+                (b'%', Some(b'b')) => match self.match_balance(s, p + 2)? {
+                    Some(e) => {
+                        s = e;
+                        p += 4;
+                        continue;
+                    }
+                    None => return Ok(None),
+                },
+                (b'%', Some(b'f')) => {
+                    p += 2;
+                    if pat.get(p) != Some(&b'[') {
+                        return Err("missing '[' after '%f' in pattern".to_string());
+                    }
+                    let ep = self.class_end(p)?;
+                    let previous = if s == 0 { 0 } else { src[s - 1] };
+                    let current = src.get(s).copied().unwrap_or(0);
+                    if !self.match_bracket_class(previous, p, ep - 1) && self.match_bracket_class(current, p, ep - 1) {
+                        p = ep;
+                        continue;
+                    }
+                    return Ok(None);
+                }
+                (b'%', Some(d)) if d.is_ascii_digit() => match self.match_capture(s, d)? {
+                    Some(e) => {
+                        s = e;
+                        p += 2;
+                        continue;
+                    }
+                    None => return Ok(None),
+                },
+                _ => {}
+            }
+
+            let ep = self.class_end(p)?;
+            let matched = s < src.len() && self.single_match(src[s], p, ep);
+            match pat.get(ep) {
+                Some(b'?') => {
+                    if matched {
+                        if let Some(e) = self.do_match(s + 1, ep + 1)? {
+                            return Ok(Some(e));
+                        }
+                    }
+                    p = ep + 1;
+                }
+                Some(b'*') => return self.max_expand(s, p, ep),
+                Some(b'+') => return if matched { self.max_expand(s + 1, p, ep) } else { Ok(None) },
+                Some(b'-') => return self.min_expand(s, p, ep),
+                _ => {
+                    if !matched {
+                        return Ok(None);
+                    }
+                    s += 1;
+                    p = ep;
+                }
+            }
+        }
+    }
+
+    /// p 에서 시작하는 문자 클래스 하나의 끝
+    fn class_end(&self, p: usize) -> Result<usize, String> {
+        let pat = self.pat;
+        let mut p = p + 1;
+        match pat[p - 1] {
+            b'%' if p >= pat.len() => Err("malformed pattern (ends with '%')".to_string()),
+            b'%' => Ok(p + 1),
+            b'[' => {
+                if pat.get(p) == Some(&b'^') {
+                    p += 1;
+                }
+                // 첫 글자는 ']' 여도 클래스에 들어간다
+                loop {
+                    if p >= pat.len() {
+                        return Err("malformed pattern (missing ']')".to_string());
+                    }
+                    let c = pat[p];
+                    p += 1;
+                    if c == b'%' && p < pat.len() {
+                        p += 1;
+                    }
+                    if p >= pat.len() {
+                        return Err("malformed pattern (missing ']')".to_string());
+                    }
+                    if pat[p] == b']' {
+                        return Ok(p + 1);
+                    }
+                }
+            }
+            _ => Ok(p),
+        }
+    }
+
+    fn single_match(&self, c: u8, p: usize, ep: usize) -> bool {
+        match self.pat[p] {
+            b'.' => true,
+            b'%' => match_class(c, self.pat[p + 1]),
+            b'[' => self.match_bracket_class(c, p, ep - 1),
+            other => other == c,
+        }
+    }
+
+    /// p 는 '[', ec 는 ']' 의 위치
+    fn match_bracket_class(&self, c: u8, p: usize, ec: usize) -> bool {
+        let pat = self.pat;
+        let mut p = p;
+        let mut sig = true;
+        if pat[p + 1] == b'^' {
+            sig = false;
+            p += 1;
+        }
+        loop {
+            p += 1;
+            if p >= ec {
+                return !sig;
+            }
+            if pat[p] == b'%' {
+                p += 1;
+                if match_class(c, pat[p]) {
+                    return sig;
+                }
+            } else if pat[p + 1] == b'-' && p + 2 < ec {
+                p += 2;
+                if pat[p - 2] <= c && c <= pat[p] {
+                    return sig;
+                }
+            } else if pat[p] == c {
+                return sig;
+            }
+        }
+    }
+
+    fn max_expand(&mut self, s: usize, p: usize, ep: usize) -> Result<Option<usize>, String> {
+        let mut i = 0;
+        while s + i < self.src.len() && self.single_match(self.src[s + i], p, ep) {
+            i += 1;
+        }
+        loop {
+            if let Some(e) = self.do_match(s + i, ep + 1)? {
+                return Ok(Some(e));
+            }
+            if i == 0 {
+                return Ok(None);
+            }
+            i -= 1;
+        }
+    }
+
+    fn min_expand(&mut self, mut s: usize, p: usize, ep: usize) -> Result<Option<usize>, String> {
+        loop {
+            if let Some(e) = self.do_match(s, ep + 1)? {
+                return Ok(Some(e));
+            }
+            if s < self.src.len() && self.single_match(self.src[s], p, ep) {
+                s += 1;
+            } else {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn start_capture(&mut self, s: usize, p: usize, what: isize) -> Result<Option<usize>, String> {
+        if self.level >= MAX_CAPTURES {
+            return Err("too many captures".to_string());
+        }
+        self.capture[self.level] = (s, what);
+        self.level += 1;
+        let result = self.do_match(s, p)?;
+        if result.is_none() {
+            self.level -= 1;
+        }
+        Ok(result)
+    }
+
+    fn end_capture(&mut self, s: usize, p: usize) -> Result<Option<usize>, String> {
+        let l = (0..self.level)
+            .rev()
+            .find(|&l| self.capture[l].1 == CAP_UNFINISHED)
+            .ok_or_else(|| "invalid pattern capture".to_string())?;
+        self.capture[l].1 = (s - self.capture[l].0) as isize;
+        let result = self.do_match(s, p)?;
+        if result.is_none() {
+            self.capture[l].1 = CAP_UNFINISHED;
+        }
+        Ok(result)
+    }
+
+    fn match_balance(&self, s: usize, p: usize) -> Result<Option<usize>, String> {
+        if p + 1 >= self.pat.len() {
+            return Err("unbalanced pattern".to_string());
+        }
+        if s >= self.src.len() || self.src[s] != self.pat[p] {
+            return Ok(None);
+        }
+        let (open, close) = (self.pat[p], self.pat[p + 1]);
+        let mut depth = 1;
+        for (i, &c) in self.src.iter().enumerate().skip(s + 1) {
+            if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(Some(i + 1));
+                }
+            } else if c == open {
+                depth += 1;
+            }
+        }
+        Ok(None)
+    }
+
+    /// %1 ~ %9 역참조
+    fn match_capture(&self, s: usize, digit: u8) -> Result<Option<usize>, String> {
+        let l = (digit as usize).wrapping_sub(b'1' as usize);
+        if l >= self.level || self.capture[l].1 == CAP_UNFINISHED {
+            return Err("invalid capture index".to_string());
+        }
+        let (start, len) = self.capture[l];
+        if len < 0 {
+            return Ok(None);
+        }
+        let len = len as usize;
+        if self.src.len() - s >= len && self.src[start..start + len] == self.src[s..s + len] {
+            Ok(Some(s + len))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+fn match_class(c: u8, class: u8) -> bool {
+    let matched = match class.to_ascii_lowercase() {
+        b'a' => c.is_ascii_alphabetic(),
+        b'c' => c.is_ascii_control(),
+        b'd' => c.is_ascii_digit(),
+        b'l' => c.is_ascii_lowercase(),
+        b'p' => c.is_ascii_punctuation(),
+        b's' => matches!(c, b' ' | b'\t' | b'\n' | 0x0b | 0x0c | b'\r'),
+        b'u' => c.is_ascii_uppercase(),
+        b'w' => c.is_ascii_alphanumeric(),
+        b'x' => c.is_ascii_hexdigit(),
+        b'z' => c == 0,
+        _ => return class == c,
+    };
+    if class.is_ascii_uppercase() {
+        !matched
+    } else {
+        matched
+    }
+}
@@ -0,0 +1,895 @@
+use crate::lua::interpreter::{Interpreter, LuaError};
+use crate::lua::stdlib::{bad_argument, check_any, check_number, check_str, library, one, opt_int};
+use crate::lua::value::{format_number, Table, TableRef, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// cjson 이 인코딩, 디코딩하는 최대 중첩 깊이
+const JSON_MAX_DEPTH: usize = 1000;
+/// cmsgpack 은 이보다 깊은 테이블을 nil 로 인코딩한다
+const MSGPACK_MAX_NESTING: usize = 16;
+/// cmsgpack 이 디코딩하는 최대 중첩 깊이 (입력이 스택을 다 쓰지 못하게 한다)
+const MSGPACK_MAX_DECODE_DEPTH: usize = 1000;
+/// struct 의 i / I 정수 크기 한계
+const STRUCT_MAX_INT_SIZE: usize = 32;
+/// struct 의 `!` 기본 정렬 (C 의 double 정렬)
+const STRUCT_MAX_ALIGN: usize = 8;
+
+/// Redis 가 스크립트에 넣어 주는 bit, cjson, cmsgpack, struct 라이브러리를 전역에 등록한다
+pub fn open(interp: &mut Interpreter) {
+    interp.set_global(
+        "bit",
+        Value::Table(library(&[
+            ("tobit", |i, a| bit_result(bit_arg(i, &a, 1, "tobit")?)),
+            ("bnot", |i, a| bit_result(!bit_arg(i, &a, 1, "bnot")?)),
+            ("band", |i, a| bit_fold(i, &a, "band", |x, y| x & y)),
+            ("bor", |i, a| bit_fold(i, &a, "bor", |x, y| x | y)),
+            ("bxor", |i, a| bit_fold(i, &a, "bxor", |x, y| x ^ y)),
+            ("lshift", |i, a| bit_shift(i, &a, "lshift", |x, n| ((x as u32) << n) as i32)),
+            ("rshift", |i, a| bit_shift(i, &a, "rshift", |x, n| ((x as u32) >> n) as i32)),
+            ("arshift", |i, a| bit_shift(i, &a, "arshift", |x, n| x >> n)),
+            ("rol", |i, a| bit_shift(i, &a, "rol", |x, n| (x as u32).rotate_left(n) as i32)),
+            ("ror", |i, a| bit_shift(i, &a, "ror", |x, n| (x as u32).rotate_right(n) as i32)),
+            ("bswap", |i, a| bit_result(bit_arg(i, &a, 1, "bswap")?.swap_bytes())),
+            ("tohex", bit_tohex),
+        ])),
+    );
+
+    // cjson.null 은 JSON null 을 나타내는 고유한 값이다 (Redis 에서는 lightuserdata)
+    let null: TableRef = Rc::new(RefCell::new(Table::new()));
+    let mut cjson = Table::new();
+    cjson.set_str("null", Value::Table(Rc::clone(&null)));
+    let encode_null = Rc::clone(&null);
+    cjson.set_str("encode", Value::builtin("encode", move |i, a| cjson_encode(i, a, &encode_null)));
+    cjson.set_str("decode", Value::builtin("decode", move |i, a| cjson_decode(i, a, &null)));
+    interp.set_global("cjson", Value::table(cjson));
+
+    interp.set_global(
+        "cmsgpack",
+        Value::Table(library(&[
+            ("pack", msgpack_pack),
+            ("unpack", |i, a| msgpack_unpack(i, a, "unpack", 0)),
+            ("unpack_one", |i, a| msgpack_unpack(i, a, "unpack_one", 1)),
+            ("unpack_limit", |i, a| {
+                let limit = opt_int(i, &a, 2, "unpack_limit", 0)?;
+                let mut a = a;
+                if a.len() >= 2 {
+                    a.remove(1);
+                }
+                msgpack_unpack(i, a, "unpack_limit", limit)
+            }),
+        ])),
+    );
+
+    interp.set_global("struct", Value::Table(library(&[("pack", struct_pack), ("unpack", struct_unpack), ("size", struct_size)])));
+}
+
+// bit (LuaBitOp): 숫자를 32비트 정수로 다룬다
+
+fn tobit(n: f64) -> i32 {
+    (n % 4294967296.0) as i64 as i32
+}
+
+fn bit_arg(interp: &Interpreter, args: &[Value], n: usize, name: &str) -> Result<i32, LuaError> {
+    Ok(tobit(check_number(interp, args, n, name)?))
+}
+
+fn bit_result(x: i32) -> Result<Vec<Value>, LuaError> {
+    one(Value::Number(x as f64))
+}
+
+fn bit_fold(interp: &mut Interpreter, args: &[Value], name: &str, f: fn(i32, i32) -> i32) -> Result<Vec<Value>, LuaError> {
+    let mut x = bit_arg(interp, args, 1, name)?;
+    for n in 2..=args.len() {
+        x = f(x, bit_arg(interp, args, n, name)?);
+    }
+    bit_result(x)
+}
+
+fn bit_shift(interp: &mut Interpreter, args: &[Value], name: &str, f: fn(i32, u32) -> i32) -> Result<Vec<Value>, LuaError> {
+    let x = bit_arg(interp, args, 1, name)?;
+    let n = bit_arg(interp, args, 2, name)? as u32 & 31;
+    bit_result(f(x, n))
+}
+
+/// 아래 n 자리 16진수. n 이 음수면 대문자.
+fn bit_tohex(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let x = bit_arg(interp, &args, 1, "tohex")? as u32;
+    let n = match args.get(1) {
+        None | Some(Value::Nil) => 8,
+        _ => bit_arg(interp, &args, 2, "tohex")?,
+    };
+    let hex = if n < 0 { format!("{:08X}", x) } else { format!("{:08x}", x) };
+    let digits = (n.unsigned_abs() as usize).min(8);
+    one(Value::str(&hex[8 - digits..]))
+}
+
+// cjson
+
+fn cjson_encode(interp: &mut Interpreter, args: Vec<Value>, null: &TableRef) -> Result<Vec<Value>, LuaError> {
+    let value = check_any(interp, &args, 1, "encode")?;
+    let mut out = Vec::new();
+    json_encode(&value, null, 0, &mut out).map_err(|e| interp.error(e))?;
+    one(Value::str(out))
+}
+
+fn json_encode(value: &Value, null: &TableRef, depth: usize, out: &mut Vec<u8>) -> Result<(), String> {
+    match value {
+        Value::Nil => out.extend_from_slice(b"null"),
+        Value::Boolean(b) => out.extend_from_slice(if *b { b"true" } else { b"false" }),
+        Value::Number(n) if !n.is_finite() => return Err("Cannot serialise number: must not be NaN or Inf".to_string()),
+        Value::Number(n) => out.extend_from_slice(format_number(*n).as_bytes()),
+        Value::Str(s) => json_string(s, out),
+        Value::Table(table) if Rc::ptr_eq(table, null) => out.extend_from_slice(b"null"),
+        Value::Table(table) => {
+            if depth >= JSON_MAX_DEPTH {
+                return Err(format!("Cannot serialise, excessive nesting ({})", depth + 1));
+            }
+            let table = table.borrow();
+            let entries = table_entries(&table);
+            match json_array_length(&entries)? {
+                Some(len) => {
+                    out.push(b'[');
+                    for i in 1..=len {
+                        if i > 1 {
+                            out.push(b',');
+                        }
+                        json_encode(&table.get(&Value::Number(i as f64)), null, depth + 1, out)?;
+                    }
+                    out.push(b']');
+                }
+                None => {
+                    out.push(b'{');
+                    for (i, (key, value)) in entries.iter().enumerate() {
+                        if i > 0 {
+                            out.push(b',');
+                        }
+                        match key {
+                            Value::Str(s) => json_string(s, out),
+                            Value::Number(n) => json_string(format_number(*n).as_bytes(), out),
+                            _ => return Err("Cannot serialise table: table key must be a number or string".to_string()),
+                        }
+                        out.push(b':');
+                        json_encode(value, null, depth + 1, out)?;
+                    }
+                    out.push(b'}');
+                }
+            }
+        }
+        Value::Function(_) => return Err("Cannot serialise function: type not supported".to_string()),
+    }
+    Ok(())
+}
+
+/// 테이블의 모든 키와 값 (pairs 순서)
+fn table_entries(table: &Table) -> Vec<(Value, Value)> {
+    let mut entries = Vec::new();
+    let mut key = Value::Nil;
+    while let Ok(Some((k, v))) = table.next(&key) {
+        key = k.clone();
+        entries.push((k, v));
+    }
+    entries
+}
+
+/// 키가 모두 양의 정수면 배열의 길이. 빈 테이블은 lua-cjson 처럼 객체로 본다.
+fn json_array_length(entries: &[(Value, Value)]) -> Result<Option<usize>, String> {
+    let mut max = 0usize;
+    for (key, _) in entries {
+        match key {
+            Value::Number(n) if n.fract() == 0.0 && *n >= 1.0 => max = max.max(*n as usize),
+            _ => return Ok(None),
+        }
+    }
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    if max > entries.len() * 2 && max > 10 {
+        return Err("Cannot serialise table: excessively sparse array".to_string());
+    }
+    Ok(Some(max))
+}
+
+fn json_string(s: &[u8], out: &mut Vec<u8>) {
+    out.push(b'"');
+    for &b in s {
+        match b {
+            b'"' => out.extend_from_slice(b"\\\""),
+            b'\\' => out.extend_from_slice(b"\\\\"),
+            b'/' => out.extend_from_slice(b"\\/"),
+            b'\n' => out.extend_from_slice(b"\\n"),
+            b'\r' => out.extend_from_slice(b"\\r"),
+            b'\t' => out.extend_from_slice(b"\\t"),
+            0x08 => out.extend_from_slice(b"\\b"),
+            0x0c => out.extend_from_slice(b"\\f"),
+            0..=0x1f | 0x7f => out.extend_from_slice(format!("\\u{:04x}", b).as_bytes()),
+            _ => out.push(b),
+        }
+    }
+    out.push(b'"');
+}
+
+fn cjson_decode(interp: &mut Interpreter, args: Vec<Value>, null: &TableRef) -> Result<Vec<Value>, LuaError> {
+    let src = check_str(interp, &args, 1, "decode")?;
+    let mut parser = JsonParser { src: &src, pos: 0, null, depth: 0 };
+    let value = parser.document().map_err(|e| interp.error(e))?;
+    one(value)
+}
+
+enum JsonToken {
+    ObjBegin,
+    ObjEnd,
+    ArrBegin,
+    ArrEnd,
+    Str(Vec<u8>),
+    Number(f64),
+    Boolean(bool),
+    Null,
+    Colon,
+    Comma,
+    End,
+    Error(&'static str),
+}
+
+impl JsonToken {
+    /// 에러 메시지에 쓰는 이름 (lua-cjson 과 같다)
+    fn name(&self) -> &'static str {
+        match self {
+            JsonToken::ObjBegin => "T_OBJ_BEGIN",
+            JsonToken::ObjEnd => "T_OBJ_END",
+            JsonToken::ArrBegin => "T_ARR_BEGIN",
+            JsonToken::ArrEnd => "T_ARR_END",
+            JsonToken::Str(_) => "T_STRING",
+            JsonToken::Number(_) => "T_NUMBER",
+            JsonToken::Boolean(_) => "T_BOOLEAN",
+            JsonToken::Null => "T_NULL",
+            JsonToken::Colon => "T_COLON",
+            JsonToken::Comma => "T_COMMA",
+            JsonToken::End => "T_END",
+            JsonToken::Error(msg) => msg,
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    null: &'a TableRef,
+    depth: usize,
+}
+
+impl JsonParser<'_> {
+    fn document(&mut self) -> Result<Value, String> {
+        let (token, at) = self.next_token();
+        let value = self.value(token, at)?;
+        match self.next_token() {
+            (JsonToken::End, _) => Ok(value),
+            (token, at) => Err(Self::expected("the end", &token, at)),
+        }
+    }
+
+    fn expected(what: &str, found: &JsonToken, at: usize) -> String {
+        format!("Expected {} but found {} at character {}", what, found.name(), at)
+    }
+
+    fn value(&mut self, token: JsonToken, at: usize) -> Result<Value, String> {
+        match token {
+            JsonToken::Str(s) => Ok(Value::str(s)),
+            JsonToken::Number(n) => Ok(Value::Number(n)),
+            JsonToken::Boolean(b) => Ok(Value::Boolean(b)),
+            JsonToken::Null => Ok(Value::Table(Rc::clone(self.null))),
+            JsonToken::ObjBegin => self.nested(at, Self::object),
+            JsonToken::ArrBegin => self.nested(at, Self::array),
+            token => Err(Self::expected("value", &token, at)),
+        }
+    }
+
+    fn nested(&mut self, at: usize, parse: fn(&mut Self) -> Result<Table, String>) -> Result<Value, String> {
+        self.depth += 1;
+        if self.depth > JSON_MAX_DEPTH {
+            return Err(format!("Found too many nested data structures ({}) at character {}", self.depth, at));
+        }
+        let table = parse(self)?;
+        self.depth -= 1;
+        Ok(Value::table(table))
+    }
+
+    fn object(&mut self) -> Result<Table, String> {
+        let mut table = Table::new();
+        let (mut token, mut at) = self.next_token();
+        if matches!(token, JsonToken::ObjEnd) {
+            return Ok(table);
+        }
+        loop {
+            let JsonToken::Str(key) = token else {
+                return Err(Self::expected("object key string", &token, at));
+            };
+            match self.next_token() {
+                (JsonToken::Colon, _) => {}
+                (token, at) => return Err(Self::expected("colon", &token, at)),
+            }
+            let (value_token, value_at) = self.next_token();
+            let value = self.value(value_token, value_at)?;
+            let _ = table.set(Value::str(key), value);
+            match self.next_token() {
+                (JsonToken::Comma, _) => (token, at) = self.next_token(),
+                (JsonToken::ObjEnd, _) => return Ok(table),
+                (token, at) => return Err(Self::expected("comma or object end", &token, at)),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Table, String> {
+        let mut items = Vec::new();
+        let (mut token, mut at) = self.next_token();
+        if matches!(token, JsonToken::ArrEnd) {
+            return Ok(Table::new());
+        }
+        loop {
+            items.push(self.value(token, at)?);
+            match self.next_token() {
+                (JsonToken::Comma, _) => (token, at) = self.next_token(),
+                (JsonToken::ArrEnd, _) => return Ok(Table::from_array(items)),
+                (token, at) => return Err(Self::expected("comma or array end", &token, at)),
+            }
+        }
+    }
+
+    /// 다음 토큰과 그 시작 위치 (1부터 센다)
+    fn next_token(&mut self) -> (JsonToken, usize) {
+        while self.src.get(self.pos).is_some_and(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+        let at = self.pos + 1;
+        let Some(&b) = self.src.get(self.pos) else {
+            return (JsonToken::End, at);
+        };
+        let token = match b {
+            b'{' | b'}' | b'[' | b']' | b':' | b',' => {
+                self.pos += 1;
+                match b {
+                    b'{' => JsonToken::ObjBegin,
+                    b'}' => JsonToken::ObjEnd,
+                    b'[' => JsonToken::ArrBegin,
+                    b']' => JsonToken::ArrEnd,
+                    b':' => JsonToken::Colon,
+                    _ => JsonToken::Comma,
+                }
+            }
+            b'"' => self.string(),
+            b'-' | b'0'..=b'9' => self.number(),
+            _ => self.literal(),
+        };
+        (token, at)
+    }
+
+    fn literal(&mut self) -> JsonToken {
+        for (word, token) in [(&b"true"[..], JsonToken::Boolean(true)), (b"false", JsonToken::Boolean(false)), (b"null", JsonToken::Null)] {
+            if self.src[self.pos..].starts_with(word) {
+                self.pos += word.len();
+                return token;
+            }
+        }
+        JsonToken::Error("invalid token")
+    }
+
+    fn number(&mut self) -> JsonToken {
+        let start = self.pos;
+        while self.src.get(self.pos).is_some_and(|b| matches!(b, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')) {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.src[start..self.pos]).unwrap();
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        // 앞자리 0 (예: 01) 은 JSON 숫자가 아니다
+        if digits.len() > 1 && digits.starts_with('0') && digits.as_bytes()[1].is_ascii_digit() {
+            return JsonToken::Error("invalid number");
+        }
+        match text.parse() {
+            Ok(n) if digits.starts_with(|c: char| c.is_ascii_digit()) => JsonToken::Number(n),
+            _ => JsonToken::Error("invalid number"),
+        }
+    }
+
+    fn string(&mut self) -> JsonToken {
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let Some(&b) = self.src.get(self.pos) else {
+                return JsonToken::Error("unexpected end of string");
+            };
+            self.pos += 1;
+            match b {
+                b'"' => return JsonToken::Str(out),
+                b'\\' => {
+                    let Some(&escape) = self.src.get(self.pos) else {
+                        return JsonToken::Error("unexpected end of string");
+                    };
+                    self.pos += 1;
+                    match escape {
+                        b'"' | b'\\' | b'/' => out.push(escape),
+                        b'b' => out.push(0x08),
+                        b'f' => out.push(0x0c),
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'u' => match self.unicode_escape() {
+                            Some(c) => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                            None => return JsonToken::Error("invalid unicode escape code"),
+                        },
+                        _ => return JsonToken::Error("invalid escape code"),
+                    }
+                }
+                _ => out.push(b),
+            }
+        }
+    }
+
+    /// \u 다음의 16진수 네 자리. 상위 서로게이트면 뒤따르는 \uDC00..DFFF 와 합친다.
+    fn unicode_escape(&mut self) -> Option<char> {
+        let high = self.hex4()?;
+        if (0xdc00..0xe000).contains(&high) {
+            return None;
+        }
+        if !(0xd800..0xdc00).contains(&high) {
+            return char::from_u32(high);
+        }
+        if !self.src[self.pos..].starts_with(b"\\u") {
+            return None;
+        }
+        self.pos += 2;
+        let low = self.hex4()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return None;
+        }
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = self.src.get(self.pos..self.pos + 4)?;
+        let n = u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+        self.pos += 4;
+        Some(n)
+    }
+}
+
+// cmsgpack
+
+fn msgpack_pack(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    if args.is_empty() {
+        return Err(interp.error("MessagePack pack needs input."));
+    }
+    let mut out = Vec::new();
+    for value in &args {
+        msgpack_encode(value, 0, &mut out);
+    }
+    one(Value::str(out))
+}
+
+fn msgpack_encode(value: &Value, level: usize, out: &mut Vec<u8>) {
+    match value {
+        Value::Boolean(b) => out.push(if *b { 0xc3 } else { 0xc2 }),
+        Value::Number(n) if (*n as i64) as f64 == *n && *n < i64::MAX as f64 => msgpack_integer(*n as i64, out),
+        Value::Number(n) => {
+            let f = *n as f32;
+            if f as f64 == *n {
+                out.push(0xca);
+                out.extend_from_slice(&f.to_be_bytes());
+            } else {
+                out.push(0xcb);
+                out.extend_from_slice(&n.to_be_bytes());
+            }
+        }
+        Value::Str(s) => {
+            match s.len() {
+                len if len < 32 => out.push(0xa0 | len as u8),
+                len if len <= 0xff => out.extend_from_slice(&[0xd9, len as u8]),
+                len if len <= 0xffff => {
+                    out.push(0xda);
+                    out.extend_from_slice(&(len as u16).to_be_bytes());
+                }
+                len => {
+                    out.push(0xdb);
+                    out.extend_from_slice(&(len as u32).to_be_bytes());
+                }
+            }
+            out.extend_from_slice(s);
+        }
+        Value::Table(table) if level < MSGPACK_MAX_NESTING => {
+            let table = table.borrow();
+            let entries = table_entries(&table);
+            let is_array = entries.iter().all(|(k, _)| matches!(k, Value::Number(n) if n.fract() == 0.0 && *n >= 1.0 && *n <= entries.len() as f64));
+            if is_array {
+                msgpack_length(entries.len(), 0x90, 0xdc, out);
+                for i in 1..=entries.len() {
+                    msgpack_encode(&table.get(&Value::Number(i as f64)), level + 1, out);
+                }
+            } else {
+                msgpack_length(entries.len(), 0x80, 0xde, out);
+                for (key, value) in &entries {
+                    msgpack_encode(key, level + 1, out);
+                    msgpack_encode(value, level + 1, out);
+                }
+            }
+        }
+        // 너무 깊은 테이블과 함수는 nil 이 된다
+        Value::Nil | Value::Table(_) | Value::Function(_) => out.push(0xc0),
+    }
+}
+
+fn msgpack_integer(n: i64, out: &mut Vec<u8>) {
+    match n {
+        0..=0x7f => out.push(n as u8),
+        0x80..=0xff => out.extend_from_slice(&[0xcc, n as u8]),
+        0x100..=0xffff => {
+            out.push(0xcd);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            out.push(0xce);
+            out.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        0x1_0000_0000.. => {
+            out.push(0xcf);
+            out.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+        -32..=-1 => out.push(n as i8 as u8),
+        -128..=-33 => out.extend_from_slice(&[0xd0, n as i8 as u8]),
+        -32768..=-129 => {
+            out.push(0xd1);
+            out.extend_from_slice(&(n as i16).to_be_bytes());
+        }
+        -2147483648..=-32769 => {
+            out.push(0xd2);
+            out.extend_from_slice(&(n as i32).to_be_bytes());
+        }
+        _ => {
+            out.push(0xd3);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+/// 배열, 맵 헤더. 15개까지는 fix 형식, 그다음은 16비트, 32비트 길이.
+fn msgpack_length(len: usize, fix: u8, wide: u8, out: &mut Vec<u8>) {
+    if len < 16 {
+        out.push(fix | len as u8);
+    } else if len <= 0xffff {
+        out.push(wide);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(wide + 1);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+/// `offset` 부터 `limit` 개 (0 이면 전부) 의 값을 디코딩한다.
+/// unpack_one / unpack_limit 은 다음 오프셋 (끝이면 -1) 을 먼저 돌려준다.
+fn msgpack_unpack(interp: &mut Interpreter, args: Vec<Value>, name: &str, limit: i64) -> Result<Vec<Value>, LuaError> {
+    let src = check_str(interp, &args, 1, name)?;
+    let offset = opt_int(interp, &args, 2, name, 0)?;
+    if offset < 0 || limit < 0 {
+        return Err(interp.error(format!("Invalid request to unpack with offset of {} and limit of {}.", offset, limit)));
+    }
+    if offset as usize > src.len() {
+        return Err(interp.error(format!("Start offset {} greater than input length {}.", offset, src.len())));
+    }
+    let mut decoder = MsgpackDecoder { src: &src, pos: offset as usize };
+    let mut values = Vec::new();
+    while decoder.pos < src.len() && (limit == 0 || values.len() < limit as usize) {
+        values.push(decoder.value(0).map_err(|e| interp.error(e))?);
+    }
+    // 오프셋과 개수를 모두 주지 않으면 값만 돌려준다
+    if limit == 0 && offset == 0 {
+        return Ok(values);
+    }
+    let next = if decoder.pos < src.len() { decoder.pos as f64 } else { -1.0 };
+    values.insert(0, Value::Number(next));
+    Ok(values)
+}
+
+struct MsgpackDecoder<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl MsgpackDecoder<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], &'static str> {
+        let bytes = self.src.get(self.pos..self.pos.saturating_add(len)).ok_or("Missing bytes in input.")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, len: usize) -> Result<u64, &'static str> {
+        Ok(self.take(len)?.iter().fold(0, |n, &b| n << 8 | b as u64))
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, &'static str> {
+        if depth > MSGPACK_MAX_DECODE_DEPTH {
+            return Err("Too many nested data structures in input.");
+        }
+        let b = self.take(1)?[0];
+        Ok(match b {
+            0x00..=0x7f => Value::Number(b as f64),
+            0xe0..=0xff => Value::Number(b as i8 as f64),
+            0xc0 => Value::Nil,
+            0xc2 => Value::Boolean(false),
+            0xc3 => Value::Boolean(true),
+            0xca => Value::Number(f32::from_bits(self.uint(4)? as u32) as f64),
+            0xcb => Value::Number(f64::from_bits(self.uint(8)?)),
+            0xcc..=0xcf => Value::Number(self.uint(1 << (b - 0xcc))? as f64),
+            0xd0 => Value::Number(self.uint(1)? as u8 as i8 as f64),
+            0xd1 => Value::Number(self.uint(2)? as u16 as i16 as f64),
+            0xd2 => Value::Number(self.uint(4)? as u32 as i32 as f64),
+            0xd3 => Value::Number(self.uint(8)? as i64 as f64),
+            0xa0..=0xbf => self.string((b & 0x1f) as usize)?,
+            0xd9 | 0xc4 => {
+                let len = self.uint(1)? as usize;
+                self.string(len)?
+            }
+            0xda | 0xc5 => {
+                let len = self.uint(2)? as usize;
+                self.string(len)?
+            }
+            0xdb | 0xc6 => {
+                let len = self.uint(4)? as usize;
+                self.string(len)?
+            }
+            0x90..=0x9f => self.array((b & 0x0f) as usize, depth)?,
+            0xdc => {
+                let len = self.uint(2)? as usize;
+                self.array(len, depth)?
+            }
+            0xdd => {
+                let len = self.uint(4)? as usize;
+                self.array(len, depth)?
+            }
+            0x80..=0x8f => self.map((b & 0x0f) as usize, depth)?,
+            0xde => {
+                let len = self.uint(2)? as usize;
+                self.map(len, depth)?
+            }
+            0xdf => {
+                let len = self.uint(4)? as usize;
+                self.map(len, depth)?
+            }
+            _ => return Err("Bad data format in input."),
+        })
+    }
+
+    fn string(&mut self, len: usize) -> Result<Value, &'static str> {
+        Ok(Value::str(self.take(len)?))
+    }
+
+    fn array(&mut self, len: usize, depth: usize) -> Result<Value, &'static str> {
+        let mut table = Table::new();
+        for i in 1..=len {
+            let value = self.value(depth + 1)?;
+            let _ = table.set(Value::Number(i as f64), value);
+        }
+        Ok(Value::table(table))
+    }
+
+    fn map(&mut self, len: usize, depth: usize) -> Result<Value, &'static str> {
+        let mut table = Table::new();
+        for _ in 0..len {
+            let key = self.value(depth + 1)?;
+            let value = self.value(depth + 1)?;
+            // nil 이나 NaN 키는 버린다
+            let _ = table.set(key, value);
+        }
+        Ok(Value::table(table))
+    }
+}
+
+// struct: C 구조체처럼 값을 바이너리로 pack / unpack 한다
+
+struct StructHeader {
+    little: bool,
+    align: usize,
+}
+
+impl Default for StructHeader {
+    fn default() -> Self {
+        StructHeader { little: true, align: 1 }
+    }
+}
+
+/// 형식 문자열의 다음 옵션 하나. 옵션 글자와 크기를 돌려준다.
+fn struct_option(interp: &Interpreter, fmt: &[u8], i: &mut usize, h: &mut StructHeader, name: &str) -> Result<(u8, usize), LuaError> {
+    let opt = fmt[*i];
+    *i += 1;
+    let size = match opt {
+        b'b' | b'B' | b'x' => 1,
+        b'h' | b'H' => 2,
+        b'l' | b'L' | b'T' | b'd' => 8,
+        b'f' => 4,
+        b'c' => struct_number(fmt, i, 1),
+        b'i' | b'I' => {
+            let size = struct_number(fmt, i, 4);
+            if size > STRUCT_MAX_INT_SIZE {
+                return Err(interp.error(format!("integral size {} is larger than limit of {}", size, STRUCT_MAX_INT_SIZE)));
+            }
+            size
+        }
+        b's' => 0,
+        b' ' => 0,
+        b'>' | b'<' => {
+            h.little = opt == b'<';
+            0
+        }
+        b'!' => {
+            let align = struct_number(fmt, i, STRUCT_MAX_ALIGN);
+            if !align.is_power_of_two() {
+                return Err(interp.error(format!("alignment {} is not a power of 2", align)));
+            }
+            h.align = align;
+            0
+        }
+        _ => return Err(bad_argument(interp, 1, name, &format!("invalid format option '{}'", opt as char))),
+    };
+    Ok((opt, size))
+}
+
+fn struct_number(fmt: &[u8], i: &mut usize, default: usize) -> usize {
+    if !fmt.get(*i).is_some_and(u8::is_ascii_digit) {
+        return default;
+    }
+    let mut n = 0usize;
+    while let Some(d) = fmt.get(*i).filter(|b| b.is_ascii_digit()) {
+        n = n.saturating_mul(10).saturating_add((d - b'0') as usize);
+        *i += 1;
+    }
+    n
+}
+
+/// `len` 바이트 뒤에 `opt` 를 두기 위해 넣을 패딩
+fn struct_padding(len: usize, h: &StructHeader, opt: u8, size: usize) -> usize {
+    if size == 0 || opt == b'c' {
+        return 0;
+    }
+    let size = size.min(h.align);
+    (size - (len & (size - 1))) & (size - 1)
+}
+
+fn is_struct_integer(opt: u8) -> bool {
+    matches!(opt, b'b' | b'B' | b'h' | b'H' | b'l' | b'L' | b'T' | b'i' | b'I')
+}
+
+fn struct_pack(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let fmt = check_str(interp, &args, 1, "pack")?;
+    let mut h = StructHeader::default();
+    let mut out = Vec::new();
+    let mut arg = 2;
+    let mut i = 0;
+    while i < fmt.len() {
+        let (opt, mut size) = struct_option(interp, &fmt, &mut i, &mut h, "pack")?;
+        let padding = struct_padding(out.len(), &h, opt, size);
+        out.resize(out.len() + padding, 0);
+        match opt {
+            _ if is_struct_integer(opt) => {
+                let n = check_number(interp, &args, arg, "pack")?;
+                arg += 1;
+                let mut value = if n < 0.0 { n as i64 as u64 } else { n as u64 };
+                let mut bytes = vec![0; size];
+                for b in bytes.iter_mut() {
+                    *b = value as u8;
+                    value = value.checked_shr(8).unwrap_or(0);
+                }
+                if !h.little {
+                    bytes.reverse();
+                }
+                out.extend_from_slice(&bytes);
+            }
+            b'x' => out.push(0),
+            b'f' | b'd' => {
+                let n = check_number(interp, &args, arg, "pack")?;
+                arg += 1;
+                let bytes = if opt == b'f' { (n as f32).to_le_bytes().to_vec() } else { n.to_le_bytes().to_vec() };
+                out.extend(if h.little { bytes } else { bytes.into_iter().rev().collect() });
+            }
+            b'c' | b's' => {
+                let s = check_str(interp, &args, arg, "pack")?;
+                arg += 1;
+                if size == 0 {
+                    size = s.len();
+                }
+                if s.len() < size {
+                    return Err(bad_argument(interp, arg - 1, "pack", "string too short"));
+                }
+                out.extend_from_slice(&s[..size]);
+                if opt == b's' {
+                    out.push(0);
+                }
+            }
+            _ => {}
+        }
+    }
+    one(Value::str(out))
+}
+
+fn struct_unpack(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let fmt = check_str(interp, &args, 1, "unpack")?;
+    let data = check_str(interp, &args, 2, "unpack")?;
+    let start = opt_int(interp, &args, 3, "unpack", 1)?;
+    if start < 1 {
+        return Err(bad_argument(interp, 3, "unpack", "offset must be 1 or greater"));
+    }
+    let mut pos = start as usize - 1;
+    let mut h = StructHeader::default();
+    let mut results = Vec::new();
+    let mut i = 0;
+    while i < fmt.len() {
+        let (opt, mut size) = struct_option(interp, &fmt, &mut i, &mut h, "unpack")?;
+        pos += struct_padding(pos, &h, opt, size);
+        if pos.saturating_add(size) > data.len() {
+            return Err(bad_argument(interp, 2, "unpack", "data string too short"));
+        }
+        match opt {
+            _ if is_struct_integer(opt) => {
+                let bytes = &data[pos..pos + size];
+                let fold = |n: u64, b: &u8| n.checked_shl(8).unwrap_or(0) | *b as u64;
+                let mut n = if h.little { bytes.iter().rev().fold(0, fold) } else { bytes.iter().fold(0, fold) };
+                let signed = opt.is_ascii_lowercase();
+                if signed {
+                    // 부호 확장
+                    let mask = u64::MAX.checked_shl(size as u32 * 8 - 1).unwrap_or(0);
+                    if n & mask != 0 {
+                        n |= mask;
+                    }
+                }
+                results.push(Value::Number(if signed { n as i64 as f64 } else { n as f64 }));
+            }
+            b'f' | b'd' => {
+                let mut bytes = data[pos..pos + size].to_vec();
+                if !h.little {
+                    bytes.reverse();
+                }
+                let n = if opt == b'f' { f32::from_le_bytes(bytes.try_into().unwrap()) as f64 } else { f64::from_le_bytes(bytes.try_into().unwrap()) };
+                results.push(Value::Number(n));
+            }
+            b'c' => {
+                if size == 0 {
+                    // c0 은 바로 앞에서 읽은 숫자만큼 읽는다
+                    let Some(Value::Number(n)) = results.pop() else {
+                        return Err(interp.error("format 'c0' needs a previous size"));
+                    };
+                    size = n.max(0.0) as usize;
+                    if pos.saturating_add(size) > data.len() {
+                        return Err(bad_argument(interp, 2, "unpack", "data string too short"));
+                    }
+                }
+                results.push(Value::str(&data[pos..pos + size]));
+            }
+            b's' => {
+                let Some(len) = data[pos..].iter().position(|&b| b == 0) else {
+                    return Err(interp.error("unfinished string in data"));
+                };
+                results.push(Value::str(&data[pos..pos + len]));
+                size = len + 1;
+            }
+            _ => {}
+        }
+        pos += size;
+    }
+    results.push(Value::Number((pos + 1) as f64));
+    Ok(results)
+}
+
+fn struct_size(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let fmt = check_str(interp, &args, 1, "size")?;
+    let mut h = StructHeader::default();
+    let mut pos = 0;
+    let mut i = 0;
+    while i < fmt.len() {
+        let (opt, size) = struct_option(interp, &fmt, &mut i, &mut h, "size")?;
+        pos += struct_padding(pos, &h, opt, size);
+        if opt == b's' {
+            return Err(bad_argument(interp, 1, "size", "option 's' has no fixed size"));
+        }
+        if opt == b'c' && size == 0 {
+            return Err(bad_argument(interp, 1, "size", "option 'c0' has no fixed size"));
+        }
+        pos += size;
+    }
+    one(Value::Number(pos as f64))
+}
@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod tests {
+    use crate::lua::interpreter::{Interpreter, LuaError};
+    use crate::lua::redislib;
+
+    /// 스크립트 전역에 redis 라이브러리를 넣고 실행한 결과를 tostring 해서 돌려준다
+    fn run(src: &str) -> Result<Vec<String>, String> {
+        let src = src.to_string();
+        std::thread::Builder::new()
+            .stack_size(16 * 1024 * 1024)
+            .spawn(move || {
+                let mut interp = Interpreter::new("user_script");
+                redislib::open(&mut interp);
+                let function = interp.load(src.as_bytes())?;
+                match interp.call(&function, Vec::new()) {
+                    Ok(values) => Ok(values.iter().map(Interpreter::display).collect()),
+                    Err(LuaError::Error(value)) => Err(Interpreter::display(&value)),
+                    Err(LuaError::Interrupted) => Err("interrupted".to_string()),
+                }
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    fn ok(src: &str) -> Vec<String> {
+        run(src).unwrap_or_else(|e| panic!("{}: {}", src, e))
+    }
+
+    fn err(src: &str) -> String {
+        run(src).expect_err(src)
+    }
+
+    #[test]
+    fn test_bit() {
+        assert_eq!(ok("return bit.tobit(0xffffffff), bit.tobit(2^32 + 5), bit.bnot(0)"), ["-1", "5", "-1"]);
+        assert_eq!(ok("return bit.band(0xff, 0x0f, 0x3), bit.bor(1, 2, 4), bit.bxor(5, 3)"), ["3", "7", "6"]);
+        assert_eq!(ok("return bit.lshift(1, 31), bit.rshift(-1, 28), bit.arshift(-256, 4), bit.lshift(1, 33)"), ["-2147483648", "15", "-16", "2"]);
+        assert_eq!(ok("return bit.rol(0x80000001, 1), bit.ror(3, 1), bit.bswap(0x01020304)"), ["3", "-2147483647", "67305985"]);
+        assert_eq!(ok("return bit.tohex(255), bit.tohex(255, -2), bit.tohex(-1, 3), bit.tohex(1, 20)"), ["000000ff", "FF", "fff", "00000001"]);
+        assert!(err("return bit.band('x')").contains("bad argument #1 to 'band' (number expected, got string)"));
+    }
+
+    #[test]
+    fn test_cjson_encode() {
+        assert_eq!(ok("return cjson.encode({1, 'a', true, cjson.null})"), [r#"[1,"a",true,null]"#]);
+        assert_eq!(ok("return cjson.encode({a = {b = 1.5}}), cjson.encode({}), cjson.encode({[2] = 'x'})"), [r#"{"a":{"b":1.5}}"#, "{}", r#"[null,"x"]"#]);
+        assert_eq!(ok("return cjson.encode({[1] = 1, x = 2})"), [r#"{"1":1,"x":2}"#]);
+        assert_eq!(ok(r#"return cjson.encode('a"b\\c/d\n\1')"#), [r#""a\"b\\c\/d\n\u0001""#]);
+        assert!(err("return cjson.encode({[20] = 1})").ends_with("Cannot serialise table: excessively sparse array"));
+        assert!(err("return cjson.encode({[true] = 1})").ends_with("Cannot serialise table: table key must be a number or string"));
+        assert!(err("return cjson.encode(0/0)").ends_with("Cannot serialise number: must not be NaN or Inf"));
+        assert!(err("return cjson.encode(math.floor)").ends_with("Cannot serialise function: type not supported"));
+        assert!(err("local t = {} for i = 1, 1001 do t = {t} end return cjson.encode(t)").ends_with("Cannot serialise, excessive nesting (1001)"));
+    }
+
+    #[test]
+    fn test_cjson_decode() {
+        assert_eq!(ok(r#"local t = cjson.decode('{"a": [1, 2.5, "x"], "b": null, "c": false}') return t.a[1], t.a[2], t.a[3], t.b == cjson.null, t.c"#), ["1", "2.5", "x", "true", "false"]);
+        assert_eq!(ok(r#"return cjson.decode('"\\u00e9\\ud83d\\ude00\\n"'), cjson.decode(' -1e2 ')"#), ["é😀\n", "-100"]);
+        assert_eq!(ok(r#"return cjson.encode(cjson.decode('{"k":[[],{}]}'))"#), [r#"{"k":[{},{}]}"#]);
+        assert!(err(r#"return cjson.decode('{"a" 1}')"#).ends_with("Expected colon but found T_NUMBER at character 6"));
+        assert!(err("return cjson.decode('[1,]')").ends_with("Expected value but found T_ARR_END at character 4"));
+        assert!(err("return cjson.decode('[1 2]')").ends_with("Expected comma or array end but found T_NUMBER at character 4"));
+        assert!(err("return cjson.decode('{1:2}')").ends_with("Expected object key string but found T_NUMBER at character 2"));
+        assert!(err("return cjson.decode('1 2')").ends_with("Expected the end but found T_NUMBER at character 3"));
+        assert!(err("return cjson.decode('nul')").ends_with("Expected value but found invalid token at character 1"));
+        assert!(err("return cjson.decode('01')").ends_with("Expected value but found invalid number at character 1"));
+        assert!(err(r#"return cjson.decode('"abc')"#).ends_with("Expected value but found unexpected end of string at character 1"));
+        assert!(err(r#"return cjson.decode('"\\q"')"#).ends_with("Expected value but found invalid escape code at character 1"));
+        assert!(err("return cjson.decode(string.rep('[', 1001))").ends_with("Found too many nested data structures (1001) at character 1001"));
+    }
+
+    #[test]
+    fn test_cmsgpack() {
+        // 정수는 가장 짧은 형식, 실수는 손실이 없으면 float32
+        assert_eq!(ok(r#"return cmsgpack.pack(1, -1, 200, -200, 70000) == '\1\255\204\200\209\255\56\206\0\1\17\112'"#), ["true"]);
+        assert_eq!(ok(r#"return cmsgpack.pack(1.5, 0.1):byte(1, 6)"#), ["202", "63", "192", "0", "0", "203"]);
+        assert_eq!(ok(r#"return cmsgpack.pack({1, 2}, {a = true}, 'hi', nil) == '\146\1\2\129\161a\195\162hi\192'"#), ["true"]);
+        assert_eq!(ok("return #cmsgpack.pack(string.rep('x', 40)), #cmsgpack.pack(string.rep('x', 300))"), ["42", "303"]);
+
+        let round_trip = "local a, b, c = cmsgpack.unpack(cmsgpack.pack({1, {x = 'y'}}, -3.25, 2^40)) return a[1], a[2].x, b, c";
+        assert_eq!(ok(round_trip), ["1", "y", "-3.25", "1099511627776"]);
+        assert_eq!(ok("return cmsgpack.unpack_one(cmsgpack.pack(1, 2, 3))"), ["1", "1"]);
+        assert_eq!(ok("return cmsgpack.unpack_one(cmsgpack.pack(1, 2, 3), 2)"), ["-1", "3"]);
+        assert_eq!(ok("return cmsgpack.unpack_limit(cmsgpack.pack(1, 2, 3), 2)"), ["2", "1", "2"]);
+        // 너무 깊은 테이블은 nil 로 인코딩한다
+        assert_eq!(ok("local t = {} for i = 1, 20 do t = {t} end local d, v = 0, cmsgpack.unpack(cmsgpack.pack(t)) while v do d, v = d + 1, v[1] end return d"), ["16"]);
+
+        assert!(err("return cmsgpack.pack()").ends_with("MessagePack pack needs input."));
+        assert!(err(r#"return cmsgpack.unpack('\146\1')"#).ends_with("Missing bytes in input."));
+        assert!(err(r#"return cmsgpack.unpack('\193')"#).ends_with("Bad data format in input."));
+    }
+
+    #[test]
+    fn test_struct() {
+        assert_eq!(ok(r#"return struct.pack('>I2', 258) == '\1\2', struct.pack('<i4', -2) == '\254\255\255\255'"#), ["true", "true"]);
+        assert_eq!(ok("return struct.unpack('>h<H', '\\255\\254\\1\\0')"), ["-2", "1", "5"]);
+        assert_eq!(ok("return struct.unpack('bBd', struct.pack('bBd', -1, 255, 1.25))"), ["-1", "255", "1.25", "11"]);
+        assert_eq!(ok("return struct.unpack('sc3', struct.pack('sc3', 'ab', 'xyz'))"), ["ab", "xyz", "7"]);
+        assert_eq!(ok("return struct.unpack('Bc0', struct.pack('Bc0', 3, 'abc'))"), ["abc", "5"]);
+        assert_eq!(ok("return struct.unpack('i2', '\\0\\0\\5\\0', 3)"), ["5", "5"]);
+        // ! 정렬은 각 값을 자기 크기 (최대 정렬 이하) 의 배수 위치에 둔다
+        assert_eq!(ok("return struct.size('!bi'), struct.size('!2bd'), struct.size('bi'), struct.size('x c5 >l')"), ["8", "10", "5", "14"]);
+        assert_eq!(ok("return #struct.pack('!4 b i', 1, 2)"), ["8"]);
+
+        assert!(err("return struct.pack('q', 1)").ends_with("bad argument #1 to 'pack' (invalid format option 'q')"));
+        assert!(err("return struct.pack('i33', 1)").ends_with("integral size 33 is larger than limit of 32"));
+        assert!(err("return struct.pack('!3i', 1)").ends_with("alignment 3 is not a power of 2"));
+        assert!(err("return struct.pack('c5', 'ab')").ends_with("bad argument #2 to 'pack' (string too short)"));
+        assert!(err("return struct.unpack('i4', 'ab')").ends_with("bad argument #2 to 'unpack' (data string too short)"));
+        assert!(err("return struct.unpack('i', 'abcd', 0)").ends_with("bad argument #3 to 'unpack' (offset must be 1 or greater)"));
+        assert!(err("return struct.unpack('c0', 'ab')").ends_with("format 'c0' needs a previous size"));
+        assert!(err("return struct.unpack('s', 'ab')").ends_with("unfinished string in data"));
+        assert!(err("return struct.size('s')").ends_with("bad argument #1 to 'size' (option 's' has no fixed size)"));
+        assert!(err("return struct.size('c0')").ends_with("bad argument #1 to 'size' (option 'c0' has no fixed size)"));
+    }
+}
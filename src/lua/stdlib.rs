@@ -0,0 +1,844 @@
+use crate::lua::interpreter::{Interpreter, LuaError};
+use crate::lua::pattern::{Capture, Matcher};
+use crate::lua::value::{format_g, format_number, Table, TableRef, Value};
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// 패턴에 이 글자가 없으면 string.find 는 단순 검색을 한다
+const SPECIALS: &[u8] = b"^$*+?.([%-";
+
+/// base, string, table, math 라이브러리를 전역에 등록한다
+pub fn open(interp: &mut Interpreter) {
+    let mut globals = interp.globals.borrow_mut();
+    for (name, f) in [
+        ("assert", assert as Native),
+        ("error", error),
+        ("ipairs", ipairs),
+        ("next", next),
+        ("pairs", pairs),
+        ("pcall", pcall),
+        ("rawequal", rawequal),
+        ("rawget", rawget),
+        ("rawset", rawset),
+        ("select", select),
+        ("tonumber", tonumber),
+        ("tostring", tostring),
+        ("type", type_),
+        ("unpack", unpack),
+    ] {
+        globals.set_str(name, Value::builtin(name, f));
+    }
+
+    let string = library(&[
+        ("byte", str_byte),
+        ("char", str_char),
+        ("find", str_find),
+        ("format", str_format),
+        ("gmatch", str_gmatch),
+        ("gsub", str_gsub),
+        ("len", str_len),
+        ("lower", str_lower),
+        ("match", str_match),
+        ("rep", str_rep),
+        ("reverse", str_reverse),
+        ("sub", str_sub),
+        ("upper", str_upper),
+    ]);
+    interp.string_lib = Rc::clone(&string);
+    globals.set_str("string", Value::Table(string));
+
+    globals.set_str(
+        "table",
+        Value::Table(library(&[
+            ("concat", table_concat),
+            ("getn", table_getn),
+            ("insert", table_insert),
+            ("remove", table_remove),
+            ("sort", table_sort),
+        ])),
+    );
+
+    let math = library(&[
+        ("abs", |i, a| math1(i, a, "abs", f64::abs)),
+        ("acos", |i, a| math1(i, a, "acos", f64::acos)),
+        ("asin", |i, a| math1(i, a, "asin", f64::asin)),
+        ("atan", |i, a| math1(i, a, "atan", f64::atan)),
+        ("ceil", |i, a| math1(i, a, "ceil", f64::ceil)),
+        ("cos", |i, a| math1(i, a, "cos", f64::cos)),
+        ("exp", |i, a| math1(i, a, "exp", f64::exp)),
+        ("floor", |i, a| math1(i, a, "floor", f64::floor)),
+        ("log", |i, a| math1(i, a, "log", f64::ln)),
+        ("log10", |i, a| math1(i, a, "log10", f64::log10)),
+        ("sin", |i, a| math1(i, a, "sin", f64::sin)),
+        ("sqrt", |i, a| math1(i, a, "sqrt", f64::sqrt)),
+        ("tan", |i, a| math1(i, a, "tan", f64::tan)),
+        ("atan2", |i, a| math2(i, a, "atan2", f64::atan2)),
+        ("fmod", |i, a| math2(i, a, "fmod", |x, y| x % y)),
+        ("pow", |i, a| math2(i, a, "pow", f64::powf)),
+        ("max", math_max),
+        ("min", math_min),
+        ("modf", math_modf),
+    ]);
+    {
+        let mut math = math.borrow_mut();
+        math.set_str("huge", Value::Number(f64::INFINITY));
+        math.set_str("pi", Value::Number(std::f64::consts::PI));
+        // 스크립트가 복제본에서도 같은 결과를 내도록 난수는 항상 같은 씨앗에서 시작한다
+        let state = Rc::new(Cell::new(seed(0)));
+        let random_state = Rc::clone(&state);
+        math.set_str("random", Value::builtin("random", move |i, a| math_random(i, a, &random_state)));
+        math.set_str(
+            "randomseed",
+            Value::builtin("randomseed", move |i, a| {
+                state.set(seed(check_number(i, &a, 1, "randomseed")? as i64 as u64));
+                Ok(Vec::new())
+            }),
+        );
+    }
+    globals.set_str("math", Value::Table(math));
+}
+
+pub type Native = fn(&mut Interpreter, Vec<Value>) -> Result<Vec<Value>, LuaError>;
+
+pub fn library(functions: &[(&'static str, Native)]) -> TableRef {
+    let mut table = Table::new();
+    for &(name, f) in functions {
+        table.set_str(name, Value::builtin(name, f));
+    }
+    Rc::new(std::cell::RefCell::new(table))
+}
+
+fn arg(args: &[Value], n: usize) -> Value {
+    args.get(n - 1).cloned().unwrap_or_default()
+}
+
+/// "bad argument #n to 'name' (msg)"
+pub fn bad_argument(interp: &Interpreter, n: usize, name: &str, msg: &str) -> LuaError {
+    interp.error(format!("bad argument #{} to '{}' ({})", n, name, msg))
+}
+
+fn type_error(interp: &Interpreter, args: &[Value], n: usize, name: &str, expected: &str) -> LuaError {
+    let got = if n > args.len() { "no value" } else { args[n - 1].type_name() };
+    bad_argument(interp, n, name, &format!("{} expected, got {}", expected, got))
+}
+
+pub fn check_any(interp: &Interpreter, args: &[Value], n: usize, name: &str) -> Result<Value, LuaError> {
+    if n > args.len() {
+        return Err(bad_argument(interp, n, name, "value expected"));
+    }
+    Ok(args[n - 1].clone())
+}
+
+fn check_table(interp: &Interpreter, args: &[Value], n: usize, name: &str) -> Result<TableRef, LuaError> {
+    match args.get(n - 1) {
+        Some(Value::Table(table)) => Ok(Rc::clone(table)),
+        _ => Err(type_error(interp, args, n, name, "table")),
+    }
+}
+
+pub fn check_number(interp: &Interpreter, args: &[Value], n: usize, name: &str) -> Result<f64, LuaError> {
+    args.get(n - 1).and_then(Value::to_number).ok_or_else(|| type_error(interp, args, n, name, "number"))
+}
+
+fn check_int(interp: &Interpreter, args: &[Value], n: usize, name: &str) -> Result<i64, LuaError> {
+    Ok(check_number(interp, args, n, name)? as i64)
+}
+
+pub fn opt_int(interp: &Interpreter, args: &[Value], n: usize, name: &str, default: i64) -> Result<i64, LuaError> {
+    match args.get(n - 1) {
+        None | Some(Value::Nil) => Ok(default),
+        _ => check_int(interp, args, n, name),
+    }
+}
+
+pub fn check_str(interp: &Interpreter, args: &[Value], n: usize, name: &str) -> Result<Rc<[u8]>, LuaError> {
+    args.get(n - 1).and_then(Value::to_str).ok_or_else(|| type_error(interp, args, n, name, "string"))
+}
+
+pub fn one(value: Value) -> Result<Vec<Value>, LuaError> {
+    Ok(vec![value])
+}
+
+// base
+
+fn assert(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    if !check_any(interp, &args, 1, "assert")?.is_truthy() {
+        let msg = match args.get(1) {
+            Some(msg) => Interpreter::display(msg),
+            None => "assertion failed!".to_string(),
+        };
+        return Err(interp.error(msg));
+    }
+    Ok(args)
+}
+
+fn error(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let level = opt_int(interp, &args, 2, "error", 1)?;
+    let value = match arg(&args, 1) {
+        Value::Str(msg) if level > 0 => Value::str([interp.location().as_bytes(), b" ", &msg].concat()),
+        other => other,
+    };
+    Err(LuaError::Error(value))
+}
+
+fn ipairs(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let table = check_table(interp, &args, 1, "ipairs")?;
+    let iterator = Value::builtin("ipairs_iterator", |interp, args| {
+        let table = check_table(interp, &args, 1, "ipairs_iterator")?;
+        let i = check_number(interp, &args, 2, "ipairs_iterator")? + 1.0;
+        let value = table.borrow().get(&Value::Number(i));
+        match value {
+            Value::Nil => one(Value::Nil),
+            value => Ok(vec![Value::Number(i), value]),
+        }
+    });
+    Ok(vec![iterator, Value::Table(table), Value::Number(0.0)])
+}
+
+fn next(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let table = check_table(interp, &args, 1, "next")?;
+    let result = table.borrow().next(&arg(&args, 2));
+    match result {
+        Ok(Some((key, value))) => Ok(vec![key, value]),
+        Ok(None) => one(Value::Nil),
+        Err(e) => Err(interp.error(e)),
+    }
+}
+
+fn pairs(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let table = check_table(interp, &args, 1, "pairs")?;
+    Ok(vec![Value::builtin("next", next), Value::Table(table), Value::Nil])
+}
+
+fn pcall(interp: &mut Interpreter, mut args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let function = check_any(interp, &args, 1, "pcall")?;
+    args.remove(0);
+    match interp.call(&function, args) {
+        Ok(mut results) => {
+            results.insert(0, Value::Boolean(true));
+            Ok(results)
+        }
+        Err(LuaError::Error(value)) => Ok(vec![Value::Boolean(false), value]),
+        Err(LuaError::Interrupted) => Err(LuaError::Interrupted),
+    }
+}
+
+fn rawequal(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let a = check_any(interp, &args, 1, "rawequal")?;
+    let b = check_any(interp, &args, 2, "rawequal")?;
+    one(Value::Boolean(a == b))
+}
+
+fn rawget(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let table = check_table(interp, &args, 1, "rawget")?;
+    let value = table.borrow().get(&arg(&args, 2));
+    one(value)
+}
+
+fn rawset(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let table = check_table(interp, &args, 1, "rawset")?;
+    let result = table.borrow_mut().set(arg(&args, 2), arg(&args, 3));
+    result.map_err(|e| interp.error(e))?;
+    one(Value::Table(table))
+}
+
+fn select(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    if let Some(Value::Str(s)) = args.first() {
+        if &s[..] == b"#" {
+            return one(Value::Number((args.len() - 1) as f64));
+        }
+    }
+    // args[0] 은 n 자신이므로 C 의 스택 위치와 같다
+    let top = args.len() as i64;
+    let mut n = check_int(interp, &args, 1, "select")?;
+    if n < 0 {
+        n += top;
+    } else if n > top {
+        n = top;
+    }
+    if n < 1 {
+        return Err(bad_argument(interp, 1, "select", "index out of range"));
+    }
+    Ok(args[n as usize..].to_vec())
+}
+
+fn tonumber(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let base = opt_int(interp, &args, 2, "tonumber", 10)?;
+    let value = check_any(interp, &args, 1, "tonumber")?;
+    if base == 10 {
+        return one(value.to_number().map_or(Value::Nil, Value::Number));
+    }
+    let s = check_str(interp, &args, 1, "tonumber")?;
+    if !(2..=36).contains(&base) {
+        return Err(bad_argument(interp, 2, "tonumber", "base out of range"));
+    }
+    let text = String::from_utf8_lossy(&s);
+    let text = text.trim_matches(|c: char| c.is_ascii_whitespace());
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let parsed = u64::from_str_radix(digits, base as u32).ok().filter(|_| !digits.starts_with('+'));
+    one(parsed.map_or(Value::Nil, |n| Value::Number(if negative { -(n as f64) } else { n as f64 })))
+}
+
+fn tostring(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let value = check_any(interp, &args, 1, "tostring")?;
+    one(Value::str(value.to_display()))
+}
+
+fn type_(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let value = check_any(interp, &args, 1, "type")?;
+    one(Value::str(value.type_name()))
+}
+
+fn unpack(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let table = check_table(interp, &args, 1, "unpack")?;
+    let table = table.borrow();
+    let i = opt_int(interp, &args, 2, "unpack", 1)?;
+    let j = opt_int(interp, &args, 3, "unpack", table.len() as i64)?;
+    Ok((i..=j).map(|k| table.get(&Value::Number(k as f64))).collect())
+}
+
+// string
+
+/// 음수 위치는 끝에서부터 센다
+fn relative_position(pos: i64, len: usize) -> i64 {
+    if pos < 0 {
+        len as i64 + pos + 1
+    } else {
+        pos
+    }
+}
+
+fn str_byte(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let s = check_str(interp, &args, 1, "byte")?;
+    let i = relative_position(opt_int(interp, &args, 2, "byte", 1)?, s.len()).max(1);
+    let j = relative_position(opt_int(interp, &args, 3, "byte", i)?, s.len()).min(s.len() as i64);
+    Ok((i..=j).map(|k| Value::Number(s[k as usize - 1] as f64)).collect())
+}
+
+fn str_char(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let mut out = Vec::with_capacity(args.len());
+    for n in 1..=args.len() {
+        let c = check_int(interp, &args, n, "char")?;
+        if !(0..=255).contains(&c) {
+            return Err(bad_argument(interp, n, "char", "invalid value"));
+        }
+        out.push(c as u8);
+    }
+    one(Value::str(out))
+}
+
+fn str_len(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    one(Value::Number(check_str(interp, &args, 1, "len")?.len() as f64))
+}
+
+fn str_lower(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    one(Value::str(check_str(interp, &args, 1, "lower")?.to_ascii_lowercase()))
+}
+
+fn str_upper(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    one(Value::str(check_str(interp, &args, 1, "upper")?.to_ascii_uppercase()))
+}
+
+fn str_rep(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let s = check_str(interp, &args, 1, "rep")?;
+    let n = check_int(interp, &args, 2, "rep")?;
+    one(Value::str(s.repeat(n.max(0) as usize)))
+}
+
+fn str_reverse(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let mut s = check_str(interp, &args, 1, "reverse")?.to_vec();
+    s.reverse();
+    one(Value::str(s))
+}
+
+fn str_sub(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let s = check_str(interp, &args, 1, "sub")?;
+    let i = relative_position(check_int(interp, &args, 2, "sub")?, s.len()).max(1);
+    let j = relative_position(opt_int(interp, &args, 3, "sub", -1)?, s.len()).min(s.len() as i64);
+    if i > j {
+        return one(Value::str(""));
+    }
+    one(Value::str(&s[i as usize - 1..j as usize]))
+}
+
+fn capture_value(capture: Capture) -> Value {
+    match capture {
+        Capture::Str(s) => Value::str(s),
+        Capture::Position(pos) => Value::Number(pos as f64),
+    }
+}
+
+fn capture_values(interp: &Interpreter, captures: Result<Vec<Capture>, String>) -> Result<Vec<Value>, LuaError> {
+    Ok(captures.map_err(|e| interp.error(e))?.into_iter().map(capture_value).collect())
+}
+
+/// string.find 와 string.match
+fn find(interp: &mut Interpreter, args: Vec<Value>, name: &str) -> Result<Vec<Value>, LuaError> {
+    let s = check_str(interp, &args, 1, name)?;
+    let pattern = check_str(interp, &args, 2, name)?;
+    let init = (relative_position(opt_int(interp, &args, 3, name, 1)?, s.len()) - 1).clamp(0, s.len() as i64) as usize;
+    let is_find = name == "find";
+    if is_find && (arg(&args, 4).is_truthy() || !pattern.iter().any(|c| SPECIALS.contains(c))) {
+        let found = s[init..].windows(pattern.len().max(1)).position(|w| w.starts_with(&pattern));
+        return match found {
+            Some(pos) if pattern.is_empty() || init + pos + pattern.len() <= s.len() => {
+                Ok(vec![Value::Number((init + pos + 1) as f64), Value::Number((init + pos + pattern.len()) as f64)])
+            }
+            None if pattern.is_empty() => Ok(vec![Value::Number((init + 1) as f64), Value::Number(init as f64)]),
+            _ => one(Value::Nil),
+        };
+    }
+
+    let (anchor, start) = if pattern.first() == Some(&b'^') { (true, 1) } else { (false, 0) };
+    let mut matcher = Matcher::new(&s, &pattern);
+    let mut s1 = init;
+    loop {
+        if let Some(e) = matcher.match_at(s1, start).map_err(|e| interp.error(e))? {
+            if !is_find {
+                return capture_values(interp, matcher.captures(s1, e, true));
+            }
+            let mut results = vec![Value::Number((s1 + 1) as f64), Value::Number(e as f64)];
+            results.extend(capture_values(interp, matcher.captures(s1, e, false))?);
+            return Ok(results);
+        }
+        s1 += 1;
+        if anchor || s1 > s.len() {
+            return one(Value::Nil);
+        }
+    }
+}
+
+fn str_find(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    find(interp, args, "find")
+}
+
+fn str_match(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    find(interp, args, "match")
+}
+
+fn str_gmatch(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let s = check_str(interp, &args, 1, "gmatch")?;
+    let pattern = check_str(interp, &args, 2, "gmatch")?;
+    let position = Cell::new(0);
+    let iterator = Value::builtin("gmatch_iterator", move |interp, _| {
+        let mut matcher = Matcher::new(&s, &pattern);
+        let mut src = position.get();
+        while src <= s.len() {
+            if let Some(e) = matcher.match_at(src, 0).map_err(|e| interp.error(e))? {
+                // 빈 매치에서는 한 글자 앞으로 나아간다
+                position.set(if e == src { e + 1 } else { e });
+                return capture_values(interp, matcher.captures(src, e, true));
+            }
+            src += 1;
+        }
+        position.set(src);
+        one(Value::Nil)
+    });
+    one(iterator)
+}
+
+fn str_gsub(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let s = check_str(interp, &args, 1, "gsub")?;
+    let pattern = check_str(interp, &args, 2, "gsub")?;
+    let replacement = arg(&args, 3);
+    if !matches!(replacement, Value::Number(_) | Value::Str(_) | Value::Table(_) | Value::Function(_)) {
+        return Err(bad_argument(interp, 3, "gsub", "string/function/table expected"));
+    }
+    let max = opt_int(interp, &args, 4, "gsub", s.len() as i64 + 1)?;
+
+    let (anchor, start) = if pattern.first() == Some(&b'^') { (true, 1) } else { (false, 0) };
+    let mut matcher = Matcher::new(&s, &pattern);
+    let mut out = Vec::with_capacity(s.len());
+    let mut src = 0;
+    let mut count = 0;
+    while count < max {
+        let end = matcher.match_at(src, start).map_err(|e| interp.error(e))?;
+        if let Some(e) = end {
+            count += 1;
+            add_replacement(interp, &matcher, src, e, &s[src..e], &replacement, &mut out)?;
+        }
+        match end {
+            Some(e) if e > src => src = e,
+            _ if src < s.len() => {
+                out.push(s[src]);
+                src += 1;
+            }
+            _ => break,
+        }
+        if anchor {
+            break;
+        }
+    }
+    out.extend_from_slice(&s[src..]);
+    Ok(vec![Value::str(out), Value::Number(count as f64)])
+}
+
+fn add_replacement(interp: &mut Interpreter, matcher: &Matcher, s: usize, e: usize, whole: &[u8], replacement: &Value, out: &mut Vec<u8>) -> Result<(), LuaError> {
+    let value = match replacement {
+        Value::Table(table) => {
+            let key = capture_value(matcher.capture(0, s, e).map_err(|e| interp.error(e))?);
+            table.borrow().get(&key)
+        }
+        Value::Function(_) => {
+            let captures = capture_values(interp, matcher.captures(s, e, true))?;
+            interp.call(replacement, captures)?.into_iter().next().unwrap_or_default()
+        }
+        _ => {
+            // %0 은 매치 전체, %1 ~ %9 는 캡처, 나머지 %x 는 x
+            let template = replacement.to_str().unwrap();
+            let mut i = 0;
+            while i < template.len() {
+                let c = template[i];
+                i += 1;
+                if c != b'%' {
+                    out.push(c);
+                    continue;
+                }
+                let Some(&d) = template.get(i) else { break };
+                i += 1;
+                if d == b'0' {
+                    out.extend_from_slice(whole);
+                } else if d.is_ascii_digit() {
+                    let capture = matcher.capture((d - b'1') as usize, s, e).map_err(|e| interp.error(e))?;
+                    out.extend_from_slice(&capture_value(capture).to_str().unwrap());
+                } else {
+                    out.push(d);
+                }
+            }
+            return Ok(());
+        }
+    };
+    match value {
+        Value::Nil | Value::Boolean(false) => out.extend_from_slice(whole),
+        Value::Str(_) | Value::Number(_) => out.extend_from_slice(&value.to_str().unwrap()),
+        other => return Err(interp.error(format!("invalid replacement value (a {})", other.type_name()))),
+    }
+    Ok(())
+}
+
+fn str_format(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let format = check_str(interp, &args, 1, "format")?;
+    let mut out = Vec::new();
+    let mut n = 1;
+    let mut i = 0;
+    while i < format.len() {
+        let c = format[i];
+        i += 1;
+        if c != b'%' {
+            out.push(c);
+            continue;
+        }
+        if format.get(i) == Some(&b'%') {
+            out.push(b'%');
+            i += 1;
+            continue;
+        }
+        let spec_start = i;
+        while i < format.len() && b"-+ #0".contains(&format[i]) {
+            i += 1;
+        }
+        let flags = &format[spec_start..i];
+        let width = digits(&format, &mut i);
+        let precision = if format.get(i) == Some(&b'.') {
+            i += 1;
+            Some(digits(&format, &mut i))
+        } else {
+            None
+        };
+        if i - spec_start > 6 || width > 99 || precision.is_some_and(|p| p > 99) {
+            return Err(interp.error("invalid format (width or precision too long)"));
+        }
+        let Some(&conversion) = format.get(i) else {
+            return Err(interp.error("invalid option '%' to 'format'"));
+        };
+        i += 1;
+        n += 1;
+        let spec = Spec { left: flags.contains(&b'-'), plus: flags.contains(&b'+'), space: flags.contains(&b' '), zero: flags.contains(&b'0'), width };
+        let formatted = match conversion {
+            b'c' => vec![check_int(interp, &args, n, "format")? as u8],
+            b'd' | b'i' => {
+                let v = check_int(interp, &args, n, "format")?;
+                spec.pad(v < 0, &with_precision(v.unsigned_abs().to_string(), precision))
+            }
+            b'u' | b'o' | b'x' | b'X' => {
+                let v = check_int(interp, &args, n, "format")? as u64;
+                let body = match conversion {
+                    b'o' => format!("{:o}", v),
+                    b'x' => format!("{:x}", v),
+                    b'X' => format!("{:X}", v),
+                    _ => v.to_string(),
+                };
+                spec.pad(false, &with_precision(body, precision))
+            }
+            b'e' | b'E' | b'f' | b'g' | b'G' => {
+                let v = check_number(interp, &args, n, "format")?;
+                let precision = precision.unwrap_or(6);
+                let body = if !v.is_finite() {
+                    format_number(v.abs())
+                } else {
+                    match conversion {
+                        b'f' => format!("{:.*}", precision, v.abs()),
+                        b'e' | b'E' => format_e(v.abs(), precision),
+                        _ => format_g(v.abs(), precision),
+                    }
+                };
+                let body = if conversion.is_ascii_uppercase() { body.to_uppercase() } else { body };
+                spec.pad(v.is_sign_negative() && !v.is_nan(), &body)
+            }
+            b'q' => quote(&check_str(interp, &args, n, "format")?),
+            b's' => {
+                let s = check_str(interp, &args, n, "format")?;
+                let s = &s[..precision.map_or(s.len(), |p| p.min(s.len()))];
+                let padding = vec![b' '; width.saturating_sub(s.len())];
+                if spec.left { [s, &padding].concat() } else { [&padding, s].concat() }
+            }
+            other => return Err(interp.error(format!("invalid option '%{}' to 'format'", other as char))),
+        };
+        out.extend(formatted);
+    }
+    one(Value::str(out))
+}
+
+fn digits(format: &[u8], i: &mut usize) -> usize {
+    let mut value = 0;
+    while *i < format.len() && format[*i].is_ascii_digit() {
+        value = value * 10 + (format[*i] - b'0') as usize;
+        *i += 1;
+    }
+    value
+}
+
+/// 정수의 정밀도는 최소 자릿수다
+fn with_precision(body: String, precision: Option<usize>) -> String {
+    match precision {
+        Some(p) if body.len() < p => format!("{}{}", "0".repeat(p - body.len()), body),
+        _ => body,
+    }
+}
+
+/// C 의 %e (지수는 부호와 두 자리 이상)
+fn format_e(n: f64, precision: usize) -> String {
+    let sci = format!("{:.*e}", precision, n);
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    format!("{}e{}{:02}", mantissa, if exp < 0 { '-' } else { '+' }, exp.abs())
+}
+
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    width: usize,
+}
+
+impl Spec {
+    /// 부호와 폭을 적용한다
+    fn pad(&self, negative: bool, body: &str) -> Vec<u8> {
+        let sign = if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        };
+        let fill = self.width.saturating_sub(sign.len() + body.len());
+        let text = if self.left {
+            format!("{}{}{}", sign, body, " ".repeat(fill))
+        } else if self.zero {
+            format!("{}{}{}", sign, "0".repeat(fill), body)
+        } else {
+            format!("{}{}{}", " ".repeat(fill), sign, body)
+        };
+        text.into_bytes()
+    }
+}
+
+/// %q: Lua 가 다시 읽을 수 있는 문자열
+fn quote(s: &[u8]) -> Vec<u8> {
+    let mut out = vec![b'"'];
+    for &c in s {
+        match c {
+            b'"' | b'\\' | b'\n' => out.extend_from_slice(&[b'\\', c]),
+            b'\r' => out.extend_from_slice(b"\\r"),
+            0 => out.extend_from_slice(b"\\000"),
+            _ => out.push(c),
+        }
+    }
+    out.push(b'"');
+    out
+}
+
+// table
+
+fn table_concat(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let table = check_table(interp, &args, 1, "concat")?;
+    let separator = match args.get(1) {
+        None | Some(Value::Nil) => Rc::from(&b""[..]),
+        Some(_) => check_str(interp, &args, 2, "concat")?,
+    };
+    let table = table.borrow();
+    let i = opt_int(interp, &args, 3, "concat", 1)?;
+    let j = opt_int(interp, &args, 4, "concat", table.len() as i64)?;
+    let mut out = Vec::new();
+    for k in i..=j {
+        match table.get(&Value::Number(k as f64)).to_str() {
+            Some(s) => out.extend_from_slice(&s),
+            None => return Err(interp.error(format!("invalid value (at index {}) in table for 'concat'", k))),
+        }
+        if k < j {
+            out.extend_from_slice(&separator);
+        }
+    }
+    one(Value::str(out))
+}
+
+fn table_getn(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let table = check_table(interp, &args, 1, "getn")?;
+    let len = table.borrow().len();
+    one(Value::Number(len as f64))
+}
+
+fn table_insert(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let table = check_table(interp, &args, 1, "insert")?;
+    let mut table = table.borrow_mut();
+    let end = table.len() as i64 + 1;
+    let (pos, value) = match args.len() {
+        2 => (end, args[1].clone()),
+        3 => (check_int(interp, &args, 2, "insert")?, args[2].clone()),
+        _ => return Err(interp.error("wrong number of arguments to 'insert'")),
+    };
+    let mut k = end.max(pos);
+    while k > pos {
+        let previous = table.get(&Value::Number((k - 1) as f64));
+        table.set(Value::Number(k as f64), previous).map_err(|e| interp.error(e))?;
+        k -= 1;
+    }
+    table.set(Value::Number(pos as f64), value).map_err(|e| interp.error(e))?;
+    Ok(Vec::new())
+}
+
+fn table_remove(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let table = check_table(interp, &args, 1, "remove")?;
+    let mut table = table.borrow_mut();
+    let end = table.len() as i64;
+    if end == 0 {
+        return Ok(Vec::new());
+    }
+    let pos = opt_int(interp, &args, 2, "remove", end)?;
+    let removed = table.get(&Value::Number(pos as f64));
+    for k in pos..end {
+        let next = table.get(&Value::Number((k + 1) as f64));
+        table.set(Value::Number(k as f64), next).map_err(|e| interp.error(e))?;
+    }
+    table.set(Value::Number(end as f64), Value::Nil).map_err(|e| interp.error(e))?;
+    one(removed)
+}
+
+fn table_sort(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let table = check_table(interp, &args, 1, "sort")?;
+    let comparator = arg(&args, 2);
+    if !matches!(comparator, Value::Nil | Value::Function(_)) {
+        return Err(type_error(interp, &args, 2, "sort", "function"));
+    }
+    // 비교 함수가 테이블을 읽을 수 있으므로 빌려 둔 채로 정렬하지 않는다
+    let values: Vec<Value> = {
+        let table = table.borrow();
+        (1..=table.len()).map(|k| table.get(&Value::Number(k as f64))).collect()
+    };
+    let sorted = merge_sort(interp, values, &comparator)?;
+    let mut table = table.borrow_mut();
+    for (k, value) in sorted.into_iter().enumerate() {
+        table.set(Value::Number((k + 1) as f64), value).map_err(|e| interp.error(e))?;
+    }
+    Ok(Vec::new())
+}
+
+fn merge_sort(interp: &mut Interpreter, mut values: Vec<Value>, comparator: &Value) -> Result<Vec<Value>, LuaError> {
+    if values.len() <= 1 {
+        return Ok(values);
+    }
+    let right = values.split_off(values.len() / 2);
+    let left = merge_sort(interp, values, comparator)?;
+    let right = merge_sort(interp, right, comparator)?;
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        let b_first = match comparator {
+            Value::Nil => interp.less_than(b, a)?,
+            _ => interp.call(comparator, vec![b.clone(), a.clone()])?.first().is_some_and(Value::is_truthy),
+        };
+        merged.push(if b_first { right.next() } else { left.next() }.unwrap());
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+// math
+
+fn math1(interp: &mut Interpreter, args: Vec<Value>, name: &str, f: fn(f64) -> f64) -> Result<Vec<Value>, LuaError> {
+    one(Value::Number(f(check_number(interp, &args, 1, name)?)))
+}
+
+fn math2(interp: &mut Interpreter, args: Vec<Value>, name: &str, f: fn(f64, f64) -> f64) -> Result<Vec<Value>, LuaError> {
+    let a = check_number(interp, &args, 1, name)?;
+    let b = check_number(interp, &args, 2, name)?;
+    one(Value::Number(f(a, b)))
+}
+
+fn math_max(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let mut max = check_number(interp, &args, 1, "max")?;
+    for n in 2..=args.len() {
+        max = max.max(check_number(interp, &args, n, "max")?);
+    }
+    one(Value::Number(max))
+}
+
+fn math_min(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let mut min = check_number(interp, &args, 1, "min")?;
+    for n in 2..=args.len() {
+        min = min.min(check_number(interp, &args, n, "min")?);
+    }
+    one(Value::Number(min))
+}
+
+fn math_modf(interp: &mut Interpreter, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+    let n = check_number(interp, &args, 1, "modf")?;
+    Ok(vec![Value::Number(n.trunc()), Value::Number(n.fract())])
+}
+
+/// lrand48 과 같은 48비트 선형 합동 생성기
+fn seed(seed: u64) -> u64 {
+    ((seed << 16) | 0x330e) & ((1 << 48) - 1)
+}
+
+fn math_random(interp: &mut Interpreter, args: Vec<Value>, state: &Cell<u64>) -> Result<Vec<Value>, LuaError> {
+    let x = (state.get().wrapping_mul(0x5_deec_e66d).wrapping_add(0xb)) & ((1 << 48) - 1);
+    state.set(x);
+    let r = ((x >> 17) % i32::MAX as u64) as f64 / i32::MAX as f64;
+    match args.len() {
+        0 => one(Value::Number(r)),
+        1 => {
+            let m = check_number(interp, &args, 1, "random")?;
+            if m < 1.0 {
+                return Err(bad_argument(interp, 1, "random", "interval is empty"));
+            }
+            one(Value::Number((r * m).floor() + 1.0))
+        }
+        2 => {
+            let m = check_number(interp, &args, 1, "random")?;
+            let n = check_number(interp, &args, 2, "random")?;
+            if m > n {
+                return Err(bad_argument(interp, 2, "random", "interval is empty"));
+            }
+            one(Value::Number((r * (n - m + 1.0)).floor() + m))
+        }
+        _ => Err(interp.error("wrong number of arguments")),
+    }
+}
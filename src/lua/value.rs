@@ -0,0 +1,356 @@
+use crate::lua::interpreter::{Interpreter, LuaError, Scope};
+use crate::lua::parser::FuncBody;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+pub type TableRef = Rc<RefCell<Table>>;
+pub type BuiltinFn = dyn Fn(&mut Interpreter, Vec<Value>) -> Result<Vec<Value>, LuaError>;
+
+#[derive(Clone, Default)]
+pub enum Value {
+    #[default]
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    Str(Rc<[u8]>),
+    Table(TableRef),
+    Function(Function),
+}
+
+#[derive(Clone)]
+pub enum Function {
+    Lua(Rc<Closure>),
+    Builtin(Rc<Builtin>),
+}
+
+pub struct Closure {
+    pub body: Rc<FuncBody>,
+    /// 함수를 만든 시점의 지역 변수들
+    pub scope: Rc<Scope>,
+}
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub f: Box<BuiltinFn>,
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "{:?}", String::from_utf8_lossy(s)),
+            other => f.write_str(&String::from_utf8_lossy(&other.to_display())),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    /// Lua 의 == (테이블과 함수는 같은 객체일 때만 같다)
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Table(a), Value::Table(b)) => Rc::ptr_eq(a, b),
+            (Value::Function(a), Value::Function(b)) => a.ptr() == b.ptr(),
+            _ => false,
+        }
+    }
+}
+
+impl Function {
+    fn ptr(&self) -> usize {
+        match self {
+            Function::Lua(c) => Rc::as_ptr(c) as *const u8 as usize,
+            Function::Builtin(b) => Rc::as_ptr(b) as *const u8 as usize,
+        }
+    }
+}
+
+impl Value {
+    pub fn str(s: impl AsRef<[u8]>) -> Value {
+        Value::Str(s.as_ref().into())
+    }
+
+    pub fn table(table: Table) -> Value {
+        Value::Table(Rc::new(RefCell::new(table)))
+    }
+
+    pub fn builtin(name: &'static str, f: impl Fn(&mut Interpreter, Vec<Value>) -> Result<Vec<Value>, LuaError> + 'static) -> Value {
+        Value::Function(Function::Builtin(Rc::new(Builtin { name, f: Box::new(f) })))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Boolean(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::Str(_) => "string",
+            Value::Table(_) => "table",
+            Value::Function(_) => "function",
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+
+    /// 산술 연산에 쓸 숫자 (숫자 문자열도 숫자로 본다)
+    pub fn to_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Str(s) => std::str::from_utf8(s).ok().and_then(parse_number),
+            _ => None,
+        }
+    }
+
+    /// 문자열 연결에 쓸 바이트 (숫자도 문자열로 본다)
+    pub fn to_str(&self) -> Option<Rc<[u8]>> {
+        match self {
+            Value::Str(s) => Some(Rc::clone(s)),
+            Value::Number(n) => Some(format_number(*n).into_bytes().into()),
+            _ => None,
+        }
+    }
+
+    /// tostring() 의 결과
+    pub fn to_display(&self) -> Vec<u8> {
+        match self {
+            Value::Nil => b"nil".to_vec(),
+            Value::Boolean(b) => b.to_string().into_bytes(),
+            Value::Number(n) => format_number(*n).into_bytes(),
+            Value::Str(s) => s.to_vec(),
+            Value::Table(t) => format!("table: {:p}", Rc::as_ptr(t)).into_bytes(),
+            Value::Function(Function::Builtin(b)) => format!("function: builtin: {:p}", Rc::as_ptr(b)).into_bytes(),
+            Value::Function(f) => format!("function: 0x{:x}", f.ptr()).into_bytes(),
+        }
+    }
+}
+
+/// 테이블 키. 숫자 키는 비트로, 테이블과 함수 키는 주소로 구별한다.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Boolean(bool),
+    Number(u64),
+    Str(Rc<[u8]>),
+    Ref(usize),
+}
+
+impl Key {
+    fn of(value: &Value) -> Option<Key> {
+        Some(match value {
+            Value::Nil => return None,
+            Value::Boolean(b) => Key::Boolean(*b),
+            // -0 과 0 은 같은 키다
+            Value::Number(n) if n.is_nan() => return None,
+            Value::Number(n) => Key::Number(if *n == 0.0 { 0.0f64.to_bits() } else { n.to_bits() }),
+            Value::Str(s) => Key::Str(Rc::clone(s)),
+            Value::Table(t) => Key::Ref(Rc::as_ptr(t) as *const u8 as usize),
+            Value::Function(f) => Key::Ref(f.ptr()),
+        })
+    }
+}
+
+/// 1..n 정수 키는 배열에, 나머지는 삽입 순서를 지키는 해시에 둔다 (pairs 의 순서가 매번 같다)
+#[derive(Default)]
+pub struct Table {
+    array: Vec<Value>,
+    entries: Vec<(Value, Value)>,
+    index: HashMap<Key, usize>,
+}
+
+impl Table {
+    pub fn new() -> Table {
+        Table::default()
+    }
+
+    pub fn from_array(values: Vec<Value>) -> Table {
+        let mut table = Table::new();
+        for (i, value) in values.into_iter().enumerate() {
+            let _ = table.set(Value::Number((i + 1) as f64), value);
+        }
+        table
+    }
+
+    fn array_index(key: &Value) -> Option<usize> {
+        match key {
+            Value::Number(n) if n.fract() == 0.0 && *n >= 1.0 && *n <= usize::MAX as f64 => Some(*n as usize - 1),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, key: &Value) -> Value {
+        if let Some(i) = Self::array_index(key) {
+            if i < self.array.len() {
+                return self.array[i].clone();
+            }
+        }
+        match Key::of(key).and_then(|k| self.index.get(&k)) {
+            Some(&i) => self.entries[i].1.clone(),
+            None => Value::Nil,
+        }
+    }
+
+    pub fn get_str(&self, key: &str) -> Value {
+        self.get(&Value::str(key))
+    }
+
+    pub fn set_str(&mut self, key: &str, value: Value) {
+        self.set(Value::str(key), value).expect("string keys are valid");
+    }
+
+    /// nil 이나 NaN 키면 에러 메시지
+    pub fn set(&mut self, key: Value, value: Value) -> Result<(), &'static str> {
+        if let Some(i) = Self::array_index(&key) {
+            if i < self.array.len() {
+                self.array[i] = value;
+                // 끝의 nil 은 배열에서 뺀다
+                while matches!(self.array.last(), Some(Value::Nil)) {
+                    self.array.pop();
+                }
+                return Ok(());
+            }
+            if i == self.array.len() && !matches!(value, Value::Nil) {
+                self.remove_entry(&key);
+                self.array.push(value);
+                self.migrate_from_entries();
+                return Ok(());
+            }
+        }
+        let k = match &key {
+            Value::Nil => return Err("table index is nil"),
+            Value::Number(n) if n.is_nan() => return Err("table index is NaN"),
+            _ => Key::of(&key).unwrap(),
+        };
+        match self.index.get(&k) {
+            Some(&i) => self.entries[i].1 = value,
+            None if matches!(value, Value::Nil) => {}
+            None => {
+                self.index.insert(k, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_entry(&mut self, key: &Value) {
+        if let Some(i) = Key::of(key).and_then(|k| self.index.get(&k).copied()) {
+            self.entries[i].1 = Value::Nil;
+        }
+    }
+
+    /// 배열 끝 다음 정수 키가 해시에 있으면 배열로 옮긴다
+    fn migrate_from_entries(&mut self) {
+        loop {
+            let next = Value::Number((self.array.len() + 1) as f64);
+            let Some(i) = Key::of(&next).and_then(|k| self.index.get(&k).copied()) else {
+                return;
+            };
+            let value = std::mem::take(&mut self.entries[i].1);
+            if matches!(value, Value::Nil) {
+                return;
+            }
+            self.array.push(value);
+        }
+    }
+
+    /// `#` 연산자
+    pub fn len(&self) -> usize {
+        self.array.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.array.is_empty() && self.entries.iter().all(|(_, v)| matches!(v, Value::Nil))
+    }
+
+    /// next(): `key` 다음의 키와 값. 끝이면 None, 없는 키면 에러 메시지.
+    pub fn next(&self, key: &Value) -> Result<Option<(Value, Value)>, &'static str> {
+        let mut entry_start = 0;
+        if !matches!(key, Value::Nil) {
+            match Self::array_index(key) {
+                Some(i) if i < self.array.len() => {
+                    // 배열의 다음 값, 없으면 해시의 처음부터
+                    if let Some(pos) = (i + 1..self.array.len()).find(|&j| !matches!(self.array[j], Value::Nil)) {
+                        return Ok(Some((Value::Number((pos + 1) as f64), self.array[pos].clone())));
+                    }
+                }
+                array_index => match Key::of(key).and_then(|k| self.index.get(&k).copied()) {
+                    Some(i) => entry_start = i + 1,
+                    // 순회 중에 배열 끝의 값을 nil 로 지웠으면 배열이 줄어 있다
+                    None if array_index.is_some() => {}
+                    None => return Err("invalid key to 'next'"),
+                },
+            }
+        } else if let Some(pos) = self.array.iter().position(|v| !matches!(v, Value::Nil)) {
+            return Ok(Some((Value::Number((pos + 1) as f64), self.array[pos].clone())));
+        }
+        Ok(self.entries[entry_start..].iter().find(|(_, v)| !matches!(v, Value::Nil)).cloned())
+    }
+}
+
+/// Lua 5.1 의 숫자 문자열 변환 (앞뒤 공백 허용, 10진 실수와 16진 정수)
+pub fn parse_number(s: &str) -> Option<f64> {
+    let s = s.trim_matches(|c: char| c.is_ascii_whitespace());
+    let (negative, body) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if let Some(hex) = body.strip_prefix("0x").or_else(|| body.strip_prefix("0X")) {
+        if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let n = hex.bytes().fold(0f64, |acc, b| acc * 16.0 + (b as char).to_digit(16).unwrap() as f64);
+        return Some(if negative { -n } else { n });
+    }
+    // Rust 는 "inf", "nan" 도 받으므로 Lua 가 받는 글자만 허용한다
+    let valid = !body.is_empty()
+        && body.bytes().all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'+' | b'-'))
+        && body.bytes().next().is_some_and(|b| b.is_ascii_digit() || b == b'.');
+    if !valid {
+        return None;
+    }
+    let n: f64 = body.parse().ok()?;
+    Some(if negative { -n } else { n })
+}
+
+/// Lua 의 "%.14g" 숫자 표기
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return if n.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    }
+    if n.is_infinite() {
+        return if n > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        return format!("{}", n as i64);
+    }
+    format_g(n, 14)
+}
+
+/// C 의 %.{precision}g
+pub fn format_g(n: f64, precision: usize) -> String {
+    if n == 0.0 {
+        return if n.is_sign_negative() { "-0" } else { "0" }.to_string();
+    }
+    let precision = precision.max(1);
+    let sci = format!("{:.*e}", precision - 1, n);
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    if exp < -4 || exp >= precision as i32 {
+        let mantissa = trim_fraction(mantissa);
+        format!("{}e{}{:02}", mantissa, if exp < 0 { '-' } else { '+' }, exp.abs())
+    } else {
+        let decimals = (precision as i32 - 1 - exp).max(0) as usize;
+        trim_fraction(&format!("{:.*}", decimals, n)).to_string()
+    }
+}
+
+fn trim_fraction(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
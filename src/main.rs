@@ -1,11 +1,18 @@
 use redis_starter_rust::config::Config;
+use redis_starter_rust::sentinel::Sentinel;
 use redis_starter_rust::server::Server;
 use anyhow::Result;
+use std::sync::Arc;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let _config = Config::new()?;
+    let config = Config::new()?;
 
-    let server = Server::new("127.0.0.1:6379").await?;
+    if config.sentinel {
+        let sentinel = Arc::new(Sentinel::new(config.sentinel_config()?));
+        return sentinel.run().await;
+    }
+
+    let server = Server::new(&format!("127.0.0.1:{}", config.port())).await?;
     server.run().await
 }
@@ -0,0 +1,143 @@
+use crate::pubsub::PubSub;
+use std::fmt;
+use std::sync::Arc;
+
+/// 이 서버는 DB 0 하나만 쓴다
+const DB: u32 = 0;
+
+/// notify-keyspace-events 의 알림 종류
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotifyFlags(u16);
+
+impl NotifyFlags {
+    pub const NONE: NotifyFlags = NotifyFlags(0);
+    /// K: `__keyspace@<db>__:<key>` 채널로 이벤트 이름을 보낸다
+    pub const KEYSPACE: NotifyFlags = NotifyFlags(1);
+    /// E: `__keyevent@<db>__:<event>` 채널로 키 이름을 보낸다
+    pub const KEYEVENT: NotifyFlags = NotifyFlags(1 << 1);
+    /// g: DEL, EXPIRE, RESTORE 같은 타입과 무관한 명령
+    pub const GENERIC: NotifyFlags = NotifyFlags(1 << 2);
+    /// $: 문자열 명령
+    pub const STRING: NotifyFlags = NotifyFlags(1 << 3);
+    /// l: 리스트 명령
+    pub const LIST: NotifyFlags = NotifyFlags(1 << 4);
+    /// s: 셋 명령
+    pub const SET: NotifyFlags = NotifyFlags(1 << 5);
+    /// h: 해시 명령
+    pub const HASH: NotifyFlags = NotifyFlags(1 << 6);
+    /// z: sorted set 명령
+    pub const ZSET: NotifyFlags = NotifyFlags(1 << 7);
+    /// x: 키가 만료됨
+    pub const EXPIRED: NotifyFlags = NotifyFlags(1 << 8);
+    /// e: 메모리가 모자라 키를 내보냄
+    pub const EVICTED: NotifyFlags = NotifyFlags(1 << 9);
+    /// t: 스트림 명령
+    pub const STREAM: NotifyFlags = NotifyFlags(1 << 10);
+    /// m: 없는 키를 읽음
+    pub const KEY_MISS: NotifyFlags = NotifyFlags(1 << 11);
+    /// d: 모듈 키 타입
+    pub const MODULE: NotifyFlags = NotifyFlags(1 << 12);
+    /// n: 새 키가 생김
+    pub const NEW: NotifyFlags = NotifyFlags(1 << 13);
+    /// A: g$lshzxetd 의 별칭 (m 과 n 은 포함하지 않는다)
+    pub const ALL: NotifyFlags = NotifyFlags(
+        Self::GENERIC.0
+            | Self::STRING.0
+            | Self::LIST.0
+            | Self::SET.0
+            | Self::HASH.0
+            | Self::ZSET.0
+            | Self::EXPIRED.0
+            | Self::EVICTED.0
+            | Self::STREAM.0
+            | Self::MODULE.0,
+    );
+
+    const CLASSES: [(char, NotifyFlags); 14] = [
+        ('g', Self::GENERIC),
+        ('$', Self::STRING),
+        ('l', Self::LIST),
+        ('s', Self::SET),
+        ('h', Self::HASH),
+        ('z', Self::ZSET),
+        ('x', Self::EXPIRED),
+        ('e', Self::EVICTED),
+        ('t', Self::STREAM),
+        ('d', Self::MODULE),
+        ('K', Self::KEYSPACE),
+        ('E', Self::KEYEVENT),
+        ('m', Self::KEY_MISS),
+        ('n', Self::NEW),
+    ];
+
+    /// "KEA", "Ex" 같은 문자열. 모르는 문자가 있으면 None.
+    pub fn parse(s: &str) -> Option<NotifyFlags> {
+        s.chars().try_fold(Self::NONE, |flags, c| match c {
+            'A' => Some(flags | Self::ALL),
+            c => Self::CLASSES.iter().find(|(name, _)| *name == c).map(|(_, class)| flags | *class),
+        })
+    }
+
+    pub fn contains(self, other: NotifyFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersects(self, other: NotifyFlags) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl std::ops::BitOr for NotifyFlags {
+    type Output = NotifyFlags;
+
+    fn bitor(self, rhs: NotifyFlags) -> NotifyFlags {
+        NotifyFlags(self.0 | rhs.0)
+    }
+}
+
+/// CONFIG GET 에서 보여주는 형식 (A 로 묶을 수 있으면 묶는다)
+impl fmt::Display for NotifyFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rest = *self;
+        if self.contains(Self::ALL) {
+            f.write_str("A")?;
+            rest = NotifyFlags(rest.0 & !Self::ALL.0);
+        }
+        for (name, class) in Self::CLASSES {
+            if rest.contains(class) {
+                write!(f, "{}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 키 변경을 pub/sub 채널로 알린다
+#[derive(Debug)]
+pub struct Notifier {
+    flags: NotifyFlags,
+    pubsub: Arc<PubSub>,
+}
+
+impl Notifier {
+    pub fn new(flags: NotifyFlags, pubsub: Arc<PubSub>) -> Self {
+        Notifier { flags, pubsub }
+    }
+
+    pub fn flags(&self) -> NotifyFlags {
+        self.flags
+    }
+
+    /// `class` 가 켜져 있으면 keyspace / keyevent 채널에 보낸다
+    pub fn notify(&self, class: NotifyFlags, event: &str, key: &str) {
+        if !self.flags.intersects(class) {
+            return;
+        }
+        if self.flags.contains(NotifyFlags::KEYSPACE) {
+            self.pubsub.publish(&format!("__keyspace@{}__:{}", DB, key), event);
+        }
+        if self.flags.contains(NotifyFlags::KEYEVENT) {
+            self.pubsub.publish(&format!("__keyevent@{}__:{}", DB, event), key);
+        }
+    }
+}
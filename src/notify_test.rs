@@ -0,0 +1,99 @@
+use crate::notify::{Notifier, NotifyFlags};
+use crate::pubsub::{Kind, PubSub};
+use crate::store::{now_millis, Store};
+use bytes::Bytes;
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::test;
+
+/// `flags` 로 알림을 켠 Store 와 모든 keyspace / keyevent 채널을 구독한 수신기
+fn notified_store(flags: &str) -> (Store, UnboundedReceiver<Bytes>) {
+    let pubsub = Arc::new(PubSub::new());
+    let (tx, rx) = mpsc::unbounded_channel();
+    let id = pubsub.next_client_id();
+    pubsub.subscribe(id, &tx, Kind::Pattern, "__key*__:*");
+    let store = Store::new();
+    store.set_notifier(Notifier::new(NotifyFlags::parse(flags).unwrap(), pubsub));
+    (store, rx)
+}
+
+/// 받은 pmessage 들의 (채널, 메시지)
+fn received(rx: &mut UnboundedReceiver<Bytes>) -> Vec<(String, String)> {
+    let mut messages = Vec::new();
+    while let Ok(frame) = rx.try_recv() {
+        let frame = String::from_utf8(frame.to_vec()).unwrap();
+        let parts: Vec<&str> = frame.split("\r\n").collect();
+        // *4 $7 pmessage $n pattern $n channel $n message
+        messages.push((parts[6].to_string(), parts[8].to_string()));
+    }
+    messages
+}
+
+fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+    expected.iter().map(|(c, m)| (c.to_string(), m.to_string())).collect()
+}
+
+#[test]
+async fn test_parse_flags() {
+    let flags = NotifyFlags::parse("KEA").unwrap();
+    assert!(flags.contains(NotifyFlags::KEYSPACE | NotifyFlags::KEYEVENT | NotifyFlags::EXPIRED | NotifyFlags::STRING));
+    assert!(!flags.contains(NotifyFlags::KEY_MISS));
+    assert_eq!(flags.to_string(), "AKE");
+    assert_eq!(NotifyFlags::parse("Ex").unwrap().to_string(), "xE");
+    assert_eq!(NotifyFlags::parse("g$lshzxetdKEmn").unwrap().to_string(), "AKEmn");
+    assert_eq!(NotifyFlags::parse("").unwrap(), NotifyFlags::NONE);
+    assert_eq!(NotifyFlags::parse("KEq"), None);
+}
+
+#[test]
+async fn test_store_mutations_notify() {
+    let (store, mut rx) = notified_store("KEA");
+    store.insert("a".to_string(), "1".to_string(), None).await;
+    store.insert("a".to_string(), "2".to_string(), Some(60_000)).await;
+    assert!(store.remove("a").await);
+    assert_eq!(
+        received(&mut rx),
+        pairs(&[
+            ("__keyspace@0__:a", "set"),
+            ("__keyevent@0__:set", "a"),
+            ("__keyspace@0__:a", "set"),
+            ("__keyevent@0__:set", "a"),
+            ("__keyspace@0__:a", "expire"),
+            ("__keyevent@0__:expire", "a"),
+            ("__keyspace@0__:a", "del"),
+            ("__keyevent@0__:del", "a"),
+        ])
+    );
+
+    store.restore_at("b".to_string(), "1".to_string(), None).await;
+    assert_eq!(received(&mut rx), pairs(&[("__keyspace@0__:b", "restore"), ("__keyevent@0__:restore", "b")]));
+}
+
+#[test]
+async fn test_expired_events() {
+    let (store, mut rx) = notified_store("Ex");
+    let past = Some(now_millis() - 1);
+    store.insert("x".to_string(), "1".to_string(), None).await;
+    store.insert_at("lazy".to_string(), "1".to_string(), past).await;
+    store.insert_at("active".to_string(), "1".to_string(), past).await;
+    // 만료 이벤트만 켰으므로 set 은 알리지 않는다
+    assert!(received(&mut rx).is_empty());
+
+    assert_eq!(store.get("lazy").await, None);
+    assert_eq!(received(&mut rx), pairs(&[("__keyevent@0__:expired", "lazy")]));
+
+    assert_eq!(store.remove_expired().await, 1);
+    assert_eq!(received(&mut rx), pairs(&[("__keyevent@0__:expired", "active")]));
+    assert_eq!(store.len().await, 1);
+}
+
+#[test]
+async fn test_key_miss_and_new_events() {
+    let (store, mut rx) = notified_store("Emn");
+    assert_eq!(store.get("missing").await, None);
+    // 내부 확인용 조회는 keymiss 를 알리지 않는다
+    assert!(!store.contains("missing").await);
+    store.insert("k".to_string(), "1".to_string(), None).await;
+    store.insert("k".to_string(), "2".to_string(), None).await;
+    assert_eq!(received(&mut rx), pairs(&[("__keyevent@0__:keymiss", "missing"), ("__keyevent@0__:new", "k")]));
+}
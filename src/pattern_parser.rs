@@ -4,15 +4,93 @@ pub trait Pattern {
     fn matches(&self, key: &str) -> bool;
 }
 
+/// Redis 의 glob 스타일 패턴 (KEYS, PSUBSCRIBE 등).
+/// `*` 는 아무 문자열, `?` 는 아무 문자 하나, `[abc]` / `[^abc]` / `[a-z]` 는 문자 집합, `\` 는 다음 문자를 그대로 비교한다.
 #[derive(Debug)]
-pub struct WildCardPattern(pub String);
+pub struct GlobPattern(pub String);
 
-impl Pattern for WildCardPattern {
-    fn matches(&self, _: &str) -> bool {
-        true
+impl Pattern for GlobPattern {
+    fn matches(&self, key: &str) -> bool {
+        glob_match(self.0.as_bytes(), key.as_bytes())
     }
 }
 
+/// `string` 전체가 `pattern` 과 맞는지 본다
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    while p < pattern.len() {
+        match pattern[p] {
+            b'*' => {
+                // 연속된 * 는 하나로 본다
+                while pattern.get(p + 1) == Some(&b'*') {
+                    p += 1;
+                }
+                if p + 1 == pattern.len() {
+                    return true;
+                }
+                return (s..=string.len()).any(|start| glob_match(&pattern[p + 1..], &string[start..]));
+            }
+            b'?' => {
+                if s >= string.len() {
+                    return false;
+                }
+                s += 1;
+            }
+            b'[' => {
+                let Some(&c) = string.get(s) else {
+                    return false;
+                };
+                p += 1;
+                let negate = pattern.get(p) == Some(&b'^');
+                if negate {
+                    p += 1;
+                }
+                let mut matched = false;
+                loop {
+                    match pattern.get(p) {
+                        // 닫는 ] 가 없으면 패턴 끝에서 멈춘다
+                        None => {
+                            p -= 1;
+                            break;
+                        }
+                        Some(b']') => break,
+                        Some(b'\\') if p + 1 < pattern.len() => {
+                            p += 1;
+                            matched |= pattern[p] == c;
+                        }
+                        Some(&start) if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() => {
+                            let end = pattern[p + 2];
+                            matched |= start.min(end) <= c && c <= start.max(end);
+                            p += 2;
+                        }
+                        Some(&other) => matched |= other == c,
+                    }
+                    p += 1;
+                }
+                if matched == negate {
+                    return false;
+                }
+                s += 1;
+            }
+            b'\\' if p + 1 < pattern.len() => {
+                p += 1;
+                if string.get(s) != Some(&pattern[p]) {
+                    return false;
+                }
+                s += 1;
+            }
+            c => {
+                if string.get(s) != Some(&c) {
+                    return false;
+                }
+                s += 1;
+            }
+        }
+        p += 1;
+    }
+    s == string.len()
+}
+
 pub trait HashMapPatternExt {
     fn contains_key_pattern<P: Pattern>(&self, pattern: P) -> bool;
 }
@@ -21,4 +99,4 @@ impl<K: AsRef<str>, V> HashMapPatternExt for HashMap<K, V> {
     fn contains_key_pattern<P: Pattern>(&self, pattern: P) -> bool {
         self.keys().any(|k| pattern.matches(k.as_ref()))
     }
-}
\ No newline at end of file
+}
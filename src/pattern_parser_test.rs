@@ -0,0 +1,44 @@
+use crate::pattern_parser::{glob_match, GlobPattern, Pattern};
+use tokio::test;
+
+fn matches(pattern: &str, string: &str) -> bool {
+    glob_match(pattern.as_bytes(), string.as_bytes())
+}
+
+#[test]
+async fn test_glob_wildcards() {
+    assert!(matches("*", ""));
+    assert!(matches("*", "anything"));
+    assert!(matches("news.*", "news.art"));
+    assert!(!matches("news.*", "new.art"));
+    assert!(matches("h?llo", "hello"));
+    assert!(!matches("h?llo", "hllo"));
+    assert!(matches("h*llo", "hllo"));
+    assert!(matches("h*llo", "heeeello"));
+    assert!(matches("a**b*c", "aXbYc"));
+    assert!(!matches("a*b", "aXbY"));
+    assert!(matches("exact", "exact"));
+    assert!(!matches("exact", "exactly"));
+}
+
+#[test]
+async fn test_glob_character_classes() {
+    assert!(matches("h[ae]llo", "hello"));
+    assert!(matches("h[ae]llo", "hallo"));
+    assert!(!matches("h[ae]llo", "hillo"));
+    assert!(matches("h[^e]llo", "hallo"));
+    assert!(!matches("h[^e]llo", "hello"));
+    assert!(matches("h[a-b]llo", "hbllo"));
+    assert!(matches("h[b-a]llo", "hallo"));
+    assert!(!matches("h[a-b]llo", "hcllo"));
+    // 닫히지 않은 [ 는 패턴 끝까지를 집합으로 본다
+    assert!(matches("h[el", "hl"));
+}
+
+#[test]
+async fn test_glob_escape() {
+    assert!(matches("h\\*llo", "h*llo"));
+    assert!(!matches("h\\*llo", "hello"));
+    assert!(matches("[\\]]", "]"));
+    assert!(GlobPattern("user:*".to_string()).matches("user:1000"));
+}
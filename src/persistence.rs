@@ -0,0 +1,222 @@
+use crate::rdb::RDB;
+use crate::store::{Snapshot, Store};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// BGSAVE 실패 후 자동 저장을 다시 시도하기까지 기다리는 시간 (초)
+const BGSAVE_RETRY_DELAY: u64 = 5;
+
+/// `save <seconds> <changes>` 설정 한 줄
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavePoint {
+    pub seconds: u64,
+    pub changes: u64,
+}
+
+impl SavePoint {
+    /// Redis 기본값: `save 3600 1 300 100 60 10000`
+    pub fn defaults() -> Vec<SavePoint> {
+        Self::parse("3600 1 300 100 60 10000").unwrap()
+    }
+
+    /// "3600 1 300 100" 형태의 문자열을 파싱한다. 빈 문자열은 자동 저장 비활성화를 뜻한다.
+    pub fn parse(s: &str) -> Option<Vec<SavePoint>> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let pairs = parts.chunks_exact(2);
+        if !pairs.remainder().is_empty() {
+            return None;
+        }
+
+        pairs
+            .map(|pair| {
+                Some(SavePoint {
+                    seconds: pair[0].parse().ok()?,
+                    changes: pair[1].parse().ok()?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SaveError {
+    /// 이미 BGSAVE 가 진행 중
+    InProgress,
+    Io(String),
+}
+
+/// RDB 스냅샷 저장 상태 (SAVE / BGSAVE / LASTSAVE / 자동 저장)
+#[derive(Debug)]
+pub struct Persistence {
+    rdb_path: String,
+    save_points: Vec<SavePoint>,
+    bgsave_in_progress: AtomicBool,
+    last_bgsave_ok: AtomicBool,
+    /// 마지막으로 저장에 성공한 시각 (Unix timestamp in seconds)
+    lastsave: AtomicU64,
+    /// 마지막으로 시도한 BGSAVE 시각 (Unix timestamp in seconds)
+    last_bgsave_try: AtomicU64,
+    /// 마지막 저장 시점의 Store dirty 카운터
+    dirty_at_last_save: AtomicU64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+impl Persistence {
+    pub fn new(rdb_path: String, save_points: Vec<SavePoint>) -> Self {
+        Persistence {
+            rdb_path,
+            save_points,
+            bgsave_in_progress: AtomicBool::new(false),
+            last_bgsave_ok: AtomicBool::new(true),
+            lastsave: AtomicU64::new(now_secs()),
+            last_bgsave_try: AtomicU64::new(0),
+            dirty_at_last_save: AtomicU64::new(0),
+        }
+    }
+
+    pub fn rdb_path(&self) -> &str {
+        &self.rdb_path
+    }
+
+    pub fn lastsave(&self) -> u64 {
+        self.lastsave.load(Ordering::SeqCst)
+    }
+
+    pub fn bgsave_in_progress(&self) -> bool {
+        self.bgsave_in_progress.load(Ordering::SeqCst)
+    }
+
+    pub fn changes_since_last_save(&self, store: &Store) -> u64 {
+        store.dirty().saturating_sub(self.dirty_at_last_save.load(Ordering::SeqCst))
+    }
+
+    /// 로드 직후처럼 Store 의 현재 상태가 이미 디스크에 있는 것으로 표시한다.
+    pub fn mark_clean(&self, store: &Store) {
+        self.dirty_at_last_save.store(store.dirty(), Ordering::SeqCst);
+    }
+
+    /// 늦게 끝난 저장이 더 오래된 스냅샷이면 dirty 기준을 되돌리지 않는다.
+    fn mark_saved(&self, dirty: u64) {
+        self.lastsave.store(now_secs(), Ordering::SeqCst);
+        self.dirty_at_last_save.fetch_max(dirty, Ordering::SeqCst);
+    }
+
+    /// 포그라운드 저장 (SAVE). 호출한 클라이언트는 쓰기가 끝날 때까지 기다린다.
+    pub async fn save(&self, store: &Store) -> Result<(), SaveError> {
+        if self.bgsave_in_progress() {
+            return Err(SaveError::InProgress);
+        }
+
+        let snapshot = store.snapshot().await;
+        let dirty = snapshot.dirty;
+        RDB::write_snapshots(&self.rdb_path, &[snapshot]).map_err(|e| SaveError::Io(e.to_string()))?;
+        self.mark_saved(dirty);
+        Ok(())
+    }
+
+    /// 이미 떠둔 스냅샷을 인코딩해 덤프 파일로도 쓰고, 쓴 내용을 돌려준다 (디스크 기반 replica 동기화).
+    /// 사용자가 요청한 저장이 아니므로 LASTSAVE 와 save point 기준은 건드리지 않는다.
+    pub async fn save_snapshot(&self, snapshot: Snapshot) -> io::Result<Vec<u8>> {
+        let path = self.rdb_path.clone();
+        tokio::task::spawn_blocking(move || {
+            let payload = RDB::encode_snapshots(&[snapshot]);
+            RDB::write_atomic(path, &payload).map(|_| payload)
+        })
+        .await
+        .unwrap_or_else(|e| Err(io::Error::other(e)))
+    }
+
+    /// 현재 시점의 스냅샷을 뜬 뒤 백그라운드 태스크에서 디스크에 쓴다 (BGSAVE).
+    pub async fn bgsave(self: &Arc<Self>, store: &Store) -> Result<(), SaveError> {
+        if self
+            .bgsave_in_progress
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(SaveError::InProgress);
+        }
+        self.last_bgsave_try.store(now_secs(), Ordering::SeqCst);
+
+        let snapshot = store.snapshot().await;
+        let persistence = Arc::clone(self);
+
+        tokio::spawn(async move {
+            let dirty = snapshot.dirty;
+            let path = persistence.rdb_path.clone();
+            let result = tokio::task::spawn_blocking(move || RDB::write_snapshots(path, &[snapshot]))
+                .await
+                .unwrap_or_else(|e| Err(io::Error::other(e)));
+
+            match result {
+                Ok(()) => {
+                    persistence.mark_saved(dirty);
+                    persistence.last_bgsave_ok.store(true, Ordering::SeqCst);
+                }
+                Err(e) => {
+                    eprintln!("Background saving error: {:?}", e);
+                    persistence.last_bgsave_ok.store(false, Ordering::SeqCst);
+                }
+            }
+            persistence.bgsave_in_progress.store(false, Ordering::SeqCst);
+        });
+
+        Ok(())
+    }
+
+    /// 설정된 save point 중 하나라도 만족하면 true
+    fn should_save(&self, store: &Store) -> bool {
+        let changes = self.changes_since_last_save(store);
+        let now = now_secs();
+        let elapsed = now.saturating_sub(self.lastsave());
+
+        // 직전 BGSAVE 가 실패했다면 잠시 기다렸다가 재시도
+        if !self.last_bgsave_ok.load(Ordering::SeqCst)
+            && now.saturating_sub(self.last_bgsave_try.load(Ordering::SeqCst)) < BGSAVE_RETRY_DELAY
+        {
+            return false;
+        }
+
+        self.save_points
+            .iter()
+            .any(|sp| changes >= sp.changes && elapsed >= sp.seconds)
+    }
+
+    /// save point 를 주기적으로 검사해서 조건을 만족하면 BGSAVE 를 실행한다.
+    pub async fn run_save_policy(self: Arc<Self>, store: Arc<Store>) {
+        if self.save_points.is_empty() {
+            return;
+        }
+
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+        loop {
+            interval.tick().await;
+            if !self.bgsave_in_progress() && self.should_save(&store) {
+                let _ = self.bgsave(&store).await;
+            }
+        }
+    }
+
+    /// INFO persistence 섹션
+    pub fn info(&self, store: &Store) -> String {
+        let status = if self.last_bgsave_ok.load(Ordering::SeqCst) { "ok" } else { "err" };
+        format!(
+            "# Persistence\r\n\
+             rdb_changes_since_last_save:{}\r\n\
+             rdb_bgsave_in_progress:{}\r\n\
+             rdb_last_save_time:{}\r\n\
+             rdb_last_bgsave_status:{}\r\n",
+            self.changes_since_last_save(store),
+            self.bgsave_in_progress() as u8,
+            self.lastsave(),
+            status,
+        )
+    }
+}
@@ -0,0 +1,83 @@
+use crate::persistence::{Persistence, SaveError, SavePoint};
+use crate::rdb::RDB;
+use crate::store::Store;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::test;
+
+#[test]
+async fn test_parse_save_points() {
+    let points = SavePoint::parse("3600 1 300 100").unwrap();
+    assert_eq!(points, vec![
+        SavePoint { seconds: 3600, changes: 1 },
+        SavePoint { seconds: 300, changes: 100 },
+    ]);
+
+    // 빈 문자열은 자동 저장 비활성화
+    assert_eq!(SavePoint::parse("").unwrap(), vec![]);
+
+    // 짝이 맞지 않거나 숫자가 아니면 실패
+    assert!(SavePoint::parse("3600").is_none());
+    assert!(SavePoint::parse("3600 abc").is_none());
+}
+
+#[test]
+async fn test_bgsave_writes_snapshot() {
+    let path = "test_bgsave.rdb";
+    let store = Store::new();
+    store.insert("key1".to_string(), "value1".to_string(), None).await;
+
+    let persistence = Arc::new(Persistence::new(path.to_string(), vec![]));
+    assert_eq!(persistence.changes_since_last_save(&store), 1);
+
+    persistence.bgsave(&store).await.unwrap();
+    // 스냅샷 이후의 변경은 파일에 포함되지 않는다
+    store.insert("key2".to_string(), "value2".to_string(), None).await;
+
+    while persistence.bgsave_in_progress() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let loaded = RDB::read_rdb(path).await.unwrap();
+    assert_eq!(loaded.get("key1").await.unwrap(), "value1");
+    assert!(loaded.get("key2").await.is_none());
+
+    // 스냅샷 이후의 변경 한 건만 남아있어야 함
+    assert_eq!(persistence.changes_since_last_save(&store), 1);
+    assert!(persistence.info(&store).contains("rdb_last_bgsave_status:ok"));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_save_rejected_during_bgsave() {
+    let path = "test_bgsave_busy.rdb";
+    let store = Store::new();
+    let persistence = Arc::new(Persistence::new(path.to_string(), vec![]));
+
+    persistence.bgsave(&store).await.unwrap();
+    assert_eq!(persistence.bgsave(&store).await, Err(SaveError::InProgress));
+
+    while persistence.bgsave_in_progress() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_save_snapshot_for_replica_sync() {
+    let path = "test_replica_sync.rdb";
+    let store = Store::new();
+    store.insert("key1".to_string(), "value1".to_string(), None).await;
+    let persistence = Arc::new(Persistence::new(path.to_string(), vec![]));
+
+    let snapshot = store.snapshot().await;
+    let payload = persistence.save_snapshot(snapshot).await.unwrap();
+    // 보낸 내용과 파일 내용이 같다
+    assert_eq!(fs::read(path).unwrap(), payload);
+    // replica 동기화는 사용자의 저장으로 치지 않는다
+    assert_eq!(persistence.changes_since_last_save(&store), 1);
+
+    fs::remove_file(path).unwrap();
+}
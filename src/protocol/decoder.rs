@@ -1,129 +1,787 @@
+use crate::protocol::error::RedisError;
 use bytes::{BytesMut, Buf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug)]
 pub enum RedisCommand {
     Ping,
     Set(String, String, Option<u64>), // key, value, expiry in milliseconds
     Get(String),
+    Del(Vec<String>),
+    Dump(String),
+    Restore(RestoreArgs),
+    MGet(Vec<String>),
     Echo(String),
     ConfigGet(String),
     Keys(String),
     Save,
+    BgSave,
+    BgRewriteAof,
+    LastSave,
+    Info(Option<String>), // section
+    ReplConf(Vec<String>), // option/value pairs
+    Psync(String, i64), // replication id, offset
+    ReplicaOf(String, String), // host, port (or NO ONE)
+    Wait(i64, i64), // numreplicas, timeout in milliseconds
+    WaitAof(i64, i64, i64), // numlocal, numreplicas, timeout in milliseconds
+    Sentinel(Vec<String>), // subcommand and arguments (sentinel mode only)
+    Cluster(Vec<String>), // subcommand and arguments (cluster mode only)
+    Asking,
+    Migrate(MigrateArgs),
+    Subscribe(Vec<String>), // channels
+    PSubscribe(Vec<String>), // patterns
+    Unsubscribe(Vec<String>), // channels (empty: all)
+    PUnsubscribe(Vec<String>), // patterns (empty: all)
+    Publish(String, String), // channel, message
+    PubSub(Vec<String>), // subcommand and arguments
+    SSubscribe(Vec<String>), // shard channels
+    SUnsubscribe(Vec<String>), // shard channels (empty: all)
+    SPublish(String, String), // shard channel, message
+    Multi,
+    Exec,
+    Discard,
+    Watch(Vec<String>), // keys
+    Unwatch,
+    FlushDb,
+    FlushAll,
+    Reset,
+    Eval(ScriptArgs),
+    EvalSha(ScriptArgs),
+    Script(Vec<String>), // subcommand and arguments
+    FCall(ScriptArgs), // script 자리에 함수 이름
+    FCallRo(ScriptArgs),
+    Function(Vec<String>), // subcommand and arguments
+    FunctionRestore(Vec<u8>, String), // DUMP 페이로드, 정책 (FLUSH | APPEND | REPLACE)
+    Client(Vec<String>), // subcommand and arguments
+    Hello(Vec<String>), // [protover [AUTH username password] [SETNAME clientname]]
+    /// 알려진 명령이지만 인자를 해석할 수 없다 (syntax error)
     Unknown,
+    /// 명령으로 만들 수 없는 요청 (알 수 없는 명령, 인자 수, 프로토콜 에러)
+    Invalid(RedisError),
 }
 
+/// from_args 가 아는 명령 이름. 인자 수가 맞지 않을 때 알 수 없는 명령과 구별한다.
+const COMMAND_NAMES: &[&str] = &[
+    "PING", "SAVE", "BGSAVE", "BGREWRITEAOF", "LASTSAVE", "INFO", "GET", "MGET", "DEL", "ECHO", "KEYS", "CONFIG", "REPLCONF", "PSYNC",
+    "REPLICAOF", "SLAVEOF", "WAIT", "WAITAOF", "SENTINEL", "CLUSTER", "ASKING", "MIGRATE", "DUMP", "RESTORE", "RESTORE-ASKING",
+    "SUBSCRIBE", "PSUBSCRIBE", "UNSUBSCRIBE", "PUNSUBSCRIBE", "PUBLISH", "PUBSUB", "SSUBSCRIBE", "SUNSUBSCRIBE", "SPUBLISH", "MULTI",
+    "EXEC", "DISCARD", "WATCH", "UNWATCH", "FLUSHDB", "FLUSHALL", "RESET", "EVAL", "EVALSHA", "SCRIPT", "FCALL", "FCALL_RO", "FUNCTION",
+    "CLIENT", "HELLO", "SET",
+];
+
+/// EVAL script numkeys [key ...] [arg ...] (EVALSHA 는 script 자리에 SHA1, FCALL 은 함수 이름)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptArgs {
+    pub script: String,
+    pub keys: Vec<String>,
+    pub args: Vec<String>,
+}
+
+/// RESTORE key ttl serialized-value [REPLACE] [ABSTTL] [IDLETIME seconds] [FREQ frequency]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreArgs {
+    pub key: String,
+    /// 밀리초 단위 TTL (0 이면 만료 없음). ABSTTL 이면 Unix timestamp.
+    pub ttl: i64,
+    /// DUMP 페이로드 (바이너리)
+    pub payload: Vec<u8>,
+    pub replace: bool,
+    pub absttl: bool,
+    pub idletime: Option<i64>,
+    pub freq: Option<i64>,
+    /// RESTORE-ASKING: 가져오는 중인 슬롯에도 쓴다 (MIGRATE 가 보낸다)
+    pub asking: bool,
+}
+
+/// MIGRATE host port key|"" destination-db timeout [COPY] [REPLACE] [AUTH password] [AUTH2 username password] [KEYS key...]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrateArgs {
+    pub host: String,
+    pub port: u16,
+    pub keys: Vec<String>,
+    pub db: u64,
+    /// 밀리초
+    pub timeout: u64,
+    /// 옮긴 키를 지우지 않는다
+    pub copy: bool,
+    /// 대상에 있는 키를 덮어쓴다
+    pub replace: bool,
+    /// (username, password)
+    pub auth: Option<(Option<String>, String)>,
+}
+
+/// 명령 테이블의 플래그
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandFlags(u8);
+
+impl CommandFlags {
+    pub const NONE: CommandFlags = CommandFlags(0);
+    /// 데이터를 바꾸는 명령 (read only replica 에서 거부)
+    pub const WRITE: CommandFlags = CommandFlags(1);
+    /// 데이터를 읽기만 하는 명령
+    pub const READONLY: CommandFlags = CommandFlags(1 << 1);
+    /// master 와의 링크가 끊겨 데이터가 오래됐을 수 있어도 실행할 수 있는 명령
+    pub const STALE: CommandFlags = CommandFlags(1 << 2);
+    /// 스크립트 안에서 redis.call 로 부를 수 없는 명령
+    pub const NOSCRIPT: CommandFlags = CommandFlags(1 << 3);
+
+    pub fn contains(self, other: CommandFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for CommandFlags {
+    type Output = CommandFlags;
+
+    fn bitor(self, rhs: CommandFlags) -> CommandFlags {
+        CommandFlags(self.0 | rhs.0)
+    }
+}
+
+/// 다른 서버가 보낸 응답 (sentinel 처럼 이 서버가 클라이언트로 동작할 때)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<String>),
+    Array(Option<Vec<Reply>>),
+    /// RESP3 맵 (RESP2 에서는 키와 값을 번갈아 담은 배열)
+    Map(Vec<(Reply, Reply)>),
+}
+
+/// 프레임을 끝까지 읽지 못한 이유
+#[derive(Debug, PartialEq, Eq)]
+pub enum FrameError {
+    /// 아직 데이터가 다 도착하지 않음 (더 읽으면 완성될 수 있음)
+    Incomplete,
+    /// RESP 형식이 아님
+    Invalid,
+    /// 길이 제한을 넘었다. 클라이언트에게 돌려줄 Protocol error 메시지.
+    TooLarge(&'static str),
+}
+
+/// redis-cli 의 sdssplitargs 처럼 한 줄을 인자로 나눈다.
+/// 큰따옴표 안에서는 \n, \r, \t, \b, \a, \xHH 이스케이프를, 작은따옴표 안에서는 \' 만 쓸 수 있다.
+/// 따옴표가 닫히지 않았거나 닫는 따옴표 뒤에 공백이 없으면 None.
+pub fn split_args(line: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let is_space = |b: u8| matches!(b, b' ' | b'\n' | b'\r' | b'\t' | 0x0b | 0x0c);
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < line.len() && is_space(line[i]) {
+            i += 1;
+        }
+        if i == line.len() {
+            return Some(args);
+        }
+
+        let mut arg = Vec::new();
+        let mut quote = None;
+        loop {
+            let Some(&c) = line.get(i) else {
+                // 따옴표가 닫히지 않았다
+                if quote.is_some() {
+                    return None;
+                }
+                break;
+            };
+            match quote {
+                Some(b'"') => {
+                    let hex = |b: u8| (b as char).to_digit(16);
+                    if c == b'\\' && i + 3 < line.len() && line[i + 1] == b'x' && hex(line[i + 2]).is_some() && hex(line[i + 3]).is_some() {
+                        arg.push((hex(line[i + 2]).unwrap() * 16 + hex(line[i + 3]).unwrap()) as u8);
+                        i += 3;
+                    } else if c == b'\\' && i + 1 < line.len() {
+                        i += 1;
+                        arg.push(match line[i] {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            other => other,
+                        });
+                    } else if c == b'"' {
+                        // 닫는 따옴표 바로 뒤는 공백이거나 줄 끝이어야 한다
+                        if line.get(i + 1).is_some_and(|&next| !is_space(next)) {
+                            return None;
+                        }
+                        i += 1;
+                        break;
+                    } else {
+                        arg.push(c);
+                    }
+                }
+                Some(_) => {
+                    if c == b'\\' && line.get(i + 1) == Some(&b'\'') {
+                        i += 1;
+                        arg.push(b'\'');
+                    } else if c == b'\'' {
+                        if line.get(i + 1).is_some_and(|&next| !is_space(next)) {
+                            return None;
+                        }
+                        i += 1;
+                        break;
+                    } else {
+                        arg.push(c);
+                    }
+                }
+                None if is_space(c) => break,
+                None if c == b'"' || c == b'\'' => quote = Some(c),
+                None => arg.push(c),
+            }
+            i += 1;
+        }
+        args.push(arg);
+    }
+}
+
+impl RedisCommand {
+    /// 인자 배열 (명령 이름 포함)을 명령으로 변환한다.
+    pub fn from_args(args: Vec<String>) -> RedisCommand {
+        let mut args = args.into_iter();
+        let Some(given) = args.next() else {
+            return RedisCommand::Unknown;
+        };
+        let name = given.to_uppercase();
+        let rest: Vec<String> = args.collect();
+
+        match (name.as_str(), rest.len()) {
+            ("PING", 0) => RedisCommand::Ping,
+            ("SAVE", 0) => RedisCommand::Save,
+            ("BGSAVE", 0) => RedisCommand::BgSave,
+            ("BGREWRITEAOF", 0) => RedisCommand::BgRewriteAof,
+            ("LASTSAVE", 0) => RedisCommand::LastSave,
+            ("INFO", 0) => RedisCommand::Info(None),
+            ("INFO", 1) => RedisCommand::Info(rest.into_iter().next()),
+            ("GET", 1) => RedisCommand::Get(rest.into_iter().next().unwrap()),
+            ("MGET", n) if n >= 1 => RedisCommand::MGet(rest),
+            ("DEL", n) if n >= 1 => RedisCommand::Del(rest),
+            ("ECHO", 1) => RedisCommand::Echo(rest.into_iter().next().unwrap()),
+            ("KEYS", 1) => RedisCommand::Keys(rest.into_iter().next().unwrap()),
+            ("CONFIG", 2) if rest[0].to_uppercase() == "GET" => {
+                RedisCommand::ConfigGet(rest.into_iter().nth(1).unwrap())
+            }
+            ("REPLCONF", n) if n >= 1 => RedisCommand::ReplConf(rest),
+            ("PSYNC", 2) => match rest[1].parse::<i64>() {
+                Ok(offset) => RedisCommand::Psync(rest.into_iter().next().unwrap(), offset),
+                Err(_) => RedisCommand::Unknown,
+            },
+            ("REPLICAOF", 2) | ("SLAVEOF", 2) => {
+                let mut rest = rest.into_iter();
+                RedisCommand::ReplicaOf(rest.next().unwrap(), rest.next().unwrap())
+            }
+            ("WAIT", 2) => match (rest[0].parse(), rest[1].parse()) {
+                (Ok(numreplicas), Ok(timeout)) => RedisCommand::Wait(numreplicas, timeout),
+                _ => RedisCommand::Unknown,
+            },
+            ("WAITAOF", 3) => match (rest[0].parse(), rest[1].parse(), rest[2].parse()) {
+                (Ok(numlocal), Ok(numreplicas), Ok(timeout)) => RedisCommand::WaitAof(numlocal, numreplicas, timeout),
+                _ => RedisCommand::Unknown,
+            },
+            ("SENTINEL", n) if n >= 1 => RedisCommand::Sentinel(rest),
+            ("CLUSTER", n) if n >= 1 => RedisCommand::Cluster(rest),
+            ("ASKING", 0) => RedisCommand::Asking,
+            ("MIGRATE", n) if n >= 5 => Self::parse_migrate(rest).unwrap_or(RedisCommand::Unknown),
+            ("DUMP", 1) => RedisCommand::Dump(rest.into_iter().next().unwrap()),
+            ("RESTORE", n) | ("RESTORE-ASKING", n) if n >= 3 => {
+                let mut rest = rest;
+                let payload = rest.remove(2).into_bytes();
+                Self::parse_restore(rest, payload, name == "RESTORE-ASKING").unwrap_or(RedisCommand::Unknown)
+            }
+            ("SUBSCRIBE", n) if n >= 1 => RedisCommand::Subscribe(rest),
+            ("PSUBSCRIBE", n) if n >= 1 => RedisCommand::PSubscribe(rest),
+            ("UNSUBSCRIBE", _) => RedisCommand::Unsubscribe(rest),
+            ("PUNSUBSCRIBE", _) => RedisCommand::PUnsubscribe(rest),
+            ("PUBLISH", 2) => {
+                let mut rest = rest.into_iter();
+                RedisCommand::Publish(rest.next().unwrap(), rest.next().unwrap())
+            }
+            ("PUBSUB", n) if n >= 1 => RedisCommand::PubSub(rest),
+            ("SSUBSCRIBE", n) if n >= 1 => RedisCommand::SSubscribe(rest),
+            ("SUNSUBSCRIBE", _) => RedisCommand::SUnsubscribe(rest),
+            ("SPUBLISH", 2) => {
+                let mut rest = rest.into_iter();
+                RedisCommand::SPublish(rest.next().unwrap(), rest.next().unwrap())
+            }
+            ("MULTI", 0) => RedisCommand::Multi,
+            ("EXEC", 0) => RedisCommand::Exec,
+            ("DISCARD", 0) => RedisCommand::Discard,
+            ("WATCH", n) if n >= 1 => RedisCommand::Watch(rest),
+            ("UNWATCH", 0) => RedisCommand::Unwatch,
+            // 항상 동기로 지운다
+            ("FLUSHDB", n) | ("FLUSHALL", n) if n <= 1 => {
+                if rest.first().is_some_and(|mode| !mode.eq_ignore_ascii_case("ASYNC") && !mode.eq_ignore_ascii_case("SYNC")) {
+                    return RedisCommand::Unknown;
+                }
+                if name == "FLUSHDB" { RedisCommand::FlushDb } else { RedisCommand::FlushAll }
+            }
+            ("RESET", 0) => RedisCommand::Reset,
+            ("EVAL", n) | ("EVALSHA", n) if n >= 2 => match Self::parse_script_args(rest) {
+                Some(args) if name == "EVAL" => RedisCommand::Eval(args),
+                Some(args) => RedisCommand::EvalSha(args),
+                None => RedisCommand::Unknown,
+            },
+            ("SCRIPT", n) if n >= 1 => RedisCommand::Script(rest),
+            ("FCALL", n) | ("FCALL_RO", n) if n >= 2 => match Self::parse_script_args(rest) {
+                Some(args) if name == "FCALL" => RedisCommand::FCall(args),
+                Some(args) => RedisCommand::FCallRo(args),
+                None => RedisCommand::Unknown,
+            },
+            ("FUNCTION", n) if n >= 1 => RedisCommand::Function(rest),
+            ("CLIENT", n) if n >= 1 => RedisCommand::Client(rest),
+            ("HELLO", _) => RedisCommand::Hello(rest),
+            ("SET", n) if n >= 2 => Self::parse_set(rest).unwrap_or(RedisCommand::Unknown),
+            _ if COMMAND_NAMES.contains(&name.as_str()) => RedisCommand::Invalid(RedisError::WrongArity(name.to_lowercase())),
+            _ => RedisCommand::Invalid(RedisError::UnknownCommand(given, rest)),
+        }
+    }
+
+    /// Redis 명령 테이블과 같은 플래그
+    pub fn flags(&self) -> CommandFlags {
+        match self {
+            RedisCommand::Set(..)
+            | RedisCommand::Del(_)
+            | RedisCommand::Restore(_)
+            | RedisCommand::Migrate(_)
+            | RedisCommand::FlushDb
+            | RedisCommand::FlushAll => CommandFlags::WRITE,
+            RedisCommand::FunctionRestore(..) => CommandFlags::WRITE | CommandFlags::NOSCRIPT,
+            RedisCommand::Function(args) if matches!(args[0].to_uppercase().as_str(), "LOAD" | "DELETE" | "FLUSH") => {
+                CommandFlags::WRITE | CommandFlags::NOSCRIPT
+            }
+            RedisCommand::Get(_) | RedisCommand::MGet(_) | RedisCommand::Keys(_) | RedisCommand::Dump(_) => CommandFlags::READONLY,
+            RedisCommand::ConfigGet(_)
+            | RedisCommand::LastSave
+            | RedisCommand::Info(_)
+            | RedisCommand::Cluster(_)
+            | RedisCommand::Publish(..)
+            | RedisCommand::PubSub(_)
+            | RedisCommand::SPublish(..) => CommandFlags::STALE,
+            RedisCommand::ReplConf(_)
+            | RedisCommand::ReplicaOf(..)
+            | RedisCommand::Subscribe(_)
+            | RedisCommand::PSubscribe(_)
+            | RedisCommand::Unsubscribe(_)
+            | RedisCommand::PUnsubscribe(_)
+            | RedisCommand::SSubscribe(_)
+            | RedisCommand::SUnsubscribe(_)
+            | RedisCommand::Multi
+            | RedisCommand::Exec
+            | RedisCommand::Discard
+            | RedisCommand::Watch(_)
+            | RedisCommand::Unwatch
+            | RedisCommand::Reset
+            | RedisCommand::Eval(_)
+            | RedisCommand::EvalSha(_)
+            | RedisCommand::FCall(_)
+            | RedisCommand::FCallRo(_)
+            | RedisCommand::Client(_)
+            | RedisCommand::Hello(_) => CommandFlags::STALE | CommandFlags::NOSCRIPT,
+            RedisCommand::Psync(..)
+            | RedisCommand::Wait(..)
+            | RedisCommand::WaitAof(..)
+            | RedisCommand::Script(_)
+            | RedisCommand::Function(_) => CommandFlags::NOSCRIPT,
+            RedisCommand::Ping
+            | RedisCommand::Echo(_)
+            | RedisCommand::Save
+            | RedisCommand::BgSave
+            | RedisCommand::BgRewriteAof
+            | RedisCommand::Sentinel(_)
+            | RedisCommand::Asking
+            | RedisCommand::Unknown
+            | RedisCommand::Invalid(_) => CommandFlags::NONE,
+        }
+    }
+
+    /// 소문자 명령 이름 (에러 메시지용)
+    pub fn name(&self) -> &'static str {
+        match self {
+            RedisCommand::Ping => "ping",
+            RedisCommand::Set(..) => "set",
+            RedisCommand::Get(_) => "get",
+            RedisCommand::Del(_) => "del",
+            RedisCommand::Dump(_) => "dump",
+            RedisCommand::Restore(args) if args.asking => "restore-asking",
+            RedisCommand::Restore(_) => "restore",
+            RedisCommand::MGet(_) => "mget",
+            RedisCommand::Echo(_) => "echo",
+            RedisCommand::ConfigGet(_) => "config|get",
+            RedisCommand::Keys(_) => "keys",
+            RedisCommand::Save => "save",
+            RedisCommand::BgSave => "bgsave",
+            RedisCommand::BgRewriteAof => "bgrewriteaof",
+            RedisCommand::LastSave => "lastsave",
+            RedisCommand::Info(_) => "info",
+            RedisCommand::ReplConf(_) => "replconf",
+            RedisCommand::Psync(..) => "psync",
+            RedisCommand::ReplicaOf(..) => "replicaof",
+            RedisCommand::Wait(..) => "wait",
+            RedisCommand::WaitAof(..) => "waitaof",
+            RedisCommand::Sentinel(_) => "sentinel",
+            RedisCommand::Cluster(_) => "cluster",
+            RedisCommand::Asking => "asking",
+            RedisCommand::Migrate(_) => "migrate",
+            RedisCommand::Subscribe(_) => "subscribe",
+            RedisCommand::PSubscribe(_) => "psubscribe",
+            RedisCommand::Unsubscribe(_) => "unsubscribe",
+            RedisCommand::PUnsubscribe(_) => "punsubscribe",
+            RedisCommand::Publish(..) => "publish",
+            RedisCommand::PubSub(_) => "pubsub",
+            RedisCommand::SSubscribe(_) => "ssubscribe",
+            RedisCommand::SUnsubscribe(_) => "sunsubscribe",
+            RedisCommand::SPublish(..) => "spublish",
+            RedisCommand::Multi => "multi",
+            RedisCommand::Exec => "exec",
+            RedisCommand::Discard => "discard",
+            RedisCommand::Watch(_) => "watch",
+            RedisCommand::Unwatch => "unwatch",
+            RedisCommand::FlushDb => "flushdb",
+            RedisCommand::FlushAll => "flushall",
+            RedisCommand::Reset => "reset",
+            RedisCommand::Eval(_) => "eval",
+            RedisCommand::EvalSha(_) => "evalsha",
+            RedisCommand::Script(_) => "script",
+            RedisCommand::FCall(_) => "fcall",
+            RedisCommand::FCallRo(_) => "fcall_ro",
+            RedisCommand::Function(_) | RedisCommand::FunctionRestore(..) => "function",
+            RedisCommand::Client(_) => "client",
+            RedisCommand::Hello(_) => "hello",
+            RedisCommand::Unknown | RedisCommand::Invalid(_) => "unknown",
+        }
+    }
+
+    /// 명령이 다루는 키들 (클러스터에서 슬롯을 정할 때 쓴다)
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            RedisCommand::Set(key, ..) | RedisCommand::Get(key) | RedisCommand::Dump(key) => vec![key],
+            // shard 채널도 키처럼 슬롯에 속한다
+            RedisCommand::SPublish(channel, _) => vec![channel],
+            RedisCommand::SSubscribe(channels) | RedisCommand::SUnsubscribe(channels) => channels.iter().map(|c| c.as_str()).collect(),
+            RedisCommand::Restore(args) => vec![&args.key],
+            RedisCommand::Eval(args) | RedisCommand::EvalSha(args) | RedisCommand::FCall(args) | RedisCommand::FCallRo(args) => args.keys.iter().map(|k| k.as_str()).collect(),
+            RedisCommand::MGet(keys) | RedisCommand::Del(keys) | RedisCommand::Watch(keys) | RedisCommand::Migrate(MigrateArgs { keys, .. }) => {
+                keys.iter().map(|k| k.as_str()).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// 인자 배열을 명령으로 변환한다. RESTORE 와 FUNCTION RESTORE 의 DUMP 페이로드는 바이너리라서 문자열로 바꾸지 않고 그대로 넘긴다.
+    pub fn from_frame(mut frame: Vec<Vec<u8>>) -> RedisCommand {
+        let is_function_restore = frame.len() >= 3 && frame[0].eq_ignore_ascii_case(b"FUNCTION") && frame[1].eq_ignore_ascii_case(b"RESTORE");
+        if is_function_restore {
+            let policy = match frame.get(3).map(|policy| String::from_utf8_lossy(policy).to_uppercase()) {
+                None => "APPEND".to_string(),
+                Some(policy) if frame.len() == 4 && matches!(policy.as_str(), "FLUSH" | "APPEND" | "REPLACE") => policy,
+                Some(_) => return RedisCommand::Unknown,
+            };
+            return RedisCommand::FunctionRestore(frame.swap_remove(2), policy);
+        }
+        let is_restore = frame.first().is_some_and(|name| name.eq_ignore_ascii_case(b"RESTORE") || name.eq_ignore_ascii_case(b"RESTORE-ASKING"));
+        if is_restore && frame.len() >= 4 {
+            let payload = frame.remove(3);
+            let asking = frame[0].eq_ignore_ascii_case(b"RESTORE-ASKING");
+            let args = frame.into_iter().skip(1).map(|arg| String::from_utf8_lossy(&arg).to_string()).collect();
+            return Self::parse_restore(args, payload, asking).unwrap_or(RedisCommand::Unknown);
+        }
+        Self::from_args(frame.into_iter().map(|arg| String::from_utf8_lossy(&arg).to_string()).collect())
+    }
+
+    /// RESTORE 의 페이로드를 뺀 인자들 (key ttl [options...])
+    fn parse_restore(args: Vec<String>, payload: Vec<u8>, asking: bool) -> Option<RedisCommand> {
+        let mut args = args.into_iter();
+        let key = args.next()?;
+        let ttl = args.next()?.parse().ok()?;
+        let mut restore = RestoreArgs { key, ttl, payload, replace: false, absttl: false, idletime: None, freq: None, asking };
+        while let Some(opt) = args.next() {
+            match opt.to_uppercase().as_str() {
+                "REPLACE" => restore.replace = true,
+                "ABSTTL" => restore.absttl = true,
+                "IDLETIME" if restore.freq.is_none() => restore.idletime = Some(args.next()?.parse().ok()?),
+                "FREQ" if restore.idletime.is_none() => restore.freq = Some(args.next()?.parse().ok()?),
+                _ => return None,
+            }
+        }
+        Some(RedisCommand::Restore(restore))
+    }
+
+    /// MIGRATE 의 인자들 (host port key db timeout [options...])
+    fn parse_migrate(args: Vec<String>) -> Option<RedisCommand> {
+        let mut args = args.into_iter();
+        let host = args.next()?;
+        let port = args.next()?.parse().ok()?;
+        let key = args.next()?;
+        let db = args.next()?.parse().ok()?;
+        let timeout = args.next()?.parse().ok()?;
+        let mut migrate = MigrateArgs { host, port, keys: vec![], db, timeout, copy: false, replace: false, auth: None };
+        while let Some(opt) = args.next() {
+            match opt.to_uppercase().as_str() {
+                "COPY" => migrate.copy = true,
+                "REPLACE" => migrate.replace = true,
+                "AUTH" => migrate.auth = Some((None, args.next()?)),
+                "AUTH2" => migrate.auth = Some((Some(args.next()?), args.next()?)),
+                // KEYS 는 마지막 옵션이고, 이때 key 인자는 빈 문자열이어야 한다
+                "KEYS" if key.is_empty() => {
+                    migrate.keys = args.by_ref().collect();
+                    if migrate.keys.is_empty() {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
+        }
+        if migrate.keys.is_empty() {
+            if key.is_empty() {
+                return None;
+            }
+            migrate.keys.push(key);
+        }
+        Some(RedisCommand::Migrate(migrate))
+    }
+
+    /// EVAL / EVALSHA 의 인자들 (script numkeys [key ...] [arg ...])
+    fn parse_script_args(args: Vec<String>) -> Option<ScriptArgs> {
+        let mut args = args.into_iter();
+        let script = args.next()?;
+        let numkeys: usize = args.next()?.parse().ok()?;
+        let mut keys: Vec<String> = args.collect();
+        if numkeys > keys.len() {
+            return None;
+        }
+        let args = keys.split_off(numkeys);
+        Some(ScriptArgs { script, keys, args })
+    }
+
+    /// SET key value [EX seconds | PX milliseconds | EXAT timestamp | PXAT timestamp]
+    fn parse_set(args: Vec<String>) -> Option<RedisCommand> {
+        let mut args = args.into_iter();
+        let key = args.next()?;
+        let value = args.next()?;
+
+        // 절대 시각 옵션은 현재 시각 기준의 상대 시간으로 바꿔서 저장
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        let mut expiry = None;
+        while let Some(opt) = args.next() {
+            let n = args.next()?.parse::<u64>().ok()?;
+            expiry = Some(match opt.to_uppercase().as_str() {
+                "PX" => n,
+                "EX" => n.checked_mul(1000)?,
+                "PXAT" => n.saturating_sub(now),
+                "EXAT" => n.checked_mul(1000)?.saturating_sub(now),
+                _ => return None,
+            });
+        }
+        Some(RedisCommand::Set(key, value, expiry))
+    }
+}
+
+/// proto-max-bulk-len 의 기본값 (512mb)
+pub const DEFAULT_MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+/// proto-max-multibulk-len 의 기본값. 명령 하나의 인자 수 (Redis 와 같이 1M)
+pub const DEFAULT_MAX_MULTIBULK_LEN: usize = 1024 * 1024;
+/// 응답 배열의 최대 길이. 스크립트가 받는 KEYS 결과처럼 명령보다 클 수 있어 RESP 의 한계 (INT_MAX) 까지 받는다.
+const MAX_REPLY_LEN: i64 = i32::MAX as i64;
+/// 줄바꿈 없이 이보다 긴 inline 명령은 거부한다
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
 #[derive(Clone)]
-pub struct RedisDecoder;
+pub struct RedisDecoder {
+    /// 인자 하나의 최대 길이 (proto-max-bulk-len)
+    max_bulk_len: usize,
+    /// 명령 하나의 최대 인자 수 (proto-max-multibulk-len)
+    max_multibulk_len: usize,
+}
+
+impl Default for RedisDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl RedisDecoder {
     pub fn new() -> Self {
-        RedisDecoder
+        Self::with_limits(DEFAULT_MAX_BULK_LEN, DEFAULT_MAX_MULTIBULK_LEN)
+    }
+
+    pub fn with_limits(max_bulk_len: usize, max_multibulk_len: usize) -> Self {
+        RedisDecoder { max_bulk_len, max_multibulk_len }
     }
 
-    fn read_resp_array_length(&self, src: &mut BytesMut) -> Option<usize> {
-        let mut length_str = Vec::new();
-        while !src.is_empty() && src[0] != b'\r' {
-            length_str.push(src[0]);
-            src.advance(1);
+    /// `prefix` 로 시작하는 한 줄 (\r\n 까지)을 정수로 읽는다.
+    fn read_line_number(src: &[u8], pos: &mut usize, prefix: u8) -> Result<i64, FrameError> {
+        if *pos >= src.len() {
+            return Err(FrameError::Incomplete);
         }
-        
-        // Skip \r\n
-        if src.len() >= 2 {
-            src.advance(2);
-            std::str::from_utf8(&length_str)
-                .ok()
-                .and_then(|s| s.parse::<usize>().ok())
-        } else {
-            None
+        if src[*pos] != prefix {
+            return Err(FrameError::Invalid);
         }
+
+        let start = *pos + 1;
+        let end = src[start..]
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .map(|i| start + i)
+            .ok_or(FrameError::Incomplete)?;
+
+        let n = std::str::from_utf8(&src[start..end])
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or(FrameError::Invalid)?;
+        *pos = end + 2;
+        Ok(n)
     }
 
-    fn read_bulk_string(&self, src: &mut BytesMut) -> Option<String> {
-        if src.is_empty() || src[0] != b'$' {
-            return None;
+    /// 버퍼 맨 앞의 RESP 배열 하나를 문자열 인자들로 읽는다. 버퍼는 건드리지 않고 소비한 바이트 수를 돌려준다.
+    pub fn parse_args(&self, src: &[u8]) -> Result<(Vec<String>, usize), FrameError> {
+        let (frame, consumed) = self.parse_frame(src)?;
+        Ok((frame.iter().map(|arg| String::from_utf8_lossy(arg).to_string()).collect(), consumed))
+    }
+
+    /// 버퍼 맨 앞의 RESP 배열 하나를 바이트 그대로 읽는다.
+    pub fn parse_frame(&self, src: &[u8]) -> Result<(Vec<Vec<u8>>, usize), FrameError> {
+        let mut pos = 0;
+        let length = Self::read_line_number(src, &mut pos, b'*')?;
+        if length < 0 || length as u64 > self.max_multibulk_len as u64 {
+            return Err(FrameError::TooLarge("invalid multibulk length"));
         }
-        src.advance(1);
 
-        let length = self.read_resp_array_length(src)?;
-        if src.len() < length + 2 {  // +2 for \r\n
-            return None;
+        // 길이만 보고 미리 크게 잡지 않는다. 인자가 실제로 도착해야 메모리를 쓴다.
+        let mut args = Vec::with_capacity((length as usize).min(1024));
+        for _ in 0..length {
+            let len = Self::read_line_number(src, &mut pos, b'$')?;
+            if len < 0 || len as u64 > self.max_bulk_len as u64 {
+                return Err(FrameError::TooLarge("invalid bulk length"));
+            }
+            let len = len as usize;
+            if src.len() < pos + len + 2 { // +2 for \r\n
+                return Err(FrameError::Incomplete);
+            }
+            if &src[pos + len..pos + len + 2] != b"\r\n" {
+                return Err(FrameError::Invalid);
+            }
+            args.push(src[pos..pos + len].to_vec());
+            pos += len + 2; // Skip string content and \r\n
         }
 
-        let string = String::from_utf8_lossy(&src[..length]).to_string();
-        src.advance(length + 2); // Skip string content and \r\n
-        Some(string)
+        Ok((args, pos))
     }
 
-    pub fn decode(&self, src: &mut BytesMut) -> Option<RedisCommand> {
-        println!("decode this -> {:?}",src);
-        if src.is_empty() {
-            return None;
+    /// inline 명령 한 줄 (공백으로 나눈 인자, 줄바꿈으로 끝난다) 을 읽는다. 인자가 없는 빈 줄이면 빈 Vec.
+    pub fn parse_inline(&self, src: &[u8]) -> Result<(Vec<Vec<u8>>, usize), FrameError> {
+        let Some(end) = src.iter().position(|&b| b == b'\n') else {
+            return Err(if src.len() > MAX_INLINE_LEN { FrameError::TooLarge("too big inline request") } else { FrameError::Incomplete });
+        };
+        let line = src[..end].strip_suffix(b"\r").unwrap_or(&src[..end]);
+        let args = split_args(line).ok_or(FrameError::Invalid)?;
+        Ok((args, end + 1))
+    }
+
+    /// 버퍼 맨 앞의 응답 하나를 읽는다. 버퍼는 건드리지 않고 소비한 바이트 수를 돌려준다.
+    pub fn parse_reply(&self, src: &[u8]) -> Result<(Reply, usize), FrameError> {
+        let mut pos = 0;
+        let reply = self.read_reply(src, &mut pos)?;
+        Ok((reply, pos))
+    }
+
+    /// 집계 응답의 길이를 읽는다. -1 (null) 은 None, 그 밖의 음수나 너무 큰 길이는 거부한다.
+    fn read_aggregate_len(src: &[u8], pos: &mut usize, prefix: u8) -> Result<Option<usize>, FrameError> {
+        match Self::read_line_number(src, pos, prefix)? {
+            -1 => Ok(None),
+            len if len < -1 => Err(FrameError::Invalid),
+            len if len > MAX_REPLY_LEN => Err(FrameError::TooLarge("invalid multibulk length")),
+            len => Ok(Some(len as usize)),
         }
-        // RESP 프로토콜에서 배열은 *로 시작
-        if src[0] == b'*' {
-            src.advance(1);
-            let length = self.read_resp_array_length(src)?;
-            
-            if length == 1 && src.len() >= 4 {
-                if let Some(cmd) = self.read_bulk_string(src) {
-                    match cmd.to_uppercase().as_str() {
-                        "PING" => return Some(RedisCommand::Ping),
-                        "SAVE" => return Some(RedisCommand::Save),
-                        _ => {}
-                    }
+    }
+
+    fn read_reply(&self, src: &[u8], pos: &mut usize) -> Result<Reply, FrameError> {
+        let prefix = *src.get(*pos).ok_or(FrameError::Incomplete)?;
+        match prefix {
+            b'+' | b'-' => {
+                let start = *pos + 1;
+                let end = src[start..]
+                    .windows(2)
+                    .position(|w| w == b"\r\n")
+                    .map(|i| start + i)
+                    .ok_or(FrameError::Incomplete)?;
+                let line = String::from_utf8_lossy(&src[start..end]).to_string();
+                *pos = end + 2;
+                Ok(if prefix == b'+' { Reply::Simple(line) } else { Reply::Error(line) })
+            }
+            b':' => Ok(Reply::Integer(Self::read_line_number(src, pos, b':')?)),
+            b'$' => {
+                let len = match Self::read_line_number(src, pos, b'$')? {
+                    -1 => return Ok(Reply::Bulk(None)),
+                    len if len < -1 => return Err(FrameError::Invalid),
+                    len if len as u64 > self.max_bulk_len as u64 => return Err(FrameError::TooLarge("invalid bulk length")),
+                    len => len as usize,
+                };
+                if src.len() < *pos + len + 2 {
+                    return Err(FrameError::Incomplete);
                 }
-            } else if length >= 3 {
-                if let Some(cmd) = self.read_bulk_string(src) {
-                    if cmd == "SET" {
-                        let key = self.read_bulk_string(src)?;
-                        let value = self.read_bulk_string(src)?;
-                        
-                        // PX 옵션 처리
-                        let mut expiry = None;
-                        if length == 5 {
-                            if let Some(opt) = self.read_bulk_string(src) {
-                                if opt.to_uppercase() == "PX" {
-                                    if let Some(ms_str) = self.read_bulk_string(src) {
-                                        if let Ok(ms) = ms_str.parse::<u64>() {
-                                            expiry = Some(ms);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        return Some(RedisCommand::Set(key, value, expiry));
-                    }else if cmd.to_uppercase() == "CONFIG" {
-                        if let Some(subcommand) = self.read_bulk_string(src) {
-                            if subcommand.to_uppercase() == "GET" {
-                                let parameter = self.read_bulk_string(src)?;
-                                return Some(RedisCommand::ConfigGet(parameter));
-                            }
-                        }
-                    }
+                let s = String::from_utf8_lossy(&src[*pos..*pos + len]).to_string();
+                *pos += len + 2;
+                Ok(Reply::Bulk(Some(s)))
+            }
+            b'*' => {
+                let Some(len) = Self::read_aggregate_len(src, pos, b'*')? else {
+                    return Ok(Reply::Array(None));
+                };
+                // parse_frame 과 같이 길이만 보고 미리 크게 잡지 않는다
+                let mut items = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    items.push(self.read_reply(src, pos)?);
                 }
-            } else if length == 2 {
-                // GET or ECHO command
-                if let Some(cmd) = self.read_bulk_string(src) {
-                    match cmd.to_uppercase().as_str() {
-                        "GET" => {
-                            let key = self.read_bulk_string(src)?;
-                            return Some(RedisCommand::Get(key));
-                        }
-                        "ECHO" => {
-                            let message = self.read_bulk_string(src)?;
-                            return Some(RedisCommand::Echo(message));
-                        }
-                        "KEYS" => {
-                            let query = self.read_bulk_string(src)?;
-                            return Some(RedisCommand::Keys(query))
-                        }
-                        _ => {}
-                    }
+                Ok(Reply::Array(Some(items)))
+            }
+            b'%' => {
+                let len = Self::read_aggregate_len(src, pos, b'%')?.unwrap_or(0);
+                let mut entries = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    let key = self.read_reply(src, pos)?;
+                    entries.push((key, self.read_reply(src, pos)?));
                 }
+                Ok(Reply::Map(entries))
             }
-            return Some(RedisCommand::Unknown);
+            _ => Err(FrameError::Invalid),
         }
+    }
+
+    /// 명령 하나를 디코딩한다. 데이터가 모자라면 버퍼를 그대로 두고 None 을 돌려준다.
+    pub fn decode(&self, src: &mut BytesMut) -> Option<RedisCommand> {
+        loop {
+            if src.is_empty() {
+                return None;
+            }
+            // RESP 프로토콜에서 배열은 *로 시작한다. 그 밖에는 telnet 같은 inline 명령이다.
+            let parsed = if src[0] == b'*' { self.parse_frame(src) } else { self.parse_inline(src) };
 
-        Some(RedisCommand::Unknown)
+            match parsed {
+                Ok((frame, consumed)) if frame.is_empty() => {
+                    // 빈 줄은 건너뛴다
+                    src.advance(consumed);
+                }
+                Ok((frame, consumed)) => {
+                    src.advance(consumed);
+                    return Some(RedisCommand::from_frame(frame));
+                }
+                Err(FrameError::Incomplete) => return None,
+                Err(FrameError::Invalid) => {
+                    let message = if src[0] == b'*' { "invalid multibulk request" } else { "unbalanced quotes in request" };
+                    src.clear();
+                    return Some(RedisCommand::Invalid(RedisError::Protocol(message.to_string())));
+                }
+                Err(FrameError::TooLarge(message)) => {
+                    src.clear();
+                    return Some(RedisCommand::Invalid(RedisError::Protocol(message.to_string())));
+                }
+            }
+        }
     }
-}
\ No newline at end of file
+}
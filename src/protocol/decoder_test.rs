@@ -1,7 +1,8 @@
 #[cfg(test)]
 mod tests {
     use bytes::BytesMut;
-    use crate::protocol::decoder::{RedisDecoder, RedisCommand};
+    use crate::protocol::decoder::{split_args, CommandFlags, FrameError, RedisDecoder, RedisCommand, Reply, DEFAULT_MAX_MULTIBULK_LEN};
+    use crate::protocol::error::RedisError;
 
     fn create_buffer(data: &[u8]) -> BytesMut {
         let mut buffer = BytesMut::new();
@@ -71,7 +72,10 @@ mod tests {
         let mut buffer = create_buffer(b"*2\r\n$3\r\nFOO\r\n$3\r\nkey\r\n");
         
         match decoder.decode(&mut buffer) {
-            Some(RedisCommand::Unknown) => (),
+            Some(RedisCommand::Invalid(RedisError::UnknownCommand(name, args))) => {
+                assert_eq!(name, "FOO");
+                assert_eq!(args, vec!["key"]);
+            }
             _ => panic!("Expected Unknown command"),
         }
     }
@@ -84,6 +88,21 @@ mod tests {
         assert!(decoder.decode(&mut buffer).is_none());
     }
 
+    #[test]
+    fn test_decode_skips_many_empty_lines() {
+        // 빈 줄이 아무리 많아도 재귀 없이 건너뛴다
+        let decoder = RedisDecoder::new();
+        let mut data = vec![b'\n'; 100_000];
+        data.extend_from_slice(b"*0\r\nPING\r\n");
+        let mut buffer = create_buffer(&data);
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::Ping)));
+        assert!(buffer.is_empty());
+
+        let mut buffer = create_buffer(&vec![b'\n'; 100_000]);
+        assert!(decoder.decode(&mut buffer).is_none());
+        assert!(buffer.is_empty());
+    }
+
     #[test]
     fn test_decode_incomplete_command() {
         let decoder = RedisDecoder::new();
@@ -95,14 +114,78 @@ mod tests {
     #[test]
     fn test_decode_malformed_command() {
         let decoder = RedisDecoder::new();
-        let mut buffer = create_buffer(b"GET key\r\n");
+        let mut buffer = create_buffer(b"GET \"key\r\n");
         
         match decoder.decode(&mut buffer) {
-            Some(RedisCommand::Unknown) => (),
-            _ => panic!("Expected Unknown command for malformed input"),
+            Some(RedisCommand::Invalid(RedisError::Protocol(msg))) => assert_eq!(msg, "unbalanced quotes in request"),
+            _ => panic!("Expected protocol error for malformed input"),
         }
     }
 
+    #[test]
+    fn test_decode_length_limits() {
+        let decoder = RedisDecoder::with_limits(5, DEFAULT_MAX_MULTIBULK_LEN);
+        let protocol_error = |buffer: &mut BytesMut| match decoder.decode(buffer) {
+            Some(RedisCommand::Invalid(RedisError::Protocol(msg))) => msg,
+            other => panic!("Expected protocol error, got {:?}", other),
+        };
+
+        // 데이터가 오기 전에 길이만 보고 거부한다
+        let mut buffer = create_buffer(b"*2\r\n$3\r\nGET\r\n$6\r\n");
+        assert_eq!(protocol_error(&mut buffer), "invalid bulk length");
+        assert!(buffer.is_empty());
+
+        let mut buffer = create_buffer(b"*1\r\n$-1\r\n");
+        assert_eq!(protocol_error(&mut buffer), "invalid bulk length");
+
+        let mut buffer = create_buffer(b"*99999999999\r\n");
+        assert_eq!(protocol_error(&mut buffer), "invalid multibulk length");
+
+        // 제한 안의 길이는 데이터가 모두 올 때까지 기다린다
+        let mut buffer = create_buffer(b"*1048576\r\n$5\r\nhel");
+        assert!(decoder.decode(&mut buffer).is_none());
+        let mut buffer = create_buffer(b"*1048577\r\n");
+        assert_eq!(protocol_error(&mut buffer), "invalid multibulk length");
+        let decoder = RedisDecoder::with_limits(5, 2);
+        let mut buffer = create_buffer(b"*3\r\n");
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::Invalid(RedisError::Protocol(msg))) if msg == "invalid multibulk length"));
+        let decoder = RedisDecoder::with_limits(5, DEFAULT_MAX_MULTIBULK_LEN);
+        let mut buffer = create_buffer(b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n");
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::Get(key)) if key == "hello"));
+
+        let mut buffer = create_buffer(&vec![b'a'; 64 * 1024 + 1]);
+        assert_eq!(protocol_error(&mut buffer), "too big inline request");
+        let mut buffer = create_buffer(&vec![b'a'; 64 * 1024]);
+        assert!(decoder.decode(&mut buffer).is_none());
+    }
+
+    #[test]
+    fn test_decode_inline_command() {
+        let decoder = RedisDecoder::new();
+        let mut buffer = create_buffer(b"\r\nGET key\r\nPING\nSET");
+
+        match decoder.decode(&mut buffer) {
+            Some(RedisCommand::Get(key)) => assert_eq!(key, "key"),
+            other => panic!("Expected GET command, got {:?}", other),
+        }
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::Ping)));
+        // 줄바꿈이 오기 전까지는 기다린다
+        assert!(decoder.decode(&mut buffer).is_none());
+        assert_eq!(&buffer[..], b"SET");
+    }
+
+    #[test]
+    fn test_split_args() {
+        let split = |line: &[u8]| split_args(line).map(|args| args.into_iter().map(|a| String::from_utf8(a).unwrap()).collect::<Vec<_>>());
+        assert_eq!(split(b"  set  k   v "), Some(vec!["set".to_string(), "k".to_string(), "v".to_string()]));
+        assert_eq!(split(b"set k \"hello world\\n\\x41\""), Some(vec!["set".to_string(), "k".to_string(), "hello world\nA".to_string()]));
+        assert_eq!(split(b"echo 'it\\'s' \"\""), Some(vec!["echo".to_string(), "it's".to_string(), "".to_string()]));
+        assert_eq!(split(b""), Some(vec![]));
+        assert_eq!(split(b"echo \"unterminated"), None);
+        assert_eq!(split(b"echo \"a\"b"), None);
+        assert_eq!(split(b"echo 'a'b"), None);
+    }
+
     #[test]
     fn test_decode_echo() {
         let decoder = RedisDecoder::new();
@@ -175,4 +258,341 @@ mod tests {
             _ => panic!("Expected KEYS command")
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_decode_bgsave_and_lastsave() {
+        let decoder = RedisDecoder::new();
+        let mut buffer = create_buffer(b"*1\r\n$6\r\nBGSAVE\r\n");
+        match decoder.decode(&mut buffer) {
+            Some(RedisCommand::BgSave) => (),
+            _ => panic!("Expected BGSAVE command"),
+        }
+        assert_eq!(buffer.len(), 0);
+
+        let mut buffer = create_buffer(b"*1\r\n$8\r\nlastsave\r\n");
+        match decoder.decode(&mut buffer) {
+            Some(RedisCommand::LastSave) => (),
+            _ => panic!("Expected LASTSAVE command"),
+        }
+        assert_eq!(buffer.len(), 0);
+    }
+
+    #[test]
+    fn test_decode_info() {
+        let decoder = RedisDecoder::new();
+        let mut buffer = create_buffer(b"*1\r\n$4\r\nINFO\r\n");
+        match decoder.decode(&mut buffer) {
+            Some(RedisCommand::Info(None)) => (),
+            _ => panic!("Expected INFO command"),
+        }
+
+        let mut buffer = create_buffer(b"*2\r\n$4\r\nINFO\r\n$11\r\npersistence\r\n");
+        match decoder.decode(&mut buffer) {
+            Some(RedisCommand::Info(Some(section))) => assert_eq!(section, "persistence"),
+            _ => panic!("Expected INFO persistence command"),
+        }
+        assert_eq!(buffer.len(), 0);
+    }
+
+    #[test]
+    fn test_decode_pipelined_commands() {
+        let decoder = RedisDecoder::new();
+        let mut buffer = create_buffer(b"*1\r\n$4\r\nPING\r\n*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n*2\r\n$3\r\nGET");
+
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::Ping)));
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::Get(key)) if key == "key"));
+
+        // 잘린 명령은 다음 read 를 위해 버퍼에 남아 있어야 함
+        assert!(decoder.decode(&mut buffer).is_none());
+        assert_eq!(&buffer[..], b"*2\r\n$3\r\nGET");
+
+        buffer.extend_from_slice(b"\r\n$3\r\nfoo\r\n");
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::Get(key)) if key == "foo"));
+        assert_eq!(buffer.len(), 0);
+    }
+
+    #[test]
+    fn test_decode_set_pxat() {
+        let decoder = RedisDecoder::new();
+        let mut buffer = create_buffer(b"*5\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n$4\r\nPXAT\r\n$13\r\n9999999999999\r\n");
+
+        match decoder.decode(&mut buffer) {
+            Some(RedisCommand::Set(key, value, Some(expiry))) => {
+                assert_eq!(key, "key");
+                assert_eq!(value, "value");
+                assert!(expiry > 0);
+            }
+            _ => panic!("Expected SET command with expiry"),
+        }
+        assert_eq!(buffer.len(), 0);
+    }
+
+    #[test]
+    fn test_decode_replication_commands() {
+        let decoder = RedisDecoder::new();
+        let mut buffer = create_buffer(b"*3\r\n$8\r\nREPLCONF\r\n$14\r\nlistening-port\r\n$4\r\n6380\r\n");
+        match decoder.decode(&mut buffer) {
+            Some(RedisCommand::ReplConf(options)) => assert_eq!(options, vec!["listening-port", "6380"]),
+            _ => panic!("Expected REPLCONF command"),
+        }
+
+        let mut buffer = create_buffer(b"*3\r\n$5\r\nPSYNC\r\n$1\r\n?\r\n$2\r\n-1\r\n");
+        match decoder.decode(&mut buffer) {
+            Some(RedisCommand::Psync(replid, offset)) => {
+                assert_eq!(replid, "?");
+                assert_eq!(offset, -1);
+            }
+            _ => panic!("Expected PSYNC command"),
+        }
+
+        let mut buffer = create_buffer(b"*3\r\n$9\r\nREPLICAOF\r\n$2\r\nNO\r\n$3\r\nONE\r\n");
+        match decoder.decode(&mut buffer) {
+            Some(RedisCommand::ReplicaOf(host, port)) => {
+                assert_eq!(host, "NO");
+                assert_eq!(port, "ONE");
+            }
+            _ => panic!("Expected REPLICAOF command"),
+        }
+    }
+
+    #[test]
+    fn test_decode_wait() {
+        let decoder = RedisDecoder::new();
+        let mut buffer = create_buffer(b"*3\r\n$4\r\nWAIT\r\n$1\r\n2\r\n$3\r\n500\r\n");
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::Wait(2, 500))));
+
+        let mut buffer = create_buffer(b"*4\r\n$7\r\nWAITAOF\r\n$1\r\n1\r\n$1\r\n0\r\n$1\r\n0\r\n");
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::WaitAof(1, 0, 0))));
+
+        let mut buffer = create_buffer(b"*3\r\n$4\r\nWAIT\r\n$3\r\nabc\r\n$1\r\n0\r\n");
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::Unknown)));
+    }
+
+    #[test]
+    fn test_command_flags() {
+        let set = RedisCommand::from_args(vec!["SET".to_string(), "k".to_string(), "v".to_string()]);
+        assert!(set.flags().contains(CommandFlags::WRITE));
+        assert!(!set.flags().contains(CommandFlags::STALE));
+
+        let get = RedisCommand::from_args(vec!["GET".to_string(), "k".to_string()]);
+        assert!(get.flags().contains(CommandFlags::READONLY));
+        assert!(!get.flags().contains(CommandFlags::WRITE));
+
+        let info = RedisCommand::from_args(vec!["INFO".to_string()]);
+        assert!(info.flags().contains(CommandFlags::STALE));
+        assert!((CommandFlags::READONLY | CommandFlags::STALE).contains(CommandFlags::STALE));
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        let decoder = RedisDecoder::new();
+        let data = b"*3\r\n:1\r\n$3\r\nabc\r\n*-1\r\n+OK\r\n";
+        let (reply, consumed) = decoder.parse_reply(data).unwrap();
+        assert_eq!(
+            reply,
+            Reply::Array(Some(vec![Reply::Integer(1), Reply::Bulk(Some("abc".to_string())), Reply::Array(None)]))
+        );
+        assert_eq!(consumed, data.len() - 5);
+
+        let (reply, _) = decoder.parse_reply(b"-ERR oops\r\n").unwrap();
+        assert_eq!(reply, Reply::Error("ERR oops".to_string()));
+        assert_eq!(decoder.parse_reply(b"$3\r\nab"), Err(FrameError::Incomplete));
+        assert_eq!(decoder.parse_reply(b"?\r\n"), Err(FrameError::Invalid));
+
+        // 상대가 보낸 길이만으로 메모리를 잡지 않는다. -1 이 아닌 음수나 너무 큰 길이는 거부.
+        assert_eq!(decoder.parse_reply(b"*1048576\r\n:1\r\n"), Err(FrameError::Incomplete));
+        assert_eq!(decoder.parse_reply(b"*9999999999999\r\n"), Err(FrameError::TooLarge("invalid multibulk length")));
+        assert_eq!(decoder.parse_reply(b"%9999999999999\r\n"), Err(FrameError::TooLarge("invalid multibulk length")));
+        assert_eq!(decoder.parse_reply(b"*-2\r\n"), Err(FrameError::Invalid));
+        assert_eq!(decoder.parse_reply(b"$-2\r\n"), Err(FrameError::Invalid));
+        assert_eq!(decoder.parse_reply(b"$9999999999999\r\n"), Err(FrameError::TooLarge("invalid bulk length")));
+    }
+
+    #[test]
+    fn test_command_keys() {
+        let args = |cmd: &[&str]| cmd.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(RedisCommand::from_args(args(&["SET", "k", "v", "PX", "100"])).keys(), vec!["k"]);
+        assert_eq!(RedisCommand::from_args(args(&["MGET", "a", "b"])).keys(), vec!["a", "b"]);
+        assert!(RedisCommand::from_args(args(&["CLUSTER", "KEYSLOT", "k"])).keys().is_empty());
+        assert!(matches!(RedisCommand::from_args(args(&["asking"])), RedisCommand::Asking));
+        assert_eq!(RedisCommand::from_args(args(&["DEL", "a", "b"])).keys(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_decode_migrate() {
+        let args = |cmd: &[&str]| cmd.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        match RedisCommand::from_args(args(&["MIGRATE", "127.0.0.1", "7001", "foo", "0", "5000"])) {
+            RedisCommand::Migrate(migrate) => {
+                assert_eq!((migrate.host.as_str(), migrate.port, migrate.db, migrate.timeout), ("127.0.0.1", 7001, 0, 5000));
+                assert_eq!(migrate.keys, vec!["foo"]);
+                assert!(!migrate.copy && !migrate.replace && migrate.auth.is_none());
+            }
+            other => panic!("Expected MIGRATE command, got {:?}", other),
+        }
+        let migrate = RedisCommand::from_args(args(&["MIGRATE", "h", "7001", "", "0", "0", "copy", "AUTH2", "u", "p", "KEYS", "a", "b"]));
+        assert!(migrate.flags().contains(CommandFlags::WRITE));
+        assert_eq!(migrate.keys(), vec!["a", "b"]);
+        match migrate {
+            RedisCommand::Migrate(migrate) => {
+                assert!(migrate.copy);
+                assert_eq!(migrate.auth, Some((Some("u".to_string()), "p".to_string())));
+            }
+            other => panic!("Expected MIGRATE command, got {:?}", other),
+        }
+
+        // KEYS 는 key 인자가 빈 문자열일 때만, key 가 비어 있으면 KEYS 가 있어야 한다
+        for bad in [
+            &["MIGRATE", "h", "port", "foo", "0", "5000"][..],
+            &["MIGRATE", "h", "7001", "foo", "0", "0", "KEYS", "a"],
+            &["MIGRATE", "h", "7001", "", "0", "0"],
+            &["MIGRATE", "h", "7001", "foo", "0", "0", "AUTH"],
+        ] {
+            assert!(matches!(RedisCommand::from_args(args(bad)), RedisCommand::Unknown), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_decode_restore_keeps_binary_payload() {
+        let decoder = RedisDecoder::new();
+        let mut buffer = create_buffer(b"*5\r\n$7\r\nRESTORE\r\n$1\r\nk\r\n$1\r\n0\r\n$4\r\n\x00\xff\xfe\x80\r\n$7\r\nREPLACE\r\n");
+        match decoder.decode(&mut buffer) {
+            Some(RedisCommand::Restore(restore)) => {
+                assert_eq!(restore.key, "k");
+                assert_eq!(restore.payload, vec![0x00, 0xFF, 0xFE, 0x80]);
+                assert!(restore.replace && !restore.absttl && !restore.asking);
+            }
+            other => panic!("Expected RESTORE command, got {:?}", other),
+        }
+
+        let args = |cmd: &[&str]| cmd.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        match RedisCommand::from_args(args(&["RESTORE-ASKING", "k", "100", "x", "ABSTTL", "IDLETIME", "10"])) {
+            RedisCommand::Restore(restore) => {
+                assert!(restore.asking && restore.absttl);
+                assert_eq!((restore.ttl, restore.idletime, restore.freq), (100, Some(10), None));
+            }
+            other => panic!("Expected RESTORE command, got {:?}", other),
+        }
+        // IDLETIME 과 FREQ 는 함께 쓸 수 없다
+        let both = args(&["RESTORE", "k", "0", "x", "IDLETIME", "1", "FREQ", "1"]);
+        assert!(matches!(RedisCommand::from_args(both), RedisCommand::Unknown));
+    }
+
+    #[test]
+    fn test_decode_pubsub_commands() {
+        let args = |cmd: &[&str]| cmd.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(matches!(RedisCommand::from_args(args(&["SUBSCRIBE", "a", "b"])), RedisCommand::Subscribe(c) if c == ["a", "b"]));
+        assert!(matches!(RedisCommand::from_args(args(&["psubscribe", "n*"])), RedisCommand::PSubscribe(_)));
+        assert!(matches!(RedisCommand::from_args(args(&["UNSUBSCRIBE"])), RedisCommand::Unsubscribe(c) if c.is_empty()));
+        assert!(matches!(RedisCommand::from_args(args(&["SUBSCRIBE"])), RedisCommand::Invalid(RedisError::WrongArity(name)) if name == "subscribe"));
+        let publish = RedisCommand::from_args(args(&["PUBLISH", "ch", "msg"]));
+        assert!(matches!(&publish, RedisCommand::Publish(c, m) if c == "ch" && m == "msg"));
+        assert!(publish.keys().is_empty());
+        assert_eq!(publish.name(), "publish");
+        assert!(matches!(RedisCommand::from_args(args(&["RESET"])), RedisCommand::Reset));
+    }
+
+    #[test]
+    fn test_decode_sharded_pubsub_commands() {
+        let args = |cmd: &[&str]| cmd.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let ssubscribe = RedisCommand::from_args(args(&["SSUBSCRIBE", "{user}a", "{user}b"]));
+        assert_eq!(ssubscribe.keys(), vec!["{user}a", "{user}b"]);
+        assert!(matches!(RedisCommand::from_args(args(&["SUNSUBSCRIBE"])), RedisCommand::SUnsubscribe(c) if c.is_empty()));
+        let spublish = RedisCommand::from_args(args(&["spublish", "ch", "msg"]));
+        assert!(matches!(&spublish, RedisCommand::SPublish(c, m) if c == "ch" && m == "msg"));
+        // 일반 PUBLISH 와 달리 채널의 슬롯으로 라우팅한다
+        assert_eq!(spublish.keys(), vec!["ch"]);
+        assert!(matches!(RedisCommand::from_args(args(&["SPUBLISH", "ch"])), RedisCommand::Invalid(RedisError::WrongArity(name)) if name == "spublish"));
+    }
+
+    #[test]
+    fn test_decode_transaction_commands() {
+        let args = |cmd: &[&str]| cmd.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(matches!(RedisCommand::from_args(args(&["MULTI"])), RedisCommand::Multi));
+        assert!(matches!(RedisCommand::from_args(args(&["exec"])), RedisCommand::Exec));
+        assert!(matches!(RedisCommand::from_args(args(&["Discard"])), RedisCommand::Discard));
+        // 인자 수가 틀리면 큐에 넣지 못하는 명령이 되어 EXECABORT 를 일으킨다
+        assert!(matches!(RedisCommand::from_args(args(&["MULTI", "x"])), RedisCommand::Invalid(RedisError::WrongArity(name)) if name == "multi"));
+        assert!(RedisCommand::Exec.flags().contains(CommandFlags::STALE));
+
+        let watch = RedisCommand::from_args(args(&["WATCH", "a", "b"]));
+        assert_eq!(watch.keys(), vec!["a", "b"]);
+        assert!(matches!(RedisCommand::from_args(args(&["WATCH"])), RedisCommand::Invalid(RedisError::WrongArity(name)) if name == "watch"));
+        assert!(matches!(RedisCommand::from_args(args(&["UNWATCH"])), RedisCommand::Unwatch));
+        assert!(matches!(RedisCommand::from_args(args(&["FLUSHDB", "async"])), RedisCommand::FlushDb));
+        assert!(matches!(RedisCommand::from_args(args(&["FLUSHALL"])), RedisCommand::FlushAll));
+        assert!(matches!(RedisCommand::from_args(args(&["FLUSHDB", "later"])), RedisCommand::Unknown));
+        assert!(RedisCommand::FlushDb.flags().contains(CommandFlags::WRITE));
+    }
+
+    #[test]
+    fn test_decode_script_commands() {
+        let args = |cmd: &[&str]| cmd.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let eval = RedisCommand::from_args(args(&["EVAL", "return 1", "2", "k1", "k2", "a1"]));
+        assert!(matches!(&eval, RedisCommand::Eval(s) if s.script == "return 1" && s.args == ["a1"]));
+        assert_eq!(eval.keys(), vec!["k1", "k2"]);
+        assert!(eval.flags().contains(CommandFlags::NOSCRIPT));
+        let evalsha = RedisCommand::from_args(args(&["evalsha", "abc", "0"]));
+        assert!(matches!(&evalsha, RedisCommand::EvalSha(s) if s.script == "abc" && s.keys.is_empty() && s.args.is_empty()));
+        // numkeys 가 숫자가 아니거나 인자보다 많으면 안 된다
+        assert!(matches!(RedisCommand::from_args(args(&["EVAL", "return 1", "x"])), RedisCommand::Unknown));
+        assert!(matches!(RedisCommand::from_args(args(&["EVAL", "return 1", "2", "k1"])), RedisCommand::Unknown));
+        assert!(matches!(RedisCommand::from_args(args(&["SCRIPT", "LOAD", "return 1"])), RedisCommand::Script(a) if a.len() == 2));
+
+        // 스크립트 안에서 부를 수 없는 명령
+        assert!(RedisCommand::Multi.flags().contains(CommandFlags::NOSCRIPT));
+        assert!(RedisCommand::from_args(args(&["SUBSCRIBE", "ch"])).flags().contains(CommandFlags::NOSCRIPT));
+        assert!(!RedisCommand::from_args(args(&["SET", "k", "v"])).flags().contains(CommandFlags::NOSCRIPT));
+        assert!(!RedisCommand::from_args(args(&["PUBLISH", "ch", "m"])).flags().contains(CommandFlags::NOSCRIPT));
+    }
+
+    #[test]
+    fn test_decode_function_commands() {
+        let args = |cmd: &[&str]| cmd.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let fcall = RedisCommand::from_args(args(&["FCALL", "myfunc", "1", "k", "a"]));
+        assert!(matches!(&fcall, RedisCommand::FCall(s) if s.script == "myfunc" && s.args == ["a"]));
+        assert_eq!(fcall.keys(), vec!["k"]);
+        assert!(matches!(RedisCommand::from_args(args(&["fcall_ro", "f", "0"])), RedisCommand::FCallRo(_)));
+        assert!(matches!(RedisCommand::from_args(args(&["FCALL", "f", "1"])), RedisCommand::Unknown));
+
+        // 라이브러리를 바꾸는 하위 명령만 쓰기 명령이다
+        let load = RedisCommand::from_args(args(&["FUNCTION", "load", "#!lua name=x"]));
+        assert!(load.flags().contains(CommandFlags::WRITE) && load.flags().contains(CommandFlags::NOSCRIPT));
+        assert!(!RedisCommand::from_args(args(&["FUNCTION", "LIST"])).flags().contains(CommandFlags::WRITE));
+
+        let decoder = RedisDecoder::new();
+        let mut buffer = create_buffer(b"*4\r\n$8\r\nFUNCTION\r\n$7\r\nrestore\r\n$3\r\n\xf5\x00\x80\r\n$7\r\nreplace\r\n");
+        match decoder.decode(&mut buffer) {
+            Some(RedisCommand::FunctionRestore(payload, policy)) => {
+                assert_eq!(payload, vec![0xF5, 0x00, 0x80]);
+                assert_eq!(policy, "REPLACE");
+            }
+            other => panic!("Expected FUNCTION RESTORE command, got {:?}", other),
+        }
+        let mut buffer = create_buffer(b"*3\r\n$8\r\nFUNCTION\r\n$7\r\nRESTORE\r\n$1\r\nx\r\n");
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::FunctionRestore(_, policy)) if policy == "APPEND"));
+        let mut buffer = create_buffer(b"*4\r\n$8\r\nFUNCTION\r\n$7\r\nRESTORE\r\n$1\r\nx\r\n$3\r\nBAD\r\n");
+        assert!(matches!(decoder.decode(&mut buffer), Some(RedisCommand::Unknown)));
+    }
+
+    #[test]
+    fn test_decode_client_command() {
+        let args = |cmd: &[&str]| cmd.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let tracking = RedisCommand::from_args(args(&["client", "TRACKING", "on", "BCAST"]));
+        assert!(matches!(&tracking, RedisCommand::Client(rest) if rest == &args(&["TRACKING", "on", "BCAST"])));
+        assert_eq!(tracking.name(), "client");
+        assert!(tracking.flags().contains(CommandFlags::NOSCRIPT));
+        assert!(matches!(RedisCommand::from_args(args(&["CLIENT"])), RedisCommand::Invalid(RedisError::WrongArity(name)) if name == "client"));
+    }
+
+    #[test]
+    fn test_decode_hello_and_map_reply() {
+        let args = |cmd: &[&str]| cmd.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(matches!(RedisCommand::from_args(args(&["HELLO"])), RedisCommand::Hello(rest) if rest.is_empty()));
+        assert!(matches!(RedisCommand::from_args(args(&["hello", "3", "SETNAME", "x"])), RedisCommand::Hello(rest) if rest.len() == 3));
+
+        let decoder = RedisDecoder::new();
+        let (reply, consumed) = decoder.parse_reply(b"%1\r\n$5\r\nproto\r\n:3\r\n").unwrap();
+        assert_eq!(reply, Reply::Map(vec![(Reply::Bulk(Some("proto".to_string())), Reply::Integer(3))]));
+        assert_eq!(consumed, 19);
+    }
+}
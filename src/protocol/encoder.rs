@@ -1,11 +1,32 @@
+use crate::protocol::decoder::Reply;
+use crate::protocol::error::RedisError;
 use bytes::BytesMut;
 
-/// Redis 프로토콜의 인코딩을 담당하는 구조체
-pub struct RedisEncoder;
+/// Redis 프로토콜의 인코딩을 담당하는 구조체.
+/// RESP3 타입은 RESP2 클라이언트에게는 Redis 처럼 가까운 RESP2 타입으로 바꿔서 쓴다.
+pub struct RedisEncoder {
+    /// HELLO 3 으로 RESP3 를 고른 클라이언트
+    resp3: bool,
+}
+
+impl Default for RedisEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl RedisEncoder {
     pub fn new() -> RedisEncoder {
-        RedisEncoder
+        RedisEncoder { resp3: false }
+    }
+
+    /// 클라이언트가 고른 프로토콜로 인코딩한다
+    pub fn with_resp3(resp3: bool) -> RedisEncoder {
+        RedisEncoder { resp3 }
+    }
+
+    pub fn is_resp3(&self) -> bool {
+        self.resp3
     }
 
     pub fn encode_pong(&self, dst: &mut BytesMut) {
@@ -16,18 +37,42 @@ impl RedisEncoder {
         dst.extend_from_slice(b"+OK\r\n");
     }
 
-    pub fn encode_error(&self, dst: &mut BytesMut) {
-        dst.extend_from_slice(b"-ERR unknown command\r\n");
+    pub fn encode_error(&self, dst: &mut BytesMut, err: &RedisError) {
+        self.encode_error_message(dst, &err.to_string());
+    }
+
+    /// 에러 응답을 쓴다. Reply::Error 처럼 이미 완성된 메시지에도 쓰인다.
+    fn encode_error_message(&self, dst: &mut BytesMut, msg: &str) {
+        encode_line(dst, b'-', msg);
+    }
+
+    pub fn encode_simple_string(&self, dst: &mut BytesMut, s: &str) {
+        encode_line(dst, b'+', s);
+    }
+
+    pub fn encode_integer(&self, dst: &mut BytesMut, n: i64) {
+        dst.extend_from_slice(format!(":{}\r\n", n).as_bytes());
     }
 
     pub fn encode_null(&self, dst: &mut BytesMut) {
-        dst.extend_from_slice(b"$-1\r\n");
+        if self.resp3 {
+            dst.extend_from_slice(b"_\r\n");
+        } else {
+            dst.extend_from_slice(b"$-1\r\n");
+        }
     }
 
     pub fn encode_bulk_string(&self, dst: &mut BytesMut, s: &str) {
         dst.extend_from_slice(format!("${}\r\n{}\r\n", s.len(), s).as_bytes());
     }
 
+    /// 바이너리 bulk string (예: DUMP 페이로드)
+    pub fn encode_bulk_bytes(&self, dst: &mut BytesMut, bytes: &[u8]) {
+        dst.extend_from_slice(format!("${}\r\n", bytes.len()).as_bytes());
+        dst.extend_from_slice(bytes);
+        dst.extend_from_slice(b"\r\n");
+    }
+
     pub fn encode_array(&self, dst: &mut BytesMut, items: &[&str]) {
         dst.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
         for item in items {
@@ -35,13 +80,99 @@ impl RedisEncoder {
         }
     }
 
+    /// 배열 헤더만 쓴다. 원소는 이어서 직접 인코딩한다 (원소 타입이 섞인 배열).
+    pub fn encode_array_len(&self, dst: &mut BytesMut, len: usize) {
+        dst.extend_from_slice(format!("*{}\r\n", len).as_bytes());
+    }
+
+    /// 정수 배열 (예: WAITAOF 응답)
+    pub fn encode_integer_array(&self, dst: &mut BytesMut, items: &[i64]) {
+        self.encode_array_len(dst, items.len());
+        for item in items {
+            self.encode_integer(dst, *item);
+        }
+    }
+
     /// 빈 배열
     pub fn encode_empty_array(&self, dst: &mut BytesMut) {
         dst.extend_from_slice(b"*0\r\n");
     }
 
-    /// null 배열
+    /// null 배열 (RESP3 에는 null 이 하나뿐이다)
     pub fn encode_null_array(&self, dst: &mut BytesMut) {
-        dst.extend_from_slice(b"*-1\r\n");
+        if self.resp3 {
+            dst.extend_from_slice(b"_\r\n");
+        } else {
+            dst.extend_from_slice(b"*-1\r\n");
+        }
+    }
+
+    /// 맵 헤더. 이어서 키와 값을 번갈아 `len` 쌍 인코딩한다. RESP2 에서는 두 배 길이의 배열이다.
+    pub fn encode_map_len(&self, dst: &mut BytesMut, len: usize) {
+        if self.resp3 {
+            dst.extend_from_slice(format!("%{}\r\n", len).as_bytes());
+        } else {
+            self.encode_array_len(dst, len * 2);
+        }
+    }
+
+    /// 문자열 키와 값의 맵 (예: CONFIG GET)
+    pub fn encode_map(&self, dst: &mut BytesMut, entries: &[(&str, &str)]) {
+        self.encode_map_len(dst, entries.len());
+        for (key, value) in entries {
+            self.encode_bulk_string(dst, key);
+            self.encode_bulk_string(dst, value);
+        }
+    }
+
+    /// 요청 없이 보내는 메시지 (pub/sub, 키 무효화) 의 헤더. RESP2 에서는 배열이다.
+    pub fn encode_push_len(&self, dst: &mut BytesMut, len: usize) {
+        if self.resp3 {
+            dst.extend_from_slice(format!(">{}\r\n", len).as_bytes());
+        } else {
+            self.encode_array_len(dst, len);
+        }
+    }
+
+    /// 형식 (세 글자, 예: "txt") 이 붙은 문자열 (예: INFO). RESP2 에서는 bulk string 이다.
+    pub fn encode_verbatim_string(&self, dst: &mut BytesMut, format: &str, s: &str) {
+        if self.resp3 {
+            dst.extend_from_slice(format!("={}\r\n{}:{}\r\n", s.len() + 4, format, s).as_bytes());
+        } else {
+            self.encode_bulk_string(dst, s);
+        }
     }
+
+    /// 다른 명령의 응답을 그대로 옮긴다 (예: 스크립트의 결과)
+    pub fn encode_reply(&self, dst: &mut BytesMut, reply: &Reply) {
+        match reply {
+            Reply::Simple(s) => self.encode_simple_string(dst, s),
+            Reply::Error(msg) => self.encode_error_message(dst, msg),
+            Reply::Integer(n) => self.encode_integer(dst, *n),
+            Reply::Bulk(Some(s)) => self.encode_bulk_string(dst, s),
+            Reply::Bulk(None) => self.encode_null(dst),
+            Reply::Array(Some(items)) => {
+                self.encode_array_len(dst, items.len());
+                for item in items {
+                    self.encode_reply(dst, item);
+                }
+            }
+            Reply::Array(None) => self.encode_null_array(dst),
+            Reply::Map(entries) => {
+                self.encode_map_len(dst, entries.len());
+                for (key, value) in entries {
+                    self.encode_reply(dst, key);
+                    self.encode_reply(dst, value);
+                }
+            }
+        }
+    }
+}
+
+/// 한 줄짜리 응답 (상태, 에러). 클라이언트가 보낸 값이나 스크립트의 문자열이 들어갈 수 있으므로
+/// Redis 처럼 \r 과 \n 을 공백으로 바꿔 응답이 끼어들지 못하게 한다.
+fn encode_line(dst: &mut BytesMut, prefix: u8, s: &str) {
+    dst.extend_from_slice(&[prefix]);
+    dst.extend(s.bytes().map(|b| if b == b'\r' || b == b'\n' { b' ' } else { b }));
+    dst.extend_from_slice(b"\r\n");
 }
@@ -2,6 +2,7 @@
 mod tests {
     use bytes::BytesMut;
     use crate::protocol::encoder::RedisEncoder;
+    use crate::protocol::error::RedisError;
 
     #[test]
     fn test_encode_pong() {
@@ -23,8 +24,25 @@ mod tests {
     fn test_encode_error() {
         let encoder = RedisEncoder::new();
         let mut dst = BytesMut::new();
-        encoder.encode_error(&mut dst);
-        assert_eq!(&dst[..], b"-ERR unknown command\r\n");
+        encoder.encode_error(&mut dst, &RedisError::Syntax);
+        assert_eq!(&dst[..], b"-ERR syntax error\r\n");
+        dst.clear();
+        encoder.encode_error(&mut dst, &RedisError::WrongType);
+        assert_eq!(&dst[..], b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n");
+    }
+
+    #[test]
+    fn test_encode_line_strips_line_breaks() {
+        // 에러나 상태 응답에 CRLF 를 넣어 가짜 응답을 끼워 넣을 수 없다
+        let encoder = RedisEncoder::new();
+        let mut dst = BytesMut::new();
+        let err = RedisError::UnknownCommand("foo\r\n+OK\r\n".to_string(), vec!["a\nb".to_string()]);
+        encoder.encode_error(&mut dst, &err);
+        assert_eq!(&dst[..], b"-ERR unknown command 'foo  +OK  ', with args beginning with: 'a b' \r\n");
+
+        dst.clear();
+        encoder.encode_simple_string(&mut dst, "x\r\n-ERR y");
+        assert_eq!(&dst[..], b"+x  -ERR y\r\n");
     }
 
     #[test]
@@ -85,4 +103,54 @@ mod tests {
         encoder.encode_array(&mut dst, &items);
         assert_eq!(&dst[..], b"*1\r\n$6\r\nsingle\r\n");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_encode_integer_and_simple_string() {
+        let encoder = RedisEncoder::new();
+        let mut dst = BytesMut::new();
+        encoder.encode_integer(&mut dst, 1700000000);
+        assert_eq!(&dst[..], b":1700000000\r\n");
+
+        dst.clear();
+        encoder.encode_simple_string(&mut dst, "Background saving started");
+        assert_eq!(&dst[..], b"+Background saving started\r\n");
+
+        dst.clear();
+        encoder.encode_error(&mut dst, &RedisError::Err("Background save already in progress".to_string()));
+        assert_eq!(&dst[..], b"-ERR Background save already in progress\r\n");
+    }
+
+    #[test]
+    fn test_encode_integer_array() {
+        let encoder = RedisEncoder::new();
+        let mut dst = BytesMut::new();
+        encoder.encode_integer_array(&mut dst, &[1, 0]);
+        assert_eq!(&dst[..], b"*2\r\n:1\r\n:0\r\n");
+    }
+
+    #[test]
+    fn test_encode_resp3_types() {
+        let encoder = RedisEncoder::with_resp3(true);
+        let mut dst = BytesMut::new();
+        encoder.encode_null(&mut dst);
+        encoder.encode_null_array(&mut dst);
+        encoder.encode_map(&mut dst, &[("k", "v")]);
+        encoder.encode_push_len(&mut dst, 3);
+        assert_eq!(&dst[..], b"_\r\n_\r\n%1\r\n$1\r\nk\r\n$1\r\nv\r\n>3\r\n");
+
+        dst.clear();
+        encoder.encode_verbatim_string(&mut dst, "txt", "Some string");
+        assert_eq!(&dst[..], b"=15\r\ntxt:Some string\r\n");
+    }
+
+    #[test]
+    fn test_encode_resp3_types_for_resp2_clients() {
+        // RESP2 클라이언트에게는 가까운 RESP2 타입으로 보낸다
+        let encoder = RedisEncoder::new();
+        let mut dst = BytesMut::new();
+        encoder.encode_map(&mut dst, &[("k", "v")]);
+        encoder.encode_push_len(&mut dst, 3);
+        encoder.encode_verbatim_string(&mut dst, "txt", "ok");
+        assert_eq!(&dst[..], b"*2\r\n$1\r\nk\r\n$1\r\nv\r\n*3\r\n$2\r\nok\r\n");
+    }
+}
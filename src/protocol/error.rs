@@ -0,0 +1,142 @@
+use std::fmt;
+
+/// 클라이언트에게 보내는 에러 응답.
+/// 메시지의 첫 단어 (ERR, WRONGTYPE, MOVED, ...) 가 에러 종류이고, 클라이언트 라이브러리는 이것으로 에러를 구별한다.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedisError {
+    /// 그 밖의 일반 에러. 메시지에 ERR 은 붙이지 않는다.
+    Err(String),
+    /// 알 수 없는 명령 (이름, 인자)
+    UnknownCommand(String, Vec<String>),
+    /// 인자 수가 틀렸다 (소문자 명령 이름)
+    WrongArity(String),
+    /// 하위 명령을 모르거나 인자 수가 틀렸다 (소문자 하위 명령 이름)
+    UnknownSubcommand(String),
+    /// 명령의 옵션을 해석할 수 없다
+    Syntax,
+    NotInteger,
+    /// RESP 형식이 아니다
+    Protocol(String),
+    /// 키에 다른 타입의 값이 들어 있다
+    WrongType,
+    /// EVALSHA 의 스크립트가 캐시에 없다
+    NoScript,
+    /// read only replica 에 쓰려고 했다
+    ReadOnly,
+    /// replica-serve-stale-data no 인 replica 가 master 와 끊겼다
+    MasterDown,
+    /// 스크립트가 busy-script-time 을 넘겨 실행 중이다
+    Busy,
+    /// RESTORE 대상 키가 이미 있다
+    BusyKey,
+    /// 슬롯의 주인이 다른 노드다 (슬롯, 주소)
+    Moved(u16, String),
+    /// 슬롯을 옮기는 중이고 키는 옮겨 간 노드에 있다 (슬롯, 주소)
+    Ask(u16, String),
+    CrossSlot,
+    TryAgain,
+    ClusterDown(String),
+    /// 인증하지 않았다
+    NoAuth,
+    WrongPass,
+    /// maxmemory 를 넘었다
+    Oom,
+    /// EXEC 가 트랜잭션을 버렸다. 쌓을 때 거부된 명령이 있었으면 None, EXEC 때 거부됐으면 그 에러.
+    ExecAbort(Option<Box<RedisError>>),
+    /// HELLO 의 프로토콜 버전을 지원하지 않는다
+    NoProto,
+    /// MIGRATE 대상과 통신하지 못했다
+    IoErr(String),
+    /// 스크립트 엔진이 만든 에러 메시지 그대로. redis.error_reply 처럼 종류도 메시지 안에 들어 있다.
+    Script(String),
+}
+
+/// 알 수 없는 명령의 에러 메시지에 넣는 인자의 최대 길이
+const ARGS_PREVIEW_LEN: usize = 128;
+
+fn truncate(s: &str) -> &str {
+    match s.char_indices().nth(ARGS_PREVIEW_LEN) {
+        Some((end, _)) => &s[..end],
+        None => s,
+    }
+}
+
+impl RedisError {
+    /// 에러 종류 (메시지의 첫 단어)
+    pub fn kind(&self) -> &str {
+        match self {
+            RedisError::Err(_)
+            | RedisError::UnknownCommand(..)
+            | RedisError::WrongArity(_)
+            | RedisError::UnknownSubcommand(_)
+            | RedisError::Syntax
+            | RedisError::NotInteger
+            | RedisError::Protocol(_) => "ERR",
+            RedisError::WrongType => "WRONGTYPE",
+            RedisError::NoScript => "NOSCRIPT",
+            RedisError::ReadOnly => "READONLY",
+            RedisError::MasterDown => "MASTERDOWN",
+            RedisError::Busy => "BUSY",
+            RedisError::BusyKey => "BUSYKEY",
+            RedisError::Moved(..) => "MOVED",
+            RedisError::Ask(..) => "ASK",
+            RedisError::CrossSlot => "CROSSSLOT",
+            RedisError::TryAgain => "TRYAGAIN",
+            RedisError::ClusterDown(_) => "CLUSTERDOWN",
+            RedisError::NoAuth => "NOAUTH",
+            RedisError::WrongPass => "WRONGPASS",
+            RedisError::Oom => "OOM",
+            RedisError::ExecAbort(_) => "EXECABORT",
+            RedisError::NoProto => "NOPROTO",
+            RedisError::IoErr(_) => "IOERR",
+            RedisError::Script(msg) => msg.split(' ').next().unwrap_or_default(),
+        }
+    }
+}
+
+impl fmt::Display for RedisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let RedisError::Script(msg) = self {
+            return write!(f, "{}", msg);
+        }
+        write!(f, "{} ", self.kind())?;
+        match self {
+            RedisError::Err(msg) | RedisError::ClusterDown(msg) | RedisError::IoErr(msg) => write!(f, "{}", msg),
+            RedisError::UnknownCommand(name, args) => {
+                // Redis 처럼 인자는 앞쪽 128 글자 정도만 보여준다
+                write!(f, "unknown command '{}', with args beginning with: ", truncate(name))?;
+                let mut len = 0;
+                for arg in args.iter().take_while(|arg| {
+                    len += arg.len();
+                    len - arg.len() < ARGS_PREVIEW_LEN
+                }) {
+                    write!(f, "'{}' ", truncate(arg))?;
+                }
+                Ok(())
+            }
+            RedisError::WrongArity(name) => write!(f, "wrong number of arguments for '{}' command", name),
+            RedisError::UnknownSubcommand(name) => write!(f, "unknown subcommand or wrong number of arguments for '{}'", name),
+            RedisError::Syntax => write!(f, "syntax error"),
+            RedisError::NotInteger => write!(f, "value is not an integer or out of range"),
+            RedisError::Protocol(msg) => write!(f, "Protocol error: {}", msg),
+            RedisError::WrongType => write!(f, "Operation against a key holding the wrong kind of value"),
+            RedisError::NoScript => write!(f, "No matching script. Please use EVAL."),
+            RedisError::ReadOnly => write!(f, "You can't write against a read only replica."),
+            RedisError::MasterDown => write!(f, "Link with MASTER is down and replica-serve-stale-data is set to 'no'."),
+            RedisError::Busy => write!(f, "Redis is busy running a script. You can only call SCRIPT KILL or FUNCTION KILL or SHUTDOWN NOSAVE."),
+            RedisError::BusyKey => write!(f, "Target key name already exists."),
+            RedisError::Moved(slot, addr) | RedisError::Ask(slot, addr) => write!(f, "{} {}", slot, addr),
+            RedisError::CrossSlot => write!(f, "Keys in request don't hash to the same slot"),
+            RedisError::TryAgain => write!(f, "Multiple keys request during rehashing of slot"),
+            RedisError::NoAuth => write!(f, "Authentication required."),
+            RedisError::WrongPass => write!(f, "invalid username-password pair or user is disabled."),
+            RedisError::Oom => write!(f, "command not allowed when used memory > 'maxmemory'."),
+            RedisError::ExecAbort(None) => write!(f, "Transaction discarded because of previous errors."),
+            RedisError::ExecAbort(Some(err)) => write!(f, "Transaction discarded because of: {}", err),
+            RedisError::NoProto => write!(f, "unsupported protocol version"),
+            RedisError::Script(_) => unreachable!(),
+        }
+    }
+}
+
+impl std::error::Error for RedisError {}
@@ -0,0 +1,47 @@
+#[cfg(test)]
+mod tests {
+    use crate::protocol::error::RedisError;
+
+    #[test]
+    fn test_kind_is_first_word() {
+        let errors = [
+            RedisError::Syntax,
+            RedisError::WrongType,
+            RedisError::Moved(3999, "127.0.0.1:6381".to_string()),
+            RedisError::ExecAbort(None),
+            RedisError::NoProto,
+            RedisError::IoErr("timeout".to_string()),
+        ];
+        for err in errors {
+            assert!(err.to_string().starts_with(&format!("{} ", err.kind())));
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            RedisError::UnknownCommand("FOO".to_string(), vec!["a".to_string(), "b".to_string()]).to_string(),
+            "ERR unknown command 'FOO', with args beginning with: 'a' 'b' "
+        );
+        assert_eq!(RedisError::WrongArity("get".to_string()).to_string(), "ERR wrong number of arguments for 'get' command");
+        assert_eq!(RedisError::Moved(3999, "127.0.0.1:6381".to_string()).to_string(), "MOVED 3999 127.0.0.1:6381");
+        assert_eq!(RedisError::Err("custom".to_string()).to_string(), "ERR custom");
+        // 스크립트 에러는 메시지를 그대로 쓰고 종류는 첫 단어
+        let script = RedisError::Script("NOTBUSY No scripts in execution right now.".to_string());
+        assert_eq!(script.to_string(), "NOTBUSY No scripts in execution right now.");
+        assert_eq!(script.kind(), "NOTBUSY");
+        assert_eq!(
+            RedisError::ExecAbort(Some(Box::new(RedisError::CrossSlot))).to_string(),
+            "EXECABORT Transaction discarded because of: CROSSSLOT Keys in request don't hash to the same slot"
+        );
+    }
+
+    #[test]
+    fn test_unknown_command_args_are_truncated() {
+        let long = "x".repeat(300);
+        let msg = RedisError::UnknownCommand("FOO".to_string(), vec![long.clone(), "next".to_string()]).to_string();
+        assert!(msg.contains(&format!("'{}' ", "x".repeat(128))));
+        assert!(!msg.contains(&"x".repeat(129)));
+        assert!(!msg.contains("next"));
+    }
+}
@@ -0,0 +1,288 @@
+use crate::pattern_parser::glob_match;
+use crate::protocol::encoder::RedisEncoder;
+use bytes::{Bytes, BytesMut};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// 구독한 커넥션으로 메시지를 보내는 통로. 커넥션은 받은 프레임을 그대로 소켓에 쓴다.
+pub type Subscriber = UnboundedSender<Bytes>;
+
+/// 구독 종류
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// SUBSCRIBE
+    Channel,
+    /// PSUBSCRIBE
+    Pattern,
+    /// SSUBSCRIBE: 클러스터에서 채널이 해시 슬롯에 속한다
+    Shard,
+}
+
+impl Kind {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// 커넥션 하나의 구독 상태
+#[derive(Debug)]
+struct Client {
+    tx: Subscriber,
+    /// 종류별 구독한 채널 (또는 패턴). UNSUBSCRIBE 를 인자 없이 보내면 이 순서대로 응답한다.
+    subscriptions: [BTreeSet<String>; 3],
+}
+
+/// 연결된 클라이언트 커넥션
+#[derive(Debug)]
+struct Connection {
+    tx: Subscriber,
+    /// HELLO 3: 메시지를 push 타입으로 보낸다
+    resp3: bool,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    /// 종류별 채널 (또는 패턴) → 구독한 클라이언트 ID
+    subscribers: [HashMap<String, HashSet<u64>>; 3],
+    clients: HashMap<u64, Client>,
+    /// 구독 여부와 상관없이 연결된 모든 클라이언트
+    connections: HashMap<u64, Connection>,
+}
+
+impl Registry {
+    fn is_resp3(&self, id: u64) -> bool {
+        self.connections.get(&id).is_some_and(|connection| connection.resp3)
+    }
+}
+
+/// 구독자에게 보낼 메시지 프레임. RESP2 는 배열, RESP3 는 push.
+fn message_frames(parts: &[&str]) -> [Bytes; 2] {
+    [false, true].map(|resp3| {
+        let encoder = RedisEncoder::with_resp3(resp3);
+        let mut frame = BytesMut::new();
+        encoder.encode_push_len(&mut frame, parts.len());
+        for part in parts {
+            encoder.encode_bulk_string(&mut frame, part);
+        }
+        frame.freeze()
+    })
+}
+
+/// 모든 커넥션이 공유하는 채널 레지스트리
+#[derive(Debug, Default)]
+pub struct PubSub {
+    next_client_id: AtomicU64,
+    registry: Mutex<Registry>,
+}
+
+impl PubSub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 커넥션마다 다른 클라이언트 ID
+    pub fn next_client_id(&self) -> u64 {
+        self.next_client_id.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// 클라이언트 커넥션이 열렸다
+    pub fn connect(&self, id: u64, tx: &Subscriber) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.connections.insert(id, Connection { tx: tx.clone(), resp3: false });
+    }
+
+    /// 커넥션이 끊겼다. 모든 구독도 지운다.
+    pub fn disconnect(&self, id: u64) {
+        self.remove_client(id);
+        self.registry.lock().unwrap().connections.remove(&id);
+    }
+
+    pub fn is_connected(&self, id: u64) -> bool {
+        self.registry.lock().unwrap().connections.contains_key(&id)
+    }
+
+    /// HELLO 로 프로토콜을 바꿨다
+    pub fn set_resp3(&self, id: u64, resp3: bool) {
+        if let Some(connection) = self.registry.lock().unwrap().connections.get_mut(&id) {
+            connection.resp3 = resp3;
+        }
+    }
+
+    pub fn is_resp3(&self, id: u64) -> bool {
+        self.registry.lock().unwrap().is_resp3(id)
+    }
+
+    /// 구독을 추가하고 이 클라이언트의 전체 구독 수를 돌려준다
+    pub fn subscribe(&self, id: u64, tx: &Subscriber, kind: Kind, name: &str) -> usize {
+        let mut registry = self.registry.lock().unwrap();
+        registry.subscribers[kind.index()].entry(name.to_string()).or_default().insert(id);
+        let client = registry.clients.entry(id).or_insert_with(|| Client { tx: tx.clone(), subscriptions: Default::default() });
+        client.subscriptions[kind.index()].insert(name.to_string());
+        client.subscriptions.iter().map(|s| s.len()).sum()
+    }
+
+    /// 구독을 지우고 이 클라이언트의 남은 구독 수를 돌려준다
+    pub fn unsubscribe(&self, id: u64, kind: Kind, name: &str) -> usize {
+        let mut registry = self.registry.lock().unwrap();
+        let subscribers = &mut registry.subscribers[kind.index()];
+        if let Some(ids) = subscribers.get_mut(name) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                subscribers.remove(name);
+            }
+        }
+        let Some(client) = registry.clients.get_mut(&id) else {
+            return 0;
+        };
+        client.subscriptions[kind.index()].remove(name);
+        let count = client.subscriptions.iter().map(|s| s.len()).sum();
+        if count == 0 {
+            registry.clients.remove(&id);
+        }
+        count
+    }
+
+    /// 이 클라이언트가 구독한 채널 (또는 패턴)
+    pub fn subscriptions(&self, id: u64, kind: Kind) -> Vec<String> {
+        let registry = self.registry.lock().unwrap();
+        registry.clients.get(&id).map(|c| c.subscriptions[kind.index()].iter().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn is_subscribed(&self, id: u64, kind: Kind, name: &str) -> bool {
+        let registry = self.registry.lock().unwrap();
+        registry.clients.get(&id).is_some_and(|c| c.subscriptions[kind.index()].contains(name))
+    }
+
+    /// 이 클라이언트의 전체 구독 수. 0 보다 크면 커넥션은 구독 모드다.
+    pub fn subscription_count(&self, id: u64) -> usize {
+        let registry = self.registry.lock().unwrap();
+        registry.clients.get(&id).map_or(0, |c| c.subscriptions.iter().map(|s| s.len()).sum())
+    }
+
+    /// 모든 구독을 지운다 (RESET, 커넥션 종료)
+    pub fn remove_client(&self, id: u64) {
+        let mut registry = self.registry.lock().unwrap();
+        let Some(client) = registry.clients.remove(&id) else {
+            return;
+        };
+        for (kind, names) in client.subscriptions.iter().enumerate() {
+            for name in names {
+                let subscribers = &mut registry.subscribers[kind];
+                if let Some(ids) = subscribers.get_mut(name) {
+                    ids.remove(&id);
+                    if ids.is_empty() {
+                        subscribers.remove(name);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 채널 구독자와 채널 이름에 맞는 패턴 구독자에게 메시지를 보내고, 받은 클라이언트 수를 돌려준다
+    pub fn publish(&self, channel: &str, message: &str) -> usize {
+        let registry = self.registry.lock().unwrap();
+        let mut receivers = 0;
+
+        if let Some(ids) = registry.subscribers[Kind::Channel.index()].get(channel) {
+            let frames = message_frames(&["message", channel, message]);
+            for id in ids {
+                if let Some(client) = registry.clients.get(id) {
+                    // 받는 커넥션이 이미 끊겼으면 그 커넥션이 정리할 때 구독이 지워진다
+                    let _ = client.tx.send(frames[registry.is_resp3(*id) as usize].clone());
+                    receivers += 1;
+                }
+            }
+        }
+
+        for (pattern, ids) in &registry.subscribers[Kind::Pattern.index()] {
+            if !glob_match(pattern.as_bytes(), channel.as_bytes()) {
+                continue;
+            }
+            let frames = message_frames(&["pmessage", pattern, channel, message]);
+            for id in ids {
+                if let Some(client) = registry.clients.get(id) {
+                    let _ = client.tx.send(frames[registry.is_resp3(*id) as usize].clone());
+                    receivers += 1;
+                }
+            }
+        }
+        receivers
+    }
+
+    /// 연결된 클라이언트 하나에게 프레임을 보낸다 (CLIENT TRACKING 의 무효화 메시지)
+    pub fn send_to(&self, id: u64, frame: Bytes) -> bool {
+        let registry = self.registry.lock().unwrap();
+        match registry.connections.get(&id) {
+            Some(connection) => connection.tx.send(frame).is_ok(),
+            None => false,
+        }
+    }
+
+    /// SPUBLISH: shard 채널 구독자에게만 보낸다
+    pub fn publish_shard(&self, channel: &str, message: &str) -> usize {
+        let registry = self.registry.lock().unwrap();
+        let Some(ids) = registry.subscribers[Kind::Shard.index()].get(channel) else {
+            return 0;
+        };
+        let frames = message_frames(&["smessage", channel, message]);
+        let mut receivers = 0;
+        for id in ids {
+            if let Some(client) = registry.clients.get(id) {
+                let _ = client.tx.send(frames[registry.is_resp3(*id) as usize].clone());
+                receivers += 1;
+            }
+        }
+        receivers
+    }
+
+    /// 이 노드가 더 이상 서비스하지 않는 슬롯의 shard 채널 구독을 모두 풀고, 구독자에게 sunsubscribe 를 보낸다
+    pub fn remove_shard_channels(&self, lost: impl Fn(&str) -> bool) {
+        let mut registry = self.registry.lock().unwrap();
+        let channels: Vec<String> = registry.subscribers[Kind::Shard.index()].keys().filter(|c| lost(c)).cloned().collect();
+        for channel in channels {
+            let ids = registry.subscribers[Kind::Shard.index()].remove(&channel).unwrap_or_default();
+            for id in ids {
+                let encoder = RedisEncoder::with_resp3(registry.is_resp3(id));
+                let Some(client) = registry.clients.get_mut(&id) else {
+                    continue;
+                };
+                client.subscriptions[Kind::Shard.index()].remove(&channel);
+                let count: usize = client.subscriptions.iter().map(|s| s.len()).sum();
+                let mut frame = BytesMut::new();
+                encoder.encode_push_len(&mut frame, 3);
+                encoder.encode_bulk_string(&mut frame, "sunsubscribe");
+                encoder.encode_bulk_string(&mut frame, &channel);
+                encoder.encode_integer(&mut frame, count as i64);
+                let _ = client.tx.send(frame.freeze());
+                if count == 0 {
+                    registry.clients.remove(&id);
+                }
+            }
+        }
+    }
+
+    /// PUBSUB CHANNELS / SHARDCHANNELS: 구독자가 있는 채널 (패턴이 있으면 맞는 것만)
+    pub fn channels(&self, kind: Kind, pattern: Option<&str>) -> Vec<String> {
+        let registry = self.registry.lock().unwrap();
+        let mut channels: Vec<String> = registry.subscribers[kind.index()]
+            .keys()
+            .filter(|channel| pattern.is_none_or(|p| glob_match(p.as_bytes(), channel.as_bytes())))
+            .cloned()
+            .collect();
+        channels.sort();
+        channels
+    }
+
+    /// PUBSUB NUMSUB / SHARDNUMSUB: 채널 구독자 수
+    pub fn numsub(&self, kind: Kind, channel: &str) -> usize {
+        let registry = self.registry.lock().unwrap();
+        registry.subscribers[kind.index()].get(channel).map_or(0, |ids| ids.len())
+    }
+
+    /// PUBSUB NUMPAT: 구독 중인 패턴 수
+    pub fn numpat(&self) -> usize {
+        self.registry.lock().unwrap().subscribers[Kind::Pattern.index()].len()
+    }
+}
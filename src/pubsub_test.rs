@@ -0,0 +1,87 @@
+use crate::pubsub::{Kind, PubSub};
+use bytes::Bytes;
+use tokio::sync::mpsc;
+use tokio::test;
+
+fn frame(parts: &[&str]) -> Bytes {
+    let mut frame = format!("*{}\r\n", parts.len());
+    for part in parts {
+        frame.push_str(&format!("${}\r\n{}\r\n", part.len(), part));
+    }
+    Bytes::from(frame)
+}
+
+#[test]
+async fn test_publish_to_channels_and_patterns() {
+    let pubsub = PubSub::new();
+    let (tx1, mut rx1) = mpsc::unbounded_channel();
+    let (tx2, mut rx2) = mpsc::unbounded_channel();
+    let (a, b) = (pubsub.next_client_id(), pubsub.next_client_id());
+    assert_ne!(a, b);
+
+    assert_eq!(pubsub.subscribe(a, &tx1, Kind::Channel, "news"), 1);
+    assert_eq!(pubsub.subscribe(a, &tx1, Kind::Pattern, "news.*"), 2);
+    assert_eq!(pubsub.subscribe(b, &tx2, Kind::Pattern, "n*"), 1);
+
+    // 채널 구독자 1 + 맞는 패턴 구독자 1
+    assert_eq!(pubsub.publish("news", "hi"), 2);
+    assert_eq!(rx1.try_recv().unwrap(), frame(&["message", "news", "hi"]));
+    assert_eq!(rx2.try_recv().unwrap(), frame(&["pmessage", "n*", "news", "hi"]));
+    assert!(rx1.try_recv().is_err());
+
+    assert_eq!(pubsub.publish("news.art", "x"), 2);
+    assert_eq!(pubsub.publish("other", "x"), 0);
+
+    assert_eq!(pubsub.channels(Kind::Channel, None), vec!["news"]);
+    assert!(pubsub.channels(Kind::Channel, Some("x*")).is_empty());
+    assert_eq!(pubsub.numsub(Kind::Channel, "news"), 1);
+    assert_eq!(pubsub.numpat(), 2);
+}
+
+#[test]
+async fn test_unsubscribe_and_remove_client() {
+    let pubsub = PubSub::new();
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let id = pubsub.next_client_id();
+    pubsub.subscribe(id, &tx, Kind::Channel, "b");
+    pubsub.subscribe(id, &tx, Kind::Channel, "a");
+    // 같은 채널을 다시 구독해도 한 번으로 센다
+    assert_eq!(pubsub.subscribe(id, &tx, Kind::Channel, "a"), 2);
+    assert_eq!(pubsub.subscriptions(id, Kind::Channel), vec!["a", "b"]);
+
+    assert_eq!(pubsub.unsubscribe(id, Kind::Channel, "a"), 1);
+    assert_eq!(pubsub.unsubscribe(id, Kind::Channel, "missing"), 1);
+    assert_eq!(pubsub.numsub(Kind::Channel, "a"), 0);
+    assert_eq!(pubsub.subscription_count(id), 1);
+
+    // 커넥션이 끊기면 남은 구독도 지운다
+    pubsub.subscribe(id, &tx, Kind::Pattern, "*");
+    pubsub.remove_client(id);
+    assert_eq!(pubsub.subscription_count(id), 0);
+    assert!(pubsub.channels(Kind::Channel, None).is_empty());
+    assert_eq!(pubsub.numpat(), 0);
+}
+
+#[test]
+async fn test_shard_channels() {
+    let pubsub = PubSub::new();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let id = pubsub.next_client_id();
+    pubsub.subscribe(id, &tx, Kind::Channel, "orders");
+    pubsub.subscribe(id, &tx, Kind::Shard, "orders");
+    assert_eq!(pubsub.subscribe(id, &tx, Kind::Shard, "users"), 3);
+
+    // shard 채널과 일반 채널은 따로 센다
+    assert_eq!(pubsub.publish_shard("orders", "x"), 1);
+    assert_eq!(rx.try_recv().unwrap(), frame(&["smessage", "orders", "x"]));
+    assert_eq!(pubsub.publish("orders", "y"), 1);
+    assert_eq!(rx.try_recv().unwrap(), frame(&["message", "orders", "y"]));
+    assert_eq!(pubsub.channels(Kind::Shard, None), vec!["orders", "users"]);
+    assert_eq!(pubsub.numsub(Kind::Shard, "users"), 1);
+
+    // 슬롯이 다른 노드로 옮겨 가면 구독을 풀고 알린다
+    pubsub.remove_shard_channels(|channel| channel == "users");
+    assert_eq!(rx.try_recv().unwrap(), Bytes::from("*3\r\n$12\r\nsunsubscribe\r\n$5\r\nusers\r\n:2\r\n"));
+    assert_eq!(pubsub.channels(Kind::Shard, None), vec!["orders"]);
+    assert_eq!(pubsub.subscription_count(id), 2);
+}
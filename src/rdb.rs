@@ -1,10 +1,30 @@
-use crate::store::Store;
-use crc::{Crc, CRC_64_MS};
-use std::fs::File;
+use crate::scripting::library_name;
+use crate::store::{Snapshot, Store};
+use crc::{Crc, CRC_64_MS, CRC_64_REDIS};
+use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::Path;
+use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Redis 와 같은 CRC-64/Jones 체크섬 (little-endian 으로 기록)
+const RDB_CRC: Crc<u64> = Crc::<u64>::new(&CRC_64_REDIS);
+
+/// 이전 버전이 쓰던 CRC-64/MS (big-endian 으로 기록). 그때 저장한 파일을 읽을 수 있도록 RDB 파일을 로드할 때만 받아준다 (DUMP 페이로드는 받지 않는다).
+const LEGACY_RDB_CRC: Crc<u64> = Crc::<u64>::new(&CRC_64_MS);
+
+/// RDB 파일과 DUMP 페이로드의 버전
+const RDB_VERSION: u16 = 11;
+
+/// 문자열 값 타입 마커
+const RDB_TYPE_STRING: u8 = 0x00;
+
+/// 함수 라이브러리 코드 (RDB_OPCODE_FUNCTION2)
+const RDB_OPCODE_FUNCTION: u8 = 0xF5;
+
+/// 같은 프로세스에서 temp-<pid>.rdb 를 동시에 쓰지 않도록 RDB 쓰기를 직렬화
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
 #[derive(Debug)]
 pub struct RDB;
 
@@ -18,60 +38,203 @@ impl RDB {
             let second_byte = (value & 0xFF) as u8;
             buffer.push(first_byte);
             buffer.push(second_byte);
-        } else { // 32 bits (10xxxxxx + 4 bytes)
+        } else if value <= u32::MAX as usize { // 32 bits (10000000 + 4 bytes)
             buffer.push(0x80); // 10000000
             buffer.extend_from_slice(&(value as u32).to_be_bytes());
+        } else { // 64 bits (10000001 + 8 bytes)
+            buffer.push(0x81);
+            buffer.extend_from_slice(&(value as u64).to_be_bytes());
         }
     }
 
-    // 현재는 값을 사용하지않고 버퍼에서 건너뛰기만 하고있음
-    pub fn length_decode_int(pos: &mut usize, buffer: &Vec<u8>) -> usize {
-        match buffer[*pos] >> 6 {
-            0 => {
-                // next 6 bits is string length
-                let num = (buffer[*pos] & 0b001111) as usize;
-                *pos += 1;
-                return num;
-            }
+    /// 길이 인코딩된 정수를 읽는다. 특수 인코딩 (11xxxxxx) 이면 (인코딩 종류, true) 를 돌려준다.
+    fn read_length(pos: &mut usize, buffer: &[u8]) -> io::Result<(usize, bool)> {
+        let first = Self::take(pos, buffer, 1)?[0];
+        let num = (first & 0x3F) as usize;
+        match first >> 6 {
+            0 => Ok((num, false)),
             1 => {
                 // read one additional byte. combined 14bits is string length
-                let num = ((buffer[*pos] as usize) << 8 | buffer[*pos + 1] as usize) & 0x3FFF;
-                *pos += 2;
-                return num;
+                let next = Self::take(pos, buffer, 1)?[0] as usize;
+                Ok((num << 8 | next, false))
             }
-            2 => {
-                // discard current 6bits. The next 4 bytes the stream represent the length
-                *pos += 5;
-            }
-            // 3
-            _ => {
-                // next 6bits
-                let num = (buffer[*pos] & 0b001111) as usize;
-                match num {
-                    0 => {
-                        // 0 is next 8bit integer
-                        *pos += 1
-                    }
-                    1 => {
-                        // 1 is next 16bit integer
-                        *pos += 2
-                    }
-                    _ => {
-                        // 2 is next 32bit integer
-                        *pos += 3
-                    }
-                }
+            2 => match first {
+                0x80 => Ok((u32::from_be_bytes(Self::take(pos, buffer, 4)?.try_into().unwrap()) as usize, false)),
+                0x81 => Ok((u64::from_be_bytes(Self::take(pos, buffer, 8)?.try_into().unwrap()) as usize, false)),
+                _ => Err(Self::invalid("Unknown length encoding")),
+            },
+            // 3: 뒤따르는 문자열이 특수 인코딩되어 있다
+            _ => Ok((num, true)),
+        }
+    }
+
+    /// 길이 인코딩된 정수를 읽는다 (resizedb 크기, DB 번호 등).
+    pub fn length_decode_int(pos: &mut usize, buffer: &[u8]) -> io::Result<usize> {
+        match Self::read_length(pos, buffer)? {
+            (len, false) => Ok(len),
+            (_, true) => Err(Self::invalid("Unexpected special encoding")),
+        }
+    }
+
+    /// 길이 + 내용으로 문자열을 쓴다
+    pub fn encode_string(s: &str, buffer: &mut Vec<u8>) {
+        Self::length_encode_int(s.len(), buffer);
+        buffer.extend_from_slice(s.as_bytes());
+    }
+
+    /// 문자열을 읽는다. 정수로 인코딩된 문자열 (0xC0 ~ 0xC2) 도 읽는다.
+    pub fn decode_string(pos: &mut usize, buffer: &[u8]) -> io::Result<String> {
+        match Self::read_length(pos, buffer)? {
+            (len, false) => Ok(String::from_utf8_lossy(Self::take(pos, buffer, len)?).to_string()),
+            (0, true) => Ok((Self::take(pos, buffer, 1)?[0] as i8).to_string()),
+            (1, true) => Ok(i16::from_le_bytes(Self::take(pos, buffer, 2)?.try_into().unwrap()).to_string()),
+            (2, true) => Ok(i32::from_le_bytes(Self::take(pos, buffer, 4)?.try_into().unwrap()).to_string()),
+            _ => Err(Self::invalid("Unsupported string encoding")),
+        }
+    }
+
+    /// 값 하나를 타입 마커와 함께 쓴다 (RDB 의 키-값 쌍과 DUMP 페이로드가 같은 형식을 쓴다)
+    pub fn encode_value(value: &str, buffer: &mut Vec<u8>) {
+        buffer.push(RDB_TYPE_STRING);
+        Self::encode_string(value, buffer);
+    }
+
+    /// 타입 마커로 시작하는 값 하나를 읽는다
+    pub fn decode_value(pos: &mut usize, buffer: &[u8]) -> io::Result<String> {
+        match Self::take(pos, buffer, 1)?[0] {
+            RDB_TYPE_STRING => Self::decode_string(pos, buffer),
+            _ => Err(Self::invalid("Unsupported value type")),
+        }
+    }
+
+    /// DUMP 페이로드: 값, 2바이트 RDB 버전, CRC64 (모두 little-endian)
+    pub fn dump_value(value: &str) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        Self::encode_value(value, &mut buffer);
+        buffer.extend_from_slice(&RDB_VERSION.to_le_bytes());
+        let checksum = RDB_CRC.checksum(&buffer);
+        buffer.extend_from_slice(&checksum.to_le_bytes());
+        buffer
+    }
+
+    /// DUMP 페이로드를 검사하고 값을 읽는다. 더 새로운 RDB 버전이나 체크섬이 틀린 페이로드는 거부한다.
+    pub fn restore_value(payload: &[u8]) -> io::Result<String> {
+        let body = Self::payload_body(payload)?;
+        let mut pos = 0;
+        let value = Self::decode_value(&mut pos, body)?;
+        if pos != body.len() {
+            return Err(Self::invalid("Trailing bytes in DUMP payload"));
+        }
+        Ok(value)
+    }
+
+    /// FUNCTION DUMP 페이로드: 라이브러리마다 함수 opcode 와 코드, 그 뒤는 DUMP 와 같다
+    pub fn dump_functions(codes: &[String]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        for code in codes {
+            buffer.push(RDB_OPCODE_FUNCTION);
+            Self::encode_string(code, &mut buffer);
+        }
+        buffer.extend_from_slice(&RDB_VERSION.to_le_bytes());
+        let checksum = RDB_CRC.checksum(&buffer);
+        buffer.extend_from_slice(&checksum.to_le_bytes());
+        buffer
+    }
+
+    /// FUNCTION RESTORE 페이로드를 검사하고 라이브러리 코드들을 읽는다
+    pub fn restore_functions(payload: &[u8]) -> io::Result<Vec<String>> {
+        let body = Self::payload_body(payload)?;
+        let mut pos = 0;
+        let mut codes = Vec::new();
+        while pos < body.len() {
+            if Self::take(&mut pos, body, 1)?[0] != RDB_OPCODE_FUNCTION {
+                return Err(Self::invalid("given type is not a function"));
             }
+            codes.push(Self::decode_string(&mut pos, body)?);
+        }
+        Ok(codes)
+    }
+
+    /// 페이로드 끝의 RDB 버전과 체크섬을 검사하고 나머지 부분을 돌려준다
+    fn payload_body(payload: &[u8]) -> io::Result<&[u8]> {
+        if payload.len() < 10 {
+            return Err(Self::invalid("DUMP payload is too short"));
+        }
+        let (body, footer) = payload.split_at(payload.len() - 10);
+        let version = u16::from_le_bytes([footer[0], footer[1]]);
+        if version > RDB_VERSION {
+            return Err(Self::invalid("DUMP payload has a newer RDB version"));
         }
-        panic!("FA decode fail");
+        // 파일과 달리 체크섬 0 이나 이전 형식의 체크섬은 받지 않는다
+        Self::verify_checksum(&payload[..payload.len() - 8], &footer[2..])?;
+        Ok(body)
+    }
+
+    /// 버퍼에서 `len` 바이트를 읽는다. 모자라면 잘린 파일로 본다.
+    fn take<'a>(pos: &mut usize, buffer: &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+        let end = pos.checked_add(len).filter(|end| *end <= buffer.len()).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "RDB data is truncated")
+        })?;
+        let bytes = &buffer[*pos..end];
+        *pos = end;
+        Ok(bytes)
+    }
+
+    fn invalid(msg: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
     }
 
-    pub async fn create_rdb<P: AsRef<Path> + std::fmt::Debug>(
+    pub async fn create_rdb<P: AsRef<Path>>(
         path: P,
         stores: Option<&[&Store]>,
     ) -> io::Result<()> {
+        let mut snapshots = Vec::new();
+        if let Some(stores) = stores {
+            for store in stores {
+                snapshots.push(store.snapshot().await);
+            }
+        }
+
+        Self::write_snapshots(path, &snapshots)
+    }
+
+    /// 스냅샷을 RDB 포맷으로 직렬화해 파일에 쓴다.
+    /// Store 락을 잡지 않으므로 BGSAVE 처럼 백그라운드 태스크에서 호출할 수 있다.
+    pub fn write_snapshots<P: AsRef<Path>>(path: P, snapshots: &[Snapshot]) -> io::Result<()> {
+        let buffer = Self::encode_snapshots(snapshots);
+        Self::write_atomic(path, &buffer)
+    }
+
+    /// 같은 디렉토리의 temp-<pid>.rdb 에 먼저 쓰고 fsync 한 뒤 rename 으로 교체한다.
+    /// 쓰는 도중 죽더라도 기존 덤프 파일은 그대로 남는다.
+    pub fn write_atomic<P: AsRef<Path>>(path: P, buffer: &[u8]) -> io::Result<()> {
+        let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let path = path.as_ref();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let temp_path = dir.join(format!("temp-{}.rdb", std::process::id()));
+
+        let result = (|| {
+            let mut file = File::create(&temp_path)?;
+            file.write_all(buffer)?;
+            file.sync_all()?;
+            fs::rename(&temp_path, path)?;
+
+            // rename 자체가 디스크에 반영되도록 디렉토리도 fsync
+            File::open(dir)?.sync_all()
+        })();
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    pub fn encode_snapshots(snapshots: &[Snapshot]) -> Vec<u8> {
         let mut buffer = Vec::new();
-        println!("{:?}", path);
         // Redis RDB 파일의 매직 넘버와 버전을 작성
         buffer.extend_from_slice(b"REDIS0011");
 
@@ -89,81 +252,84 @@ impl RDB {
         buffer.push(0xC0); // 특수 인코딩 표시 (11000000)
         buffer.push(0x40); // 64 비트 값
 
-        // stores가 있는 경우에만 데이터 처리
-        if let Some(stores) = stores {
-            // 각 Store에 대해 처리
-            for (db_index, store) in stores.iter().enumerate() {
-                // 데이터베이스 선택
-                buffer.push(0xFE); // Select DB
-                buffer.push(db_index as u8);
-
-                // Resizedb 필드
-                buffer.push(0xFB); // Resizedb marker
-                let hash_table_size = store.len().await;
-                Self::length_encode_int(hash_table_size, &mut buffer);
-                let expire_table_size = store.expire_len().await;
-                Self::length_encode_int(expire_table_size, &mut buffer);
-
-                // 데이터베이스 내용을 RDB 파일에 기록
-                for (key, value, expiry) in store.iter_for_rdb().await {
-                    match expiry {
-                        Some(expiry_ts) => {
-                            if expiry_ts <= SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .unwrap()
-                                .as_millis() as u64
-                            {
-                                continue;
-                            }
-                            buffer.push(0xFC); // 밀리초 단위 만료 시간
-                            buffer.extend_from_slice(&expiry_ts.to_le_bytes());
-                        }
-                        None => {}
-                    }
+        // 함수 라이브러리는 데이터베이스에 속하지 않으므로 DB 선택 전에 쓴다
+        for code in snapshots.iter().flat_map(|snapshot| &snapshot.functions) {
+            buffer.push(RDB_OPCODE_FUNCTION);
+            Self::encode_string(code, &mut buffer);
+        }
 
-                    // 문자열 값 타입 마커
-                    buffer.push(0x00);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
 
-                    // 키 길이와 키 데이터
-                    buffer.push(key.len() as u8);
-                    buffer.extend_from_slice(key.as_bytes());
+        // 각 스냅샷(데이터베이스)에 대해 처리
+        for (db_index, snapshot) in snapshots.iter().enumerate() {
+            // 데이터베이스 선택
+            buffer.push(0xFE); // Select DB
+            Self::length_encode_int(db_index, &mut buffer);
 
-                    // 값 길이와 값 데이터
-                    buffer.push(value.len() as u8);
-                    buffer.extend_from_slice(value.as_bytes());
+            // Resizedb 필드
+            buffer.push(0xFB); // Resizedb marker
+            Self::length_encode_int(snapshot.entries.len(), &mut buffer);
+            let expire_table_size = snapshot.entries.iter()
+                .filter(|(_, _, expiry)| expiry.is_some())
+                .count();
+            Self::length_encode_int(expire_table_size, &mut buffer);
+
+            // 데이터베이스 내용을 RDB 파일에 기록
+            for (key, value, expiry) in &snapshot.entries {
+                if let Some(expiry_ts) = expiry {
+                    if *expiry_ts <= now {
+                        continue;
+                    }
+                    buffer.push(0xFC); // 밀리초 단위 만료 시간
+                    buffer.extend_from_slice(&expiry_ts.to_le_bytes());
                 }
+
+                // 타입 마커, 키, 값
+                buffer.push(RDB_TYPE_STRING);
+                Self::encode_string(key, &mut buffer);
+                Self::encode_string(value, &mut buffer);
             }
         }
 
         // RDB 파일 끝 마커
         buffer.push(0xFF);
 
-        // CRC64 체크섬 계산 및 추가 (big-endian 형식)
-        let crc = Crc::<u64>::new(&CRC_64_MS);
-        let checksum = crc.checksum(&buffer);
-        buffer.extend_from_slice(&checksum.to_be_bytes());
+        // CRC64 체크섬 계산 및 추가 (little-endian 형식)
+        let checksum = RDB_CRC.checksum(&buffer);
+        buffer.extend_from_slice(&checksum.to_le_bytes());
 
-        // 파일에 버퍼 내용 쓰기
-        let mut file = File::create(path)?;
-        file.write_all(&buffer)?;
+        buffer
+    }
 
+    /// `trailer` 가 `payload` 의 CRC-64/Jones (little-endian) 와 같은지 검사한다
+    fn verify_checksum(payload: &[u8], trailer: &[u8]) -> io::Result<()> {
+        if RDB_CRC.checksum(payload).to_le_bytes() != trailer {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Wrong RDB checksum",
+            ));
+        }
         Ok(())
     }
 
     pub async fn read_rdb<P: AsRef<Path>>(path: P) -> io::Result<Store> {
+        Self::read_rdb_with_checksum(path, true).await
+    }
+
+    /// `verify_checksum` 이 false 이면 (rdbchecksum no) 파일 끝의 CRC64 를 검사하지 않는다.
+    pub async fn read_rdb_with_checksum<P: AsRef<Path>>(path: P, verify_checksum: bool) -> io::Result<Store> {
         let mut file = File::open(path)?;
         let mut buffer = Vec::new();
         io::Read::read_to_end(&mut file, &mut buffer)?;
 
-        println!("RDB file contents:");
-        for (i, &byte) in buffer.iter().enumerate() {
-            print!("{:02X} ", byte);
-            if (i + 1) % 16 == 0 {
-                println!();
-            }
-        }
-        println!();
+        Self::decode(&buffer, verify_checksum).await
+    }
 
+    /// 메모리에 있는 RDB 내용을 Store 로 읽는다 (replica 의 full resync 등).
+    pub async fn decode(buffer: &[u8], verify_checksum: bool) -> io::Result<Store> {
         // Redis RDB 파일의 매직 넘버와 버전 확인 (REDIS0011)
         if buffer.len() < 9 || &buffer[0..9] != b"REDIS0011" {
             return Err(io::Error::new(
@@ -174,106 +340,86 @@ impl RDB {
 
         let store = Store::new();
         let mut pos = 9; // 매직 넘버와 버전 다음부터 시작
+        // EOF 마커 없이 끝나면 잘린 파일이다
+        let mut eof = false;
 
         while pos < buffer.len() {
             match buffer[pos] {
                 0xFA => {
-                    // Auxiliary field
+                    // Auxiliary field (키와 값 모두 건너뛴다)
                     pos += 1;
-                    // key length와 key
-                    let key_len = Self::length_decode_int(&mut pos, &buffer);
-                    pos += key_len; // key 건너뛰기
-                    
-                    // value length와 value
-                    if pos < buffer.len() && buffer[pos] == 0xC0 { // 특수 인코딩
-                        pos += 2; // 0xC0와 값 건너뛰기
-                    } else {
-                        let value_len = Self::length_decode_int(&mut pos, &buffer);
-                        pos += value_len; // value 건너뛰기
-                    }
+                    Self::decode_string(&mut pos, buffer)?;
+                    Self::decode_string(&mut pos, buffer)?;
                 }
                 0xFB => {
                     // Resizedb 필드
                     pos += 1;
                     // Hash table size
-                    let _hash_table_size = Self::length_decode_int(&mut pos, &buffer);
+                    let _hash_table_size = Self::length_decode_int(&mut pos, buffer)?;
                     // Expire hash table size
-                    let _expire_table_size = Self::length_decode_int(&mut pos, &buffer);
+                    let _expire_table_size = Self::length_decode_int(&mut pos, buffer)?;
+                }
+                RDB_OPCODE_FUNCTION => {
+                    pos += 1;
+                    let code = Self::decode_string(&mut pos, buffer)?;
+                    let name = library_name(&code).map_err(|e| Self::invalid(&e))?;
+                    store.set_library(name, code);
                 }
                 0xFE => {
                     // 데이터베이스 선택자
-                    pos += 2; // 선택자와 데이터베이스 인덱스 건너뛰기
-                }
-                0xFC => {
-                    // 만료 시간이 있는 키-값 쌍
                     pos += 1;
-                    let expiry = u64::from_le_bytes([
-                        buffer[pos],
-                        buffer[pos + 1],
-                        buffer[pos + 2],
-                        buffer[pos + 3],
-                        buffer[pos + 4],
-                        buffer[pos + 5],
-                        buffer[pos + 6],
-                        buffer[pos + 7],
-                    ]);
-                    pos += 8;
+                    let _db_index = Self::length_decode_int(&mut pos, buffer)?;
+                }
+                0xFC | 0xFD => {
+                    // 만료 시간이 있는 키-값 쌍 (0xFC 는 밀리초, 0xFD 는 초 단위)
+                    let expiry = if buffer[pos] == 0xFC {
+                        pos += 1;
+                        u64::from_le_bytes(Self::take(&mut pos, buffer, 8)?.try_into().unwrap())
+                    } else {
+                        pos += 1;
+                        u32::from_le_bytes(Self::take(&mut pos, buffer, 4)?.try_into().unwrap()) as u64 * 1000
+                    };
 
                     // 값 타입 마커 확인
-                    if buffer[pos] != 0x00 {
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "Unsupported value type",
-                        ));
+                    if buffer.get(pos) != Some(&RDB_TYPE_STRING) {
+                        return Err(Self::invalid("Unsupported value type"));
                     }
                     pos += 1;
 
-                    // 키 읽기
-                    let key_len = buffer[pos] as usize;
-                    pos += 1;
-                    let key = String::from_utf8_lossy(&buffer[pos..pos + key_len]).to_string();
-                    pos += key_len;
-
-                    // 값 읽기
-                    let value_len = buffer[pos] as usize;
-                    pos += 1;
-                    let value = String::from_utf8_lossy(&buffer[pos..pos + value_len]).to_string();
-                    pos += value_len;
-
-                    store.insert(key, value, Some(expiry)).await;
+                    let key = Self::decode_string(&mut pos, buffer)?;
+                    let value = Self::decode_string(&mut pos, buffer)?;
+                    store.insert_at(key, value, Some(expiry)).await;
                 }
-                0x00 => {
+                RDB_TYPE_STRING => {
                     // 만료 시간이 없는 키-값 쌍
                     pos += 1;
-
-                    // 키 읽기
-                    let key_len = buffer[pos] as usize;
-                    pos += 1;
-                    let key = String::from_utf8_lossy(&buffer[pos..pos + key_len]).to_string();
-                    pos += key_len;
-
-                    // 값 읽기
-                    let value_len = buffer[pos] as usize;
-                    pos += 1;
-                    let value = String::from_utf8_lossy(&buffer[pos..pos + value_len]).to_string();
-                    pos += value_len;
-
+                    let key = Self::decode_string(&mut pos, buffer)?;
+                    let value = Self::decode_string(&mut pos, buffer)?;
                     store.insert(key, value, None).await;
                 }
                 0xFF => {
-                    // EOF 마커
+                    // EOF 마커 다음 8바이트가 CRC64 체크섬
+                    if verify_checksum {
+                        let trailer = buffer.get(pos + 1..pos + 9).ok_or_else(|| {
+                            io::Error::new(io::ErrorKind::UnexpectedEof, "RDB file is truncated: missing checksum")
+                        })?;
+                        // 체크섬이 0 이면 체크섬 없이 저장된 파일이므로 검사를 건너뛴다.
+                        // 이전 버전 형식의 체크섬도 맞으면 통과시킨다 (다음 저장부터는 새 형식으로 쓴다).
+                        let legacy = LEGACY_RDB_CRC.checksum(&buffer[..=pos]).to_be_bytes() == trailer;
+                        if trailer != [0; 8] && !legacy {
+                            Self::verify_checksum(&buffer[..=pos], trailer)?;
+                        }
+                    }
+                    eof = true;
                     break;
                 }
-                _ => {
-                    println!("here -> {:?} ", String::from_utf8_lossy(&buffer[pos..pos+1]));
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "Invalid RDB file format",
-                    ));
-                }
+                _ => return Err(Self::invalid("Invalid RDB file format")),
             }
         }
 
+        if !eof {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "RDB file is truncated"));
+        }
         Ok(store)
     }
 }
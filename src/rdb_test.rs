@@ -358,3 +358,211 @@ async fn test_read_rdb_with_hex() {
     // 테스트 후 파일 삭제
     fs::remove_file(path).unwrap();
 }
+
+#[test]
+async fn test_create_rdb_leaves_no_temp_file() {
+    let dir = "test_atomic_dir";
+    fs::create_dir_all(dir).unwrap();
+    let path = format!("{}/dump.rdb", dir);
+
+    // 기존 덤프 파일을 새 내용으로 교체
+    fs::write(&path, b"old contents").unwrap();
+    let store = Store::new();
+    store.insert("key1".to_string(), "value1".to_string(), None).await;
+    RDB::create_rdb(&path, Some(&[&store])).await.unwrap();
+
+    let loaded = RDB::read_rdb(&path).await.unwrap();
+    assert_eq!(loaded.get("key1").await.unwrap(), "value1");
+
+    // temp-<pid>.rdb 는 rename 되어 남아있지 않아야 함
+    let entries: Vec<_> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(entries, vec!["dump.rdb"]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+async fn test_read_rdb_checksum_mismatch() {
+    let path = "test_bad_checksum.rdb";
+
+    let store = Store::new();
+    store.insert("key1".to_string(), "value1".to_string(), None).await;
+    RDB::create_rdb(path, Some(&[&store])).await.unwrap();
+
+    // 값 한 바이트를 망가뜨린다 ("value1" -> "valuX")
+    let mut contents = fs::read(path).unwrap();
+    let pos = contents.windows(6).position(|w| w == b"value1").unwrap();
+    contents[pos + 5] = b'X';
+    fs::write(path, &contents).unwrap();
+
+    let err = RDB::read_rdb(path).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // rdbchecksum no 이면 그대로 로드
+    let loaded = RDB::read_rdb_with_checksum(path, false).await.unwrap();
+    assert_eq!(loaded.get("key1").await.unwrap(), "valueX");
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_read_rdb_zero_checksum_is_skipped() {
+    let path = "test_zero_checksum.rdb";
+
+    let store = Store::new();
+    store.insert("key1".to_string(), "value1".to_string(), None).await;
+    RDB::create_rdb(path, Some(&[&store])).await.unwrap();
+
+    // 체크섬 없이 저장된 파일은 CRC 자리가 0 으로 채워져 있다
+    let mut contents = fs::read(path).unwrap();
+    let len = contents.len();
+    contents[len - 8..].fill(0);
+    fs::write(path, &contents).unwrap();
+
+    let loaded = RDB::read_rdb(path).await.unwrap();
+    assert_eq!(loaded.get("key1").await.unwrap(), "value1");
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_read_rdb_legacy_checksum() {
+    let path = "test_legacy_checksum.rdb";
+
+    let store = Store::new();
+    store.insert("key1".to_string(), "value1".to_string(), None).await;
+    RDB::create_rdb(path, Some(&[&store])).await.unwrap();
+
+    // 이전 버전은 CRC-64/MS 를 big-endian 으로 썼다
+    let mut contents = fs::read(path).unwrap();
+    let len = contents.len();
+    let checksum = crc::Crc::<u64>::new(&crc::CRC_64_MS).checksum(&contents[..len - 8]);
+    contents[len - 8..].copy_from_slice(&checksum.to_be_bytes());
+    fs::write(path, &contents).unwrap();
+
+    let loaded = RDB::read_rdb(path).await.unwrap();
+    assert_eq!(loaded.get("key1").await.unwrap(), "value1");
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_read_truncated_rdb() {
+    let path = "test_truncated.rdb";
+
+    let store = Store::new();
+    store.insert("key1".to_string(), "value1".to_string(), None).await;
+    RDB::create_rdb(path, Some(&[&store])).await.unwrap();
+
+    // EOF 마커 (0xFF) 바로 앞에서 자른다. 키-값 쌍은 모두 온전하다.
+    let contents = fs::read(path).unwrap();
+    fs::write(path, &contents[..contents.len() - 9]).unwrap();
+
+    for verify_checksum in [true, false] {
+        let err = RDB::read_rdb_with_checksum(path, verify_checksum).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    // EOF 마커는 있지만 체크섬이 잘렸다
+    fs::write(path, &contents[..contents.len() - 4]).unwrap();
+    let err = RDB::read_rdb(path).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_long_keys_and_values_roundtrip() {
+    let path = "test_long_values.rdb";
+
+    // 63 바이트를 넘는 문자열은 14비트, 16383 바이트를 넘으면 32비트 길이로 쓴다
+    let store = Store::new();
+    let medium = "m".repeat(300);
+    let large = "L".repeat(20000);
+    store.insert(medium.clone(), large.clone(), None).await;
+    store.insert("k".to_string(), medium.clone(), Some(60000)).await;
+    RDB::create_rdb(path, Some(&[&store])).await.unwrap();
+
+    let loaded = RDB::read_rdb(path).await.unwrap();
+    assert_eq!(loaded.get(&medium).await, Some(large));
+    assert_eq!(loaded.get("k").await, Some(medium));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+async fn test_decode_string_encodings() {
+    // 길이 인코딩된 문자열과 정수로 인코딩된 문자열 (0xC0 ~ 0xC2)
+    let mut pos = 0;
+    let buffer = [0x03, b'a', b'b', b'c', 0xC0, 0xFF, 0xC1, 0x39, 0x30, 0xC2, 0x40, 0xE2, 0x01, 0x00];
+    assert_eq!(RDB::decode_string(&mut pos, &buffer).unwrap(), "abc");
+    assert_eq!(RDB::decode_string(&mut pos, &buffer).unwrap(), "-1");
+    assert_eq!(RDB::decode_string(&mut pos, &buffer).unwrap(), "12345");
+    assert_eq!(RDB::decode_string(&mut pos, &buffer).unwrap(), "123456");
+    assert_eq!(pos, buffer.len());
+
+    // 잘린 문자열은 panic 하지 않고 에러
+    let mut pos = 0;
+    assert!(RDB::decode_string(&mut pos, &[0x05, b'a']).is_err());
+}
+
+#[test]
+async fn test_dump_payload() {
+    let payload = RDB::dump_value("hello");
+    // 타입, 길이, 값, RDB 버전 (11), CRC64
+    assert_eq!(&payload[..9], &[0x00, 0x05, b'h', b'e', b'l', b'l', b'o', 0x0B, 0x00]);
+    assert_eq!(payload.len(), 9 + 8);
+    assert_eq!(RDB::restore_value(&payload).unwrap(), "hello");
+
+    let long = "x".repeat(1000);
+    assert_eq!(RDB::restore_value(&RDB::dump_value(&long)).unwrap(), long);
+
+    // 체크섬이 틀리거나, 더 새로운 RDB 버전이거나, 너무 짧으면 거부한다
+    let mut corrupted = payload.clone();
+    corrupted[2] = b'j';
+    assert!(RDB::restore_value(&corrupted).is_err());
+    let mut newer = payload.clone();
+    newer[7] = 0xFF;
+    assert!(RDB::restore_value(&newer).is_err());
+    assert!(RDB::restore_value(&payload[..5]).is_err());
+
+    // 파일 로드와 달리 체크섬 0 이나 이전 형식 (CRC-64/MS, big-endian) 은 받지 않는다
+    let len = payload.len();
+    let mut zeroed = payload.clone();
+    zeroed[len - 8..].fill(0);
+    assert!(RDB::restore_value(&zeroed).is_err());
+    let mut legacy = payload.clone();
+    let checksum = crc::Crc::<u64>::new(&crc::CRC_64_MS).checksum(&payload[..len - 8]);
+    legacy[len - 8..].copy_from_slice(&checksum.to_be_bytes());
+    assert!(RDB::restore_value(&legacy).is_err());
+}
+
+#[test]
+async fn test_function_libraries() {
+    let store = Store::new();
+    let code = "#!lua name=mylib\nredis.register_function('f', function() return 1 end)".to_string();
+    store.set_library("mylib".to_string(), code.clone());
+    store.insert("k".to_string(), "v".to_string(), None).await;
+
+    // 함수 opcode 는 DB 선택보다 앞에 온다
+    let buffer = RDB::encode_snapshots(&[store.snapshot().await]);
+    let opcode = buffer.iter().position(|&b| b == 0xF5).unwrap();
+    assert!(opcode < buffer.iter().position(|&b| b == 0xFE).unwrap());
+
+    let loaded = RDB::decode(&buffer, true).await.unwrap();
+    assert_eq!(loaded.library("mylib"), Some(code.clone()));
+    assert_eq!(loaded.get("k").await, Some("v".to_string()));
+
+    // FUNCTION DUMP 페이로드
+    let payload = RDB::dump_functions(&store.libraries());
+    assert_eq!(RDB::restore_functions(&payload).unwrap(), vec![code]);
+    assert_eq!(RDB::restore_functions(&RDB::dump_functions(&[])).unwrap(), Vec::<String>::new());
+    assert!(RDB::restore_functions(&RDB::dump_value("v")).is_err());
+    let mut corrupted = payload.clone();
+    corrupted[5] ^= 1;
+    assert!(RDB::restore_functions(&corrupted).is_err());
+    let mut zeroed = payload.clone();
+    let len = zeroed.len();
+    zeroed[len - 8..].fill(0);
+    assert!(RDB::restore_functions(&zeroed).is_err());
+}
@@ -0,0 +1,822 @@
+use crate::protocol::decoder::{FrameError, RedisCommand, RedisDecoder};
+use crate::protocol::encoder::RedisEncoder;
+use crate::rdb::RDB;
+use crate::server::{execute, execute_transaction, ClientState, ServerState};
+use anyhow::{anyhow, bail, Result};
+use bytes::{Buf, Bytes, BytesMut};
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, watch, Notify};
+use tokio::time::Instant;
+
+/// master 와의 연결이 끊겼을 때 다시 접속하기까지 기다리는 시간
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// replid2 가 없을 때의 값
+const NO_REPLID: &str = "0000000000000000000000000000000000000000";
+
+/// 40자리 16진수 replication ID
+pub fn new_replid() -> String {
+    let mut id = String::with_capacity(40);
+    while id.len() < 40 {
+        // RandomState 는 매번 다른 키로 초기화되므로 난수원으로 쓸 수 있다
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos());
+        hasher.write_u32(std::process::id());
+        id.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    id.truncate(40);
+    id
+}
+
+/// 0 ~ max 사이의 임의의 시간 (여러 노드가 동시에 같은 일을 시작하지 않도록)
+pub fn random_delay(max: Duration) -> Duration {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    Duration::from_millis(hasher.finish() % (max.as_millis() as u64 + 1))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MasterAddr {
+    pub host: String,
+    pub port: u16,
+}
+
+impl MasterAddr {
+    /// "host port" 형식 (`--replicaof "localhost 6379"`)
+    pub fn parse(s: &str) -> Option<MasterAddr> {
+        let mut parts = s.split_whitespace();
+        let host = parts.next()?.to_string();
+        let port = parts.next()?.parse().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(MasterAddr { host, port })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReplicaState {
+    /// RDB 를 보내는 중
+    SendBulk,
+    /// 명령 스트림을 받는 중
+    Online,
+}
+
+/// master 쪽에서 관리하는 replica 연결 하나
+#[derive(Debug)]
+struct ReplicaHandle {
+    id: u64,
+    ip: IpAddr,
+    listening_port: Option<u16>,
+    state: ReplicaState,
+    sender: mpsc::UnboundedSender<Bytes>,
+    /// REPLCONF ACK 로 알려준 반영된 offset
+    ack_offset: u64,
+    /// REPLCONF ACK ... FACK 로 알려준 AOF 에 fsync 된 offset (replica 의 AOF 가 꺼져 있으면 None)
+    aof_ack_offset: Option<u64>,
+}
+
+/// replica 쪽에서 본 master 링크 상태
+#[derive(Debug, Default)]
+struct LinkState {
+    up: bool,
+    sync_in_progress: bool,
+}
+
+/// 최근에 전파한 명령 스트림을 담아두는 원형 버퍼.
+/// 잠깐 끊겼던 replica 는 여기서 빠진 부분만 받아 간다.
+#[derive(Debug)]
+struct Backlog {
+    data: VecDeque<u8>,
+    size: usize,
+    /// data[0] 의 replication offset
+    first_offset: u64,
+}
+
+impl Backlog {
+    fn new(size: usize, next_offset: u64) -> Self {
+        Backlog { data: VecDeque::with_capacity(size), size, first_offset: next_offset }
+    }
+
+    fn feed(&mut self, bytes: &[u8]) {
+        self.data.extend(bytes);
+        if self.data.len() > self.size {
+            let excess = self.data.len() - self.size;
+            self.data.drain(..excess);
+            self.first_offset += excess as u64;
+        }
+    }
+
+    /// `offset` 부터 끝까지의 스트림. backlog 에 남아 있지 않으면 None
+    fn range_from(&self, offset: u64) -> Option<Vec<u8>> {
+        if offset < self.first_offset || offset > self.first_offset + self.data.len() as u64 {
+            return None;
+        }
+        let skip = (offset - self.first_offset) as usize;
+        Some(self.data.iter().skip(skip).copied().collect())
+    }
+}
+
+/// replication ID 와 offset, backlog.
+/// offset 은 스트림의 바이트 수이고, PSYNC 에는 다음에 받을 바이트 (offset + 1) 를 보낸다.
+#[derive(Debug)]
+struct History {
+    replid: String,
+    /// 승격되기 전에 따르던 master 의 replication ID
+    replid2: String,
+    /// replid2 로 이어받을 수 있는 마지막 PSYNC offset (-1 이면 없음)
+    second_replid_offset: i64,
+    offset: u64,
+    backlog: Option<Backlog>,
+    /// replica 일 때 이 history 로 부분 동기화를 시도해도 되는지
+    /// (처음 뜬 replica 는 PSYNC ? -1 로 full resync 를 요청한다)
+    resumable: bool,
+}
+
+impl History {
+    /// 지금까지의 history 를 replid2 로 옮기고 새 replication ID 를 시작한다.
+    fn shift_replid(&mut self, replid: String) {
+        self.replid2 = std::mem::replace(&mut self.replid, replid);
+        self.second_replid_offset = self.offset as i64 + 1;
+    }
+}
+
+/// 복제 상태 (master/replica 공통)
+#[derive(Debug)]
+pub struct Replication {
+    /// 이 서버의 리스닝 포트 (REPLCONF listening-port 로 master 에 알려준다)
+    port: u16,
+    backlog_size: usize,
+    /// full resync 때 RDB 를 디스크에 쓰지 않고 소켓으로 바로 보낸다 (capa eof 를 지원하는 replica 만)
+    diskless_sync: bool,
+    /// 링크가 끊긴 동안 STALE 플래그가 없는 명령도 실행할지 (replica-serve-stale-data)
+    serve_stale_data: bool,
+    history: Mutex<History>,
+    replicas: Mutex<Vec<ReplicaHandle>>,
+    next_replica_id: AtomicU64,
+    /// 따라갈 master. None 이면 이 서버가 master
+    master: watch::Sender<Option<MasterAddr>>,
+    link: Mutex<LinkState>,
+    is_replica: AtomicBool,
+    /// REPLCONF ACK 를 받을 때마다 WAIT 중인 클라이언트를 깨운다
+    ack_notify: Notify,
+}
+
+impl Replication {
+    pub fn new(
+        port: u16,
+        master: Option<MasterAddr>,
+        backlog_size: usize,
+        diskless_sync: bool,
+        serve_stale_data: bool,
+    ) -> Self {
+        let is_replica = master.is_some();
+        Replication {
+            port,
+            backlog_size,
+            diskless_sync,
+            serve_stale_data,
+            history: Mutex::new(History {
+                replid: new_replid(),
+                replid2: NO_REPLID.to_string(),
+                second_replid_offset: -1,
+                offset: 0,
+                backlog: None,
+                resumable: !is_replica,
+            }),
+            replicas: Mutex::new(Vec::new()),
+            next_replica_id: AtomicU64::new(1),
+            master: watch::channel(master).0,
+            link: Mutex::new(LinkState::default()),
+            is_replica: AtomicBool::new(is_replica),
+            ack_notify: Notify::new(),
+        }
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.is_replica.load(Ordering::SeqCst)
+    }
+
+    /// replica 인데 master 와 동기화된 링크가 없어서 데이터가 오래됐을 수 있는지
+    pub fn is_stale(&self) -> bool {
+        self.is_replica() && !self.link.lock().unwrap().up
+    }
+
+    /// 오래된 데이터로 응답해도 되는지 (replica-serve-stale-data)
+    pub fn serve_stale_data(&self) -> bool {
+        self.serve_stale_data
+    }
+
+    pub fn replid(&self) -> String {
+        self.history.lock().unwrap().replid.clone()
+    }
+
+    pub fn master_repl_offset(&self) -> u64 {
+        self.history.lock().unwrap().offset
+    }
+
+    /// REPLICAOF host port / REPLICAOF NO ONE
+    pub fn set_master(&self, master: Option<MasterAddr>) {
+        // 이미 같은 master 를 따르고 있으면 다시 동기화하지 않는다
+        if *self.master.borrow() == master {
+            return;
+        }
+        if master.is_none() && self.is_replica() {
+            // 승격해도 이전 history 는 replid2 로 남겨서 다른 replica 들이 부분 동기화할 수 있게 한다
+            self.history.lock().unwrap().shift_replid(new_replid());
+        }
+        self.is_replica.store(master.is_some(), Ordering::SeqCst);
+        *self.link.lock().unwrap() = LinkState::default();
+        // 하위 replica 들은 다시 접속해서 바뀐 replication ID 를 받아 가야 한다
+        self.disconnect_replicas();
+        self.master.send_replace(master);
+    }
+
+    pub fn connected_replicas(&self) -> usize {
+        self.replicas.lock().unwrap().len()
+    }
+
+    /// 처음 replica 가 붙을 때 backlog 를 만든다. 이후로는 replica 가 없어도 계속 쌓는다.
+    pub fn create_backlog(&self) {
+        let mut history = self.history.lock().unwrap();
+        if history.backlog.is_none() {
+            history.backlog = Some(Backlog::new(self.backlog_size, history.offset + 1));
+        }
+    }
+
+    /// PSYNC <replid> <offset> 을 backlog 로 이어갈 수 있으면 보내야 할 스트림을 돌려준다.
+    pub fn partial_resync(&self, replid: &str, offset: i64) -> Option<Vec<u8>> {
+        let history = self.history.lock().unwrap();
+        let known = replid == history.replid
+            || (replid == history.replid2 && offset <= history.second_replid_offset);
+        if !known || offset < 0 {
+            return None;
+        }
+        history.backlog.as_ref()?.range_from(offset as u64)
+    }
+
+    /// replica 쪽: full resync 로 받은 master 의 history 로 새로 시작한다.
+    fn reset_history(&self, replid: String, offset: u64) {
+        let mut history = self.history.lock().unwrap();
+        history.replid = replid;
+        history.replid2 = NO_REPLID.to_string();
+        history.second_replid_offset = -1;
+        history.offset = offset;
+        history.backlog = Some(Backlog::new(self.backlog_size, offset + 1));
+        history.resumable = true;
+        drop(history);
+        self.disconnect_replicas();
+    }
+
+    /// replica 쪽: +CONTINUE 를 받았을 때. master 가 승격된 replica 라 ID 가 바뀌었으면 따라 바꾼다.
+    fn continue_with(&self, replid: &str) {
+        let mut history = self.history.lock().unwrap();
+        if history.backlog.is_none() {
+            history.backlog = Some(Backlog::new(self.backlog_size, history.offset + 1));
+        }
+        if !replid.is_empty() && replid != history.replid {
+            history.shift_replid(replid.to_string());
+            drop(history);
+            self.disconnect_replicas();
+        }
+    }
+
+    /// 새 replica 를 등록하고, 이후 전파될 명령 스트림을 받을 채널을 돌려준다.
+    fn register_replica(&self, ip: IpAddr, listening_port: Option<u16>, state: ReplicaState) -> (u64, mpsc::UnboundedReceiver<Bytes>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let id = self.next_replica_id.fetch_add(1, Ordering::SeqCst);
+        self.replicas.lock().unwrap().push(ReplicaHandle {
+            id,
+            ip,
+            listening_port,
+            state,
+            sender,
+            ack_offset: 0,
+            aof_ack_offset: None,
+        });
+        (id, receiver)
+    }
+
+    /// REPLCONF ACK <offset> [FACK <aofoffset>]
+    fn ack(&self, id: u64, offset: u64, aof_offset: Option<u64>) {
+        if let Some(replica) = self.replicas.lock().unwrap().iter_mut().find(|r| r.id == id) {
+            replica.ack_offset = replica.ack_offset.max(offset);
+            if let Some(aof_offset) = aof_offset {
+                replica.aof_ack_offset = Some(replica.aof_ack_offset.unwrap_or(0).max(aof_offset));
+            }
+        }
+        self.ack_notify.notify_waiters();
+    }
+
+    /// `offset` 까지 반영한 (`fsynced` 면 AOF 에 fsync 까지 한) replica 수
+    pub fn acked_replicas(&self, offset: u64, fsynced: bool) -> usize {
+        self.replicas
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.state == ReplicaState::Online)
+            .filter(|r| match fsynced {
+                true => r.aof_ack_offset.is_some_and(|o| o >= offset),
+                false => r.ack_offset >= offset,
+            })
+            .count()
+    }
+
+    fn set_replica_state(&self, id: u64, state: ReplicaState) {
+        if let Some(replica) = self.replicas.lock().unwrap().iter_mut().find(|r| r.id == id) {
+            replica.state = state;
+        }
+    }
+
+    fn unregister_replica(&self, id: u64) {
+        self.replicas.lock().unwrap().retain(|r| r.id != id);
+    }
+
+    /// 채널을 닫아서 모든 replica 링크를 끊는다.
+    fn disconnect_replicas(&self) {
+        self.replicas.lock().unwrap().clear();
+    }
+
+    /// 쓰기 명령을 replica 들에게 전파한다. backlog 가 없으면 (replica 가 붙은 적이 없으면) 아무것도 하지 않는다.
+    pub fn feed(&self, args: &[String]) {
+        if self.history.lock().unwrap().backlog.is_none() {
+            return;
+        }
+
+        let mut buf = BytesMut::new();
+        let items: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        RedisEncoder::new().encode_array(&mut buf, &items);
+        self.feed_raw(buf.freeze());
+    }
+
+    /// 이미 RESP 로 인코딩된 스트림을 backlog 에 쌓고 replica 들에게 보낸다.
+    /// replica 는 master 에게서 받은 바이트를 그대로 하위 replica 에게 넘긴다.
+    pub fn feed_raw(&self, bytes: Bytes) {
+        {
+            let mut history = self.history.lock().unwrap();
+            history.offset += bytes.len() as u64;
+            if let Some(backlog) = history.backlog.as_mut() {
+                backlog.feed(&bytes);
+            }
+        }
+        self.replicas
+            .lock()
+            .unwrap()
+            .retain(|replica| replica.sender.send(bytes.clone()).is_ok());
+    }
+
+    /// INFO replication 섹션
+    pub fn info(&self) -> String {
+        let mut info = String::from("# Replication\r\n");
+        match self.master.borrow().as_ref() {
+            Some(master) => {
+                let link = self.link.lock().unwrap();
+                info.push_str("role:slave\r\n");
+                info.push_str(&format!("master_host:{}\r\n", master.host));
+                info.push_str(&format!("master_port:{}\r\n", master.port));
+                info.push_str(&format!("master_link_status:{}\r\n", if link.up { "up" } else { "down" }));
+                info.push_str(&format!("master_sync_in_progress:{}\r\n", link.sync_in_progress as u8));
+                info.push_str(&format!("slave_repl_offset:{}\r\n", self.master_repl_offset()));
+            }
+            None => info.push_str("role:master\r\n"),
+        }
+
+        let replicas = self.replicas.lock().unwrap();
+        info.push_str(&format!("connected_slaves:{}\r\n", replicas.len()));
+        for (i, replica) in replicas.iter().enumerate() {
+            let state = match replica.state {
+                ReplicaState::SendBulk => "send_bulk",
+                ReplicaState::Online => "online",
+            };
+            info.push_str(&format!(
+                "slave{}:ip={},port={},state={}\r\n",
+                i,
+                replica.ip,
+                replica.listening_port.unwrap_or(0),
+                state,
+            ));
+        }
+        drop(replicas);
+
+        let history = self.history.lock().unwrap();
+        info.push_str(&format!("master_replid:{}\r\n", history.replid));
+        info.push_str(&format!("master_replid2:{}\r\n", history.replid2));
+        info.push_str(&format!("master_repl_offset:{}\r\n", history.offset));
+        info.push_str(&format!("second_repl_offset:{}\r\n", history.second_replid_offset));
+        match &history.backlog {
+            Some(backlog) => {
+                info.push_str("repl_backlog_active:1\r\n");
+                info.push_str(&format!("repl_backlog_size:{}\r\n", backlog.size));
+                info.push_str(&format!("repl_backlog_first_byte_offset:{}\r\n", backlog.first_offset));
+                info.push_str(&format!("repl_backlog_histlen:{}\r\n", backlog.data.len()));
+            }
+            None => {
+                info.push_str("repl_backlog_active:0\r\n");
+                info.push_str(&format!("repl_backlog_size:{}\r\n", self.backlog_size));
+                info.push_str("repl_backlog_first_byte_offset:0\r\n");
+                info.push_str("repl_backlog_histlen:0\r\n");
+            }
+        }
+        info
+    }
+}
+
+/// master 설정이 바뀔 때마다 master 와의 링크를 다시 맺는다.
+pub async fn run(state: Arc<ServerState>) {
+    let mut master_rx = state.replication.master.subscribe();
+    loop {
+        let master = master_rx.borrow_and_update().clone();
+        match master {
+            Some(master) => {
+                tokio::select! {
+                    _ = follow_master(&state, &master) => {}
+                    _ = master_rx.changed() => {}
+                }
+            }
+            None => {
+                if master_rx.changed().await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// 연결이 끊기면 잠시 후 다시 접속한다.
+async fn follow_master(state: &Arc<ServerState>, master: &MasterAddr) {
+    loop {
+        if let Err(e) = sync_with_master(state, master).await {
+            eprintln!("Replication link with {}:{} failed: {:?}", master.host, master.port, e);
+        }
+        *state.replication.link.lock().unwrap() = LinkState::default();
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn send_command(stream: &mut TcpStream, args: &[&str]) -> Result<()> {
+    let mut buf = BytesMut::new();
+    RedisEncoder::new().encode_array(&mut buf, args);
+    stream.write_all(&buf).await?;
+    Ok(())
+}
+
+/// \r\n 으로 끝나는 한 줄을 읽는다.
+async fn read_line(stream: &mut TcpStream, buf: &mut BytesMut) -> Result<String> {
+    loop {
+        if let Some(end) = buf.windows(2).position(|w| w == b"\r\n") {
+            let line = String::from_utf8_lossy(&buf[..end]).to_string();
+            buf.advance(end + 2);
+            return Ok(line);
+        }
+        if stream.read_buf(buf).await? == 0 {
+            bail!("connection closed by master");
+        }
+    }
+}
+
+/// 응답이 `expected` 로 시작하지 않으면 에러
+async fn expect_reply(stream: &mut TcpStream, buf: &mut BytesMut, expected: &str) -> Result<String> {
+    let line = read_line(stream, buf).await?;
+    if !line.starts_with(expected) {
+        bail!("unexpected reply from master: {:?}", line);
+    }
+    Ok(line)
+}
+
+/// diskless sync 에서 RDB 끝을 알리는 마커의 길이
+const EOF_MARK_LEN: usize = 40;
+
+/// full resync 의 RDB 페이로드를 읽는다.
+/// `$<len>\r\n` 뒤에 len 바이트가 오거나 (끝에 \r\n 없음),
+/// diskless sync 면 `$EOF:<mark>\r\n` 뒤에 같은 mark 가 나올 때까지가 RDB 다.
+pub(crate) async fn read_rdb_payload(stream: &mut TcpStream, buf: &mut BytesMut) -> Result<Vec<u8>> {
+    let header = loop {
+        let line = read_line(stream, buf).await?;
+        // master 가 RDB 를 준비하는 동안 보내는 keepalive 개행 (\n) 은 건너뛴다
+        let line = line.trim_start_matches('\n');
+        if !line.is_empty() {
+            break line.to_string();
+        }
+    };
+
+    if let Some(mark) = header.strip_prefix("$EOF:") {
+        let mark = mark.as_bytes();
+        if mark.len() != EOF_MARK_LEN {
+            bail!("invalid EOF mark: {:?}", header);
+        }
+        let mut scanned = 0;
+        loop {
+            if let Some(i) = buf[scanned..].windows(EOF_MARK_LEN).position(|w| w == mark) {
+                let payload = buf.split_to(scanned + i).to_vec();
+                buf.advance(EOF_MARK_LEN);
+                return Ok(payload);
+            }
+            // 마커가 두 번의 read 에 걸쳐 올 수 있으니 마지막 조각은 다시 본다
+            scanned = buf.len().saturating_sub(EOF_MARK_LEN - 1);
+            if stream.read_buf(buf).await? == 0 {
+                bail!("connection closed while reading RDB payload");
+            }
+        }
+    }
+
+    let len: usize = header
+        .strip_prefix('$')
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| anyhow!("invalid RDB payload header: {:?}", header))?;
+
+    while buf.len() < len {
+        if stream.read_buf(buf).await? == 0 {
+            bail!("connection closed while reading RDB payload");
+        }
+    }
+    Ok(buf.split_to(len).to_vec())
+}
+
+/// replica 쪽: 핸드셰이크, full resync, 이후 명령 스트림 적용
+async fn sync_with_master(state: &Arc<ServerState>, master: &MasterAddr) -> Result<()> {
+    let replication = &state.replication;
+    let mut stream = TcpStream::connect((master.host.as_str(), master.port)).await?;
+    let mut buf = BytesMut::new();
+
+    send_command(&mut stream, &["PING"]).await?;
+    expect_reply(&mut stream, &mut buf, "+PONG").await?;
+
+    let port = replication.port.to_string();
+    send_command(&mut stream, &["REPLCONF", "listening-port", &port]).await?;
+    expect_reply(&mut stream, &mut buf, "+OK").await?;
+    send_command(&mut stream, &["REPLCONF", "capa", "eof", "capa", "psync2"]).await?;
+    expect_reply(&mut stream, &mut buf, "+OK").await?;
+
+    // 이전에 따르던 history 가 있으면 이어받기를 시도한다
+    let (psync_replid, psync_offset) = {
+        let history = replication.history.lock().unwrap();
+        if history.resumable {
+            (history.replid.clone(), (history.offset + 1).to_string())
+        } else {
+            ("?".to_string(), "-1".to_string())
+        }
+    };
+    send_command(&mut stream, &["PSYNC", &psync_replid, &psync_offset]).await?;
+    let reply = read_line(&mut stream, &mut buf).await?;
+
+    if let Some(rest) = reply.strip_prefix("+CONTINUE") {
+        // 부분 동기화: 빠진 스트림이 이어서 온다
+        replication.continue_with(rest.trim());
+        *replication.link.lock().unwrap() = LinkState { up: true, sync_in_progress: false };
+        println!("MASTER <-> REPLICA sync: Master accepted a Partial Resynchronization.");
+    } else if reply.starts_with("+FULLRESYNC") {
+        let mut parts = reply.split_whitespace().skip(1);
+        let (replid, offset) = match (parts.next(), parts.next().and_then(|o| o.parse::<u64>().ok())) {
+            (Some(replid), Some(offset)) => (replid.to_string(), offset),
+            _ => bail!("invalid FULLRESYNC reply: {:?}", reply),
+        };
+
+        replication.link.lock().unwrap().sync_in_progress = true;
+        let payload = read_rdb_payload(&mut stream, &mut buf).await?;
+        let loaded = RDB::decode(&payload, true).await?;
+        {
+            let _lock = state.command_lock.lock().await;
+            state.store.replace(loaded).await;
+            replication.reset_history(replid, offset);
+        }
+        *replication.link.lock().unwrap() = LinkState { up: true, sync_in_progress: false };
+        println!("MASTER <-> REPLICA sync: finished with success");
+    } else {
+        bail!("unexpected reply from master: {:?}", reply);
+    }
+
+    // master 가 보내는 쓰기 명령을 그대로 적용한다. master 에게는 REPLCONF ACK 외에는 응답하지 않는다.
+    // master 가 보내는 명령에는 proto-max-bulk-len / proto-max-multibulk-len 을 적용하지 않는다
+    let decoder = RedisDecoder::with_limits(usize::MAX, usize::MAX);
+    let mut client = ClientState::default();
+    let mut discard = BytesMut::new();
+    // master 의 MULTI 이후 EXEC 까지 쌓아둔 명령. EXEC 에서 한 번에 적용한다.
+    let mut queued: Option<Vec<RedisCommand>> = None;
+    let mut aof_fsynced = state.aof.as_ref().map(|aof| aof.fsynced_reploff());
+    loop {
+        loop {
+            match decoder.parse_args(&buf) {
+                Ok((args, consumed)) => {
+                    let raw = buf.split_to(consumed).freeze();
+                    let command = RedisCommand::from_args(args);
+                    let _lock = state.command_lock.lock().await;
+                    if is_getack(&command) {
+                        // GETACK 자신은 빼고 지금까지 반영한 offset 을 알려준다
+                        send_ack(state, &mut stream).await?;
+                    }
+                    // offset 을 먼저 올려야 AOF 에 기록되는 offset 이 이 명령을 포함한다
+                    replication.feed_raw(raw);
+                    match (command, queued.as_mut()) {
+                        (RedisCommand::Multi, _) => queued = Some(Vec::new()),
+                        (RedisCommand::Exec, Some(_)) => {
+                            execute_transaction(state, &mut client, queued.take().unwrap(), &mut discard).await;
+                        }
+                        (command, Some(commands)) => commands.push(command),
+                        (command, None) => execute(state, &mut client, command, &mut discard).await,
+                    }
+                    discard.clear();
+                }
+                Err(FrameError::Incomplete) => break,
+                Err(FrameError::Invalid | FrameError::TooLarge(_)) => bail!("protocol error in replication stream"),
+            }
+        }
+
+        tokio::select! {
+            n = stream.read_buf(&mut buf) => {
+                if n? == 0 {
+                    bail!("connection closed by master");
+                }
+            }
+            // AOF fsync 가 끝나면 WAITAOF 를 기다리는 master 에게 알린다
+            _ = fsync_changed(aof_fsynced.as_mut()) => send_ack(state, &mut stream).await?,
+        }
+    }
+}
+
+/// AOF 의 fsync offset 이 바뀔 때까지 기다린다. 지켜볼 AOF 가 없으면 끝나지 않는다.
+async fn fsync_changed(fsynced: Option<&mut watch::Receiver<u64>>) {
+    if let Some(rx) = fsynced {
+        if rx.changed().await.is_ok() {
+            return;
+        }
+    }
+    std::future::pending().await
+}
+
+fn is_getack(command: &RedisCommand) -> bool {
+    matches!(command, RedisCommand::ReplConf(options) if options[0].eq_ignore_ascii_case("getack"))
+}
+
+/// REPLCONF ACK <offset> [FACK <aofoffset>]
+async fn send_ack(state: &ServerState, stream: &mut TcpStream) -> Result<()> {
+    let offset = state.replication.master_repl_offset().to_string();
+    match &state.aof {
+        Some(aof) => {
+            let fsynced = aof.fsynced_reploff().borrow().to_string();
+            send_command(stream, &["REPLCONF", "ACK", &offset, "FACK", &fsynced]).await
+        }
+        None => send_command(stream, &["REPLCONF", "ACK", &offset]).await,
+    }
+}
+
+/// master 쪽: replica 가 보낸 REPLCONF ACK 를 읽는다.
+fn read_acks(replication: &Replication, id: u64, buf: &mut BytesMut) -> Result<()> {
+    let decoder = RedisDecoder::new();
+    loop {
+        match decoder.parse_args(buf) {
+            Ok((args, consumed)) => {
+                buf.advance(consumed);
+                if args.len() >= 3 && args[0].eq_ignore_ascii_case("replconf") && args[1].eq_ignore_ascii_case("ack") {
+                    let offset = args[2].parse().unwrap_or(0);
+                    let aof_offset = match args.get(3) {
+                        Some(fack) if fack.eq_ignore_ascii_case("fack") => args.get(4).and_then(|o| o.parse().ok()),
+                        _ => None,
+                    };
+                    replication.ack(id, offset, aof_offset);
+                }
+            }
+            Err(FrameError::Incomplete) => return Ok(()),
+            Err(FrameError::Invalid | FrameError::TooLarge(_)) => bail!("protocol error from replica"),
+        }
+    }
+}
+
+/// WAIT / WAITAOF: 클라이언트의 마지막 쓰기 (`offset`) 까지가 `numreplicas` 개의 replica 에 반영 (`fsynced` 면 AOF fsync) 되고,
+/// `local` 이면 이 서버의 AOF 에도 fsync 될 때까지 기다린다.
+/// 타임아웃이 지나면 그때까지의 결과를 돌려준다. 결과는 (로컬 fsync 여부, replica 수).
+/// 타임아웃이 0 이면 명령 락을 잡은 채로 불릴 수 있으므로 기다리지도, GETACK 을 보내지도 않는다.
+pub async fn wait(
+    state: &ServerState,
+    offset: u64,
+    local: bool,
+    numreplicas: usize,
+    fsynced: bool,
+    timeout: Option<Duration>,
+) -> (bool, usize) {
+    let replication = &state.replication;
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let mut local_fsynced = state.aof.as_ref().map(|aof| aof.fsynced_reploff());
+    let mut requested = timeout == Some(Duration::ZERO);
+
+    loop {
+        let notified = replication.ack_notify.notified();
+        let local_done = local_fsynced.as_ref().is_some_and(|rx| *rx.borrow() >= offset);
+        let acked = replication.acked_replicas(offset, fsynced);
+        if (!local || local_done) && acked >= numreplicas {
+            return (local_done, acked);
+        }
+
+        if !requested && acked < numreplicas {
+            // replica 들에게 지금 바로 ACK 를 보내달라고 요청한다
+            let _lock = state.command_lock.lock().await;
+            replication.feed(&["REPLCONF".to_string(), "GETACK".to_string(), "*".to_string()]);
+            requested = true;
+        }
+
+        let timed_out = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = notified => {}
+            _ = fsync_changed(local_fsynced.as_mut().filter(|_| local)) => {}
+            _ = timed_out => {
+                let local_done = local_fsynced.as_ref().is_some_and(|rx| *rx.borrow() >= offset);
+                return (local_done, replication.acked_replicas(offset, fsynced));
+            }
+        }
+    }
+}
+
+/// master 쪽: PSYNC 를 보낸 커넥션을 replica 링크로 전환한다.
+/// backlog 로 이어갈 수 있으면 빠진 스트림만 보내고, 아니면 스냅샷을 RDB 로 보낸다.
+/// 이후 쓰기 명령을 계속 흘려보낸다.
+pub async fn serve_replica(
+    mut socket: TcpStream,
+    state: Arc<ServerState>,
+    client: ClientState,
+    psync_replid: String,
+    psync_offset: i64,
+    mut buf: BytesMut,
+) -> Result<()> {
+    let replication = &state.replication;
+    let ip = socket.peer_addr()?.ip();
+
+    // 스냅샷 (또는 backlog) 과 replica 등록을 명령 락 안에서 해야 이후의 쓰기가 빠짐없이 스트림에 들어간다
+    let (sync, replid, offset, id, mut receiver) = {
+        let _lock = state.command_lock.lock().await;
+        let (sync, id, receiver) = match replication.partial_resync(&psync_replid, psync_offset) {
+            Some(missing) => {
+                let (id, receiver) = replication.register_replica(ip, client.listening_port, ReplicaState::Online);
+                (Ok(missing), id, receiver)
+            }
+            None => {
+                replication.create_backlog();
+                let snapshot = state.store.snapshot().await;
+                let (id, receiver) = replication.register_replica(ip, client.listening_port, ReplicaState::SendBulk);
+                (Err(snapshot), id, receiver)
+            }
+        };
+        (sync, replication.replid(), replication.master_repl_offset(), id, receiver)
+    };
+
+    let result = async {
+        match sync {
+            Ok(missing) => {
+                socket.write_all(format!("+CONTINUE {}\r\n", replid).as_bytes()).await?;
+                socket.write_all(&missing).await?;
+            }
+            Err(snapshot) => {
+                socket
+                    .write_all(format!("+FULLRESYNC {} {}\r\n", replid, offset).as_bytes())
+                    .await?;
+
+                if replication.diskless_sync && client.capa_eof {
+                    // diskless: 스냅샷을 메모리에서 인코딩해 바로 보내고 끝을 EOF 마커로 알린다
+                    let payload = RDB::encode_snapshots(&[snapshot]);
+                    let mark = new_replid();
+                    socket.write_all(format!("$EOF:{}\r\n", mark).as_bytes()).await?;
+                    socket.write_all(&payload).await?;
+                    socket.write_all(mark.as_bytes()).await?;
+                } else {
+                    // 디스크 기반 동기화: 덤프 파일을 먼저 쓰고 같은 내용을 전송한다.
+                    // 그 사이 BGSAVE 등이 파일을 바꿀 수 있으므로 경로로 다시 읽지 않는다.
+                    let payload = state.persistence.save_snapshot(snapshot).await?;
+                    socket.write_all(format!("${}\r\n", payload.len()).as_bytes()).await?;
+                    socket.write_all(&payload).await?;
+                }
+                replication.set_replica_state(id, ReplicaState::Online);
+            }
+        }
+
+        loop {
+            tokio::select! {
+                bytes = receiver.recv() => match bytes {
+                    Some(bytes) => socket.write_all(&bytes).await?,
+                    None => break,
+                },
+                n = socket.read_buf(&mut buf) => {
+                    if n? == 0 {
+                        break;
+                    }
+                    read_acks(replication, id, &mut buf)?;
+                }
+            }
+        }
+        Ok::<(), anyhow::Error>(())
+    }
+    .await;
+
+    replication.unregister_replica(id);
+    result
+}
@@ -0,0 +1,157 @@
+use crate::replication::{new_replid, read_rdb_payload, MasterAddr, Replication};
+use bytes::BytesMut;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::test;
+
+#[test]
+async fn test_parse_master_addr() {
+    assert_eq!(
+        MasterAddr::parse("localhost 6379"),
+        Some(MasterAddr { host: "localhost".to_string(), port: 6379 })
+    );
+    assert_eq!(MasterAddr::parse("localhost"), None);
+    assert_eq!(MasterAddr::parse("localhost abc"), None);
+    assert_eq!(MasterAddr::parse("localhost 6379 extra"), None);
+}
+
+#[test]
+async fn test_new_replid() {
+    let id = new_replid();
+    assert_eq!(id.len(), 40);
+    assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_ne!(id, new_replid());
+}
+
+#[test]
+async fn test_set_master() {
+    let replication = Replication::new(6380, Some(MasterAddr { host: "localhost".to_string(), port: 6379 }), 1024, false, true);
+    assert!(replication.is_replica());
+    assert!(replication.info().contains("role:slave\r\n"));
+
+    // replica 가 master 로 승격되면 새 replication id 를 가진다
+    let replid = replication.replid();
+    replication.set_master(None);
+    assert!(!replication.is_replica());
+    assert_ne!(replication.replid(), replid);
+    assert!(replication.info().contains("role:master\r\n"));
+}
+
+fn args(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+async fn test_partial_resync_from_backlog() {
+    let replication = Replication::new(6379, None, 1024, false, true);
+
+    // backlog 가 생기기 전의 쓰기는 offset 에 잡히지 않는다
+    replication.feed(&args(&["SET", "a", "1"]));
+    assert_eq!(replication.master_repl_offset(), 0);
+
+    replication.create_backlog();
+    let replid = replication.replid();
+    replication.feed(&args(&["SET", "a", "1"]));
+    let first = replication.master_repl_offset();
+    replication.feed(&args(&["SET", "b", "2"]));
+
+    // 첫 명령까지 받은 replica 는 두 번째 명령만 받으면 된다
+    let missing = replication.partial_resync(&replid, first as i64 + 1).unwrap();
+    assert_eq!(missing, b"*3\r\n$3\r\nSET\r\n$1\r\nb\r\n$1\r\n2\r\n");
+
+    // 이미 다 받은 replica
+    let end = replication.master_repl_offset() as i64 + 1;
+    assert_eq!(replication.partial_resync(&replid, end).unwrap(), b"");
+
+    // 모르는 ID 나 backlog 범위를 벗어난 offset 은 full resync
+    assert!(replication.partial_resync("?", -1).is_none());
+    assert!(replication.partial_resync(&replid, end + 1).is_none());
+    assert!(replication.partial_resync(&replid, 0).is_none());
+}
+
+#[test]
+async fn test_backlog_wraps_around() {
+    let replication = Replication::new(6379, None, 16, false, true);
+    replication.create_backlog();
+    let replid = replication.replid();
+    for _ in 0..4 {
+        replication.feed(&args(&["PING"]));
+    }
+    let end = replication.master_repl_offset() as i64 + 1;
+
+    // 마지막 16 바이트만 남아 있다
+    assert!(replication.partial_resync(&replid, end - 16).is_some());
+    assert!(replication.partial_resync(&replid, end - 17).is_none());
+    assert!(replication.info().contains("repl_backlog_histlen:16\r\n"));
+}
+
+#[test]
+async fn test_promoted_replica_keeps_history() {
+    let replication = Replication::new(6380, Some(MasterAddr { host: "localhost".to_string(), port: 6379 }), 1024, false, true);
+    replication.create_backlog();
+    let old_replid = replication.replid();
+    replication.feed_raw(bytes::Bytes::from_static(b"*1\r\n$4\r\nPING\r\n"));
+    let offset = replication.master_repl_offset() as i64 + 1;
+
+    replication.set_master(None);
+    assert_ne!(replication.replid(), old_replid);
+    assert!(replication.info().contains(&format!("master_replid2:{}\r\n", old_replid)));
+
+    // 예전 master 를 따르던 replica 는 승격된 서버에서 이어받을 수 있다
+    assert!(replication.partial_resync(&old_replid, offset).is_some());
+    // 승격 이후의 스트림은 예전 ID 로 받을 수 없다
+    replication.feed(&args(&["SET", "a", "1"]));
+    let end = replication.master_repl_offset() as i64 + 1;
+    assert!(replication.partial_resync(&old_replid, end).is_none());
+    assert!(replication.partial_resync(&replication.replid(), end).is_some());
+}
+
+#[test]
+async fn test_read_diskless_payload() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mark = new_replid();
+
+    let master = {
+        let mark = mark.clone();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket.write_all(format!("$EOF:{}\r\nREDIS0011", mark).as_bytes()).await.unwrap();
+            socket.flush().await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+            // 마커가 두 번에 나눠 도착하고, 바로 뒤에 명령 스트림이 붙어 온다
+            socket.write_all(format!("\u{ff}{}", &mark[..10]).as_bytes()).await.unwrap();
+            socket.flush().await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            socket.write_all(format!("{}*1\r\n$4\r\nPING\r\n", &mark[10..]).as_bytes()).await.unwrap();
+        })
+    };
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut buf = BytesMut::new();
+    let payload = read_rdb_payload(&mut stream, &mut buf).await.unwrap();
+    assert_eq!(payload, "REDIS0011\u{ff}".as_bytes());
+
+    master.await.unwrap();
+    while buf.len() < 14 {
+        use tokio::io::AsyncReadExt;
+        stream.read_buf(&mut buf).await.unwrap();
+    }
+    assert_eq!(&buf[..], b"*1\r\n$4\r\nPING\r\n");
+}
+
+#[test]
+async fn test_read_payload_with_length() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        socket.write_all(b"\n$9\r\nREDIS0011*1\r\n$4\r\nPING\r\n").await.unwrap();
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut buf = BytesMut::new();
+    let payload = read_rdb_payload(&mut stream, &mut buf).await.unwrap();
+    assert_eq!(payload, b"REDIS0011");
+}
@@ -0,0 +1,583 @@
+use crate::lua::interpreter::{Interpreter, LuaError};
+use crate::lua::parser::parse;
+use crate::lua::redislib;
+use crate::lua::value::{Table, Value};
+use crate::protocol::decoder::Reply;
+use crate::sha1::sha1_hex;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+
+/// 에러 메시지와 컴파일에 쓰는 청크 이름
+const CHUNK_NAME: &str = "user_script";
+const FUNCTION_CHUNK_NAME: &str = "user_function";
+/// 라이브러리 코드가 이보다 오래 실행되면 FUNCTION LOAD 를 실패시킨다
+const LIBRARY_LOAD_TIMEOUT: Duration = Duration::from_millis(500);
+/// redis.register_function 에 줄 수 있는 함수 플래그
+const FUNCTION_FLAGS: [&str; 5] = ["no-writes", "allow-oom", "allow-stale", "no-cluster", "allow-cross-slot-keys"];
+/// loglevel 과 redis.LOG_DEBUG .. LOG_WARNING 의 순서
+pub const LOG_LEVELS: [&str; 4] = ["debug", "verbose", "notice", "warning"];
+/// Redis 의 기본 loglevel
+pub const LOG_NOTICE: usize = 2;
+
+/// 인터프리터가 재귀로 동작하므로 스크립트 스레드의 스택을 넉넉히 잡는다
+const SCRIPT_STACK_SIZE: usize = 32 * 1024 * 1024;
+
+/// EVAL / EVALSHA 로 실행 중인 스크립트
+#[derive(Debug)]
+pub struct RunningScript {
+    started: Instant,
+    killed: AtomicBool,
+    /// 쓰기 명령을 실행한 스크립트는 SCRIPT KILL 로 멈출 수 없다
+    wrote: AtomicBool,
+    /// FCALL 로 실행한 함수 (FUNCTION KILL 로만 멈춘다)
+    function: bool,
+    /// FCALL_RO 나 no-writes 함수. 쓰기 명령을 실행할 수 없다.
+    read_only: bool,
+}
+
+impl RunningScript {
+    pub fn mark_write(&self) {
+        self.wrote.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+}
+
+/// 라이브러리에 등록된 함수
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub flags: Vec<String>,
+}
+
+impl FunctionInfo {
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+}
+
+/// FUNCTION LOAD 로 올린 라이브러리
+#[derive(Debug)]
+pub struct Library {
+    pub name: String,
+    pub code: Arc<str>,
+    pub functions: Vec<FunctionInfo>,
+}
+
+/// 스크립트 스레드가 명령 실행 쪽 (EVAL 을 받은 커넥션) 에 보내는 요청
+pub enum Event {
+    /// redis.call / redis.pcall. 응답을 `reply` 로 돌려받는다.
+    Call { args: Vec<String>, reply: std_mpsc::Sender<Reply> },
+    /// 스크립트가 끝났다. 에러는 그대로 보낼 에러 응답이다.
+    Done(Result<Reply, String>),
+}
+
+enum Job {
+    Run { target: Target, keys: Vec<String>, args: Vec<String>, script: Arc<RunningScript>, events: UnboundedSender<Event> },
+    /// 라이브러리 코드를 실행해서 등록된 함수들을 돌려준다
+    LoadLibrary { code: Arc<str>, reply: oneshot::Sender<Result<Vec<FunctionInfo>, String>> },
+    Flush,
+    FlushFunctions,
+}
+
+enum Target {
+    Script { sha: String, source: Arc<str> },
+    Function { code: Arc<str>, name: String },
+}
+
+/// 스크립트 캐시와 스크립트를 실행하는 스레드
+pub struct Scripting {
+    /// SHA1 -> 소스
+    scripts: Mutex<HashMap<String, Arc<str>>>,
+    running: Mutex<Option<Arc<RunningScript>>>,
+    /// 스크립트가 이보다 오래 실행되면 다른 명령에 BUSY 로 응답한다
+    busy_time: Duration,
+    jobs: Mutex<std_mpsc::Sender<Job>>,
+    /// 라이브러리 코드의 SHA1 -> 라이브러리. 라이브러리 목록 자체는 Store 에 있다.
+    libraries: Mutex<HashMap<String, Arc<Library>>>,
+}
+
+impl Scripting {
+    pub fn new(busy_time: Duration) -> Scripting {
+        Self::with_log_level(busy_time, LOG_NOTICE)
+    }
+
+    /// `log_level` 보다 낮은 레벨의 redis.log 는 버린다
+    pub fn with_log_level(busy_time: Duration, log_level: usize) -> Scripting {
+        let (jobs, receiver) = std_mpsc::channel();
+        thread::Builder::new()
+            .name("scripting".to_string())
+            .stack_size(SCRIPT_STACK_SIZE)
+            .spawn(move || run_scripts(receiver, log_level))
+            .expect("failed to spawn scripting thread");
+        Scripting {
+            scripts: Mutex::new(HashMap::new()),
+            running: Mutex::new(None),
+            busy_time,
+            jobs: Mutex::new(jobs),
+            libraries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 스크립트를 컴파일해 보고 캐시에 넣는다. SHA1 을 돌려준다.
+    pub fn load(&self, source: &str) -> Result<String, String> {
+        parse(source.as_bytes(), CHUNK_NAME).map_err(|e| format!("ERR Error compiling script (new function): {}", e))?;
+        let sha = sha1_hex(source.as_bytes());
+        self.scripts.lock().unwrap().entry(sha.clone()).or_insert_with(|| source.into());
+        Ok(sha)
+    }
+
+    pub fn get(&self, sha: &str) -> Option<Arc<str>> {
+        self.scripts.lock().unwrap().get(&sha.to_lowercase()).cloned()
+    }
+
+    pub fn exists(&self, sha: &str) -> bool {
+        self.scripts.lock().unwrap().contains_key(&sha.to_lowercase())
+    }
+
+    pub fn flush(&self) {
+        self.scripts.lock().unwrap().clear();
+        let _ = self.jobs.lock().unwrap().send(Job::Flush);
+    }
+
+    /// 스크립트 스레드에서 실행을 시작한다. 끝날 때까지 돌려받은 수신기의 Event 를 처리하고 finish() 를 부른다.
+    pub fn start(&self, sha: String, source: Arc<str>, keys: Vec<String>, args: Vec<String>) -> (Arc<RunningScript>, UnboundedReceiver<Event>) {
+        self.run(Target::Script { sha, source }, keys, args, false)
+    }
+
+    /// FCALL / FCALL_RO. start() 와 같이 Event 를 처리한다.
+    pub fn start_function(&self, library: &Library, name: &str, keys: Vec<String>, args: Vec<String>, read_only: bool) -> (Arc<RunningScript>, UnboundedReceiver<Event>) {
+        self.run(Target::Function { code: Arc::clone(&library.code), name: name.to_string() }, keys, args, read_only)
+    }
+
+    fn run(&self, target: Target, keys: Vec<String>, args: Vec<String>, read_only: bool) -> (Arc<RunningScript>, UnboundedReceiver<Event>) {
+        let script = Arc::new(RunningScript {
+            started: Instant::now(),
+            killed: AtomicBool::new(false),
+            wrote: AtomicBool::new(false),
+            function: matches!(target, Target::Function { .. }),
+            read_only,
+        });
+        let (events, receiver) = mpsc::unbounded_channel();
+        *self.running.lock().unwrap() = Some(Arc::clone(&script));
+        let job = Job::Run { target, keys, args, script: Arc::clone(&script), events };
+        if let Err(std_mpsc::SendError(Job::Run { events, .. })) = self.jobs.lock().unwrap().send(job) {
+            let _ = events.send(Event::Done(Err("ERR Error running script: scripting thread is not running".to_string())));
+        }
+        (script, receiver)
+    }
+
+    /// 라이브러리 코드를 실행해서 등록된 함수들을 알아낸다. 같은 코드는 다시 실행하지 않는다.
+    pub async fn library(&self, code: &str) -> Result<Arc<Library>, String> {
+        let name = library_name(code)?;
+        let sha = sha1_hex(code.as_bytes());
+        if let Some(library) = self.libraries.lock().unwrap().get(&sha) {
+            return Ok(Arc::clone(library));
+        }
+        let code: Arc<str> = code.into();
+        let (reply, receiver) = oneshot::channel();
+        let not_running = || "ERR Error loading library: scripting thread is not running".to_string();
+        self.jobs.lock().unwrap().send(Job::LoadLibrary { code: Arc::clone(&code), reply }).map_err(|_| not_running())?;
+        let functions = receiver.await.map_err(|_| not_running())??;
+        let library = Arc::new(Library { name, code, functions });
+        self.libraries.lock().unwrap().insert(sha, Arc::clone(&library));
+        Ok(library)
+    }
+
+    /// FUNCTION FLUSH / DELETE 뒤에 쓰지 않는 라이브러리를 잊는다
+    pub fn flush_functions(&self) {
+        self.libraries.lock().unwrap().clear();
+        let _ = self.jobs.lock().unwrap().send(Job::FlushFunctions);
+    }
+
+    pub fn finish(&self) {
+        *self.running.lock().unwrap() = None;
+    }
+
+    /// busy_time 보다 오래 실행 중인 스크립트가 있는지
+    pub fn is_busy(&self) -> bool {
+        self.running.lock().unwrap().as_ref().is_some_and(|script| script.started.elapsed() >= self.busy_time)
+    }
+
+    /// SCRIPT KILL (`function` 이 true 면 FUNCTION KILL)
+    pub fn kill(&self, function: bool) -> Result<(), &'static str> {
+        let running = self.running.lock().unwrap();
+        let Some(script) = running.as_ref() else {
+            return Err("NOTBUSY No scripts in execution right now.");
+        };
+        if script.wrote.load(Ordering::SeqCst) {
+            return Err("UNKILLABLE Sorry the script already executed write commands against the dataset. You can either wait the script termination or kill the server in a hard way using the SHUTDOWN NOSAVE command.");
+        }
+        match (script.function, function) {
+            (true, false) => return Err("BUSY Redis is busy running a script. You can only call FUNCTION KILL or SHUTDOWN NOSAVE."),
+            (false, true) => return Err("BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE."),
+            _ => {}
+        }
+        script.killed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// 실행 중인 스크립트의 정보. redis 라이브러리 함수들이 공유한다.
+struct Context {
+    script: Arc<RunningScript>,
+    events: UnboundedSender<Event>,
+}
+
+/// 스크립트 스레드. 인터프리터는 하나를 계속 쓰고, 컴파일한 함수는 SHA1 으로 캐시한다.
+/// FCALL 로 부르는 함수는 EVAL 과 섞이지 않도록 다른 인터프리터에서 실행한다.
+fn run_scripts(jobs: std_mpsc::Receiver<Job>, log_level: usize) {
+    let context: Rc<RefCell<Option<Context>>> = Rc::new(RefCell::new(None));
+    let mut interp = Interpreter::new(CHUNK_NAME);
+    interp.set_global("redis", Value::table(redis_library(&context, log_level)));
+    redislib::open(&mut interp);
+    // 스크립트끼리 전역 변수로 상태를 주고받지 못하게 한다
+    interp.strict_globals = true;
+    let interrupt_context = Rc::clone(&context);
+    interp.set_interrupt(move || interrupt_context.borrow().as_ref().is_some_and(|c| c.script.killed.load(Ordering::SeqCst)));
+
+    let registry: Registry = Rc::new(RefCell::new(None));
+    let load_deadline: Rc<Cell<Option<Instant>>> = Rc::new(Cell::new(None));
+    let mut functions = Interpreter::new(FUNCTION_CHUNK_NAME);
+    let mut redis = redis_library(&context, log_level);
+    let register_registry = Rc::clone(&registry);
+    redis.set_str("register_function", Value::builtin("register_function", move |interp, args| register_function(interp, args, &register_registry)));
+    functions.set_global("redis", Value::table(redis));
+    redislib::open(&mut functions);
+    functions.strict_globals = true;
+    let interrupt_context = Rc::clone(&context);
+    let interrupt_deadline = Rc::clone(&load_deadline);
+    functions.set_interrupt(move || {
+        interrupt_deadline.get().is_some_and(|deadline| Instant::now() >= deadline)
+            || interrupt_context.borrow().as_ref().is_some_and(|c| c.script.killed.load(Ordering::SeqCst))
+    });
+
+    let mut compiled: HashMap<String, Value> = HashMap::new();
+    // 라이브러리 코드의 SHA1 -> 함수 이름 -> 콜백
+    let mut libraries: HashMap<String, HashMap<String, Value>> = HashMap::new();
+    for job in jobs {
+        let (target, keys, args, script, events) = match job {
+            Job::Run { target, keys, args, script, events } => (target, keys, args, script, events),
+            Job::LoadLibrary { code, reply } => {
+                let result = load_library(&mut functions, &registry, &load_deadline, &code).map(|registered| {
+                    let infos = registered.iter().map(|(info, _)| info.clone()).collect();
+                    libraries.insert(sha1_hex(code.as_bytes()), registered.into_iter().map(|(info, callback)| (info.name, callback)).collect());
+                    infos
+                });
+                let _ = reply.send(result);
+                continue;
+            }
+            Job::Flush => {
+                compiled.clear();
+                continue;
+            }
+            Job::FlushFunctions => {
+                libraries.clear();
+                continue;
+            }
+        };
+
+        let (interp, function, call_args, name, kill_command) = match target {
+            Target::Script { sha, source } => {
+                let function = match compiled.get(&sha) {
+                    Some(function) => function.clone(),
+                    None => match interp.load(source.as_bytes()) {
+                        Ok(function) => compiled.entry(sha.clone()).or_insert(function).clone(),
+                        Err(e) => {
+                            let _ = events.send(Event::Done(Err(format!("ERR Error compiling script (new function): {}", e))));
+                            continue;
+                        }
+                    },
+                };
+                interp.set_global("KEYS", Value::table(Table::from_array(keys.into_iter().map(Value::str).collect())));
+                interp.set_global("ARGV", Value::table(Table::from_array(args.into_iter().map(Value::str).collect())));
+                (&mut interp, function, Vec::new(), sha, "SCRIPT KILL")
+            }
+            Target::Function { code, name } => {
+                let sha = sha1_hex(code.as_bytes());
+                if !libraries.contains_key(&sha) {
+                    match load_library(&mut functions, &registry, &load_deadline, &code) {
+                        Ok(registered) => {
+                            libraries.insert(sha.clone(), registered.into_iter().map(|(info, callback)| (info.name, callback)).collect());
+                        }
+                        Err(e) => {
+                            let _ = events.send(Event::Done(Err(e)));
+                            continue;
+                        }
+                    }
+                }
+                let Some(callback) = libraries[&sha].get(&name).cloned() else {
+                    let _ = events.send(Event::Done(Err("ERR Function not found".to_string())));
+                    continue;
+                };
+                let keys = Value::table(Table::from_array(keys.into_iter().map(Value::str).collect()));
+                let args = Value::table(Table::from_array(args.into_iter().map(Value::str).collect()));
+                (&mut functions, callback, vec![keys, args], name, "FUNCTION KILL")
+            }
+        };
+
+        *context.borrow_mut() = Some(Context { script, events: events.clone() });
+        let result = interp.call(&function, call_args);
+        context.borrow_mut().take();
+
+        let reply = match result {
+            Ok(values) => Ok(lua_to_reply(values.first().unwrap_or(&Value::Nil))),
+            Err(LuaError::Interrupted) => Err(format!("ERR Script killed by user with {}...", kill_command)),
+            Err(LuaError::Error(value)) => Err(format!("{} script: {}", error_message(&value), name)),
+        };
+        let _ = events.send(Event::Done(reply));
+    }
+}
+
+/// 라이브러리를 올리는 동안 redis.register_function 으로 등록한 함수들
+type Registry = Rc<RefCell<Option<Vec<(FunctionInfo, Value)>>>>;
+
+/// 라이브러리 코드를 실행해서 등록한 함수들을 모은다
+fn load_library(interp: &mut Interpreter, registry: &Registry, deadline: &Cell<Option<Instant>>, code: &str) -> Result<Vec<(FunctionInfo, Value)>, String> {
+    // 첫 줄의 메타데이터는 Lua 코드가 아니다. 줄 번호가 맞도록 줄바꿈은 남긴다.
+    let body = code.find('\n').map_or("", |i| &code[i..]);
+    let chunk = interp.load(body.as_bytes()).map_err(|e| format!("ERR Error compiling function: {}", e))?;
+    *registry.borrow_mut() = Some(Vec::new());
+    deadline.set(Some(Instant::now() + LIBRARY_LOAD_TIMEOUT));
+    let result = interp.call(&chunk, Vec::new());
+    deadline.set(None);
+    let registered = registry.borrow_mut().take().unwrap_or_default();
+    match result {
+        Ok(_) if registered.is_empty() => Err("ERR No functions registered".to_string()),
+        Ok(_) => Ok(registered),
+        Err(LuaError::Interrupted) => Err("ERR FUNCTION LOAD timeout".to_string()),
+        Err(LuaError::Error(value)) => Err(format!("ERR Error registering functions: {}", Interpreter::display(&value))),
+    }
+}
+
+/// redis.register_function('name', callback) 또는
+/// redis.register_function{function_name='name', callback=callback, flags={...}, description='...'}
+fn register_function(interp: &mut Interpreter, args: Vec<Value>, registry: &Registry) -> Result<Vec<Value>, LuaError> {
+    let mut registry = registry.borrow_mut();
+    let Some(registered) = registry.as_mut() else {
+        return Err(interp.error("redis.register_function can only be called on FUNCTION LOAD command"));
+    };
+    let (name, callback, flags, description) = match args.as_slice() {
+        [name, callback] => (name.clone(), callback.clone(), Value::Nil, Value::Nil),
+        [Value::Table(table)] => {
+            let table = table.borrow();
+            (table.get_str("function_name"), table.get_str("callback"), table.get_str("flags"), table.get_str("description"))
+        }
+        _ => return Err(interp.error("wrong number of arguments to redis.register_function")),
+    };
+    let Value::Str(name) = name else {
+        return Err(interp.error("function_name argument given to redis.register_function must be a string"));
+    };
+    let name = String::from_utf8_lossy(&name).to_string();
+    if !is_valid_name(&name) {
+        return Err(interp.error("Function names can only contain letters, numbers, or underscores(_) and must be at least one character long"));
+    }
+    if !matches!(callback, Value::Function(_)) {
+        return Err(interp.error("callback argument given to redis.register_function must be a function"));
+    }
+    let flags = match flags {
+        Value::Nil => Vec::new(),
+        Value::Table(table) => {
+            let table = table.borrow();
+            let mut flags = Vec::new();
+            for i in 1..=table.len() {
+                match table.get(&Value::Number(i as f64)) {
+                    Value::Str(flag) if FUNCTION_FLAGS.contains(&&*String::from_utf8_lossy(&flag)) => flags.push(String::from_utf8_lossy(&flag).to_string()),
+                    _ => return Err(interp.error("unknown flag given")),
+                }
+            }
+            flags
+        }
+        _ => return Err(interp.error("flags argument to redis.register_function must be a table representing function flags")),
+    };
+    let description = match description {
+        Value::Nil => None,
+        Value::Str(description) => Some(String::from_utf8_lossy(&description).to_string()),
+        _ => return Err(interp.error("description argument given to redis.register_function must be a string")),
+    };
+    if registered.iter().any(|(info, _)| info.name == name) {
+        return Err(interp.error("Function already exists in the library"));
+    }
+    registered.push((FunctionInfo { name, description, flags }, callback));
+    Ok(Vec::new())
+}
+
+/// 라이브러리 코드 첫 줄의 메타데이터 (`#!lua name=mylib`) 에서 라이브러리 이름을 읽는다
+pub fn library_name(code: &str) -> Result<String, String> {
+    let Some(metadata) = code.lines().next().and_then(|line| line.strip_prefix("#!")) else {
+        return Err("ERR Missing library metadata".to_string());
+    };
+    let mut parts = metadata.split_whitespace();
+    let engine = parts.next().unwrap_or("");
+    if !engine.eq_ignore_ascii_case("lua") {
+        return Err(format!("ERR Engine '{}' not found", engine));
+    }
+    let mut name = None;
+    for part in parts {
+        match part.strip_prefix("name=") {
+            Some(value) => name = Some(value),
+            None => return Err(format!("ERR Invalid metadata value given: {}", part)),
+        }
+    }
+    let Some(name) = name else {
+        return Err("ERR Library name was not given".to_string());
+    };
+    if !is_valid_name(name) {
+        return Err("ERR Library names can only contain letters, numbers, or underscores(_) and must be at least one character long".to_string());
+    }
+    Ok(name.to_string())
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// 스크립트를 멈춘 에러 값을 에러 응답으로 바꾼다
+fn error_message(value: &Value) -> String {
+    if let Value::Table(table) = value {
+        if let Value::Str(err) = table.borrow().get_str("err") {
+            return String::from_utf8_lossy(&err).to_string();
+        }
+    }
+    format!("ERR {}", Interpreter::display(value))
+}
+
+/// redis.call, redis.pcall 등이 들어 있는 redis 테이블
+fn redis_library(context: &Rc<RefCell<Option<Context>>>, log_level: usize) -> Table {
+    let mut redis = Table::new();
+    for (name, raise) in [("call", true), ("pcall", false)] {
+        let context = Rc::clone(context);
+        redis.set_str(name, Value::builtin(name, move |interp, args| call(interp, args, &context, raise)));
+    }
+    redis.set_str(
+        "error_reply",
+        Value::builtin("error_reply", |interp, args| match args.first().and_then(Value::to_str) {
+            Some(msg) => Ok(vec![reply_table("err", Value::Str(msg))]),
+            None => Err(interp.error("wrong number or type of arguments")),
+        }),
+    );
+    redis.set_str(
+        "status_reply",
+        Value::builtin("status_reply", |interp, args| match args.first().and_then(Value::to_str) {
+            Some(msg) => Ok(vec![reply_table("ok", Value::Str(msg))]),
+            None => Err(interp.error("wrong number or type of arguments")),
+        }),
+    );
+    redis.set_str(
+        "sha1hex",
+        Value::builtin("sha1hex", |interp, args| match args.first().and_then(Value::to_str) {
+            Some(s) => Ok(vec![Value::str(sha1_hex(&s))]),
+            None => Err(interp.error("wrong number of arguments")),
+        }),
+    );
+    redis.set_str(
+        "log",
+        Value::builtin("log", move |interp, args| {
+            if args.len() < 2 {
+                return Err(interp.error("redis.log() requires two arguments or more."));
+            }
+            let Some(level) = args[0].to_number() else {
+                return Err(interp.error("First argument must be a number (log level)."));
+            };
+            if !(0.0..LOG_LEVELS.len() as f64).contains(&level) {
+                return Err(interp.error("Invalid debug level."));
+            }
+            if (level as usize) < log_level {
+                return Ok(Vec::new());
+            }
+            let message: Vec<String> = args[1..].iter().map(Interpreter::display).collect();
+            // Redis 로그와 같은 레벨 표시 (. - * #)
+            eprintln!("{} {}", ['.', '-', '*', '#'][level as usize], message.join(" "));
+            Ok(Vec::new())
+        }),
+    );
+    for (i, level) in LOG_LEVELS.iter().enumerate() {
+        redis.set_str(&format!("LOG_{}", level.to_uppercase()), Value::Number(i as f64));
+    }
+    redis
+}
+
+fn reply_table(field: &str, value: Value) -> Value {
+    let mut table = Table::new();
+    table.set_str(field, value);
+    Value::table(table)
+}
+
+/// redis.call / redis.pcall. call 은 에러 응답을 Lua 에러로 던지고, pcall 은 {err=...} 로 돌려준다.
+fn call(interp: &mut Interpreter, args: Vec<Value>, context: &Rc<RefCell<Option<Context>>>, raise: bool) -> Result<Vec<Value>, LuaError> {
+    if args.is_empty() {
+        return Err(interp.error("Please specify at least one argument for this redis lib call"));
+    }
+    let mut command = Vec::with_capacity(args.len());
+    for arg in &args {
+        match arg {
+            Value::Str(_) | Value::Number(_) => command.push(String::from_utf8_lossy(&arg.to_str().unwrap()).to_string()),
+            _ => return Err(interp.error("Lua redis lib command arguments must be strings or integers")),
+        }
+    }
+    let events = match context.borrow().as_ref() {
+        Some(context) => context.events.clone(),
+        None => return Err(interp.error("redis.call can only be used inside a script")),
+    };
+    let (reply, receiver) = std_mpsc::channel();
+    let reply = match events.send(Event::Call { args: command, reply }) {
+        Ok(()) => receiver.recv().ok(),
+        Err(_) => None,
+    };
+    let Some(reply) = reply else {
+        return Err(LuaError::Interrupted);
+    };
+    match reply {
+        Reply::Error(err) if raise => Err(LuaError::Error(reply_table("err", Value::str(err)))),
+        reply => Ok(vec![reply_to_lua(&reply)]),
+    }
+}
+
+/// 명령 응답을 Lua 값으로 바꾼다 (nil 은 false, 상태와 에러는 {ok=...} / {err=...})
+pub fn reply_to_lua(reply: &Reply) -> Value {
+    match reply {
+        Reply::Integer(n) => Value::Number(*n as f64),
+        Reply::Bulk(Some(s)) => Value::str(s),
+        Reply::Bulk(None) | Reply::Array(None) => Value::Boolean(false),
+        Reply::Array(Some(items)) => Value::table(Table::from_array(items.iter().map(reply_to_lua).collect())),
+        // 스크립트는 RESP2 로 받으므로 맵은 키와 값을 번갈아 담은 배열이 된다
+        Reply::Map(entries) => Value::table(Table::from_array(entries.iter().flat_map(|(k, v)| [reply_to_lua(k), reply_to_lua(v)]).collect())),
+        Reply::Simple(s) => reply_table("ok", Value::str(s)),
+        Reply::Error(s) => reply_table("err", Value::str(s)),
+    }
+}
+
+/// 스크립트가 돌려준 Lua 값을 응답으로 바꾼다. 숫자는 정수로 자르고, 배열은 첫 nil 앞까지만 쓴다.
+pub fn lua_to_reply(value: &Value) -> Reply {
+    match value {
+        Value::Number(n) => Reply::Integer(*n as i64),
+        Value::Str(s) => Reply::Bulk(Some(String::from_utf8_lossy(s).to_string())),
+        Value::Boolean(true) => Reply::Integer(1),
+        Value::Table(table) => {
+            let table = table.borrow();
+            if let Value::Str(err) = table.get_str("err") {
+                return Reply::Error(String::from_utf8_lossy(&err).to_string());
+            }
+            if let Value::Str(ok) = table.get_str("ok") {
+                return Reply::Simple(String::from_utf8_lossy(&ok).to_string());
+            }
+            let items = (1..)
+                .map(|i| table.get(&Value::Number(i as f64)))
+                .take_while(|item| !matches!(item, Value::Nil))
+                .map(|item| lua_to_reply(&item))
+                .collect();
+            Reply::Array(Some(items))
+        }
+        Value::Nil | Value::Boolean(false) | Value::Function(_) => Reply::Bulk(None),
+    }
+}
@@ -0,0 +1,205 @@
+use crate::lua::value::Value;
+use crate::protocol::decoder::Reply;
+use crate::protocol::encoder::RedisEncoder;
+use crate::scripting::{library_name, lua_to_reply, reply_to_lua, Event, FunctionInfo, Scripting};
+use bytes::BytesMut;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::test;
+
+fn bulk(s: &str) -> Reply {
+    Reply::Bulk(Some(s.to_string()))
+}
+
+/// 스크립트가 끝날 때까지 redis.call 에 `respond` 의 응답을 돌려준다
+async fn finish(events: &mut UnboundedReceiver<Event>, respond: impl Fn(Vec<String>) -> Reply) -> Result<Reply, String> {
+    loop {
+        match events.recv().await.unwrap() {
+            Event::Call { args, reply } => reply.send(respond(args)).unwrap(),
+            Event::Done(result) => return result,
+        }
+    }
+}
+
+async fn run(scripting: &Scripting, source: &str, keys: &[&str], args: &[&str]) -> Result<Reply, String> {
+    let sha = scripting.load(source)?;
+    let (_, mut events) = scripting.start(sha.clone(), scripting.get(&sha).unwrap(), keys.iter().map(|k| k.to_string()).collect(), args.iter().map(|a| a.to_string()).collect());
+    let result = finish(&mut events, |_| Reply::Simple("OK".to_string())).await;
+    scripting.finish();
+    result
+}
+
+#[test]
+async fn test_reply_conversions() {
+    // nil 은 false 가 되고, false 는 다시 nil 이 된다
+    let reply = Reply::Array(Some(vec![Reply::Integer(1), bulk("a"), Reply::Bulk(None), Reply::Simple("OK".to_string()), Reply::Error("ERR x".to_string())]));
+    assert_eq!(lua_to_reply(&reply_to_lua(&reply)), reply);
+    assert!(matches!(reply_to_lua(&Reply::Bulk(None)), Value::Boolean(false)));
+
+    assert_eq!(lua_to_reply(&Value::Number(3.99)), Reply::Integer(3));
+    assert_eq!(lua_to_reply(&Value::Boolean(true)), Reply::Integer(1));
+    assert_eq!(lua_to_reply(&Value::Nil), Reply::Bulk(None));
+}
+
+#[test]
+async fn test_eval_with_keys_and_args() {
+    let scripting = Scripting::new(Duration::from_secs(5));
+    let result = run(&scripting, "return {KEYS[1], ARGV[2], 1.5, nil, 'after nil'}", &["k"], &["a", "b"]).await;
+    assert_eq!(result, Ok(Reply::Array(Some(vec![bulk("k"), bulk("b"), Reply::Integer(1)]))));
+    let result = run(&scripting, "return redis.status_reply('PONG')", &[], &[]).await;
+    assert_eq!(result, Ok(Reply::Simple("PONG".to_string())));
+    // 상태 응답의 줄바꿈으로 가짜 응답을 끼워 넣을 수 없다
+    let result = run(&scripting, "return redis.status_reply('x\\r\\n+INJ')", &[], &[]).await.unwrap();
+    let mut dst = BytesMut::new();
+    RedisEncoder::new().encode_reply(&mut dst, &result);
+    assert_eq!(&dst[..], b"+x  +INJ\r\n");
+    let result = run(&scripting, "return redis.sha1hex('')", &[], &[]).await;
+    assert_eq!(result, Ok(bulk("da39a3ee5e6b4b0d3255bfef95601890afd80709")));
+    // Redis 가 넣어 주는 라이브러리
+    let result = run(&scripting, "return {cjson.encode({ARGV[1]}), cmsgpack.unpack(cmsgpack.pack(7)), struct.size('>I4'), bit.band(6, 3)}", &[], &["a"]).await;
+    assert_eq!(result, Ok(Reply::Array(Some(vec![bulk(r#"["a"]"#), Reply::Integer(7), Reply::Integer(4), Reply::Integer(2)]))));
+}
+
+#[test]
+async fn test_redis_call_and_errors() {
+    let scripting = Scripting::new(Duration::from_secs(5));
+    let source = "local v = redis.call('GET', KEYS[1]) local r = redis.pcall('INCR', KEYS[1]) return {v, r.err}";
+    let sha = scripting.load(source).unwrap();
+    let (_, mut events) = scripting.start(sha.clone(), scripting.get(&sha).unwrap(), vec!["k".to_string()], vec![]);
+    let result = finish(&mut events, |args| match args[0].as_str() {
+        "GET" => bulk("v"),
+        _ => Reply::Error("ERR value is not an integer or out of range".to_string()),
+    })
+    .await;
+    scripting.finish();
+    assert_eq!(result, Ok(Reply::Array(Some(vec![bulk("v"), bulk("ERR value is not an integer or out of range")]))));
+
+    // redis.call 의 에러 응답은 스크립트를 멈춘다
+    let sha = scripting.load("return redis.call('INCR', 'k')").unwrap();
+    let (_, mut events) = scripting.start(sha.clone(), scripting.get(&sha).unwrap(), vec![], vec![]);
+    let result = finish(&mut events, |_| Reply::Error("ERR boom".to_string())).await;
+    scripting.finish();
+    assert_eq!(result, Err(format!("ERR boom script: {}", sha)));
+
+    let sha = scripting.load("local x = 1\nreturn x.y").unwrap();
+    let result = run(&scripting, "local x = 1\nreturn x.y", &[], &[]).await;
+    assert_eq!(result, Err(format!("ERR user_script:2: attempt to index local 'x' (a number value) script: {}", sha)));
+    assert_eq!(run(&scripting, "foo = 1", &[], &[]).await.unwrap_err().split(" script:").next(), Some("ERR user_script:1: Attempt to modify a readonly table"));
+    assert_eq!(scripting.load("return ("), Err("ERR Error compiling script (new function): user_script:1: unexpected symbol near <eof>".to_string()));
+}
+
+#[test]
+async fn test_script_cache() {
+    let scripting = Scripting::new(Duration::from_secs(5));
+    let sha = scripting.load("return 'hello moon'").unwrap();
+    assert_eq!(sha, "8e3d8cfcbb6571ecf555cc0a7d6fb950b4437dc6");
+    assert!(scripting.exists(&sha.to_uppercase()));
+    scripting.flush();
+    assert!(!scripting.exists(&sha));
+    assert!(scripting.get(&sha).is_none());
+}
+
+#[test]
+async fn test_redis_log() {
+    // loglevel 보다 낮은 레벨은 버리고, 레벨이 숫자가 아니거나 범위 밖이면 에러
+    let scripting = Scripting::with_log_level(Duration::from_secs(5), 3);
+    let result = run(&scripting, "redis.log(redis.LOG_DEBUG, 'dropped') redis.log(redis.LOG_WARNING, 'kept', 1) return redis.LOG_WARNING", &[], &[]).await;
+    assert_eq!(result, Ok(Reply::Integer(3)));
+    let err = run(&scripting, "redis.log(4, 'x')", &[], &[]).await.unwrap_err();
+    assert!(err.contains("Invalid debug level."), "{}", err);
+    let err = run(&scripting, "redis.log('warning', 'x')", &[], &[]).await.unwrap_err();
+    assert!(err.contains("First argument must be a number (log level)."), "{}", err);
+}
+
+#[test]
+async fn test_script_kill() {
+    let scripting = Scripting::new(Duration::from_millis(10));
+    assert_eq!(scripting.kill(false), Err("NOTBUSY No scripts in execution right now."));
+
+    let sha = scripting.load("while true do end").unwrap();
+    let (_, mut events) = scripting.start(sha.clone(), scripting.get(&sha).unwrap(), vec![], vec![]);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(scripting.is_busy());
+    assert_eq!(scripting.kill(false), Ok(()));
+    assert_eq!(finish(&mut events, |_| Reply::Bulk(None)).await, Err("ERR Script killed by user with SCRIPT KILL...".to_string()));
+    scripting.finish();
+    assert!(!scripting.is_busy());
+
+    // 쓰기를 한 스크립트는 멈출 수 없다
+    let sha = scripting.load("redis.call('SET', 'k', 'v') return 1").unwrap();
+    let (script, mut events) = scripting.start(sha.clone(), scripting.get(&sha).unwrap(), vec![], vec![]);
+    let Some(Event::Call { reply, .. }) = events.recv().await else { panic!("expected redis.call") };
+    script.mark_write();
+    assert!(scripting.kill(false).unwrap_err().starts_with("UNKILLABLE"));
+    reply.send(Reply::Simple("OK".to_string())).unwrap();
+    assert_eq!(finish(&mut events, |_| Reply::Bulk(None)).await, Ok(Reply::Integer(1)));
+    scripting.finish();
+}
+
+#[test]
+async fn test_library_metadata() {
+    assert_eq!(library_name("#!lua name=mylib\nreturn 1"), Ok("mylib".to_string()));
+    assert_eq!(library_name("#!LUA name=my_lib2"), Ok("my_lib2".to_string()));
+    assert_eq!(library_name("return 1"), Err("ERR Missing library metadata".to_string()));
+    assert_eq!(library_name("#!js name=x"), Err("ERR Engine 'js' not found".to_string()));
+    assert_eq!(library_name("#!lua"), Err("ERR Library name was not given".to_string()));
+    assert_eq!(library_name("#!lua name=x foo=bar"), Err("ERR Invalid metadata value given: foo=bar".to_string()));
+    assert!(library_name("#!lua name=a-b").unwrap_err().starts_with("ERR Library names can only contain"));
+}
+
+#[test]
+async fn test_function_library() {
+    let scripting = Scripting::new(Duration::from_secs(5));
+    let code = "#!lua name=mylib
+local function echo(keys, args) return {keys[1], args[1]} end
+redis.register_function('echo', echo)
+redis.register_function{function_name='get', callback=function(keys) return redis.call('GET', keys[1]) end, flags={'no-writes'}, description='reads'}";
+    let library = scripting.library(code).await.unwrap();
+    assert_eq!(library.name, "mylib");
+    assert_eq!(
+        library.functions,
+        vec![
+            FunctionInfo { name: "echo".to_string(), description: None, flags: vec![] },
+            FunctionInfo { name: "get".to_string(), description: Some("reads".to_string()), flags: vec!["no-writes".to_string()] },
+        ]
+    );
+
+    let (script, mut events) = scripting.start_function(&library, "echo", vec!["k".to_string()], vec!["a".to_string()], false);
+    assert!(!script.is_read_only());
+    assert_eq!(finish(&mut events, |_| Reply::Bulk(None)).await, Ok(Reply::Array(Some(vec![bulk("k"), bulk("a")]))));
+    scripting.finish();
+    let (script, mut events) = scripting.start_function(&library, "get", vec!["k".to_string()], vec![], true);
+    assert!(script.is_read_only());
+    assert_eq!(finish(&mut events, |args| bulk(&args.join(" "))).await, Ok(bulk("GET k")));
+    scripting.finish();
+
+    // 라이브러리 코드의 에러
+    for (code, expected) in [
+        ("#!lua name=e\nlocal x = 1", "ERR No functions registered"),
+        ("#!lua name=e\nreturn (", "ERR Error compiling function: user_function:2: unexpected symbol near <eof>"),
+        ("#!lua name=e\nwhile true do end", "ERR FUNCTION LOAD timeout"),
+        ("#!lua name=e\nredis.register_function('f', 1)", "ERR Error registering functions: user_function:2: callback argument given to redis.register_function must be a function"),
+        (
+            "#!lua name=e\nredis.register_function('f', function() end)\nredis.register_function('f', function() end)",
+            "ERR Error registering functions: user_function:3: Function already exists in the library",
+        ),
+        (
+            "#!lua name=e\nredis.register_function{function_name='f', callback=function() end, flags={'bad'}}",
+            "ERR Error registering functions: user_function:2: unknown flag given",
+        ),
+    ] {
+        assert_eq!(scripting.library(code).await.map(|_| ()), Err(expected.to_string()));
+    }
+}
+
+#[test]
+async fn test_function_kill() {
+    let scripting = Scripting::new(Duration::from_millis(10));
+    let library = scripting.library("#!lua name=l\nredis.register_function('spin', function() while true do end end)").await.unwrap();
+    let (_, mut events) = scripting.start_function(&library, "spin", vec![], vec![], false);
+    // 함수는 FUNCTION KILL 로만 멈출 수 있다
+    assert!(scripting.kill(false).unwrap_err().contains("You can only call FUNCTION KILL"));
+    assert_eq!(scripting.kill(true), Ok(()));
+    assert_eq!(finish(&mut events, |_| Reply::Bulk(None)).await, Err("ERR Script killed by user with FUNCTION KILL...".to_string()));
+    scripting.finish();
+}
@@ -0,0 +1,683 @@
+use crate::client::Client;
+use crate::protocol::decoder::{RedisCommand, RedisDecoder, Reply};
+use crate::protocol::encoder::RedisEncoder;
+use crate::protocol::error::RedisError;
+use crate::replication::{new_replid, random_delay, MasterAddr};
+use anyhow::Result;
+use bytes::BytesMut;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::Instant;
+
+/// 인스턴스들을 확인하는 주기
+const CRON_PERIOD: Duration = Duration::from_secs(1);
+/// 다른 인스턴스에 보내는 요청 하나의 타임아웃
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+/// 여러 sentinel 이 동시에 선거를 시작하지 않도록 더하는 최대 지연
+const MAX_DESYNC: Duration = Duration::from_millis(1000);
+
+/// `sentinel monitor` 와 관련 설정
+#[derive(Debug, Clone)]
+pub struct SentinelConfig {
+    pub port: u16,
+    pub master_name: String,
+    pub master: MasterAddr,
+    pub quorum: usize,
+    pub down_after: Duration,
+    pub failover_timeout: Duration,
+    pub known_sentinels: Vec<MasterAddr>,
+}
+
+/// INFO replication 에서 읽은 인스턴스 상태
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstanceInfo {
+    pub is_master: bool,
+    /// replica 가 따르고 있는 master
+    pub master: Option<MasterAddr>,
+    pub master_link_up: bool,
+    pub repl_offset: u64,
+    /// master 에 붙어 있는 replica 들 (listening port 기준)
+    pub replicas: Vec<MasterAddr>,
+}
+
+impl InstanceInfo {
+    pub fn parse(info: &str) -> InstanceInfo {
+        let mut parsed = InstanceInfo::default();
+        let mut master_host = None;
+        let mut master_port = None;
+        for line in info.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            match key {
+                "role" => parsed.is_master = value == "master",
+                "master_host" => master_host = Some(value.to_string()),
+                "master_port" => master_port = value.parse().ok(),
+                "master_link_status" => parsed.master_link_up = value == "up",
+                "slave_repl_offset" => parsed.repl_offset = value.parse().unwrap_or(0),
+                "master_repl_offset" if parsed.is_master => parsed.repl_offset = value.parse().unwrap_or(0),
+                key if key.starts_with("slave") && key[5..].chars().all(|c| c.is_ascii_digit()) => {
+                    // slave0:ip=127.0.0.1,port=6380,state=online
+                    let fields: HashMap<&str, &str> = value.split(',').filter_map(|f| f.split_once('=')).collect();
+                    if let (Some(ip), Some(port)) = (fields.get("ip"), fields.get("port").and_then(|p| p.parse().ok())) {
+                        parsed.replicas.push(MasterAddr { host: ip.to_string(), port });
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let (Some(host), Some(port)) = (master_host, master_port) {
+            parsed.master = Some(MasterAddr { host, port });
+        }
+        parsed
+    }
+}
+
+/// 감시하는 인스턴스 (master 또는 replica)
+#[derive(Debug)]
+struct Instance {
+    addr: MasterAddr,
+    /// 마지막으로 PING 에 정상 응답한 시각
+    last_ok: Instant,
+    info: Option<InstanceInfo>,
+}
+
+impl Instance {
+    fn new(addr: MasterAddr) -> Self {
+        Instance { addr, last_ok: Instant::now(), info: None }
+    }
+
+    /// subjectively down: down-after-milliseconds 동안 응답이 없었다
+    fn is_sdown(&self, down_after: Duration) -> bool {
+        self.last_ok.elapsed() > down_after
+    }
+}
+
+/// 같은 master 를 감시하는 다른 sentinel
+#[derive(Debug)]
+struct Peer {
+    addr: MasterAddr,
+    runid: Option<String>,
+    last_ok: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct SentinelState {
+    current_epoch: u64,
+    master: Instance,
+    /// 현재 master 설정이 정해진 epoch. 더 큰 epoch 의 설정을 보면 그쪽을 따른다.
+    config_epoch: u64,
+    replicas: Vec<Instance>,
+    peers: Vec<Peer>,
+    /// objectively down: quorum 이상의 sentinel 이 master 가 죽었다고 동의했다
+    odown: bool,
+    /// 이 sentinel 이 투표한 리더와 그 epoch
+    leader: Option<String>,
+    leader_epoch: u64,
+    failover_in_progress: bool,
+    /// 다음 선거를 시작할 수 있는 시각
+    next_failover_attempt: Option<Instant>,
+}
+
+/// master 와 replica 들을 감시하다가 master 가 죽으면 다른 sentinel 들과 합의해서 replica 하나를 승격시킨다.
+///
+/// sentinel 끼리는 `sentinel known-sentinel` 로 알려준 주소로 직접 hello 를 주고받아서
+/// 서로를 발견하고, 바뀐 master 설정 (config epoch) 을 전파한다.
+pub struct Sentinel {
+    runid: String,
+    config: SentinelConfig,
+    state: Mutex<SentinelState>,
+}
+
+impl Sentinel {
+    pub fn new(config: SentinelConfig) -> Self {
+        let peers = config
+            .known_sentinels
+            .iter()
+            .map(|addr| Peer { addr: addr.clone(), runid: None, last_ok: None })
+            .collect();
+        let state = SentinelState {
+            current_epoch: 0,
+            master: Instance::new(config.master.clone()),
+            config_epoch: 0,
+            replicas: Vec::new(),
+            peers,
+            odown: false,
+            leader: None,
+            leader_epoch: 0,
+            failover_in_progress: false,
+            next_failover_attempt: None,
+        };
+        Sentinel { runid: new_replid(), config, state: Mutex::new(state) }
+    }
+
+    pub fn runid(&self) -> &str {
+        &self.runid
+    }
+
+    pub fn master_addr(&self) -> MasterAddr {
+        self.state.lock().unwrap().master.addr.clone()
+    }
+
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", self.config.port)).await?;
+        println!("Sentinel {} monitoring {} at {}:{}", self.runid, self.config.master_name, self.config.master.host, self.config.master.port);
+
+        tokio::spawn(Arc::clone(&self).cron());
+
+        loop {
+            let (socket, _) = listener.accept().await?;
+            let sentinel = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(err) = sentinel.handle_connection(socket).await {
+                    eprintln!("Error: {:?}", err);
+                }
+            });
+        }
+    }
+
+    async fn handle_connection(&self, mut socket: TcpStream) -> Result<()> {
+        let mut buf = BytesMut::with_capacity(1024);
+        let decoder = RedisDecoder::new();
+        let mut response = BytesMut::new();
+
+        loop {
+            if socket.read_buf(&mut buf).await? == 0 {
+                return Ok(());
+            }
+            while let Some(command) = decoder.decode(&mut buf) {
+                let close = matches!(command, RedisCommand::Invalid(RedisError::Protocol(_)));
+                self.execute(command, &mut response);
+                if close {
+                    socket.write_all(&response).await?;
+                    return Ok(());
+                }
+            }
+            socket.write_all(&response).await?;
+            response.clear();
+        }
+    }
+
+    /// sentinel 모드에서 쓸 수 있는 명령은 PING, INFO, SENTINEL 뿐이다.
+    fn execute(&self, command: RedisCommand, response: &mut BytesMut) {
+        let encoder = RedisEncoder::new();
+        match command {
+            RedisCommand::Ping => encoder.encode_pong(response),
+            RedisCommand::Info(_) => encoder.encode_bulk_string(response, &self.info()),
+            RedisCommand::Sentinel(args) => self.sentinel_command(args, response),
+            RedisCommand::Invalid(err) => encoder.encode_error(response, &err),
+            command => encoder.encode_error(response, &RedisError::UnknownCommand(command.name().to_string(), vec![])),
+        }
+    }
+
+    fn sentinel_command(&self, args: Vec<String>, response: &mut BytesMut) {
+        let encoder = RedisEncoder::new();
+        let subcommand = args[0].to_lowercase();
+        let args: Vec<&str> = args[1..].iter().map(|s| s.as_str()).collect();
+        let name_matches = |name: &str| name == self.config.master_name;
+
+        match (subcommand.as_str(), args.as_slice()) {
+            ("myid", []) => encoder.encode_bulk_string(response, &self.runid),
+            ("get-master-addr-by-name", [name]) => {
+                if name_matches(name) {
+                    let addr = self.master_addr();
+                    encoder.encode_array(response, &[&addr.host, &addr.port.to_string()]);
+                } else {
+                    encoder.encode_null_array(response);
+                }
+            }
+            ("is-master-down-by-addr", [host, port, epoch, runid]) => {
+                match (port.parse::<u16>(), epoch.parse::<u64>()) {
+                    (Ok(port), Ok(epoch)) => {
+                        let addr = MasterAddr { host: host.to_string(), port };
+                        let (down, leader, leader_epoch) = self.is_master_down_by_addr(&addr, epoch, runid);
+                        encoder.encode_array_len(response, 3);
+                        encoder.encode_integer(response, down as i64);
+                        encoder.encode_bulk_string(response, &leader);
+                        encoder.encode_integer(response, leader_epoch as i64);
+                    }
+                    _ => encoder.encode_error(response, &RedisError::NotInteger),
+                }
+            }
+            ("hello", [host, port, runid, current_epoch, name, master_host, master_port, config_epoch]) => {
+                let parsed = (port.parse::<u16>(), current_epoch.parse::<u64>(), master_port.parse::<u16>(), config_epoch.parse::<u64>());
+                match parsed {
+                    (Ok(port), Ok(current_epoch), Ok(master_port), Ok(config_epoch)) if name_matches(name) => {
+                        let peer = MasterAddr { host: host.to_string(), port };
+                        let master = MasterAddr { host: master_host.to_string(), port: master_port };
+                        self.process_hello(peer, runid, current_epoch, master, config_epoch);
+                        encoder.encode_ok(response);
+                    }
+                    _ => encoder.encode_error(response, &RedisError::Err("invalid hello message".to_string())),
+                }
+            }
+            ("replicas", [name]) | ("slaves", [name]) if name_matches(name) => {
+                let state = self.state.lock().unwrap();
+                encoder.encode_array_len(response, state.replicas.len());
+                for replica in &state.replicas {
+                    let flags = if replica.is_sdown(self.config.down_after) { "slave,s_down" } else { "slave" };
+                    let port = replica.addr.port.to_string();
+                    encoder.encode_array(response, &["ip", &replica.addr.host, "port", &port, "flags", flags]);
+                }
+            }
+            ("sentinels", [name]) if name_matches(name) => {
+                let state = self.state.lock().unwrap();
+                encoder.encode_array_len(response, state.peers.len());
+                for peer in &state.peers {
+                    let port = peer.addr.port.to_string();
+                    let runid = peer.runid.as_deref().unwrap_or("");
+                    encoder.encode_array(response, &["ip", &peer.addr.host, "port", &port, "runid", runid]);
+                }
+            }
+            ("replicas", [_]) | ("slaves", [_]) | ("sentinels", [_]) => {
+                encoder.encode_error(response, &RedisError::Err("No such master with that name".to_string()));
+            }
+            _ => encoder.encode_error(response, &RedisError::Err("Unknown sentinel subcommand or wrong number of arguments".to_string())),
+        }
+    }
+
+    /// 다른 sentinel 이 master 상태를 묻는다. runid 가 `*` 가 아니면 그 sentinel 에게 리더 투표를 요청하는 것이다.
+    /// 한 epoch 에는 한 번만 투표한다.
+    pub(crate) fn is_master_down_by_addr(&self, addr: &MasterAddr, req_epoch: u64, runid: &str) -> (bool, String, u64) {
+        let mut state = self.state.lock().unwrap();
+        let is_our_master = state.master.addr == *addr;
+        let down = is_our_master && state.master.is_sdown(self.config.down_after);
+
+        if is_our_master && runid != "*" && state.leader_epoch < req_epoch && state.current_epoch <= req_epoch {
+            state.leader = Some(runid.to_string());
+            state.leader_epoch = req_epoch;
+            state.current_epoch = req_epoch;
+            println!("Voted for {} in epoch {}", runid, req_epoch);
+        }
+
+        let leader = state.leader.clone().unwrap_or_else(|| "*".to_string());
+        (down, leader, state.leader_epoch)
+    }
+
+    /// hello: 보낸 sentinel 을 기억하고, 더 새로운 master 설정이면 그쪽으로 바꾼다.
+    pub(crate) fn process_hello(&self, peer_addr: MasterAddr, runid: &str, current_epoch: u64, master: MasterAddr, config_epoch: u64) {
+        let mut state = self.state.lock().unwrap();
+        match state.peers.iter_mut().find(|p| p.addr == peer_addr) {
+            Some(peer) => {
+                peer.runid = Some(runid.to_string());
+                peer.last_ok = Some(Instant::now());
+            }
+            None => {
+                println!("Discovered sentinel {} at {}:{}", runid, peer_addr.host, peer_addr.port);
+                state.peers.push(Peer { addr: peer_addr, runid: Some(runid.to_string()), last_ok: Some(Instant::now()) });
+            }
+        }
+        state.current_epoch = state.current_epoch.max(current_epoch);
+
+        if config_epoch > state.config_epoch && master != state.master.addr {
+            println!("Switching master to {}:{} (config epoch {})", master.host, master.port, config_epoch);
+            Self::switch_master(&mut state, master);
+        }
+        state.config_epoch = state.config_epoch.max(config_epoch);
+    }
+
+    /// master 를 바꾼다. 예전 master 는 돌아오면 새 master 의 replica 로 만들기 위해 replica 목록에 넣는다.
+    fn switch_master(state: &mut SentinelState, master: MasterAddr) {
+        let old = std::mem::replace(&mut state.master, Instance::new(master));
+        state.replicas.retain(|r| r.addr != state.master.addr);
+        if !state.replicas.iter().any(|r| r.addr == old.addr) {
+            state.replicas.push(Instance { addr: old.addr, last_ok: old.last_ok, info: None });
+        }
+        state.odown = false;
+        state.failover_in_progress = false;
+        state.next_failover_attempt = None;
+    }
+
+    /// INFO (sentinel 섹션)
+    fn info(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let status = if state.odown {
+            "odown"
+        } else if state.master.is_sdown(self.config.down_after) {
+            "sdown"
+        } else {
+            "ok"
+        };
+        format!(
+            "# Sentinel\r\n\
+             sentinel_masters:1\r\n\
+             sentinel_runid:{}\r\n\
+             sentinel_current_epoch:{}\r\n\
+             master0:name={},status={},address={}:{},slaves={},sentinels={}\r\n",
+            self.runid,
+            state.current_epoch,
+            self.config.master_name,
+            status,
+            state.master.addr.host,
+            state.master.addr.port,
+            state.replicas.len(),
+            state.peers.len() + 1,
+        )
+    }
+
+    /// 주기적으로 인스턴스들을 확인하고, 필요하면 failover 한다.
+    /// 네트워크 요청은 상태 락 밖에서 한다.
+    async fn cron(self: Arc<Self>) {
+        let mut links = Links::default();
+        let mut interval = tokio::time::interval(CRON_PERIOD);
+        loop {
+            interval.tick().await;
+            self.refresh_instances(&mut links).await;
+            self.send_hello(&mut links).await;
+            self.reconfigure_replicas(&mut links).await;
+            self.check_master_down(&mut links).await;
+        }
+    }
+
+    /// master 와 replica 들에 PING 과 INFO 를 보낸다. master 의 INFO 로 새 replica 를 발견한다.
+    async fn refresh_instances(&self, links: &mut Links) {
+        let (master, replicas) = {
+            let state = self.state.lock().unwrap();
+            (state.master.addr.clone(), state.replicas.iter().map(|r| r.addr.clone()).collect::<Vec<_>>())
+        };
+
+        for addr in std::iter::once(master.clone()).chain(replicas) {
+            let pong = links.ping(&addr).await;
+            let info = match pong {
+                true => links.info(&addr).await,
+                false => None,
+            };
+
+            let mut state = self.state.lock().unwrap();
+            let is_master = addr == state.master.addr;
+            if is_master {
+                if let Some(info) = &info {
+                    for replica in &info.replicas {
+                        if !state.replicas.iter().any(|r| r.addr == *replica) && *replica != state.master.addr {
+                            println!("Discovered replica {}:{}", replica.host, replica.port);
+                            state.replicas.push(Instance::new(replica.clone()));
+                        }
+                    }
+                }
+            }
+            let instance = match is_master {
+                true => Some(&mut state.master),
+                false => state.replicas.iter_mut().find(|r| r.addr == addr),
+            };
+            if let Some(instance) = instance {
+                if pong {
+                    instance.last_ok = Instant::now();
+                }
+                if info.is_some() {
+                    instance.info = info;
+                }
+            }
+        }
+    }
+
+    /// 알고 있는 sentinel 들에게 이 sentinel 과 현재 master 설정을 알린다.
+    async fn send_hello(&self, links: &mut Links) {
+        let (peers, current_epoch, master, config_epoch) = {
+            let state = self.state.lock().unwrap();
+            let peers: Vec<MasterAddr> = state.peers.iter().map(|p| p.addr.clone()).collect();
+            (peers, state.current_epoch, state.master.addr.clone(), state.config_epoch)
+        };
+
+        for peer in peers {
+            let Some(client) = links.get(&peer).await else {
+                continue;
+            };
+            // 상대가 이 sentinel 에 접속할 수 있는 주소
+            let ip = match client.local_ip() {
+                Ok(ip) => ip.to_string(),
+                Err(_) => continue,
+            };
+            let args = [
+                "SENTINEL",
+                "HELLO",
+                &ip,
+                &self.config.port.to_string(),
+                &self.runid,
+                &current_epoch.to_string(),
+                &self.config.master_name,
+                &master.host,
+                &master.port.to_string(),
+                &config_epoch.to_string(),
+            ];
+            let ok = matches!(links.command(&peer, &args).await, Some(Reply::Simple(_)));
+            if ok {
+                let mut state = self.state.lock().unwrap();
+                if let Some(p) = state.peers.iter_mut().find(|p| p.addr == peer) {
+                    p.last_ok = Some(Instant::now());
+                }
+            }
+        }
+    }
+
+    /// 현재 master 를 따르지 않는 replica (돌아온 예전 master 포함) 를 다시 설정한다.
+    async fn reconfigure_replicas(&self, links: &mut Links) {
+        let (master, wrong) = {
+            let state = self.state.lock().unwrap();
+            if state.failover_in_progress {
+                return;
+            }
+            let master = state.master.addr.clone();
+            let wrong: Vec<MasterAddr> = state
+                .replicas
+                .iter()
+                .filter(|r| !r.is_sdown(self.config.down_after))
+                .filter(|r| match &r.info {
+                    Some(info) => info.is_master || info.master.as_ref() != Some(&master),
+                    None => false,
+                })
+                .map(|r| r.addr.clone())
+                .collect();
+            (master, wrong)
+        };
+
+        for addr in wrong {
+            println!("Reconfiguring {}:{} as a replica of {}:{}", addr.host, addr.port, master.host, master.port);
+            links.command(&addr, &["REPLICAOF", &master.host, &master.port.to_string()]).await;
+            // 다음 INFO 를 받기 전까지 다시 보내지 않는다
+            let mut state = self.state.lock().unwrap();
+            if let Some(r) = state.replicas.iter_mut().find(|r| r.addr == addr) {
+                r.info = None;
+            }
+        }
+    }
+
+    /// master 가 sdown 이면 다른 sentinel 들에게 물어서 odown 을 판단하고, odown 이면 리더 선거와 failover 를 한다.
+    async fn check_master_down(&self, links: &mut Links) {
+        let (master, sdown, peers, epoch) = {
+            let state = self.state.lock().unwrap();
+            let peers: Vec<MasterAddr> = state.peers.iter().map(|p| p.addr.clone()).collect();
+            (state.master.addr.clone(), state.master.is_sdown(self.config.down_after), peers, state.current_epoch)
+        };
+
+        if !sdown {
+            let mut state = self.state.lock().unwrap();
+            if state.odown {
+                println!("Master {}:{} is reachable again", master.host, master.port);
+            }
+            state.odown = false;
+            state.next_failover_attempt = None;
+            return;
+        }
+
+        // quorum: 이 sentinel 을 포함해서 master 가 죽었다고 보는 sentinel 수
+        let mut down_votes = 1;
+        for peer in &peers {
+            let reply = links.is_master_down(peer, &master, epoch, "*").await;
+            if matches!(reply, Some((true, _, _))) {
+                down_votes += 1;
+            }
+        }
+
+        let start_election = {
+            let mut state = self.state.lock().unwrap();
+            // 묻는 동안 hello 로 master 가 바뀌었다
+            if state.master.addr != master {
+                return;
+            }
+            let odown = down_votes >= self.config.quorum;
+            if odown && !state.odown {
+                println!("Master {}:{} is objectively down ({}/{} sentinels agree)", master.host, master.port, down_votes, self.config.quorum);
+                state.next_failover_attempt = Some(Instant::now() + random_delay(MAX_DESYNC));
+            }
+            state.odown = odown;
+            odown
+                && !state.failover_in_progress
+                && state.next_failover_attempt.is_some_and(|t| Instant::now() >= t)
+        };
+        if !start_election {
+            return;
+        }
+
+        // 새 epoch 를 열고 자기 자신에게 투표한 뒤 다른 sentinel 들에게 투표를 요청한다
+        let epoch = {
+            let mut state = self.state.lock().unwrap();
+            state.current_epoch += 1;
+            state.leader = Some(self.runid.clone());
+            state.leader_epoch = state.current_epoch;
+            state.current_epoch
+        };
+        let mut votes = 1;
+        for peer in &peers {
+            if let Some((_, leader, leader_epoch)) = links.is_master_down(peer, &master, epoch, &self.runid).await {
+                if leader == self.runid && leader_epoch == epoch {
+                    votes += 1;
+                }
+            }
+        }
+
+        // quorum 이상이면서 전체 sentinel 의 과반수
+        let voters = peers.len() + 1;
+        let needed = self.config.quorum.max(voters / 2 + 1);
+        if self.master_addr() != master {
+            return;
+        }
+        if votes < needed {
+            println!("Election for epoch {} failed ({}/{} votes)", epoch, votes, needed);
+            let mut state = self.state.lock().unwrap();
+            state.next_failover_attempt = Some(Instant::now() + self.config.failover_timeout + random_delay(MAX_DESYNC));
+            return;
+        }
+
+        println!("Elected leader for epoch {} ({}/{} votes), starting failover", epoch, votes, needed);
+        self.failover(links, epoch).await;
+    }
+
+    /// 반영된 데이터가 가장 많은 replica 를 master 로 승격시키고 나머지를 그 replica 로 돌린다.
+    async fn failover(&self, links: &mut Links, epoch: u64) {
+        let candidate = {
+            let mut state = self.state.lock().unwrap();
+            state.failover_in_progress = true;
+            state
+                .replicas
+                .iter()
+                .filter(|r| !r.is_sdown(self.config.down_after))
+                .filter_map(|r| r.info.as_ref().filter(|info| !info.is_master).map(|info| (r.addr.clone(), info.repl_offset)))
+                .max_by_key(|(_, offset)| *offset)
+                .map(|(addr, _)| addr)
+        };
+
+        let promoted = match candidate {
+            Some(addr) => self.promote(links, &addr).await.then_some(addr),
+            None => None,
+        };
+        let Some(promoted) = promoted else {
+            println!("Failover aborted: no suitable replica");
+            let mut state = self.state.lock().unwrap();
+            state.failover_in_progress = false;
+            state.next_failover_attempt = Some(Instant::now() + self.config.failover_timeout + random_delay(MAX_DESYNC));
+            return;
+        };
+
+        println!("Promoted {}:{} to master (epoch {})", promoted.host, promoted.port, epoch);
+        {
+            let mut state = self.state.lock().unwrap();
+            Self::switch_master(&mut state, promoted);
+            state.config_epoch = epoch;
+            // 아직 예전 master 를 가리키고 있으므로 다음 INFO 를 보고 다시 설정한다
+            for replica in state.replicas.iter_mut() {
+                replica.info = None;
+            }
+        }
+        // 다른 sentinel 들이 바로 새 설정을 따르도록 알린다
+        self.send_hello(links).await;
+        self.refresh_instances(links).await;
+        self.reconfigure_replicas(links).await;
+    }
+
+    /// REPLICAOF NO ONE 을 보내고 INFO 에서 master 가 될 때까지 기다린다.
+    async fn promote(&self, links: &mut Links, addr: &MasterAddr) -> bool {
+        println!("Sending REPLICAOF NO ONE to {}:{}", addr.host, addr.port);
+        if !matches!(links.command(addr, &["REPLICAOF", "NO", "ONE"]).await, Some(Reply::Simple(_))) {
+            return false;
+        }
+
+        let deadline = Instant::now() + self.config.failover_timeout;
+        while Instant::now() < deadline {
+            if links.info(addr).await.is_some_and(|info| info.is_master) {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        false
+    }
+}
+
+/// 다른 인스턴스들과의 연결. 실패한 연결은 버리고 다음 요청 때 다시 맺는다.
+#[derive(Default)]
+struct Links {
+    clients: HashMap<MasterAddr, Client>,
+}
+
+impl Links {
+    async fn get(&mut self, addr: &MasterAddr) -> Option<&mut Client> {
+        if !self.clients.contains_key(addr) {
+            let client = Client::connect(addr, REQUEST_TIMEOUT).await.ok()?;
+            self.clients.insert(addr.clone(), client);
+        }
+        self.clients.get_mut(addr)
+    }
+
+    async fn command(&mut self, addr: &MasterAddr, args: &[&str]) -> Option<Reply> {
+        let client = self.get(addr).await?;
+        match client.command(args).await {
+            Ok(reply) => Some(reply),
+            Err(_) => {
+                self.clients.remove(addr);
+                None
+            }
+        }
+    }
+
+    /// PING 에 PONG 이나 LOADING, MASTERDOWN 으로 응답하면 살아 있는 것으로 본다.
+    async fn ping(&mut self, addr: &MasterAddr) -> bool {
+        match self.command(addr, &["PING"]).await {
+            Some(Reply::Simple(_)) => true,
+            Some(Reply::Error(e)) => e.starts_with("LOADING") || e.starts_with("MASTERDOWN"),
+            _ => false,
+        }
+    }
+
+    async fn info(&mut self, addr: &MasterAddr) -> Option<InstanceInfo> {
+        match self.command(addr, &["INFO", "replication"]).await {
+            Some(Reply::Bulk(Some(info))) => Some(InstanceInfo::parse(&info)),
+            _ => None,
+        }
+    }
+
+    /// SENTINEL is-master-down-by-addr 의 응답 (down 여부, 투표한 리더, 리더 epoch)
+    async fn is_master_down(&mut self, peer: &MasterAddr, master: &MasterAddr, epoch: u64, runid: &str) -> Option<(bool, String, u64)> {
+        let args = ["SENTINEL", "is-master-down-by-addr", &master.host, &master.port.to_string(), &epoch.to_string(), runid];
+        match self.command(peer, &args).await? {
+            Reply::Array(Some(items)) => match items.as_slice() {
+                [Reply::Integer(down), Reply::Bulk(Some(leader)), Reply::Integer(leader_epoch)] => {
+                    Some((*down == 1, leader.clone(), *leader_epoch as u64))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::replication::MasterAddr;
+use crate::sentinel::{InstanceInfo, Sentinel, SentinelConfig};
+use std::time::Duration;
+use tokio::test;
+
+fn addr(port: u16) -> MasterAddr {
+    MasterAddr { host: "127.0.0.1".to_string(), port }
+}
+
+fn config() -> SentinelConfig {
+    SentinelConfig {
+        port: 26379,
+        master_name: "mymaster".to_string(),
+        master: addr(6379),
+        quorum: 2,
+        down_after: Duration::from_millis(30000),
+        failover_timeout: Duration::from_millis(180000),
+        known_sentinels: vec![addr(26380)],
+    }
+}
+
+#[test]
+async fn test_parse_master_info() {
+    let info = "# Replication\r\nrole:master\r\nconnected_slaves:2\r\n\
+                slave0:ip=127.0.0.1,port=6380,state=online,offset=42,lag=0\r\n\
+                slave1:ip=127.0.0.1,port=6381,state=online,offset=42,lag=0\r\n\
+                master_replid:abc\r\nmaster_repl_offset:42\r\n";
+    let parsed = InstanceInfo::parse(info);
+    assert!(parsed.is_master);
+    assert_eq!(parsed.repl_offset, 42);
+    assert_eq!(parsed.replicas, vec![addr(6380), addr(6381)]);
+    assert_eq!(parsed.master, None);
+}
+
+#[test]
+async fn test_parse_replica_info() {
+    let info = "# Replication\r\nrole:slave\r\nmaster_host:127.0.0.1\r\nmaster_port:6379\r\n\
+                master_link_status:up\r\nslave_repl_offset:100\r\nmaster_repl_offset:100\r\n";
+    let parsed = InstanceInfo::parse(info);
+    assert!(!parsed.is_master);
+    assert_eq!(parsed.master, Some(addr(6379)));
+    assert!(parsed.master_link_up);
+    assert_eq!(parsed.repl_offset, 100);
+}
+
+#[test]
+async fn test_vote_once_per_epoch() {
+    let sentinel = Sentinel::new(config());
+
+    // 막 시작한 sentinel 에게 master 는 아직 down 이 아니다
+    let (down, leader, epoch) = sentinel.is_master_down_by_addr(&addr(6379), 0, "*");
+    assert_eq!((down, leader.as_str(), epoch), (false, "*", 0));
+
+    let (_, leader, epoch) = sentinel.is_master_down_by_addr(&addr(6379), 1, "a");
+    assert_eq!((leader.as_str(), epoch), ("a", 1));
+
+    // 같은 epoch 에는 다시 투표하지 않는다
+    let (_, leader, epoch) = sentinel.is_master_down_by_addr(&addr(6379), 1, "b");
+    assert_eq!((leader.as_str(), epoch), ("a", 1));
+
+    let (_, leader, epoch) = sentinel.is_master_down_by_addr(&addr(6379), 2, "b");
+    assert_eq!((leader.as_str(), epoch), ("b", 2));
+}
+
+#[test]
+async fn test_hello_switches_master() {
+    let sentinel = Sentinel::new(config());
+
+    sentinel.process_hello(addr(26381), "peer", 3, addr(6380), 3);
+    assert_eq!(sentinel.master_addr(), addr(6380));
+
+    // 더 오래된 설정은 무시한다
+    sentinel.process_hello(addr(26381), "peer", 3, addr(6381), 2);
+    assert_eq!(sentinel.master_addr(), addr(6380));
+}
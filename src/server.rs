@@ -1,53 +1,257 @@
-use crate::protocol::decoder::{RedisDecoder, RedisCommand};
+use crate::aof::{Aof, RewriteError};
+use crate::client::Client;
+use crate::cluster::{self, Cluster, SetSlot};
+use crate::cluster_bus;
+use crate::notify::Notifier;
+use crate::protocol::decoder::Reply;
+use crate::protocol::decoder::{CommandFlags, MigrateArgs, RedisDecoder, RedisCommand, RestoreArgs, ScriptArgs};
 use crate::protocol::encoder::RedisEncoder;
+use crate::protocol::error::RedisError;
+use crate::persistence::{Persistence, SaveError};
+use crate::pubsub::{self, PubSub, Subscriber};
 use crate::rdb::RDB;
-use crate::store::Store;
+use crate::replication::{self, MasterAddr, Replication};
+use crate::pattern_parser::glob_match;
+use crate::scripting::{Event, FunctionInfo, Library, RunningScript, Scripting};
+use crate::store::{now_millis, Store};
+use crate::tracking::{Tracking, TrackingOptions};
 use anyhow::Result;
 use bytes::BytesMut;
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Mutex, MutexGuard};
 use crate::config::Config;
 
+/// active expiry 주기 (Redis 의 hz 10)
+const ACTIVE_EXPIRE_PERIOD: Duration = Duration::from_millis(100);
+/// 명령 락을 기다리는 동안 스크립트가 busy-script-time 을 넘겼는지 확인하는 주기
+const BUSY_CHECK_PERIOD: Duration = Duration::from_millis(10);
+
+/// 모든 커넥션이 공유하는 서버 상태
+pub struct ServerState {
+    pub store: Arc<Store>,
+    pub persistence: Arc<Persistence>,
+    pub aof: Option<Arc<Aof>>,
+    pub replication: Replication,
+    /// cluster-enabled 일 때만 있다
+    pub cluster: Option<Cluster>,
+    pub pubsub: Arc<PubSub>,
+    /// CLIENT TRACKING 으로 읽은 키를 추적하는 클라이언트들
+    pub tracking: Arc<Tracking>,
+    /// EVAL 스크립트 캐시와 실행 중인 스크립트
+    pub scripting: Scripting,
+    /// 클라이언트 명령을 읽는 디코더 (proto-max-bulk-len, proto-max-multibulk-len 적용)
+    pub decoder: RedisDecoder,
+    /// 아직 명령으로 읽지 못한 입력이 이보다 커지면 커넥션을 닫는다 (client-query-buffer-limit)
+    pub query_buffer_limit: usize,
+    /// Redis 처럼 명령을 한 번에 하나씩 실행하기 위한 락.
+    /// execute() 를 호출하는 쪽에서 잡고 있어야 한다.
+    pub command_lock: Mutex<()>,
+}
+
+impl ServerState {
+    /// Store 에 적용한 쓰기 명령을 AOF 와 replica 들에 전파한다.
+    /// EXEC 중의 첫 쓰기 앞에는 MULTI 를 먼저 보낸다.
+    async fn propagate(&self, client: &mut ClientState, args: &[String]) {
+        if client.exec_propagation == Some(false) {
+            client.exec_propagation = Some(true);
+            self.feed(&["MULTI".to_string()]).await;
+        }
+        self.feed(args).await;
+        client.woff = self.replication.master_repl_offset();
+    }
+
+    async fn feed(&self, args: &[String]) {
+        // replica 는 master 에게서 받은 스트림을 그대로 하위 replica 에게 넘기므로 여기서는 보내지 않는다
+        if !self.replication.is_replica() {
+            self.replication.feed(args);
+        }
+
+        if let Some(aof) = &self.aof {
+            let mut writer = aof.writer().await;
+            writer.set_reploff(self.replication.master_repl_offset());
+            if let Err(e) = writer.append(args) {
+                eprintln!("Failed to write to AOF: {:?}", e);
+            }
+        }
+    }
+
+    /// 슬롯이 다른 shard 로 옮겨 갔으면 그 슬롯의 shard 채널 구독을 푼다
+    pub fn drop_unserved_shard_channels(&self) {
+        if let Some(cluster) = &self.cluster {
+            self.pubsub.remove_shard_channels(|channel| !cluster.serves_slot(cluster::key_hash_slot(channel)));
+        }
+    }
+}
+
+/// 커넥션별 상태
+#[derive(Debug, Default)]
+pub struct ClientState {
+    /// REPLCONF listening-port 로 알려준 replica 의 포트
+    pub listening_port: Option<u16>,
+    /// REPLCONF capa eof: EOF 마커 형식의 diskless RDB 를 받을 수 있는 replica
+    pub capa_eof: bool,
+    /// 이 클라이언트의 마지막 쓰기까지의 replication offset (WAIT 가 기다릴 위치)
+    pub woff: u64,
+    /// EXEC 로 명령들을 실행하는 중이면 Some. 그 안의 쓰기를 전파하면서 MULTI 를 이미 보냈으면 true.
+    pub exec_propagation: Option<bool>,
+    /// ASKING 직후의 명령 하나는 가져오는 중인 슬롯의 키를 이 노드에서 처리한다
+    pub asking: bool,
+    /// pub/sub 에서 커넥션을 구별하는 ID
+    pub id: u64,
+    /// 구독한 채널의 메시지를 이 커넥션으로 보내는 통로 (클라이언트 커넥션에만 있다)
+    pub subscriber: Option<Subscriber>,
+    /// MULTI 이후 EXEC 를 기다리는 트랜잭션
+    pub transaction: Option<Transaction>,
+    /// CLIENT CACHING YES / NO. 다음 명령 하나 (MULTI 중이면 EXEC 까지) 에만 적용된다.
+    pub caching: Option<bool>,
+    /// HELLO 3 으로 RESP3 를 고른 클라이언트
+    pub resp3: bool,
+    /// HELLO SETNAME / CLIENT SETNAME
+    pub name: Option<String>,
+}
+
+impl ClientState {
+    /// 이 클라이언트가 고른 프로토콜의 인코더
+    pub fn encoder(&self) -> RedisEncoder {
+        RedisEncoder::with_resp3(self.resp3)
+    }
+}
+
+/// MULTI 부터 EXEC 까지 쌓아둔 명령
+#[derive(Debug, Default)]
+pub struct Transaction {
+    commands: Vec<RedisCommand>,
+    /// 쌓는 중에 거부된 명령이 있으면 EXEC 는 아무것도 실행하지 않는다
+    aborted: bool,
+}
+
 pub struct Server {
     listener: TcpListener,
-    store: Arc<Store>,
+    state: Arc<ServerState>,
 }
 
 impl Server {
     pub async fn new(addr: &str) -> Result<Server> {
         let listener = TcpListener::bind(addr).await?;
-        
+
         // Config에서 RDB 파일 정보 가져오기
         let config = Config::new()?;
-        let rdb_path = match (config.dir, config.dbfilename) {
-            (Some(dir), Some(filename)) => format!("{}/{}", dir, filename),
-            _ => "dump.rdb".to_string(), // 기본값 사용
+        let rdb_path = config.rdb_path();
+        let aof_dir = config.aof_dir();
+
+        let persistence = Arc::new(Persistence::new(rdb_path.clone(), config.save_points()));
+        let cluster = if config.cluster_enabled() {
+            Some(Cluster::load(&config.cluster_config_path(), config.port(), config.cluster_node_timeout()?)?)
+        } else {
+            None
+        };
+        // 클러스터 모드에서는 nodes.conf 의 master 를 따른다
+        let master = config.master().or_else(|| cluster.as_ref().and_then(|c| c.master_addr()));
+        let pubsub = Arc::new(PubSub::new());
+        let mut state = ServerState {
+            store: Arc::new(Store::new()),
+            persistence,
+            aof: None,
+            replication: Replication::new(config.port(), master, config.repl_backlog_size(), config.repl_diskless_sync(), config.replica_serve_stale_data()),
+            cluster,
+            tracking: Arc::new(Tracking::new(Arc::clone(&pubsub))),
+            pubsub,
+            scripting: Scripting::with_log_level(config.busy_script_time()?, config.loglevel()),
+            decoder: RedisDecoder::with_limits(config.proto_max_bulk_len(), config.proto_max_multibulk_len()),
+            query_buffer_limit: config.client_query_buffer_limit(),
+            command_lock: Mutex::new(()),
         };
 
-        // RDB 파일이 존재하면 로드, 없으면 새로운 Store 생성
-        let store = if std::path::Path::new(&rdb_path).exists() {
-            match RDB::read_rdb(&rdb_path).await {
-                Ok(loaded_store) => Arc::new(loaded_store),
+        // AOF 가 켜져 있으면 RDB 대신 AOF 를 재생해서 복원한다
+        let aof_data = if config.appendonly() {
+            match Aof::load(aof_dir.clone(), config.appendfilename(), config.aof_path(), config.aof_load_truncated()).await {
+                Ok(data) => data,
                 Err(e) => {
-                    eprintln!("Failed to load RDB file: {:?}", e);
-                    Arc::new(Store::new())
+                    eprintln!("Failed to load AOF file: {:?}", e);
+                    return Err(e.into());
                 }
             }
         } else {
-            Arc::new(Store::new())
+            None
         };
 
-        Ok(Server { listener, store })
+        if let Some(data) = aof_data {
+            if let Some(base) = data.store {
+                state.store = Arc::new(base);
+            }
+            let mut client = ClientState::default();
+            let mut discard = BytesMut::new();
+            for args in data.commands {
+                // 로드 중에는 다른 클라이언트가 없으므로 MULTI / EXEC 는 건너뛰고 그 안의 명령만 실행한다.
+                // EXEC 가 없는 트랜잭션은 Aof::load 가 이미 버렸다.
+                let command = RedisCommand::from_args(args);
+                if !matches!(command, RedisCommand::Multi | RedisCommand::Exec) {
+                    execute(&state, &mut client, command, &mut discard).await;
+                }
+                discard.clear();
+            }
+        } else if Path::new(&rdb_path).exists() {
+            // RDB 파일이 존재하면 로드, 없으면 새로운 Store 생성
+            // 로드에 실패하면 기존 덤프를 빈 데이터로 덮어쓰지 않도록 서버를 띄우지 않는다
+            match RDB::read_rdb_with_checksum(&rdb_path, config.rdb_checksum()).await {
+                Ok(loaded_store) => state.store = Arc::new(loaded_store),
+                Err(e) => {
+                    eprintln!("Failed to load RDB file: {:?}", e);
+                    return Err(e.into());
+                }
+            }
+        }
+        state.persistence.mark_clean(&state.store);
+
+        if config.appendonly() {
+            let aof = Aof::open(&aof_dir, config.appendfilename(), config.appendfsync(), config.aof_use_rdb_preamble())?;
+            if aof.needs_base().await {
+                // 새 AOF 는 현재 데이터로 base 를 먼저 만들어둬야 재시작 시 RDB 내용이 사라지지 않는다
+                aof.rewrite_now(&state.store).await?;
+            }
+            state.aof = Some(Arc::new(aof));
+            // WAITAOF 가 replication offset 으로 fsync 여부를 판단하므로 replica 가 없어도 offset 을 쌓는다
+            state.replication.create_backlog();
+        }
+
+        // 로딩이 끝난 뒤부터 키 변경을 알린다
+        state.store.set_notifier(Notifier::new(config.notify_keyspace_events()?, Arc::clone(&state.pubsub)));
+        state.store.set_tracking(Arc::clone(&state.tracking));
+
+        Ok(Server { listener, state: Arc::new(state) })
     }
 
     pub async fn run(&self) -> Result<()> {
+        // save point 에 따른 자동 BGSAVE
+        tokio::spawn(Arc::clone(&self.state.persistence).run_save_policy(Arc::clone(&self.state.store)));
+
+        // appendfsync everysec
+        if let Some(aof) = &self.state.aof {
+            tokio::spawn(Arc::clone(aof).run_fsync_policy());
+        }
+
+        // replicaof 가 설정되어 있으면 master 와 동기화
+        tokio::spawn(replication::run(Arc::clone(&self.state)));
+
+        tokio::spawn(active_expire(Arc::clone(&self.state)));
+
+        if self.state.cluster.is_some() {
+            tokio::spawn(cluster_bus::run(Arc::clone(&self.state)));
+        }
+
         loop {
             let (socket, _) = self.listener.accept().await?;
-            let store = Arc::clone(&self.store);
+            let state = Arc::clone(&self.state);
 
             tokio::spawn(async move {
-                if let Err(err) = handle_connection(socket, store).await {
+                if let Err(err) = handle_connection(socket, state).await {
                     eprintln!("Error: {:?}", err);
                 }
             });
@@ -55,103 +259,121 @@ impl Server {
     }
 }
 
-async fn handle_connection(mut socket: TcpStream, store: Arc<Store>) -> Result<()> {
+/// 읽히지 않는 키도 만료 시각이 지나면 지운다. replica 는 master 를 따르므로 직접 지우지 않는다.
+async fn active_expire(state: Arc<ServerState>) {
+    let mut interval = tokio::time::interval(ACTIVE_EXPIRE_PERIOD);
+    loop {
+        interval.tick().await;
+        if !state.replication.is_replica() {
+            // 만료는 클라이언트가 바꾼 것이 아니므로 NOLOOP 클라이언트에게도 알린다
+            state.tracking.set_caller(0);
+            state.store.remove_expired().await;
+        }
+    }
+}
+
+/// 커넥션이 어떻게 끝나든 구독과 WATCH, 키 추적을 지운다
+struct ClientGuard(Arc<ServerState>, u64);
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        self.0.pubsub.disconnect(self.1);
+        self.0.store.unwatch_all(self.1);
+        self.0.tracking.disable(self.1);
+    }
+}
+
+async fn handle_connection(mut socket: TcpStream, state: Arc<ServerState>) -> Result<()> {
     let mut buf = BytesMut::with_capacity(1024);
-    let decoder = RedisDecoder::new();
+    let decoder = state.decoder.clone();
     let encoder = RedisEncoder::new();
+    let (tx, mut messages) = mpsc::unbounded_channel();
+    let id = state.pubsub.next_client_id();
+    state.pubsub.connect(id, &tx);
+    let mut client = ClientState { id, subscriber: Some(tx), ..Default::default() };
+    let _guard = ClientGuard(Arc::clone(&state), client.id);
     let mut response = BytesMut::new();
+    // 바로 전 명령이 CLIENT CACHING 이었는지
+    let mut after_caching = false;
 
     loop {
-        match socket.read_buf(&mut buf).await? {
+        let read = tokio::select! {
+            read = socket.read_buf(&mut buf) => read?,
+            // 구독한 채널의 메시지는 도착하는 대로 보낸다
+            Some(message) = messages.recv() => {
+                socket.write_all(&message).await?;
+                continue;
+            }
+        };
+        match read {
             0 => break, // connection closed
-            bytes => {
-                println!("accepted {} bytes", bytes);
-                let mut s = buf.split_to(bytes);
-
-                match decoder.decode(&mut s) {
-                    Some(RedisCommand::Set(key, value, expiry)) => {
-                        store.insert(key, value, expiry).await;
-                        encoder.encode_ok(&mut response);
-                    }
-                    Some(RedisCommand::Get(key)) => {
-                        match store.get(&key).await {
-                            Some(value) => encoder.encode_bulk_string(&mut response, &value),
-                            None => encoder.encode_null(&mut response),
-                        }
+            _ => {
+                // 한 번에 여러 명령이 들어올 수 있고 (pipelining), 명령이 잘려서 들어올 수도 있다.
+                // 완성된 명령만 처리하고 나머지는 다음 read 까지 버퍼에 남겨둔다.
+                while let Some(command) = decoder.decode(&mut buf) {
+                    // 형식이 틀린 입력 뒤에서는 명령의 경계를 알 수 없으므로 에러를 보내고 커넥션을 닫는다
+                    if let RedisCommand::Invalid(err @ RedisError::Protocol(_)) = &command {
+                        client.encoder().encode_error(&mut response, err);
+                        socket.write_all(&response).await?;
+                        return Ok(());
                     }
-                    Some(RedisCommand::ConfigGet(item)) => {
-                        let key = item.to_uppercase();
-                        match key.as_str() {
-                            "DIR" | "DBFILENAME" => {
-                                let config = match Config::new() {
-                                    Ok(c) => {
-                                        c
-                                    },
-                                    Err(e) => {
-                                        eprintln!("Error: {:?}", e);
-                                        encoder.encode_null(&mut response);
-                                        continue;
-                                    },
-                                };
-                                let value = match key.as_str() {
-                                    "DIR" => config.dir.as_deref(),
-                                    "DBFILENAME" => config.dbfilename.as_deref(),
-                                    _ => unreachable!(),
-                                };
-
-                                if let Some(v) = value {
-                                    let arr = [&key.as_str().to_lowercase(),v];
-                                    encoder.encode_array(&mut response, &arr )
-                                } else {
-                                    encoder.encode_null(&mut response)
-                                }
-                            },
-                            _ => {
-                                encoder.encode_null(&mut response);
-                                continue;
-                            }
-                        }
+                    if !after_caching && client.transaction.is_none() {
+                        client.caching = None;
                     }
-                    Some(RedisCommand::Ping) => {
-                        encoder.encode_pong(&mut response);
-                    }
-                    Some(RedisCommand::Echo(message)) => {
-                        encoder.encode_bulk_string(&mut response, &message);
-                    }
-                    Some(RedisCommand::Save) => {
-                        match Config::new() {
-                            Ok(config) => {
-                                let dir = config.dir.unwrap_or_else(|| String::from("."));
-                                let filename = config.dbfilename.unwrap_or_else(|| String::from("dump.rdb"));
-                                let path = format!("{}/{}", dir, filename);
-                                
-                                match RDB::create_rdb(&path, Some(&[store.as_ref()])).await {
-                                    Ok(_) => encoder.encode_ok(&mut response),
-                                    Err(e) => {
-                                        eprintln!("Failed to save RDB: {:?}", e);
-                                        encoder.encode_error(&mut response);
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to load config: {:?}", e);
-                                encoder.encode_error(&mut response);
-                            }
+                    after_caching = matches!(&command, RedisCommand::Client(args) if args[0].eq_ignore_ascii_case("CACHING"));
+                    // SCRIPT KILL / FUNCTION KILL 은 스크립트가 명령 락을 잡고 있는 동안 실행해야 하므로 락 없이 처리한다
+                    if let RedisCommand::Script(args) | RedisCommand::Function(args) = &command {
+                        if args.len() == 1 && args[0].eq_ignore_ascii_case("KILL") {
+                            script_kill(&state, matches!(command, RedisCommand::Function(_)), &mut response);
+                            continue;
                         }
                     }
-                    Some(RedisCommand::Keys(query)) => {
-                        let keys = store.keys(&query).await;
-                        let key_refs: Vec<&str> = keys.iter().map(|s| s.as_str()).collect();
-                        encoder.encode_array(&mut response, &key_refs);
+                    // MULTI 중에는 EXEC / DISCARD / MULTI / WATCH / RESET 외의 명령을 실행하지 않고 쌓아둔다
+                    let unqueued = matches!(
+                        command,
+                        RedisCommand::Multi | RedisCommand::Exec | RedisCommand::Discard | RedisCommand::Watch(_) | RedisCommand::Reset
+                    );
+                    if client.transaction.is_some() && !unqueued {
+                        queue(&state, &mut client, command, &mut response).await;
+                        continue;
                     }
-                    Some(RedisCommand::Unknown) => {
-                        encoder.encode_error(&mut response);
+                    if let RedisCommand::Psync(replid, offset) = command {
+                        // 이 커넥션은 이제부터 replica 링크
+                        socket.write_all(&response).await?;
+                        return replication::serve_replica(socket, state, client, replid, offset, buf).await;
                     }
-                    None => {
-                        encoder.encode_error(&mut response);
+                    if let RedisCommand::Wait(..) | RedisCommand::WaitAof(..) = command {
+                        // 다른 클라이언트의 명령을 막지 않도록 명령 락 없이 기다린다
+                        wait(&state, &client, command, &mut response, true).await;
+                        continue;
                     }
-                };
 
+                    let Some(_lock) = lock_unless_busy(&state).await else {
+                        encoder.encode_error(&mut response, &RedisError::Busy);
+                        continue;
+                    };
+                    if let Some(err) = rejection(&state, &mut client, &command).await {
+                        encoder.encode_error(&mut response, &err);
+                        continue;
+                    }
+                    match command {
+                        RedisCommand::Multi => multi(&mut client, &mut response),
+                        RedisCommand::Exec => exec(&state, &mut client, &mut response).await,
+                        RedisCommand::Discard => discard(&state, &mut client, &mut response),
+                        RedisCommand::Watch(_) if client.transaction.is_some() => {
+                            encoder.encode_error(&mut response, &RedisError::Err("WATCH inside MULTI is not allowed".to_string()));
+                        }
+                        command => execute(&state, &mut client, command, &mut response).await,
+                    }
+                }
+
+                if buf.len() > state.query_buffer_limit {
+                    eprintln!("Closing client {} that reached max query buffer length ({} bytes)", client.id, buf.len());
+                    let err = RedisError::Protocol("client query buffer limit exceeded".to_string());
+                    client.encoder().encode_error(&mut response, &err);
+                    socket.write_all(&response).await?;
+                    return Ok(());
+                }
                 socket.write_all(&response).await?;
                 response.clear();
             }
@@ -159,3 +381,1345 @@ async fn handle_connection(mut socket: TcpStream, store: Arc<Store>) -> Result<(
     }
     Ok(())
 }
+
+/// HELLO 가 알려주는 호환 Redis 버전
+const REDIS_VERSION: &str = "7.2.0";
+
+/// 명령 락을 잡는다. 기다리는 동안 스크립트가 busy-script-time 을 넘기면 None (BUSY 로 응답한다).
+async fn lock_unless_busy(state: &ServerState) -> Option<MutexGuard<'_, ()>> {
+    loop {
+        if state.scripting.is_busy() {
+            return None;
+        }
+        tokio::select! {
+            lock = state.command_lock.lock() => return Some(lock),
+            _ = tokio::time::sleep(BUSY_CHECK_PERIOD) => {}
+        }
+    }
+}
+
+/// 이 커넥션에서 지금 실행할 수 없는 명령이면 에러 메시지를 돌려준다. 명령 락을 잡고 호출한다.
+async fn rejection(state: &ServerState, client: &mut ClientState, command: &RedisCommand) -> Option<RedisError> {
+    if let Some(err) = replica_rejection(state, command) {
+        return Some(err);
+    }
+    if let Some(err) = subscribed_rejection(state, client, command) {
+        return Some(err);
+    }
+    // ASKING 은 바로 다음 명령 하나에만 적용된다. RESTORE-ASKING 은 스스로 ASKING 을 포함한다.
+    let asking = (!matches!(command, RedisCommand::Asking) && std::mem::take(&mut client.asking))
+        || matches!(command, RedisCommand::Restore(args) if args.asking);
+    if let Some(cluster) = &state.cluster {
+        if let Err(redirect) = cluster.route(&state.store, &command.keys(), asking).await {
+            return Some(redirect.into());
+        }
+    }
+    None
+}
+
+/// MULTI: EXEC 까지 받은 명령을 쌓아두기 시작한다.
+pub(crate) fn multi(client: &mut ClientState, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    if client.transaction.is_some() {
+        encoder.encode_error(response, &RedisError::Err("MULTI calls can not be nested".to_string()));
+    } else {
+        client.transaction = Some(Transaction::default());
+        encoder.encode_ok(response);
+    }
+}
+
+/// DISCARD: 쌓아둔 명령을 버리고 WATCH 를 푼다.
+pub(crate) fn discard(state: &ServerState, client: &mut ClientState, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    match client.transaction.take() {
+        Some(_) => {
+            state.store.unwatch_all(client.id);
+            encoder.encode_ok(response);
+        }
+        None => encoder.encode_error(response, &RedisError::Err("DISCARD without MULTI".to_string())),
+    }
+}
+
+/// MULTI 중에 받은 명령을 검사해서 쌓아둔다. 거부된 명령이 있으면 EXEC 가 EXECABORT 로 실패한다.
+pub(crate) async fn queue(state: &ServerState, client: &mut ClientState, command: RedisCommand, response: &mut BytesMut) {
+    let encoder = RedisEncoder::new();
+    let err = match &command {
+        RedisCommand::Unknown => Some(RedisError::Syntax),
+        RedisCommand::Invalid(err) => Some(err.clone()),
+        RedisCommand::Psync(..) => Some(RedisError::Err("Command not allowed inside a transaction".to_string())),
+        _ => match lock_unless_busy(state).await {
+            Some(_lock) => rejection(state, client, &command).await,
+            None => Some(RedisError::Busy),
+        },
+    };
+    let transaction = client.transaction.as_mut().unwrap();
+    match err {
+        Some(err) => {
+            transaction.aborted = true;
+            encoder.encode_error(response, &err);
+        }
+        None => {
+            transaction.commands.push(command);
+            encoder.encode_simple_string(response, "QUEUED");
+        }
+    }
+}
+
+/// EXEC: 쌓아둔 명령을 명령 락을 잡은 채로 한 번에 실행한다. 다른 클라이언트의 명령이 끼어들지 않는다.
+pub(crate) async fn exec(state: &ServerState, client: &mut ClientState, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    let Some(transaction) = client.transaction.take() else {
+        encoder.encode_error(response, &RedisError::Err("EXEC without MULTI".to_string()));
+        return;
+    };
+    // EXEC 가 어떻게 끝나든 WATCH 는 풀린다
+    let touched = state.store.watch_touched(client.id).await;
+    state.store.unwatch_all(client.id);
+    if transaction.aborted {
+        encoder.encode_error(response, &RedisError::ExecAbort(None));
+        return;
+    }
+    // 쌓는 동안 replica 가 됐으면 쓰기를 실행하지 않는다
+    if let Some(err) = transaction.commands.iter().find_map(|command| replica_rejection(state, command)) {
+        encoder.encode_error(response, &RedisError::ExecAbort(Some(Box::new(err))));
+        return;
+    }
+    // 클러스터에서는 트랜잭션 전체가 한 슬롯의 키만 다뤄야 한다
+    if state.cluster.is_some() {
+        let mut slots = transaction.commands.iter().flat_map(|command| command.keys()).map(cluster::key_hash_slot);
+        if let Some(first) = slots.next() {
+            if slots.any(|slot| slot != first) {
+                encoder.encode_error(response, &RedisError::CrossSlot);
+                return;
+            }
+        }
+    }
+    // WATCH 한 키가 바뀌었으면 아무것도 실행하지 않는다
+    if touched {
+        encoder.encode_null_array(response);
+        return;
+    }
+
+    encoder.encode_array_len(response, transaction.commands.len());
+    execute_transaction(state, client, transaction.commands, response).await;
+}
+
+/// 명령들을 차례로 실행한다. 쓰기는 MULTI ... EXEC 로 감싸서 전파하므로 replica 는 한 번에 적용하고,
+/// AOF 에 EXEC 없이 남은 트랜잭션은 로드할 때 버린다. 명령 락을 잡고 호출한다.
+pub(crate) async fn execute_transaction(state: &ServerState, client: &mut ClientState, commands: Vec<RedisCommand>, response: &mut BytesMut) {
+    client.exec_propagation = Some(false);
+    for command in commands {
+        execute(state, client, command, response).await;
+    }
+    if client.exec_propagation.take() == Some(true) {
+        state.feed(&["EXEC".to_string()]).await;
+        client.woff = state.replication.master_repl_offset();
+    }
+}
+
+/// replica 에서 실행할 수 없는 명령이면 에러 메시지를 돌려준다.
+/// master 에게서 받은 명령은 handle_connection 을 거치지 않으므로 여기에 걸리지 않는다.
+fn replica_rejection(state: &ServerState, command: &RedisCommand) -> Option<RedisError> {
+    let replication = &state.replication;
+    if !replication.is_replica() || matches!(command, RedisCommand::Unknown | RedisCommand::Invalid(_)) {
+        return None;
+    }
+
+    let flags = command.flags();
+    if flags.contains(CommandFlags::WRITE) {
+        return Some(RedisError::ReadOnly);
+    }
+    if replication.is_stale() && !replication.serve_stale_data() && !flags.contains(CommandFlags::STALE) {
+        return Some(RedisError::MasterDown);
+    }
+    None
+}
+
+/// RESP2 구독 모드 (구독한 채널이나 패턴이 있는 커넥션) 에서는 구독 관련 명령과 PING, RESET 만 실행할 수 있다.
+fn subscribed_rejection(state: &ServerState, client: &ClientState, command: &RedisCommand) -> Option<RedisError> {
+    let allowed = matches!(
+        command,
+        RedisCommand::Subscribe(_)
+            | RedisCommand::PSubscribe(_)
+            | RedisCommand::Unsubscribe(_)
+            | RedisCommand::PUnsubscribe(_)
+            | RedisCommand::SSubscribe(_)
+            | RedisCommand::SUnsubscribe(_)
+            | RedisCommand::Ping
+            | RedisCommand::Reset
+            | RedisCommand::Unknown
+            | RedisCommand::Invalid(_)
+    );
+    // RESP3 는 메시지가 push 타입이라 응답과 섞이지 않으므로 구독 중에도 모든 명령을 실행할 수 있다
+    if allowed || client.resp3 || state.pubsub.subscription_count(client.id) == 0 {
+        return None;
+    }
+    Some(RedisError::Err(format!(
+        "Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+        command.name()
+    )))
+}
+
+/// WAIT / WAITAOF. `block` 이 false 면 기다리지 않고 현재 상태를 돌려준다.
+async fn wait(state: &ServerState, client: &ClientState, command: RedisCommand, response: &mut BytesMut, block: bool) {
+    let encoder = RedisEncoder::new();
+    let (local, numreplicas, timeout, name) = match command {
+        RedisCommand::Wait(numreplicas, timeout) => (0, numreplicas, timeout, "WAIT"),
+        RedisCommand::WaitAof(numlocal, numreplicas, timeout) => (numlocal, numreplicas, timeout, "WAITAOF"),
+        _ => unreachable!(),
+    };
+
+    if state.replication.is_replica() {
+        let msg = format!("{} cannot be used with replica instances. Please also note that writes to replicas are just local and are not propagated.", name);
+        encoder.encode_error(response, &RedisError::Err(msg));
+        return;
+    }
+    if local > 0 && state.aof.is_none() {
+        encoder.encode_error(response, &RedisError::Err("WAITAOF cannot be used when numlocal is set but appendonly is disabled.".to_string()));
+        return;
+    }
+    if timeout < 0 {
+        encoder.encode_error(response, &RedisError::Err("timeout is negative".to_string()));
+        return;
+    }
+
+    // timeout 0 은 무한히 기다린다
+    let timeout = match (block, timeout) {
+        (false, _) => Some(Duration::ZERO),
+        (true, 0) => None,
+        (true, ms) => Some(Duration::from_millis(ms as u64)),
+    };
+    let numreplicas = numreplicas.max(0) as usize;
+    if name == "WAIT" {
+        let (_, acked) = replication::wait(state, client.woff, false, numreplicas, false, timeout).await;
+        encoder.encode_integer(response, acked as i64);
+    } else {
+        let (fsynced, acked) = replication::wait(state, client.woff, local > 0, numreplicas, true, timeout).await;
+        encoder.encode_integer_array(response, &[fsynced as i64, acked as i64]);
+    }
+}
+
+/// 명령 하나를 실행하고 응답을 `response` 에 쓴다.
+pub async fn execute(state: &ServerState, client: &mut ClientState, command: RedisCommand, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    let store = &state.store;
+    let persistence = &state.persistence;
+
+    state.tracking.set_caller(client.id);
+    if command.flags().contains(CommandFlags::READONLY) {
+        state.tracking.remember(client.id, client.caching, &command.keys());
+    }
+
+    match command {
+        RedisCommand::Set(key, value, expiry) => {
+            let expiry_ts = expiry.map(|ms| now_millis() + ms);
+
+            // 상대 만료 시간은 재생 시점에 달라지므로 PXAT 절대 시각으로 전파
+            let mut args = vec!["SET".to_string(), key.clone(), value.clone()];
+            if let Some(ts) = expiry_ts {
+                args.push("PXAT".to_string());
+                args.push(ts.to_string());
+            }
+
+            store.insert_at(key, value, expiry_ts).await;
+            state.propagate(client, &args).await;
+            encoder.encode_ok(response);
+        }
+        RedisCommand::Get(key) => {
+            match store.get(&key).await {
+                Some(value) => encoder.encode_bulk_string(response, &value),
+                None => encoder.encode_null(response),
+            }
+        }
+        RedisCommand::Del(keys) => {
+            let mut deleted = 0;
+            for key in &keys {
+                if store.remove(key).await {
+                    deleted += 1;
+                }
+            }
+            if deleted > 0 {
+                let args: Vec<String> = std::iter::once("DEL".to_string()).chain(keys).collect();
+                state.propagate(client, &args).await;
+            }
+            encoder.encode_integer(response, deleted);
+        }
+        RedisCommand::MGet(keys) => {
+            encoder.encode_array_len(response, keys.len());
+            for key in keys {
+                match store.get(&key).await {
+                    Some(value) => encoder.encode_bulk_string(response, &value),
+                    None => encoder.encode_null(response),
+                }
+            }
+        }
+        RedisCommand::ConfigGet(item) => {
+            let key = item.to_uppercase();
+            match key.as_str() {
+                "NOTIFY-KEYSPACE-EVENTS" => {
+                    let flags = store.notifier().map(|n| n.flags().to_string()).unwrap_or_default();
+                    encoder.encode_map(response, &[("notify-keyspace-events", &flags)]);
+                }
+                "DIR" | "DBFILENAME" => {
+                    let config = match Config::new() {
+                        Ok(c) => {
+                            c
+                        },
+                        Err(e) => {
+                            eprintln!("Error: {:?}", e);
+                            encoder.encode_null(response);
+                            return;
+                        },
+                    };
+                    let value = match key.as_str() {
+                        "DIR" => config.dir.as_deref(),
+                        "DBFILENAME" => config.dbfilename.as_deref(),
+                        _ => unreachable!(),
+                    };
+
+                    if let Some(v) = value {
+                        encoder.encode_map(response, &[(&key.as_str().to_lowercase(), v)])
+                    } else {
+                        encoder.encode_null(response)
+                    }
+                },
+                _ => {
+                    encoder.encode_null(response);
+                }
+            }
+        }
+        RedisCommand::Ping if !client.resp3 && state.pubsub.subscription_count(client.id) > 0 => {
+            // RESP2 구독 모드의 PING 은 메시지처럼 배열로 응답한다
+            encoder.encode_array(response, &["pong", ""]);
+        }
+        RedisCommand::Ping => {
+            encoder.encode_pong(response);
+        }
+        RedisCommand::Echo(message) => {
+            encoder.encode_bulk_string(response, &message);
+        }
+        RedisCommand::Save => {
+            match persistence.save(store).await {
+                Ok(_) => encoder.encode_ok(response),
+                Err(SaveError::InProgress) => {
+                    encoder.encode_error(response, &RedisError::Err("Background save already in progress".to_string()));
+                }
+                Err(e) => {
+                    eprintln!("Failed to save RDB: {:?}", e);
+                    encoder.encode_error(response, &RedisError::Err(format!("Failed to save the RDB file: {:?}", e)));
+                }
+            }
+        }
+        RedisCommand::BgSave => {
+            match persistence.bgsave(store).await {
+                Ok(_) => encoder.encode_simple_string(response, "Background saving started"),
+                Err(SaveError::InProgress) => {
+                    encoder.encode_error(response, &RedisError::Err("Background save already in progress".to_string()));
+                }
+                Err(e) => {
+                    eprintln!("Failed to start BGSAVE: {:?}", e);
+                    encoder.encode_error(response, &RedisError::Err(format!("Background save failed to start: {:?}", e)));
+                }
+            }
+        }
+        RedisCommand::BgRewriteAof => {
+            match &state.aof {
+                Some(aof) => match aof.rewrite(store).await {
+                    Ok(_) => encoder.encode_simple_string(response, "Background append only file rewriting started"),
+                    Err(RewriteError::InProgress) => {
+                        encoder.encode_error(response, &RedisError::Err("Background append only file rewriting already in progress".to_string()));
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to start BGREWRITEAOF: {:?}", e);
+                        encoder.encode_error(response, &RedisError::Err(format!("Background append only file rewriting failed to start: {:?}", e)));
+                    }
+                },
+                None => encoder.encode_error(response, &RedisError::Err("Append only file is disabled".to_string())),
+            }
+        }
+        RedisCommand::LastSave => {
+            encoder.encode_integer(response, persistence.lastsave() as i64);
+        }
+        RedisCommand::Info(section) => {
+            let section = section.map(|s| s.to_lowercase());
+            let mut info = String::new();
+            if matches!(section.as_deref(), None | Some("all") | Some("persistence")) {
+                info.push_str(&persistence.info(store));
+                info.push_str(&format!("aof_enabled:{}\r\n", state.aof.is_some() as u8));
+                if let Some(aof) = &state.aof {
+                    info.push_str(&aof.info());
+                }
+            }
+            if matches!(section.as_deref(), None | Some("all") | Some("replication")) {
+                info.push_str(&state.replication.info());
+            }
+            if matches!(section.as_deref(), None | Some("all") | Some("cluster")) {
+                info.push_str(&format!("# Cluster\r\ncluster_enabled:{}\r\n", state.cluster.is_some() as u8));
+            }
+            encoder.encode_verbatim_string(response, "txt", &info);
+        }
+        RedisCommand::ReplConf(options) => {
+            // master 쪽에서 replica 가 보내는 설정 (listening-port, capa)
+            for pair in options.chunks(2) {
+                if let [option, value] = pair {
+                    if option.eq_ignore_ascii_case("listening-port") {
+                        client.listening_port = value.parse().ok();
+                    } else if option.eq_ignore_ascii_case("capa") && value.eq_ignore_ascii_case("eof") {
+                        client.capa_eof = true;
+                    }
+                }
+            }
+            encoder.encode_ok(response);
+        }
+        RedisCommand::Psync(..) | RedisCommand::Multi | RedisCommand::Exec | RedisCommand::Discard => {
+            // 커넥션 상태를 바꾸는 명령이라 handle_connection 에서 처리한다.
+            // replication 스트림과 AOF 의 MULTI / EXEC 는 그것을 읽는 쪽에서 처리한다.
+            encoder.encode_error(response, &RedisError::Err(format!("'{}' is not allowed in this context", command.name())));
+        }
+        RedisCommand::ReplicaOf(..) if state.cluster.is_some() => {
+            encoder.encode_error(response, &RedisError::Err("REPLICAOF not allowed in cluster mode.".to_string()));
+        }
+        RedisCommand::ReplicaOf(host, port) => {
+            if host.eq_ignore_ascii_case("no") && port.eq_ignore_ascii_case("one") {
+                state.replication.set_master(None);
+                encoder.encode_ok(response);
+            } else {
+                match port.parse::<u16>() {
+                    Ok(port) => {
+                        state.replication.set_master(Some(MasterAddr { host, port }));
+                        encoder.encode_ok(response);
+                    }
+                    Err(_) => encoder.encode_error(response, &RedisError::Err("Invalid master port".to_string())),
+                }
+            }
+        }
+        RedisCommand::Wait(..) | RedisCommand::WaitAof(..) => {
+            // 커넥션에서 온 WAIT 는 handle_connection 에서 처리한다. 여기서는 기다리지 않는다.
+            wait(state, client, command, response, false).await;
+        }
+        RedisCommand::Keys(query) => {
+            let keys = store.keys(&query).await;
+            let key_refs: Vec<&str> = keys.iter().map(|s| s.as_str()).collect();
+            encoder.encode_array(response, &key_refs);
+        }
+        RedisCommand::Cluster(args) => {
+            cluster_command(state, args, response).await;
+        }
+        RedisCommand::Asking => {
+            if state.cluster.is_some() {
+                client.asking = true;
+                encoder.encode_ok(response);
+            } else {
+                encoder.encode_error(response, &RedisError::Err("This instance has cluster support disabled".to_string()));
+            }
+        }
+        RedisCommand::Dump(key) => {
+            match store.get(&key).await {
+                Some(value) => encoder.encode_bulk_bytes(response, &RDB::dump_value(&value)),
+                None => encoder.encode_null(response),
+            }
+        }
+        RedisCommand::Restore(args) => {
+            match restore(state, client, args).await {
+                Ok(()) => encoder.encode_ok(response),
+                Err(e) => encoder.encode_error(response, &e),
+            }
+        }
+        RedisCommand::Migrate(args) => {
+            match migrate(state, client, args).await {
+                Ok(true) => encoder.encode_ok(response),
+                Ok(false) => encoder.encode_simple_string(response, "NOKEY"),
+                Err(e) => encoder.encode_error(response, &e),
+            }
+        }
+        RedisCommand::Subscribe(channels) => subscribe(state, client, pubsub::Kind::Channel, channels, response),
+        RedisCommand::PSubscribe(patterns) => subscribe(state, client, pubsub::Kind::Pattern, patterns, response),
+        RedisCommand::Unsubscribe(channels) => unsubscribe(state, client, pubsub::Kind::Channel, channels, response),
+        RedisCommand::PUnsubscribe(patterns) => unsubscribe(state, client, pubsub::Kind::Pattern, patterns, response),
+        RedisCommand::Publish(channel, message) => {
+            let receivers = state.pubsub.publish(&channel, &message);
+            // replica 의 구독자도 받을 수 있게 replica 에 전파한다 (AOF 에는 남기지 않는다)
+            if !state.replication.is_replica() {
+                state.replication.feed(&["PUBLISH".to_string(), channel, message]);
+            }
+            encoder.encode_integer(response, receivers as i64);
+        }
+        RedisCommand::SSubscribe(channels) => subscribe(state, client, pubsub::Kind::Shard, channels, response),
+        RedisCommand::SUnsubscribe(channels) => unsubscribe(state, client, pubsub::Kind::Shard, channels, response),
+        RedisCommand::SPublish(channel, message) => {
+            let receivers = state.pubsub.publish_shard(&channel, &message);
+            // 같은 shard 의 replica 구독자에게만 전달된다
+            if !state.replication.is_replica() {
+                state.replication.feed(&["SPUBLISH".to_string(), channel, message]);
+            }
+            encoder.encode_integer(response, receivers as i64);
+        }
+        RedisCommand::PubSub(args) => pubsub_command(state, client, args, response),
+        RedisCommand::Client(args) => client_command(state, client, args, response),
+        RedisCommand::Hello(args) => hello(state, client, args, response),
+        RedisCommand::Watch(keys) => {
+            for key in keys {
+                store.watch(client.id, &key).await;
+            }
+            encoder.encode_ok(response);
+        }
+        RedisCommand::Unwatch => {
+            store.unwatch_all(client.id);
+            encoder.encode_ok(response);
+        }
+        RedisCommand::FlushDb | RedisCommand::FlushAll => {
+            store.clear().await;
+            state.propagate(client, &[command.name().to_uppercase()]).await;
+            encoder.encode_ok(response);
+        }
+        RedisCommand::Reset => {
+            state.pubsub.remove_client(client.id);
+            store.unwatch_all(client.id);
+            state.tracking.disable(client.id);
+            state.pubsub.set_resp3(client.id, false);
+            client.asking = false;
+            client.transaction = None;
+            client.caching = None;
+            client.resp3 = false;
+            client.name = None;
+            encoder.encode_simple_string(response, "RESET");
+        }
+        RedisCommand::Eval(args) => match state.scripting.load(&args.script) {
+            Ok(sha) => eval(state, client, sha, args, response).await,
+            Err(err) => encoder.encode_error(response, &RedisError::Script(err)),
+        },
+        RedisCommand::EvalSha(args) => {
+            let sha = args.script.to_lowercase();
+            eval(state, client, sha, args, response).await;
+        }
+        RedisCommand::Script(args) => script_command(state, args, response),
+        RedisCommand::FCall(args) => fcall(state, client, args, false, response).await,
+        RedisCommand::FCallRo(args) => fcall(state, client, args, true, response).await,
+        RedisCommand::Function(args) => function_command(state, client, args, response).await,
+        RedisCommand::FunctionRestore(payload, policy) => match function_restore(state, client, &payload, &policy).await {
+            Ok(()) => encoder.encode_ok(response),
+            Err(err) => encoder.encode_error(response, &err),
+        },
+        RedisCommand::Sentinel(args) => {
+            // SENTINEL 은 sentinel 모드에서만 쓸 수 있다
+            encoder.encode_error(response, &RedisError::UnknownCommand("SENTINEL".to_string(), args));
+        }
+        RedisCommand::Unknown => encoder.encode_error(response, &RedisError::Syntax),
+        RedisCommand::Invalid(err) => encoder.encode_error(response, &err),
+    }
+}
+
+/// EVAL / EVALSHA: 스크립트 스레드에서 실행하고, redis.call 로 요청한 명령은 이 커넥션으로 실행한다.
+/// 명령 락을 잡은 채로 실행하므로 스크립트는 다른 명령과 섞이지 않는다.
+async fn eval(state: &ServerState, client: &mut ClientState, sha: String, args: ScriptArgs, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    let Some(source) = state.scripting.get(&sha) else {
+        encoder.encode_error(response, &RedisError::NoScript);
+        return;
+    };
+    let (script, events) = state.scripting.start(sha, source, args.keys, args.args);
+    run_script(state, client, script, events, response).await;
+}
+
+/// 스크립트 스레드가 보내는 redis.call 요청을 실행하면서 스크립트가 끝나기를 기다린다
+async fn run_script(state: &ServerState, client: &mut ClientState, script: Arc<RunningScript>, mut events: mpsc::UnboundedReceiver<Event>, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    let result = loop {
+        match events.recv().await {
+            Some(Event::Call { args, reply }) => {
+                let command = RedisCommand::from_args(args);
+                let write = command.flags().contains(CommandFlags::WRITE);
+                let result = if write && script.is_read_only() {
+                    Reply::Error("ERR Write commands are not allowed from read-only scripts.".to_string())
+                } else {
+                    script_call(state, client, command).await
+                };
+                if write && !matches!(result, Reply::Error(_)) {
+                    script.mark_write();
+                }
+                let _ = reply.send(result);
+            }
+            Some(Event::Done(result)) => break result,
+            None => break Err("ERR Error running script: scripting thread stopped".to_string()),
+        }
+    };
+    state.scripting.finish();
+    match result {
+        Ok(reply) => encoder.encode_reply(response, &reply),
+        Err(err) => encoder.encode_error(response, &RedisError::Script(err)),
+    }
+}
+
+/// 스크립트가 redis.call 로 요청한 명령 하나를 실행한다.
+/// execute 가 EVAL 을 거쳐 다시 이 함수를 부르므로 Future 를 박스에 담아 돌려준다.
+fn script_call<'a>(state: &'a ServerState, client: &'a mut ClientState, command: RedisCommand) -> Pin<Box<dyn Future<Output = Reply> + Send + 'a>> {
+    Box::pin(async move {
+        match &command {
+            RedisCommand::Invalid(RedisError::WrongArity(_)) => return Reply::Error("ERR Wrong number of args calling Redis command from script".to_string()),
+            RedisCommand::Invalid(_) => return Reply::Error("ERR Unknown Redis command called from script".to_string()),
+            _ => {}
+        }
+        if command.flags().contains(CommandFlags::NOSCRIPT) {
+            return Reply::Error("ERR This Redis command is not allowed from script".to_string());
+        }
+        if let Some(err) = replica_rejection(state, &command) {
+            return Reply::Error(err.to_string());
+        }
+        // 키는 EVAL 에서 이미 이 노드로 라우팅했으므로 스크립트는 다른 슬롯의 키를 다룰 수 없다
+        if let Some(cluster) = &state.cluster {
+            if cluster.route(&state.store, &command.keys(), false).await.is_err() {
+                return Reply::Error("ERR Script attempted to access a non local key in a cluster node".to_string());
+            }
+        }
+        // 스크립트는 클라이언트의 프로토콜과 상관없이 RESP2 로 응답을 받는다
+        let mut out = BytesMut::new();
+        let resp3 = std::mem::replace(&mut client.resp3, false);
+        execute(state, client, command, &mut out).await;
+        client.resp3 = resp3;
+        match RedisDecoder::new().parse_reply(&out) {
+            Ok((reply, _)) => reply,
+            Err(_) => Reply::Error("ERR Error parsing reply from command called from script".to_string()),
+        }
+    })
+}
+
+/// SCRIPT LOAD / EXISTS / FLUSH / KILL
+fn script_command(state: &ServerState, args: Vec<String>, response: &mut BytesMut) {
+    let encoder = RedisEncoder::new();
+    let subcommand = args[0].to_uppercase();
+    match (subcommand.as_str(), args.len()) {
+        ("LOAD", 2) => match state.scripting.load(&args[1]) {
+            Ok(sha) => encoder.encode_bulk_string(response, &sha),
+            Err(err) => encoder.encode_error(response, &RedisError::Script(err)),
+        },
+        ("EXISTS", n) if n >= 2 => {
+            let exists: Vec<i64> = args[1..].iter().map(|sha| state.scripting.exists(sha) as i64).collect();
+            encoder.encode_integer_array(response, &exists);
+        }
+        ("FLUSH", 1) => {
+            state.scripting.flush();
+            encoder.encode_ok(response);
+        }
+        ("FLUSH", 2) if args[1].eq_ignore_ascii_case("ASYNC") || args[1].eq_ignore_ascii_case("SYNC") => {
+            state.scripting.flush();
+            encoder.encode_ok(response);
+        }
+        ("KILL", 1) => script_kill(state, false, response),
+        _ => encoder.encode_error(response, &RedisError::UnknownSubcommand(subcommand.to_lowercase())),
+    }
+}
+
+/// SCRIPT KILL / FUNCTION KILL
+fn script_kill(state: &ServerState, function: bool, response: &mut BytesMut) {
+    let encoder = RedisEncoder::new();
+    match state.scripting.kill(function) {
+        Ok(()) => encoder.encode_ok(response),
+        Err(err) => encoder.encode_error(response, &RedisError::Script(err.to_string())),
+    }
+}
+
+/// FCALL / FCALL_RO: 라이브러리에 등록된 함수를 EVAL 처럼 실행한다.
+/// FCALL_RO 와 no-writes 함수는 쓰기 명령을 실행할 수 없다.
+async fn fcall(state: &ServerState, client: &mut ClientState, args: ScriptArgs, read_only: bool, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    let Some((library, function)) = find_function(state, &args.script).await else {
+        encoder.encode_error(response, &RedisError::Err("Function not found".to_string()));
+        return;
+    };
+    let no_writes = function.has_flag("no-writes");
+    if read_only && !no_writes {
+        encoder.encode_error(response, &RedisError::Err("Can not execute a script with write flag using *_ro command.".to_string()));
+        return;
+    }
+    if !no_writes && state.replication.is_replica() {
+        encoder.encode_error(response, &RedisError::ReadOnly);
+        return;
+    }
+    let (script, events) = state.scripting.start_function(&library, &function.name, args.keys, args.args, no_writes);
+    run_script(state, client, script, events, response).await;
+}
+
+/// 모든 라이브러리에서 이름으로 함수를 찾는다
+async fn find_function(state: &ServerState, name: &str) -> Option<(Arc<Library>, FunctionInfo)> {
+    for code in state.store.libraries() {
+        let Ok(library) = state.scripting.library(&code).await else {
+            continue;
+        };
+        if let Some(function) = library.functions.iter().find(|function| function.name == name).cloned() {
+            return Some((library, function));
+        }
+    }
+    None
+}
+
+/// FUNCTION LOAD / DELETE / FLUSH / LIST / DUMP / KILL (RESTORE 는 페이로드가 바이너리라서 따로 받는다)
+async fn function_command(state: &ServerState, client: &mut ClientState, args: Vec<String>, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    let subcommand = args[0].to_uppercase();
+    match (subcommand.as_str(), args.len()) {
+        ("LOAD", 2) | ("LOAD", 3) => {
+            if args.len() == 3 && !args[1].eq_ignore_ascii_case("REPLACE") {
+                encoder.encode_error(response, &RedisError::Err(format!("Unknown option given: {}", args[1])));
+                return;
+            }
+            let policy = if args.len() == 3 { "REPLACE" } else { "APPEND" };
+            let loaded = match state.scripting.library(&args[args.len() - 1]).await {
+                Ok(library) => install_libraries(state, vec![Arc::clone(&library)], policy).await.map(|()| library.name.clone()),
+                Err(err) => Err(RedisError::Script(err)),
+            };
+            match loaded {
+                Ok(name) => {
+                    let propagated: Vec<String> = std::iter::once("FUNCTION".to_string()).chain(args).collect();
+                    state.propagate(client, &propagated).await;
+                    encoder.encode_bulk_string(response, &name);
+                }
+                Err(err) => encoder.encode_error(response, &err),
+            }
+        }
+        ("DELETE", 2) => {
+            if state.store.remove_library(&args[1]) {
+                state.propagate(client, &["FUNCTION".to_string(), "DELETE".to_string(), args[1].clone()]).await;
+                encoder.encode_ok(response);
+            } else {
+                encoder.encode_error(response, &RedisError::Err("Library not found".to_string()));
+            }
+        }
+        ("FLUSH", 1) | ("FLUSH", 2) => {
+            if args.len() == 2 && !args[1].eq_ignore_ascii_case("ASYNC") && !args[1].eq_ignore_ascii_case("SYNC") {
+                encoder.encode_error(response, &RedisError::Err("FUNCTION FLUSH only supports SYNC|ASYNC option".to_string()));
+                return;
+            }
+            state.store.clear_libraries();
+            state.scripting.flush_functions();
+            state.propagate(client, &["FUNCTION".to_string(), "FLUSH".to_string()]).await;
+            encoder.encode_ok(response);
+        }
+        ("LIST", _) => match function_list(state, &args[1..]).await {
+            Ok(reply) => encoder.encode_reply(response, &reply),
+            Err(err) => encoder.encode_error(response, &err),
+        },
+        ("DUMP", 1) => encoder.encode_bulk_bytes(response, &RDB::dump_functions(&state.store.libraries())),
+        ("KILL", 1) => script_kill(state, true, response),
+        _ => encoder.encode_error(response, &RedisError::UnknownSubcommand(subcommand.to_lowercase())),
+    }
+}
+
+/// FUNCTION LIST [WITHCODE] [LIBRARYNAME pattern]
+async fn function_list(state: &ServerState, options: &[String]) -> Result<Reply, RedisError> {
+    let mut with_code = false;
+    let mut pattern = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.to_uppercase().as_str() {
+            "WITHCODE" => with_code = true,
+            "LIBRARYNAME" if pattern.is_none() => {
+                pattern = Some(options.next().ok_or_else(|| RedisError::Err("library name argument was not given".to_string()))?);
+            }
+            _ => return Err(RedisError::Err(format!("Unknown argument {}", option))),
+        }
+    }
+
+    let bulk = |s: &str| Reply::Bulk(Some(s.to_string()));
+    let mut libraries = Vec::new();
+    for code in state.store.libraries() {
+        let library = state.scripting.library(&code).await.map_err(RedisError::Script)?;
+        if pattern.is_some_and(|pattern| !glob_match(pattern.as_bytes(), library.name.as_bytes())) {
+            continue;
+        }
+        let functions = library
+            .functions
+            .iter()
+            .map(|function| {
+                Reply::Map(vec![
+                    (bulk("name"), bulk(&function.name)),
+                    (bulk("description"), Reply::Bulk(function.description.clone())),
+                    (bulk("flags"), Reply::Array(Some(function.flags.iter().map(|flag| Reply::Simple(flag.clone())).collect()))),
+                ])
+            })
+            .collect();
+        let mut entry = vec![(bulk("library_name"), bulk(&library.name)), (bulk("engine"), bulk("LUA")), (bulk("functions"), Reply::Array(Some(functions)))];
+        if with_code {
+            entry.push((bulk("library_code"), bulk(&library.code)));
+        }
+        libraries.push(Reply::Map(entry));
+    }
+    Ok(Reply::Array(Some(libraries)))
+}
+
+/// FUNCTION RESTORE payload [FLUSH | APPEND | REPLACE]
+async fn function_restore(state: &ServerState, client: &mut ClientState, payload: &[u8], policy: &str) -> Result<(), RedisError> {
+    let codes = RDB::restore_functions(payload).map_err(|_| RedisError::Err("payload version or checksum are wrong".to_string()))?;
+    let mut libraries = Vec::with_capacity(codes.len());
+    for code in &codes {
+        libraries.push(state.scripting.library(code).await.map_err(RedisError::Script)?);
+    }
+    install_libraries(state, libraries, policy).await?;
+
+    // replica 와 AOF 에는 페이로드 대신 FUNCTION LOAD 로 전파한다
+    if policy == "FLUSH" {
+        state.propagate(client, &["FUNCTION".to_string(), "FLUSH".to_string()]).await;
+    }
+    for code in codes {
+        state.propagate(client, &["FUNCTION".to_string(), "LOAD".to_string(), "REPLACE".to_string(), code]).await;
+    }
+    Ok(())
+}
+
+/// 라이브러리들을 Store 에 올린다. `policy` 는 FUNCTION RESTORE 의 정책과 같다:
+/// APPEND 는 같은 이름의 라이브러리가 있으면 실패하고, REPLACE 는 바꾸고, FLUSH 는 기존 라이브러리를 모두 지운다.
+/// 다른 라이브러리와 함수 이름이 겹치면 아무것도 올리지 않는다.
+async fn install_libraries(state: &ServerState, libraries: Vec<Arc<Library>>, policy: &str) -> Result<(), RedisError> {
+    let names: HashSet<&str> = libraries.iter().map(|library| library.name.as_str()).collect();
+    let mut taken = HashSet::new();
+    if policy != "FLUSH" {
+        for code in state.store.libraries() {
+            let existing = state.scripting.library(&code).await.map_err(RedisError::Script)?;
+            if names.contains(existing.name.as_str()) {
+                if policy == "APPEND" {
+                    return Err(RedisError::Err(format!("Library '{}' already exists", existing.name)));
+                }
+                continue;
+            }
+            taken.extend(existing.functions.iter().map(|function| function.name.clone()));
+        }
+    }
+    for function in libraries.iter().flat_map(|library| &library.functions) {
+        if !taken.insert(function.name.clone()) {
+            return Err(RedisError::Err(format!("Function {} already exists", function.name)));
+        }
+    }
+
+    if policy == "FLUSH" {
+        state.store.clear_libraries();
+    }
+    for library in libraries {
+        state.store.set_library(library.name.clone(), library.code.to_string());
+    }
+    Ok(())
+}
+
+/// (P|S)SUBSCRIBE: 채널마다 [subscribe, 채널, 이 커넥션의 구독 수] 를 보낸다
+fn subscribe(state: &ServerState, client: &ClientState, kind: pubsub::Kind, names: Vec<String>, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    let Some(tx) = &client.subscriber else {
+        encoder.encode_error(response, &RedisError::Err("SUBSCRIBE isn't allowed for this client".to_string()));
+        return;
+    };
+    let reply = match kind {
+        pubsub::Kind::Channel => "subscribe",
+        pubsub::Kind::Pattern => "psubscribe",
+        pubsub::Kind::Shard => "ssubscribe",
+    };
+    for name in names {
+        let count = state.pubsub.subscribe(client.id, tx, kind, &name);
+        encoder.encode_push_len(response, 3);
+        encoder.encode_bulk_string(response, reply);
+        encoder.encode_bulk_string(response, &name);
+        encoder.encode_integer(response, count as i64);
+    }
+}
+
+/// (P|S)UNSUBSCRIBE: 인자가 없으면 그 종류의 구독을 모두 푼다
+fn unsubscribe(state: &ServerState, client: &ClientState, kind: pubsub::Kind, names: Vec<String>, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    let reply = match kind {
+        pubsub::Kind::Channel => "unsubscribe",
+        pubsub::Kind::Pattern => "punsubscribe",
+        pubsub::Kind::Shard => "sunsubscribe",
+    };
+    let names = if names.is_empty() { state.pubsub.subscriptions(client.id, kind) } else { names };
+    if names.is_empty() {
+        encoder.encode_push_len(response, 3);
+        encoder.encode_bulk_string(response, reply);
+        encoder.encode_null(response);
+        encoder.encode_integer(response, state.pubsub.subscription_count(client.id) as i64);
+        return;
+    }
+    for name in names {
+        let count = state.pubsub.unsubscribe(client.id, kind, &name);
+        encoder.encode_push_len(response, 3);
+        encoder.encode_bulk_string(response, reply);
+        encoder.encode_bulk_string(response, &name);
+        encoder.encode_integer(response, count as i64);
+    }
+}
+
+/// CLIENT 하위 명령
+fn client_command(state: &ServerState, client: &mut ClientState, args: Vec<String>, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    let subcommand = args[0].to_uppercase();
+    match (subcommand.as_str(), args.len()) {
+        ("ID", 1) => encoder.encode_integer(response, client.id as i64),
+        ("SETNAME", 2) => match client_name(&args[1]) {
+            Ok(name) => {
+                client.name = name;
+                encoder.encode_ok(response);
+            }
+            Err(err) => encoder.encode_error(response, &err),
+        },
+        ("GETNAME", 1) => match &client.name {
+            Some(name) => encoder.encode_bulk_string(response, name),
+            None => encoder.encode_null(response),
+        },
+        ("TRACKING", n) if n >= 2 => match client_tracking(state, client, &args[1..]) {
+            Ok(()) => encoder.encode_ok(response),
+            Err(err) => encoder.encode_error(response, &err),
+        },
+        ("CACHING", 2) => {
+            let options = state.tracking.options(client.id).filter(|options| options.optin || options.optout);
+            let Some(options) = options else {
+                encoder.encode_error(response, &RedisError::Err("CLIENT CACHING can be called only when the client is in tracking mode with OPTIN or OPTOUT mode enabled".to_string()));
+                return;
+            };
+            match args[1].to_uppercase().as_str() {
+                "YES" if options.optin => client.caching = Some(true),
+                "YES" => return encoder.encode_error(response, &RedisError::Err("CLIENT CACHING YES is only valid when tracking is enabled in OPTIN mode.".to_string())),
+                "NO" if options.optout => client.caching = Some(false),
+                "NO" => return encoder.encode_error(response, &RedisError::Err("CLIENT CACHING NO is only valid when tracking is enabled in OPTOUT mode.".to_string())),
+                _ => return encoder.encode_error(response, &RedisError::Syntax),
+            }
+            encoder.encode_ok(response);
+        }
+        ("GETREDIR", 1) => {
+            let redirect = match state.tracking.options(client.id) {
+                Some(options) => options.redirect.map_or(0, |id| id as i64),
+                None => -1,
+            };
+            encoder.encode_integer(response, redirect);
+        }
+        ("TRACKINGINFO", 1) => encoder.encode_reply(response, &tracking_info(state.tracking.options(client.id), client.caching)),
+        _ => encoder.encode_error(response, &RedisError::UnknownSubcommand(subcommand.to_lowercase())),
+    }
+}
+
+/// 클라이언트 이름을 확인한다. 빈 이름은 이름을 지운다.
+fn client_name(name: &str) -> Result<Option<String>, RedisError> {
+    if name.is_empty() {
+        return Ok(None);
+    }
+    if !name.bytes().all(|b| (b'!'..=b'~').contains(&b)) {
+        return Err(RedisError::Err("Client names cannot contain spaces, newlines or special characters.".to_string()));
+    }
+    Ok(Some(name.to_string()))
+}
+
+/// HELLO [protover [AUTH username password] [SETNAME clientname]]
+fn hello(state: &ServerState, client: &mut ClientState, args: Vec<String>, response: &mut BytesMut) {
+    let mut args = args.iter();
+    let mut resp3 = client.resp3;
+    if let Some(version) = args.next() {
+        match version.parse::<i64>() {
+            Ok(2) => resp3 = false,
+            Ok(3) => resp3 = true,
+            Ok(_) => return client.encoder().encode_error(response, &RedisError::NoProto),
+            Err(_) => return client.encoder().encode_error(response, &RedisError::Err("Protocol version is not an integer or out of range".to_string())),
+        }
+    }
+    let mut name = client.name.clone();
+    while let Some(option) = args.next() {
+        let syntax_error = || RedisError::Err(format!("Syntax error in HELLO option '{}'", option));
+        let value = match option.to_uppercase().as_str() {
+            "AUTH" => match (args.next(), args.next()) {
+                // requirepass 가 없으므로 default 사용자는 비밀번호 없이 인증된다
+                (Some(username), Some(_)) if username == "default" => Ok(()),
+                (Some(_), Some(_)) => Err(RedisError::WrongPass),
+                _ => Err(syntax_error()),
+            },
+            "SETNAME" => match args.next().map(|n| client_name(n)) {
+                Some(Ok(n)) => {
+                    name = n;
+                    Ok(())
+                }
+                Some(Err(err)) => Err(err),
+                None => Err(syntax_error()),
+            },
+            _ => Err(syntax_error()),
+        };
+        if let Err(err) = value {
+            return client.encoder().encode_error(response, &err);
+        }
+    }
+
+    client.resp3 = resp3;
+    client.name = name;
+    state.pubsub.set_resp3(client.id, resp3);
+    let encoder = client.encoder();
+    let role = if state.replication.is_replica() { "replica" } else { "master" };
+    let mode = if state.cluster.is_some() { "cluster" } else { "standalone" };
+    encoder.encode_map_len(response, 7);
+    for (key, value) in [("server", "redis"), ("version", REDIS_VERSION)] {
+        encoder.encode_bulk_string(response, key);
+        encoder.encode_bulk_string(response, value);
+    }
+    encoder.encode_bulk_string(response, "proto");
+    encoder.encode_integer(response, if resp3 { 3 } else { 2 });
+    encoder.encode_bulk_string(response, "id");
+    encoder.encode_integer(response, client.id as i64);
+    for (key, value) in [("mode", mode), ("role", role)] {
+        encoder.encode_bulk_string(response, key);
+        encoder.encode_bulk_string(response, value);
+    }
+    encoder.encode_bulk_string(response, "modules");
+    encoder.encode_empty_array(response);
+}
+
+/// CLIENT TRACKING ON|OFF [REDIRECT client-id] [PREFIX prefix ...] [BCAST] [OPTIN] [OPTOUT] [NOLOOP]
+fn client_tracking(state: &ServerState, client: &ClientState, args: &[String]) -> Result<(), RedisError> {
+    let on = match args[0].to_uppercase().as_str() {
+        "ON" => true,
+        "OFF" => false,
+        _ => return Err(RedisError::Syntax),
+    };
+    let mut options = TrackingOptions::default();
+    let mut rest = args[1..].iter();
+    while let Some(option) = rest.next() {
+        match option.to_uppercase().as_str() {
+            "REDIRECT" if options.redirect.is_none() => {
+                let id = rest.next().ok_or(RedisError::Syntax)?;
+                let id = id.parse().map_err(|_| RedisError::NotInteger)?;
+                if !state.pubsub.is_connected(id) {
+                    return Err(RedisError::Err("The client ID you want redirect to does not exist".to_string()));
+                }
+                options.redirect = Some(id);
+            }
+            "PREFIX" => options.prefixes.push(rest.next().ok_or(RedisError::Syntax)?.clone()),
+            "BCAST" => options.bcast = true,
+            "OPTIN" => options.optin = true,
+            "OPTOUT" => options.optout = true,
+            "NOLOOP" => options.noloop = true,
+            _ => return Err(RedisError::Syntax),
+        }
+    }
+
+    if !on {
+        state.tracking.disable(client.id);
+        return Ok(());
+    }
+    if !options.prefixes.is_empty() && !options.bcast {
+        return Err(RedisError::Err("PREFIX option requires BCAST mode to be enabled".to_string()));
+    }
+    if options.bcast && (options.optin || options.optout) {
+        return Err(RedisError::Err("OPTIN and OPTOUT are not compatible with BCAST".to_string()));
+    }
+    if options.optin && options.optout {
+        return Err(RedisError::Err("You can't use both OPTIN and OPTOUT".to_string()));
+    }
+    state.tracking.enable(client.id, options)
+}
+
+/// CLIENT TRACKINGINFO: flags, redirect, prefixes
+fn tracking_info(options: Option<TrackingOptions>, caching: Option<bool>) -> Reply {
+    let bulk = |s: &str| Reply::Bulk(Some(s.to_string()));
+    let Some(options) = options else {
+        return Reply::Map(vec![(bulk("flags"), Reply::Array(Some(vec![bulk("off")]))), (bulk("redirect"), Reply::Integer(-1)), (bulk("prefixes"), Reply::Array(Some(vec![])))]);
+    };
+    let mut flags = vec![bulk("on")];
+    for (flag, set) in [("bcast", options.bcast), ("optin", options.optin), ("optout", options.optout), ("caching-yes", caching == Some(true)), ("caching-no", caching == Some(false)), ("noloop", options.noloop)] {
+        if set {
+            flags.push(bulk(flag));
+        }
+    }
+    Reply::Map(vec![
+        (bulk("flags"), Reply::Array(Some(flags))),
+        (bulk("redirect"), Reply::Integer(options.redirect.map_or(0, |id| id as i64))),
+        (bulk("prefixes"), Reply::Array(Some(options.prefixes.iter().map(|prefix| bulk(prefix)).collect()))),
+    ])
+}
+
+/// PUBSUB 하위 명령
+fn pubsub_command(state: &ServerState, client: &ClientState, args: Vec<String>, response: &mut BytesMut) {
+    let encoder = client.encoder();
+    let subcommand = args[0].to_uppercase();
+    let args: Vec<&str> = args[1..].iter().map(|s| s.as_str()).collect();
+
+    match (subcommand.as_str(), args.as_slice()) {
+        ("CHANNELS", []) | ("CHANNELS", [_]) | ("SHARDCHANNELS", []) | ("SHARDCHANNELS", [_]) => {
+            let kind = if subcommand == "CHANNELS" { pubsub::Kind::Channel } else { pubsub::Kind::Shard };
+            let channels = state.pubsub.channels(kind, args.first().copied());
+            let channel_refs: Vec<&str> = channels.iter().map(|s| s.as_str()).collect();
+            encoder.encode_array(response, &channel_refs);
+        }
+        ("NUMSUB", channels) | ("SHARDNUMSUB", channels) => {
+            let kind = if subcommand == "NUMSUB" { pubsub::Kind::Channel } else { pubsub::Kind::Shard };
+            encoder.encode_map_len(response, channels.len());
+            for channel in channels {
+                encoder.encode_bulk_string(response, channel);
+                encoder.encode_integer(response, state.pubsub.numsub(kind, channel) as i64);
+            }
+        }
+        ("NUMPAT", []) => encoder.encode_integer(response, state.pubsub.numpat() as i64),
+        _ => encoder.encode_error(response, &RedisError::UnknownSubcommand(subcommand.to_lowercase())),
+    }
+}
+
+/// CLUSTER 하위 명령
+async fn cluster_command(state: &ServerState, args: Vec<String>, response: &mut BytesMut) {
+    let encoder = RedisEncoder::new();
+    let Some(cluster) = &state.cluster else {
+        encoder.encode_error(response, &RedisError::Err("This instance has cluster support disabled".to_string()));
+        return;
+    };
+    let subcommand = args[0].to_uppercase();
+    let args: Vec<&str> = args[1..].iter().map(|s| s.as_str()).collect();
+
+    match (subcommand.as_str(), args.as_slice()) {
+        ("MYID", []) => encoder.encode_bulk_string(response, &cluster.myid()),
+        ("INFO", []) => encoder.encode_bulk_string(response, &cluster.info()),
+        ("NODES", []) => encoder.encode_bulk_string(response, &cluster.nodes()),
+        ("KEYSLOT", [key]) => encoder.encode_integer(response, cluster::key_hash_slot(key) as i64),
+        ("COUNTKEYSINSLOT", [slot]) => match cluster::parse_slot(slot) {
+            Ok(slot) => {
+                let count = keys_in_slot(&state.store, slot, usize::MAX).await.len();
+                encoder.encode_integer(response, count as i64);
+            }
+            Err(_) => encoder.encode_error(response, &RedisError::Err("Invalid slot".to_string())),
+        },
+        ("GETKEYSINSLOT", [slot, count]) => match (cluster::parse_slot(slot), count.parse::<usize>()) {
+            (Ok(slot), Ok(count)) => {
+                let keys = keys_in_slot(&state.store, slot, count).await;
+                let key_refs: Vec<&str> = keys.iter().map(|s| s.as_str()).collect();
+                encoder.encode_array(response, &key_refs);
+            }
+            (Err(_), _) => encoder.encode_error(response, &RedisError::Err("Invalid slot".to_string())),
+            (_, Err(_)) => encoder.encode_error(response, &RedisError::Err("Invalid number of keys".to_string())),
+        },
+        ("ADDSLOTS", slots) | ("DELSLOTS", slots) if !slots.is_empty() => {
+            let slots: Result<Vec<u16>> = slots.iter().map(|s| cluster::parse_slot(s)).collect();
+            let result = slots.and_then(|slots| match subcommand.as_str() {
+                "ADDSLOTS" => cluster.add_slots(&slots),
+                _ => cluster.del_slots(&slots),
+            });
+            match result {
+                Ok(()) => {
+                    state.drop_unserved_shard_channels();
+                    encoder.encode_ok(response);
+                }
+                Err(e) => encoder.encode_error(response, &RedisError::Err(e.to_string())),
+            }
+        }
+        ("ADDSLOTSRANGE", ranges) | ("DELSLOTSRANGE", ranges) if !ranges.is_empty() && ranges.len() % 2 == 0 => {
+            let mut slots = Vec::new();
+            for range in ranges.chunks(2) {
+                match (cluster::parse_slot(range[0]), cluster::parse_slot(range[1])) {
+                    (Ok(start), Ok(end)) if start <= end => slots.extend(start..=end),
+                    (Ok(_), Ok(_)) => {
+                        encoder.encode_error(response, &RedisError::Err("start slot number is greater than end slot number".to_string()));
+                        return;
+                    }
+                    _ => {
+                        encoder.encode_error(response, &RedisError::Err("Invalid or out of range slot".to_string()));
+                        return;
+                    }
+                }
+            }
+            let result = match subcommand.as_str() {
+                "ADDSLOTSRANGE" => cluster.add_slots(&slots),
+                _ => cluster.del_slots(&slots),
+            };
+            match result {
+                Ok(()) => {
+                    state.drop_unserved_shard_channels();
+                    encoder.encode_ok(response);
+                }
+                Err(e) => encoder.encode_error(response, &RedisError::Err(e.to_string())),
+            }
+        }
+        ("MEET", [ip, port]) | ("MEET", [ip, port, _]) => {
+            let cport = match args.get(2) {
+                Some(cport) => cport.parse::<u16>().ok(),
+                None => port.parse::<u16>().ok().and_then(|port| port.checked_add(cluster::BUS_PORT_OFFSET)),
+            };
+            match cport {
+                Some(cport) if port.parse::<u16>().is_ok() => {
+                    cluster.meet(ip, cport);
+                    encoder.encode_ok(response);
+                }
+                _ => encoder.encode_error(response, &RedisError::Err(format!("Invalid node address specified: {}:{}", ip, port))),
+            }
+        }
+        ("SETSLOT", [slot, rest @ ..]) => {
+            let Ok(slot) = cluster::parse_slot(slot) else {
+                encoder.encode_error(response, &RedisError::Err("Invalid or out of range slot".to_string()));
+                return;
+            };
+            let action = match rest {
+                [action, id] if action.eq_ignore_ascii_case("IMPORTING") => SetSlot::Importing(id.to_string()),
+                [action, id] if action.eq_ignore_ascii_case("MIGRATING") => SetSlot::Migrating(id.to_string()),
+                [action, id] if action.eq_ignore_ascii_case("NODE") => SetSlot::Node(id.to_string()),
+                [action] if action.eq_ignore_ascii_case("STABLE") => SetSlot::Stable,
+                _ => {
+                    encoder.encode_error(response, &RedisError::Err("Invalid CLUSTER SETSLOT action or number of arguments. Try CLUSTER HELP".to_string()));
+                    return;
+                }
+            };
+            let keys = keys_in_slot(&state.store, slot, usize::MAX).await.len();
+            match cluster.set_slot(slot, action, keys) {
+                Ok(()) => {
+                    state.drop_unserved_shard_channels();
+                    encoder.encode_ok(response);
+                }
+                Err(e) => encoder.encode_error(response, &RedisError::Err(e.to_string())),
+            }
+        }
+        ("REPLICATE", [id]) => {
+            if !state.replication.is_replica() && !state.store.is_empty().await {
+                encoder.encode_error(response, &RedisError::Err("To set a master the node must be empty and without assigned slots.".to_string()));
+                return;
+            }
+            match cluster.replicate(id) {
+                Ok(master) => {
+                    state.replication.set_master(Some(master));
+                    state.drop_unserved_shard_channels();
+                    encoder.encode_ok(response);
+                }
+                Err(e) => encoder.encode_error(response, &RedisError::Err(e.to_string())),
+            }
+        }
+        ("SLOTS", []) => {
+            // 슬롯 구간마다 [start, end, master, replica...]
+            let shards = cluster.shards();
+            let ranges: Vec<_> = shards.iter().flat_map(|shard| shard.ranges.iter().map(move |range| (range, shard))).collect();
+            encoder.encode_array_len(response, ranges.len());
+            for ((start, end), shard) in ranges {
+                encoder.encode_array_len(response, 3 + shard.replicas.len());
+                encoder.encode_integer(response, *start as i64);
+                encoder.encode_integer(response, *end as i64);
+                for node in std::iter::once(&shard.master).chain(&shard.replicas) {
+                    encoder.encode_array_len(response, 3);
+                    encoder.encode_bulk_string(response, &node.ip);
+                    encoder.encode_integer(response, node.port as i64);
+                    encoder.encode_bulk_string(response, &node.id);
+                }
+            }
+        }
+        ("SHARDS", []) => {
+            let shards = cluster.shards();
+            encoder.encode_array_len(response, shards.len());
+            for shard in shards {
+                encoder.encode_array_len(response, 4);
+                encoder.encode_bulk_string(response, "slots");
+                let bounds: Vec<i64> = shard.ranges.iter().flat_map(|(start, end)| [*start as i64, *end as i64]).collect();
+                encoder.encode_integer_array(response, &bounds);
+                encoder.encode_bulk_string(response, "nodes");
+                encoder.encode_array_len(response, 1 + shard.replicas.len());
+                for node in std::iter::once(&shard.master).chain(&shard.replicas) {
+                    let role = if node.is_master() { "master" } else { "replica" };
+                    encoder.encode_array_len(response, 14);
+                    for (name, value) in [("id", node.id.as_str()), ("ip", &node.ip), ("endpoint", &node.ip), ("role", role), ("health", "online")] {
+                        encoder.encode_bulk_string(response, name);
+                        encoder.encode_bulk_string(response, value);
+                    }
+                    encoder.encode_bulk_string(response, "port");
+                    encoder.encode_integer(response, node.port as i64);
+                    encoder.encode_bulk_string(response, "replication-offset");
+                    encoder.encode_integer(response, 0);
+                }
+            }
+        }
+        _ => encoder.encode_error(response, &RedisError::Err("Unknown subcommand or wrong number of arguments for 'CLUSTER'".to_string())),
+    }
+}
+
+/// RESTORE: DUMP 페이로드로 키를 만든다. 복제와 AOF 에는 SET 으로 전파한다.
+/// 이 서버는 LRU/LFU 정보를 따로 두지 않으므로 IDLETIME 과 FREQ 는 값만 검사한다.
+async fn restore(state: &ServerState, client: &mut ClientState, args: RestoreArgs) -> Result<(), RedisError> {
+    let store = &state.store;
+    if args.ttl < 0 {
+        return Err(RedisError::Err("Invalid TTL value, must be >= 0".to_string()));
+    }
+    if args.idletime.is_some_and(|idletime| idletime < 0) {
+        return Err(RedisError::Err("Invalid IDLETIME value, must be >= 0".to_string()));
+    }
+    if args.freq.is_some_and(|freq| !(0..=255).contains(&freq)) {
+        return Err(RedisError::Err("Invalid FREQ value, must be >= 0 and <= 255".to_string()));
+    }
+    if !args.replace && store.contains(&args.key).await {
+        return Err(RedisError::BusyKey);
+    }
+    let value = RDB::restore_value(&args.payload).map_err(|_| RedisError::Err("DUMP payload version or checksum are wrong".to_string()))?;
+
+    let now = now_millis();
+    let expiry_ts = match (args.ttl as u64, args.absttl) {
+        (0, _) => None,
+        (ts, true) => Some(ts),
+        (ttl, false) => Some(now + ttl),
+    };
+    if expiry_ts.is_some_and(|ts| ts <= now) {
+        // 이미 지난 만료 시각이면 키를 만들지 않는다 (REPLACE 면 있던 키는 지운다)
+        if store.remove(&args.key).await {
+            state.propagate(client, &["DEL".to_string(), args.key]).await;
+        }
+        return Ok(());
+    }
+
+    let mut propagated = vec!["SET".to_string(), args.key.clone(), value.clone()];
+    if let Some(ts) = expiry_ts {
+        propagated.push("PXAT".to_string());
+        propagated.push(ts.to_string());
+    }
+    store.restore_at(args.key, value, expiry_ts).await;
+    state.propagate(client, &propagated).await;
+    Ok(())
+}
+
+/// MIGRATE: 키들을 DUMP 페이로드로 다른 인스턴스에 RESTORE 하고, COPY 가 아니면 여기서는 지운다.
+/// 옮길 키가 하나도 없으면 false. 받는 쪽은 슬롯을 가져오는 중일 수 있으므로 RESTORE-ASKING 을 보낸다.
+async fn migrate(state: &ServerState, client: &mut ClientState, args: MigrateArgs) -> Result<bool, RedisError> {
+    let mut entries = Vec::new();
+    for key in args.keys {
+        if let Some((value, expiry)) = state.store.get_with_expiry(&key).await {
+            entries.push((key, value, expiry));
+        }
+    }
+    if entries.is_empty() {
+        return Ok(false);
+    }
+
+    let ioerr = |e: anyhow::Error| RedisError::IoErr(format!("error or timeout writing to target instance: {}", e));
+    let target_error = |e: String| RedisError::Err(format!("Target instance replied with error: {}", e));
+    let timeout = Duration::from_millis(if args.timeout == 0 { 1000 } else { args.timeout });
+    let mut target = Client::connect(&MasterAddr { host: args.host, port: args.port }, timeout).await.map_err(ioerr)?;
+
+    if let Some((username, password)) = &args.auth {
+        let mut auth = vec!["AUTH"];
+        auth.extend(username.as_deref());
+        auth.push(password);
+        if let Reply::Error(e) = target.command(&auth).await.map_err(ioerr)? {
+            return Err(target_error(e));
+        }
+    }
+    if args.db != 0 {
+        if let Reply::Error(e) = target.command(&["SELECT", &args.db.to_string()]).await.map_err(ioerr)? {
+            return Err(target_error(e));
+        }
+    }
+
+    for (key, value, expiry) in entries {
+        // 남은 TTL 을 상대 시간으로 보낸다 (0 은 만료 없음)
+        let ttl = expiry.map_or(0, |ts| ts.saturating_sub(now_millis()).max(1)).to_string();
+        let payload = RDB::dump_value(&value);
+        let mut restore: Vec<&[u8]> = vec![b"RESTORE-ASKING", key.as_bytes(), ttl.as_bytes(), &payload];
+        if args.replace {
+            restore.push(b"REPLACE");
+        }
+        if let Reply::Error(e) = target.command_bytes(&restore).await.map_err(ioerr)? {
+            return Err(target_error(e));
+        }
+        if !args.copy {
+            state.store.remove(&key).await;
+            state.propagate(client, &["DEL".to_string(), key]).await;
+        }
+    }
+    Ok(true)
+}
+
+/// 슬롯에 속한 키를 최대 `count` 개까지 찾는다
+async fn keys_in_slot(store: &Store, slot: u16, count: usize) -> Vec<String> {
+    store
+        .keys("*")
+        .await
+        .into_iter()
+        .filter(|key| cluster::key_hash_slot(key) == slot)
+        .take(count)
+        .collect()
+}
@@ -0,0 +1,131 @@
+use crate::persistence::Persistence;
+use crate::protocol::decoder::{RedisDecoder, RedisCommand};
+use crate::pubsub::PubSub;
+use crate::replication::Replication;
+use crate::scripting::Scripting;
+use crate::server::{discard, exec, execute, multi, queue, ClientState, ServerState};
+use crate::store::Store;
+use crate::tracking::Tracking;
+use bytes::BytesMut;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::test;
+
+fn new_state() -> ServerState {
+    let pubsub = Arc::new(PubSub::new());
+    ServerState {
+        store: Arc::new(Store::new()),
+        persistence: Arc::new(Persistence::new("test_server.rdb".to_string(), vec![])),
+        aof: None,
+        replication: Replication::new(6379, None, 1024 * 1024, true, true),
+        cluster: None,
+        tracking: Arc::new(Tracking::new(Arc::clone(&pubsub))),
+        pubsub,
+        scripting: Scripting::new(Duration::from_secs(5)),
+        decoder: RedisDecoder::new(),
+        query_buffer_limit: usize::MAX,
+        command_lock: Mutex::new(()),
+    }
+}
+
+fn command(args: &[&str]) -> RedisCommand {
+    RedisCommand::from_args(args.iter().map(|s| s.to_string()).collect())
+}
+
+/// MULTI 를 보내고 명령들을 쌓는다. 쌓을 때의 응답을 돌려준다.
+async fn queue_all(state: &ServerState, client: &mut ClientState, commands: &[&[&str]]) -> BytesMut {
+    let mut response = BytesMut::new();
+    multi(client, &mut response);
+    for args in commands {
+        queue(state, client, command(args), &mut response).await;
+    }
+    response
+}
+
+#[test]
+async fn test_exec_replies_match_commands() {
+    let commands: &[&[&str]] = &[&["SET", "a", "1"], &["GET", "a"], &["DEL", "a", "b"], &["GET", "a"]];
+
+    let state = new_state();
+    let mut client = ClientState::default();
+    let queued = queue_all(&state, &mut client, commands).await;
+    assert_eq!(&queued[..], b"+OK\r\n+QUEUED\r\n+QUEUED\r\n+QUEUED\r\n+QUEUED\r\n");
+    // EXEC 전에는 아무것도 실행하지 않는다
+    assert!(state.store.get("a").await.is_none());
+
+    let mut response = BytesMut::new();
+    exec(&state, &mut client, &mut response).await;
+    assert!(client.transaction.is_none());
+
+    // 같은 명령을 하나씩 실행한 응답을 배열로 묶은 것과 같다
+    let single = new_state();
+    let mut expected = BytesMut::from(&b"*4\r\n"[..]);
+    for args in commands {
+        execute(&single, &mut ClientState::default(), command(args), &mut expected).await;
+    }
+    assert_eq!(response, expected);
+}
+
+#[test]
+async fn test_exec_aborts_after_queue_error() {
+    let state = new_state();
+    let mut client = ClientState::default();
+    let queued = queue_all(&state, &mut client, &[&["SET", "a", "1"], &["GET"], &["NOSUCH", "x"]]).await;
+    assert_eq!(
+        &queued[..],
+        b"+OK\r\n+QUEUED\r\n-ERR wrong number of arguments for 'get' command\r\n-ERR unknown command 'NOSUCH', with args beginning with: 'x' \r\n"
+    );
+
+    let mut response = BytesMut::new();
+    exec(&state, &mut client, &mut response).await;
+    assert_eq!(&response[..], b"-EXECABORT Transaction discarded because of previous errors.\r\n");
+    // 거부되지 않은 명령도 실행하지 않는다
+    assert!(state.store.get("a").await.is_none());
+    assert!(client.transaction.is_none());
+}
+
+#[test]
+async fn test_multi_nesting_and_discard() {
+    let state = new_state();
+    let mut client = ClientState::default();
+    let mut response = queue_all(&state, &mut client, &[&["SET", "a", "1"]]).await;
+    response.clear();
+
+    multi(&mut client, &mut response);
+    assert_eq!(&response[..], b"-ERR MULTI calls can not be nested\r\n");
+    // 중첩된 MULTI 는 트랜잭션을 망가뜨리지 않는다
+    assert!(client.transaction.is_some());
+    response.clear();
+
+    discard(&state, &mut client, &mut response);
+    assert_eq!(&response[..], b"+OK\r\n");
+    assert!(client.transaction.is_none());
+    assert!(state.store.get("a").await.is_none());
+    response.clear();
+
+    discard(&state, &mut client, &mut response);
+    exec(&state, &mut client, &mut response).await;
+    assert_eq!(&response[..], b"-ERR DISCARD without MULTI\r\n-ERR EXEC without MULTI\r\n");
+}
+
+#[test]
+async fn test_exec_propagates_writes_in_multi() {
+    let state = new_state();
+    // replica 가 붙은 것처럼 backlog 를 만들어 전파된 바이트를 센다
+    state.replication.create_backlog();
+    let mut client = ClientState::default();
+
+    // 읽기만 하는 트랜잭션은 전파하지 않는다
+    queue_all(&state, &mut client, &[&["GET", "a"]]).await;
+    exec(&state, &mut client, &mut BytesMut::new()).await;
+    assert_eq!(state.replication.master_repl_offset(), 0);
+
+    queue_all(&state, &mut client, &[&["SET", "a", "1"], &["GET", "a"], &["SET", "b", "2"]]).await;
+    exec(&state, &mut client, &mut BytesMut::new()).await;
+    let multi = "*1\r\n$5\r\nMULTI\r\n".len();
+    let set = "*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n".len();
+    let exec = "*1\r\n$4\r\nEXEC\r\n".len();
+    assert_eq!(state.replication.master_repl_offset() as usize, multi + 2 * set + exec);
+    assert_eq!(client.woff, state.replication.master_repl_offset());
+}
@@ -0,0 +1,12 @@
+use crate::sha1::sha1_hex;
+use tokio::test;
+
+#[test]
+async fn test_sha1_known_digests() {
+    assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    // 패딩이 블록 두 개에 걸친다
+    assert_eq!(sha1_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+    // SCRIPT LOAD "return 'hello moon'" 의 결과
+    assert_eq!(sha1_hex(b"return 'hello moon'"), "8e3d8cfcbb6571ecf555cc0a7d6fb950b4437dc6");
+}
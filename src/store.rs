@@ -1,8 +1,12 @@
 // store.rs
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as SyncMutex, OnceLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
-use crate::pattern_parser::{HashMapPatternExt, WildCardPattern};
+use crate::notify::{Notifier, NotifyFlags};
+use crate::pattern_parser::{GlobPattern, Pattern};
+use crate::tracking::Tracking;
 
 #[derive(Debug)]
 struct Value {
@@ -10,61 +14,201 @@ struct Value {
     expiry: Option<u64>, // 만료 시간 (Unix timestamp in milliseconds)
 }
 
+impl Value {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expiry.is_some_and(|expiry| now > expiry)
+    }
+}
+
+/// 특정 시점의 Store 내용 복사본 (BGSAVE 등에서 사용)
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub entries: Vec<(String, String, Option<u64>)>,
+    /// 함수 라이브러리 코드 (라이브러리 이름 순)
+    pub functions: Vec<String>,
+    /// 스냅샷을 뜬 시점의 dirty 카운터
+    pub dirty: u64,
+}
+
+/// 현재 시각 (Unix timestamp in milliseconds)
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
 #[derive(Debug)]
 pub struct Store {
     data: Mutex<HashMap<String, Value>>,
+    /// 마지막 저장 이후 변경 횟수를 계산하기 위한 누적 변경 카운터
+    dirty: AtomicU64,
+    /// 키스페이스 알림. 데이터를 다 불러온 뒤에 설정하므로 로딩 중에는 알리지 않는다.
+    notifier: OnceLock<Notifier>,
+    /// CLIENT TRACKING 무효화. notifier 처럼 로딩이 끝난 뒤에 설정한다.
+    tracking: OnceLock<Arc<Tracking>>,
+    /// WATCH 중인 키의 변경 버전. 아무도 WATCH 하지 않는 키는 기록하지 않는다.
+    /// 커넥션이 끊길 때 Drop 에서 지울 수 있도록 std Mutex 를 쓴다.
+    watched: SyncMutex<HashMap<String, WatchedKey>>,
+    /// FUNCTION LOAD 로 올린 라이브러리 이름 → 코드. FLUSHALL 로 지워지지 않는다.
+    functions: SyncMutex<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Default)]
+struct WatchedKey {
+    /// 마지막으로 바뀐 시점의 dirty 카운터 (WATCH 이후 바뀐 적이 없으면 0)
+    version: u64,
+    /// WATCH 한 클라이언트 ID → WATCH 할 때의 버전
+    watchers: HashMap<u64, u64>,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Store {
     pub fn new() -> Self {
         Store {
-            data: Mutex::new(HashMap::new())
+            data: Mutex::new(HashMap::new()),
+            dirty: AtomicU64::new(0),
+            notifier: OnceLock::new(),
+            tracking: OnceLock::new(),
+            watched: SyncMutex::new(HashMap::new()),
+            functions: SyncMutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn set_notifier(&self, notifier: Notifier) {
+        let _ = self.notifier.set(notifier);
+    }
+
+    pub fn notifier(&self) -> Option<&Notifier> {
+        self.notifier.get()
+    }
+
+    pub fn set_tracking(&self, tracking: Arc<Tracking>) {
+        let _ = self.tracking.set(tracking);
+    }
+
+    fn notify(&self, class: NotifyFlags, event: &str, key: &str) {
+        if let Some(notifier) = self.notifier.get() {
+            notifier.notify(class, event, key);
         }
     }
 
     pub async fn insert(&self, key: String, value: String, expiry: Option<u64>) {
+        let expiry_ts = expiry.map(|ms| now_millis() + ms);
+        self.insert_at(key, value, expiry_ts).await;
+    }
+
+    /// 만료 시각을 절대 시각 (Unix timestamp in milliseconds)으로 받는 insert
+    pub async fn insert_at(&self, key: String, value: String, expiry_ts: Option<u64>) {
+        let notify_key = self.notifier.get().map(|_| key.clone());
+        let created = self.write(key, value, expiry_ts).await;
+        if let Some(key) = notify_key {
+            self.notify(NotifyFlags::STRING, "set", &key);
+            if expiry_ts.is_some() {
+                self.notify(NotifyFlags::GENERIC, "expire", &key);
+            }
+            if created {
+                self.notify(NotifyFlags::NEW, "new", &key);
+            }
+        }
+    }
+
+    /// RESTORE 로 만든 키. insert_at 과 같지만 restore 이벤트로 알린다.
+    pub async fn restore_at(&self, key: String, value: String, expiry_ts: Option<u64>) {
+        let notify_key = self.notifier.get().map(|_| key.clone());
+        let created = self.write(key, value, expiry_ts).await;
+        if let Some(key) = notify_key {
+            self.notify(NotifyFlags::GENERIC, "restore", &key);
+            if created {
+                self.notify(NotifyFlags::NEW, "new", &key);
+            }
+        }
+    }
+
+    /// 값을 쓰고, 새로 생긴 키인지 돌려준다 (만료된 키를 덮어쓴 것도 새 키)
+    async fn write(&self, key: String, value: String, expiry_ts: Option<u64>) -> bool {
         let mut store = self.data.lock().await;
-        let expiry_ts = expiry.map(|ms| {
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as u64 + ms
-        });
-        
-        store.insert(key, Value {
+        self.touch(&key);
+        let previous = store.insert(key, Value {
             data: value,
             expiry: expiry_ts,
         });
+        previous.is_none_or(|value| value.is_expired(now_millis()))
     }
 
     pub async fn get(&self, key: &str) -> Option<String> {
+        let value = self.get_with_expiry(key).await.map(|(value, _)| value);
+        if value.is_none() {
+            self.notify(NotifyFlags::KEY_MISS, "keymiss", key);
+        }
+        value
+    }
+
+    /// 키가 있는지 (keymiss 를 알리지 않는다)
+    pub async fn contains(&self, key: &str) -> bool {
+        self.get_with_expiry(key).await.is_some()
+    }
+
+    /// 값과 만료 시각 (Unix timestamp in milliseconds). 만료된 키는 여기서 지운다.
+    pub async fn get_with_expiry(&self, key: &str) -> Option<(String, Option<u64>)> {
         let mut store = self.data.lock().await;
-        
-        if let Some(value) = store.get(key) {
-            // 만료 시간 체크
-            if let Some(expiry) = value.expiry {
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis() as u64;
-                
-                if now > expiry {
-                    // 만료된 키 삭제
-                    store.remove(key);
-                    return None;
-                }
+        let value = store.get(key)?;
+        if value.is_expired(now_millis()) {
+            store.remove(key);
+            self.touch(key);
+            drop(store);
+            self.notify(NotifyFlags::EXPIRED, "expired", key);
+            return None;
+        }
+        Some((value.data.clone(), value.expiry))
+    }
+
+    /// 키를 지운다. 만료된 키는 없던 것으로 본다.
+    pub async fn remove(&self, key: &str) -> bool {
+        let mut store = self.data.lock().await;
+        let Some(value) = store.remove(key) else {
+            return false;
+        };
+        self.touch(key);
+        drop(store);
+        if value.is_expired(now_millis()) {
+            self.notify(NotifyFlags::EXPIRED, "expired", key);
+            return false;
+        }
+        self.notify(NotifyFlags::GENERIC, "del", key);
+        true
+    }
+
+    /// 만료된 키를 찾아 지우고 (active expiry), 지운 수를 돌려준다.
+    /// 읽히지 않는 키도 만료 시각에 맞춰 expired 이벤트가 나가게 한다.
+    pub async fn remove_expired(&self) -> usize {
+        let now = now_millis();
+        let expired: Vec<String> = {
+            let mut store = self.data.lock().await;
+            let expired: Vec<String> = store.iter().filter(|(_, v)| v.is_expired(now)).map(|(k, _)| k.clone()).collect();
+            for key in &expired {
+                store.remove(key);
+                self.touch(key);
             }
-            Some(value.data.clone())
-        } else {
-            None
+            expired
+        };
+        for key in &expired {
+            self.notify(NotifyFlags::EXPIRED, "expired", key);
         }
+        expired.len()
     }
 
     pub async fn keys(&self, pattern: &str) -> Vec<String> {
         let store = self.data.lock().await;
+        let pattern = GlobPattern(pattern.to_string());
         store
             .keys()
-            .filter(|_| store.contains_key_pattern(WildCardPattern(pattern.to_string())))
+            .filter(|key| pattern.matches(key))
             .map(|k| k.to_string())
             .collect()
     }
@@ -79,11 +223,131 @@ impl Store {
             .into_iter()
     }
 
+    /// 락을 한 번만 잡고 현재 내용을 복사한다.
+    /// 복사가 끝나면 락을 바로 풀기 때문에 직렬화/디스크 쓰기 동안 다른 클라이언트가 막히지 않는다.
+    pub async fn snapshot(&self) -> Snapshot {
+        let store = self.data.lock().await;
+        Snapshot {
+            entries: store
+                .iter()
+                .map(|(k, v)| (k.clone(), v.data.clone(), v.expiry))
+                .collect(),
+            functions: self.functions.lock().unwrap().values().cloned().collect(),
+            dirty: self.dirty.load(Ordering::SeqCst),
+        }
+    }
+
+    /// 다른 Store 의 내용으로 통째로 교체한다 (replica 가 master 의 RDB 를 받았을 때).
+    pub async fn replace(&self, other: Store) {
+        let data = other.data.into_inner();
+        let mut store = self.data.lock().await;
+        *store = data;
+        *self.functions.lock().unwrap() = other.functions.into_inner().unwrap();
+        self.touch_all();
+    }
+
+    /// FLUSHDB / FLUSHALL: 모든 키를 지우고 지운 수를 돌려준다
+    pub async fn clear(&self) -> usize {
+        let mut store = self.data.lock().await;
+        let removed = store.len();
+        store.clear();
+        self.touch_all();
+        removed
+    }
+
+    /// 라이브러리 코드 (라이브러리 이름 순)
+    pub fn libraries(&self) -> Vec<String> {
+        self.functions.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn library(&self, name: &str) -> Option<String> {
+        self.functions.lock().unwrap().get(name).cloned()
+    }
+
+    /// 라이브러리를 추가하거나 같은 이름의 라이브러리를 바꾼다
+    pub fn set_library(&self, name: String, code: String) {
+        self.functions.lock().unwrap().insert(name, code);
+        self.dirty.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn remove_library(&self, name: &str) -> bool {
+        let removed = self.functions.lock().unwrap().remove(name).is_some();
+        if removed {
+            self.dirty.fetch_add(1, Ordering::SeqCst);
+        }
+        removed
+    }
+
+    /// FUNCTION FLUSH
+    pub fn clear_libraries(&self) {
+        self.functions.lock().unwrap().clear();
+        self.dirty.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// 키가 바뀌었다. dirty 카운터를 올리고, WATCH 중인 키면 버전을 새로 매기고, 추적 중인 클라이언트에게 알린다.
+    fn touch(&self, key: &str) {
+        let version = self.dirty.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(watched) = self.watched.lock().unwrap().get_mut(key) {
+            watched.version = version;
+        }
+        if let Some(tracking) = self.tracking.get() {
+            tracking.invalidate(key);
+        }
+    }
+
+    /// 모든 키가 바뀌었다 (FLUSHDB, full resync)
+    fn touch_all(&self) {
+        let version = self.dirty.fetch_add(1, Ordering::SeqCst) + 1;
+        for watched in self.watched.lock().unwrap().values_mut() {
+            watched.version = version;
+        }
+        if let Some(tracking) = self.tracking.get() {
+            tracking.invalidate_all();
+        }
+    }
+
+    /// WATCH: 지금 버전을 기억해 두고 EXEC 때 비교한다. 이미 만료된 키는 먼저 지워서 WATCH 이후의 변경으로 치지 않는다.
+    pub async fn watch(&self, client_id: u64, key: &str) {
+        self.get_with_expiry(key).await;
+        let mut watched = self.watched.lock().unwrap();
+        let entry = watched.entry(key.to_string()).or_default();
+        let version = entry.version;
+        entry.watchers.entry(client_id).or_insert(version);
+    }
+
+    /// UNWATCH (EXEC, DISCARD, 커넥션 종료도 포함): 이 클라이언트가 WATCH 한 키를 모두 잊는다
+    pub fn unwatch_all(&self, client_id: u64) {
+        self.watched.lock().unwrap().retain(|_, watched| {
+            watched.watchers.remove(&client_id);
+            !watched.watchers.is_empty()
+        });
+    }
+
+    /// 이 클라이언트가 WATCH 한 키 중에 WATCH 이후 바뀌거나 만료된 키가 있는지
+    pub async fn watch_touched(&self, client_id: u64) -> bool {
+        let store = self.data.lock().await;
+        let now = now_millis();
+        let watched = self.watched.lock().unwrap();
+        watched.iter().any(|(key, watched)| match watched.watchers.get(&client_id) {
+            Some(&version) => version != watched.version || store.get(key).is_some_and(|value| value.is_expired(now)),
+            None => false,
+        })
+    }
+
+    /// 서버 시작 이후 누적된 변경 횟수
+    pub fn dirty(&self) -> u64 {
+        self.dirty.load(Ordering::SeqCst)
+    }
+
     pub async fn len(&self) -> usize {
         let store = self.data.lock().await;
         store.len()
     }
 
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
     pub async fn expire_len(&self) -> usize {
         let store = self.data.lock().await;
         store.iter()
@@ -0,0 +1,46 @@
+use crate::store::{now_millis, Store};
+use tokio::test;
+
+#[test]
+async fn test_watch_detects_writes_and_deletes() {
+    let store = Store::new();
+    store.insert("a".to_string(), "1".to_string(), None).await;
+    store.watch(1, "a").await;
+    store.watch(2, "missing").await;
+    assert!(!store.watch_touched(1).await);
+    assert!(!store.watch_touched(2).await);
+
+    // 없던 키가 생겨도 바뀐 것이다
+    store.insert("missing".to_string(), "1".to_string(), None).await;
+    assert!(!store.watch_touched(1).await);
+    assert!(store.watch_touched(2).await);
+
+    assert!(store.remove("a").await);
+    assert!(store.watch_touched(1).await);
+
+    // UNWATCH 후 다시 WATCH 하면 그 시점부터 본다
+    store.unwatch_all(1);
+    store.watch(1, "a").await;
+    assert!(!store.watch_touched(1).await);
+}
+
+#[test]
+async fn test_watch_detects_expiry_and_flush() {
+    let store = Store::new();
+    store.insert_at("soon".to_string(), "1".to_string(), Some(now_millis() + 50)).await;
+    store.insert_at("gone".to_string(), "1".to_string(), Some(now_millis() - 1)).await;
+    store.watch(1, "soon").await;
+    // WATCH 할 때 이미 만료된 키는 WATCH 이후의 변경이 아니다
+    store.watch(2, "gone").await;
+    assert!(!store.watch_touched(2).await);
+
+    tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+    // 아직 지워지지 않았어도 만료됐으면 바뀐 것이다
+    assert!(store.watch_touched(1).await);
+
+    store.insert("b".to_string(), "1".to_string(), None).await;
+    store.watch(3, "b").await;
+    assert_eq!(store.clear().await, 2);
+    assert!(store.watch_touched(3).await);
+    assert!(store.is_empty().await);
+}
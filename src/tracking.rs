@@ -0,0 +1,168 @@
+use crate::protocol::encoder::RedisEncoder;
+use crate::protocol::error::RedisError;
+use crate::pubsub::{Kind, PubSub};
+use bytes::BytesMut;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// RESP2 에서는 REDIRECT 로 지정한 클라이언트가 이 채널을 구독해서 무효화 메시지를 받는다.
+/// RESP3 클라이언트는 구독 없이 push 로 받는다.
+pub const INVALIDATE_CHANNEL: &str = "__redis__:invalidate";
+
+/// CLIENT TRACKING ON 의 옵션
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackingOptions {
+    /// 무효화 메시지를 받을 클라이언트 ID
+    pub redirect: Option<u64>,
+    /// 읽은 키 대신 접두사에 맞는 모든 키의 변경을 알린다
+    pub bcast: bool,
+    /// BCAST 모드의 접두사 (비어 있으면 모든 키)
+    pub prefixes: Vec<String>,
+    /// CLIENT CACHING YES 다음 명령이 읽은 키만 추적한다
+    pub optin: bool,
+    /// CLIENT CACHING NO 다음 명령이 읽은 키는 추적하지 않는다
+    pub optout: bool,
+    /// 자기가 바꾼 키는 알리지 않는다
+    pub noloop: bool,
+}
+
+#[derive(Debug, Default)]
+struct Table {
+    clients: HashMap<u64, TrackingOptions>,
+    /// 키 → 그 키를 읽은 클라이언트 ID (BCAST 가 아닌 클라이언트만)
+    keys: HashMap<String, HashSet<u64>>,
+}
+
+/// 클라이언트 쪽 캐시를 위한 키 추적 (CLIENT TRACKING)
+#[derive(Debug)]
+pub struct Tracking {
+    pubsub: Arc<PubSub>,
+    table: Mutex<Table>,
+    /// 지금 명령을 실행 중인 클라이언트 (NOLOOP). 0 이면 클라이언트가 아니다 (만료, replication 등).
+    caller: AtomicU64,
+}
+
+impl Tracking {
+    pub fn new(pubsub: Arc<PubSub>) -> Self {
+        Tracking { pubsub, table: Mutex::new(Table::default()), caller: AtomicU64::new(0) }
+    }
+
+    /// 추적을 켠다. 이미 켜져 있으면 BCAST 접두사를 더한다.
+    pub fn enable(&self, id: u64, mut options: TrackingOptions) -> Result<(), RedisError> {
+        let mut table = self.table.lock().unwrap();
+        if let Some(current) = table.clients.get(&id) {
+            if current.bcast != options.bcast {
+                return Err(RedisError::Err("You can't switch BCAST mode on/off before disabling tracking for this client, and then re-enabling it with a different mode.".to_string()));
+            }
+            if current.optin != options.optin || current.optout != options.optout {
+                return Err(RedisError::Err("You can't switch OPTIN/OPTOUT mode before disabling tracking for this client, and then re-enabling it with a different mode.".to_string()));
+            }
+            let mut prefixes = current.prefixes.clone();
+            prefixes.append(&mut options.prefixes);
+            options.prefixes = prefixes;
+        }
+        // 한 클라이언트의 접두사는 서로 겹치면 안 된다
+        for (i, a) in options.prefixes.iter().enumerate() {
+            for b in &options.prefixes[i + 1..] {
+                if a.starts_with(b.as_str()) || b.starts_with(a.as_str()) {
+                    return Err(RedisError::Err(format!("Prefix '{}' overlaps with an existing prefix '{}'. Prefixes for a single client must not overlap.", b, a)));
+                }
+            }
+        }
+        table.clients.insert(id, options);
+        Ok(())
+    }
+
+    /// CLIENT TRACKING OFF 또는 커넥션 종료
+    pub fn disable(&self, id: u64) {
+        let mut table = self.table.lock().unwrap();
+        if table.clients.remove(&id).is_some() {
+            table.keys.retain(|_, ids| {
+                ids.remove(&id);
+                !ids.is_empty()
+            });
+        }
+    }
+
+    pub fn options(&self, id: u64) -> Option<TrackingOptions> {
+        self.table.lock().unwrap().clients.get(&id).cloned()
+    }
+
+    /// 명령을 실행하는 클라이언트를 기록한다 (NOLOOP)
+    pub fn set_caller(&self, id: u64) {
+        self.caller.store(id, Ordering::SeqCst);
+    }
+
+    /// 클라이언트가 읽은 키를 기억한다. `caching` 은 직전의 CLIENT CACHING YES / NO.
+    pub fn remember(&self, id: u64, caching: Option<bool>, keys: &[&str]) {
+        let mut table = self.table.lock().unwrap();
+        let Some(options) = table.clients.get(&id) else {
+            return;
+        };
+        let tracked = if options.bcast {
+            false
+        } else if options.optin {
+            caching == Some(true)
+        } else if options.optout {
+            caching != Some(false)
+        } else {
+            true
+        };
+        if tracked {
+            for key in keys {
+                table.keys.entry(key.to_string()).or_default().insert(id);
+            }
+        }
+    }
+
+    /// 키가 바뀌었다. 그 키를 읽은 클라이언트와 접두사가 맞는 BCAST 클라이언트에게 알린다.
+    /// 읽은 키는 한 번 알리면 다시 읽을 때까지 추적하지 않는다.
+    pub fn invalidate(&self, key: &str) {
+        let caller = self.caller.load(Ordering::SeqCst);
+        let mut table = self.table.lock().unwrap();
+        let readers = table.keys.remove(key).unwrap_or_default();
+        for (id, options) in &table.clients {
+            let interested = if options.bcast {
+                options.prefixes.is_empty() || options.prefixes.iter().any(|prefix| key.starts_with(prefix.as_str()))
+            } else {
+                readers.contains(id)
+            };
+            if interested && !(options.noloop && *id == caller) {
+                self.send(*id, options, Some(key));
+            }
+        }
+    }
+
+    /// FLUSHALL 처럼 모든 키가 바뀌었다. 추적 중인 모든 클라이언트에게 null 로 알린다.
+    pub fn invalidate_all(&self) {
+        let mut table = self.table.lock().unwrap();
+        table.keys.clear();
+        for (id, options) in &table.clients {
+            self.send(*id, options, None);
+        }
+    }
+
+    /// REDIRECT 가 없으면 추적하는 클라이언트 자신에게 보낸다. RESP2 는 push 를 받을 수 없으므로
+    /// REDIRECT 클라이언트가 구독한 채널로만 보낼 수 있다.
+    fn send(&self, id: u64, options: &TrackingOptions, key: Option<&str>) {
+        let target = options.redirect.unwrap_or(id);
+        let encoder = RedisEncoder::with_resp3(self.pubsub.is_resp3(target));
+        let mut frame = BytesMut::new();
+        if encoder.is_resp3() {
+            encoder.encode_push_len(&mut frame, 2);
+            encoder.encode_bulk_string(&mut frame, "invalidate");
+        } else if options.redirect.is_some() && self.pubsub.is_subscribed(target, Kind::Channel, INVALIDATE_CHANNEL) {
+            encoder.encode_array_len(&mut frame, 3);
+            encoder.encode_bulk_string(&mut frame, "message");
+            encoder.encode_bulk_string(&mut frame, INVALIDATE_CHANNEL);
+        } else {
+            return;
+        }
+        match key {
+            Some(key) => encoder.encode_array(&mut frame, &[key]),
+            None => encoder.encode_null_array(&mut frame),
+        }
+        self.pubsub.send_to(target, frame.freeze());
+    }
+}
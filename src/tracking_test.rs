@@ -0,0 +1,122 @@
+use crate::pubsub::{Kind, PubSub};
+use crate::tracking::{Tracking, TrackingOptions, INVALIDATE_CHANNEL};
+use bytes::Bytes;
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::test;
+
+fn invalidation(key: Option<&str>) -> Bytes {
+    let mut frame = format!("*3\r\n$7\r\nmessage\r\n${}\r\n{}\r\n", INVALIDATE_CHANNEL.len(), INVALIDATE_CHANNEL);
+    match key {
+        Some(key) => frame.push_str(&format!("*1\r\n${}\r\n{}\r\n", key.len(), key)),
+        None => frame.push_str("*-1\r\n"),
+    }
+    Bytes::from(frame)
+}
+
+/// 무효화 채널을 구독한 REDIRECT 대상 클라이언트 하나를 만든다
+fn setup() -> (Tracking, u64, UnboundedReceiver<Bytes>) {
+    let pubsub = Arc::new(PubSub::new());
+    let (tx, rx) = mpsc::unbounded_channel();
+    let target = pubsub.next_client_id();
+    pubsub.connect(target, &tx);
+    pubsub.subscribe(target, &tx, Kind::Channel, INVALIDATE_CHANNEL);
+    (Tracking::new(pubsub), target, rx)
+}
+
+#[test]
+async fn test_default_mode_tracks_read_keys() {
+    let (tracking, target, mut rx) = setup();
+    tracking.enable(1, TrackingOptions { redirect: Some(target), ..Default::default() }).unwrap();
+
+    // 읽지 않은 키는 알리지 않는다
+    tracking.invalidate("a");
+    assert!(rx.try_recv().is_err());
+
+    tracking.remember(1, None, &["a", "b"]);
+    tracking.invalidate("a");
+    assert_eq!(rx.try_recv().unwrap(), invalidation(Some("a")));
+    // 한 번 알린 키는 다시 읽을 때까지 알리지 않는다
+    tracking.invalidate("a");
+    assert!(rx.try_recv().is_err());
+
+    tracking.invalidate_all();
+    assert_eq!(rx.try_recv().unwrap(), invalidation(None));
+    tracking.invalidate("b");
+    assert!(rx.try_recv().is_err());
+
+    tracking.remember(1, None, &["c"]);
+    tracking.disable(1);
+    tracking.invalidate("c");
+    assert!(rx.try_recv().is_err());
+    assert_eq!(tracking.options(1), None);
+}
+
+#[test]
+async fn test_bcast_prefixes() {
+    let (tracking, target, mut rx) = setup();
+    let options = TrackingOptions { redirect: Some(target), bcast: true, prefixes: vec!["user:".to_string()], ..Default::default() };
+    tracking.enable(1, options).unwrap();
+
+    // BCAST 는 읽지 않은 키도 접두사가 맞으면 알린다
+    tracking.invalidate("user:1");
+    assert_eq!(rx.try_recv().unwrap(), invalidation(Some("user:1")));
+    tracking.invalidate("order:1");
+    assert!(rx.try_recv().is_err());
+
+    // 다시 켜면 접두사를 더한다. 겹치는 접두사와 모드 전환은 거부한다.
+    tracking.enable(1, TrackingOptions { redirect: Some(target), bcast: true, prefixes: vec!["order:".to_string()], ..Default::default() }).unwrap();
+    assert_eq!(tracking.options(1).unwrap().prefixes, vec!["user:", "order:"]);
+    let overlap = TrackingOptions { bcast: true, prefixes: vec!["user:admin:".to_string()], ..Default::default() };
+    assert!(tracking.enable(1, overlap).unwrap_err().to_string().contains("overlaps"));
+    assert!(tracking.enable(1, TrackingOptions::default()).unwrap_err().to_string().contains("BCAST"));
+}
+
+#[test]
+async fn test_optin_optout_and_noloop() {
+    let (tracking, target, mut rx) = setup();
+    tracking.enable(1, TrackingOptions { redirect: Some(target), optin: true, noloop: true, ..Default::default() }).unwrap();
+    tracking.enable(2, TrackingOptions { redirect: Some(target), optout: true, ..Default::default() }).unwrap();
+
+    // OPTIN 은 CACHING YES 다음에 읽은 키만, OPTOUT 은 CACHING NO 다음에 읽은 키를 빼고 추적한다
+    tracking.remember(1, None, &["a"]);
+    tracking.remember(1, Some(true), &["b"]);
+    tracking.remember(2, Some(false), &["a"]);
+    tracking.remember(2, None, &["c"]);
+    tracking.invalidate("a");
+    assert!(rx.try_recv().is_err());
+    tracking.invalidate("b");
+    assert_eq!(rx.try_recv().unwrap(), invalidation(Some("b")));
+    tracking.invalidate("c");
+    assert_eq!(rx.try_recv().unwrap(), invalidation(Some("c")));
+
+    // NOLOOP: 자기가 바꾼 키는 알리지 않는다
+    tracking.remember(1, Some(true), &["d"]);
+    tracking.set_caller(1);
+    tracking.invalidate("d");
+    assert!(rx.try_recv().is_err());
+
+    assert!(tracking.enable(1, TrackingOptions { redirect: Some(target), ..Default::default() }).unwrap_err().to_string().contains("OPTIN/OPTOUT"));
+}
+
+#[test]
+async fn test_resp3_push_to_self() {
+    let pubsub = Arc::new(PubSub::new());
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let id = pubsub.next_client_id();
+    pubsub.connect(id, &tx);
+    let tracking = Tracking::new(Arc::clone(&pubsub));
+    tracking.enable(id, TrackingOptions::default()).unwrap();
+
+    // RESP2 클라이언트는 REDIRECT 없이 무효화 메시지를 받을 수 없다
+    tracking.remember(id, None, &["k"]);
+    tracking.invalidate("k");
+    assert!(rx.try_recv().is_err());
+
+    pubsub.set_resp3(id, true);
+    tracking.remember(id, None, &["k"]);
+    tracking.invalidate("k");
+    assert_eq!(rx.try_recv().unwrap(), Bytes::from(">2\r\n$10\r\ninvalidate\r\n*1\r\n$1\r\nk\r\n"));
+    tracking.invalidate_all();
+    assert_eq!(rx.try_recv().unwrap(), Bytes::from(">2\r\n$10\r\ninvalidate\r\n_\r\n"));
+}